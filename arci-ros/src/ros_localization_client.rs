@@ -12,7 +12,13 @@ rosrust::rosmsg_include! {
 }
 
 const AMCL_POSE_TOPIC: &str = "/amcl_pose";
+const INITIAL_POSE_TOPIC: &str = "/initialpose";
 const NO_MOTION_UPDATE_SERVICE: &str = "request_nomotion_update";
+/// Time to wait for the subscriber of the initial pose, e.g. amcl which is starting up.
+const INITIAL_POSE_SUBSCRIBER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+/// Indices of (x, y, yaw) in the row-major 6x6 covariance of
+/// `geometry_msgs/PoseWithCovariance`.
+const COVARIANCE_INDICES: [usize; 3] = [0, 1, 5];
 
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(deny_unknown_fields)]
@@ -25,7 +31,7 @@ pub struct RosLocalizationClientConfig {
 /// # Examples
 ///
 /// ```no_run
-/// let client = arci_ros::RosLocalizationClientBuilder::new().request_final_nomotion_update_hack(true).finalize();
+/// let client = arci_ros::RosLocalizationClientBuilder::new().request_final_nomotion_update_hack(true).finalize().unwrap();
 /// ```
 #[derive(Clone, Debug)]
 pub struct RosLocalizationClientBuilder {
@@ -72,9 +78,9 @@ impl RosLocalizationClientBuilder {
     /// # Examples
     ///
     /// ```no_run
-    /// let client = arci_ros::RosLocalizationClientBuilder::new().finalize();
+    /// let client = arci_ros::RosLocalizationClientBuilder::new().finalize().unwrap();
     /// ```
-    pub fn finalize(self) -> RosLocalizationClient {
+    pub fn finalize(self) -> Result<RosLocalizationClient, Error> {
         RosLocalizationClient::new(self.request_final_nomotion_update_hack)
     }
 }
@@ -87,29 +93,46 @@ impl Default for RosLocalizationClientBuilder {
 
 pub struct RosLocalizationClient {
     pose_subscriber: SubscriberHandler<msg::geometry_msgs::PoseWithCovarianceStamped>,
+    initial_pose_publisher: rosrust::Publisher<msg::geometry_msgs::PoseWithCovarianceStamped>,
     nomotion_update_client: Option<rosrust::Client<msg::std_srvs::Empty>>,
 }
 
 impl RosLocalizationClient {
-    pub fn new(request_final_nomotion_update_hack: bool) -> Self {
+    pub fn new(request_final_nomotion_update_hack: bool) -> Result<Self, Error> {
         let pose_subscriber = SubscriberHandler::new(AMCL_POSE_TOPIC, 1);
+        let initial_pose_publisher =
+            rosrust::publish(INITIAL_POSE_TOPIC, 1).map_err(|e| Error::Connection {
+                message: format!("Failed to advertise {}: {:?}", INITIAL_POSE_TOPIC, e),
+            })?;
         let nomotion_update_client = if request_final_nomotion_update_hack {
             rosrust::wait_for_service(
                 NO_MOTION_UPDATE_SERVICE,
                 Some(std::time::Duration::from_secs(10)),
             )
-            .unwrap();
-            Some(rosrust::client::<msg::std_srvs::Empty>(NO_MOTION_UPDATE_SERVICE).unwrap())
+            .map_err(|e| Error::Connection {
+                message: format!("Failed to wait for {}: {:?}", NO_MOTION_UPDATE_SERVICE, e),
+            })?;
+            Some(
+                rosrust::client::<msg::std_srvs::Empty>(NO_MOTION_UPDATE_SERVICE).map_err(|e| {
+                    Error::Connection {
+                        message: format!(
+                            "Failed to connect to {}: {:?}",
+                            NO_MOTION_UPDATE_SERVICE, e
+                        ),
+                    }
+                })?,
+            )
         } else {
             None
         };
-        Self {
+        Ok(Self {
             pose_subscriber,
+            initial_pose_publisher,
             nomotion_update_client,
-        }
+        })
     }
 
-    pub fn new_from_config(config: RosLocalizationClientConfig) -> Self {
+    pub fn new_from_config(config: RosLocalizationClientConfig) -> Result<Self, Error> {
         Self::new(config.request_final_nomotion_update_hack)
     }
 
@@ -125,7 +148,11 @@ impl RosLocalizationClient {
 }
 
 impl Localization for RosLocalizationClient {
-    fn current_pose(&self, _frame_id: &str) -> Result<na::Isometry2<f64>, Error> {
+    fn current_pose(&self, frame_id: &str) -> Result<na::Isometry2<f64>, Error> {
+        Ok(self.current_pose_with_covariance(frame_id)?.pose)
+    }
+
+    fn current_pose_with_covariance(&self, _frame_id: &str) -> Result<PoseWithCovariance, Error> {
         self.pose_subscriber.wait_message(100);
         let pose_with_cov_stamped =
             self.pose_subscriber
@@ -134,10 +161,49 @@ impl Localization for RosLocalizationClient {
                     message: format!("Failed to get pose from {}", AMCL_POSE_TOPIC),
                 })?;
         let pose: na::Isometry3<f64> = pose_with_cov_stamped.pose.pose.into();
-
-        Ok(na::Isometry2::new(
-            na::Vector2::new(pose.translation.vector[0], pose.translation.vector[1]),
-            pose.rotation.euler_angles().2,
+        let covariance = &pose_with_cov_stamped.pose.covariance;
+
+        Ok(PoseWithCovariance::new(
+            na::Isometry2::new(
+                na::Vector2::new(pose.translation.vector[0], pose.translation.vector[1]),
+                pose.rotation.euler_angles().2,
+            ),
+            na::Matrix3::from_fn(|row, col| {
+                covariance[COVARIANCE_INDICES[row] * 6 + COVARIANCE_INDICES[col]]
+            }),
         ))
     }
+
+    fn set_initial_pose(&self, pose: PoseWithCovariance, frame_id: &str) -> Result<(), Error> {
+        let start = std::time::Instant::now();
+        let rate = rosrust::rate(10.0);
+        while self.initial_pose_publisher.subscriber_count() == 0 {
+            if !rosrust::is_ok() || start.elapsed() > INITIAL_POSE_SUBSCRIBER_TIMEOUT {
+                return Err(Error::Connection {
+                    message: format!("No subscriber for {}", INITIAL_POSE_TOPIC),
+                });
+            }
+            rate.sleep();
+        }
+        let mut covariance = [0.0; 36];
+        for (row, &i) in COVARIANCE_INDICES.iter().enumerate() {
+            for (col, &j) in COVARIANCE_INDICES.iter().enumerate() {
+                covariance[i * 6 + j] = pose.covariance[(row, col)];
+            }
+        }
+        let mut initial_pose = msg::geometry_msgs::PoseWithCovarianceStamped::default();
+        initial_pose.header.frame_id = frame_id.to_owned();
+        initial_pose.header.stamp = rosrust::now();
+        initial_pose.pose.pose = na::Isometry3::from_parts(
+            na::Translation3::new(pose.pose.translation.x, pose.pose.translation.y, 0.0),
+            na::UnitQuaternion::from_euler_angles(0.0, 0.0, pose.pose.rotation.angle()),
+        )
+        .into();
+        initial_pose.pose.covariance = covariance.into();
+        self.initial_pose_publisher
+            .send(initial_pose)
+            .map_err(|e| Error::Connection {
+                message: format!("rosrust publish error: {:?}", e),
+            })
+    }
 }
@@ -10,7 +10,8 @@ use std::{
 use anyhow::format_err;
 use arci::{
     nalgebra as na, BaseVelocity, JointPositionLimit, JointPositionLimiter, JointTrajectoryClient,
-    JointVelocityLimiter, Localization, MoveBase, Navigation, TrajectoryPoint, WaitFuture,
+    JointVelocityLimiter, Localization, MoveBase, Navigation, PoseWithCovariance, TrajectoryPoint,
+    WaitFuture,
 };
 use openrr_sleep::ScopedSleep;
 use schemars::JsonSchema;
@@ -474,6 +475,22 @@ impl Localization for UrdfVizWebClient {
             yaw,
         ))
     }
+
+    fn current_pose_with_covariance(
+        &self,
+        frame_id: &str,
+    ) -> Result<PoseWithCovariance, arci::Error> {
+        // The pose in urdf-viz is always exact.
+        Ok(self.current_pose(frame_id)?.into())
+    }
+
+    fn set_initial_pose(
+        &self,
+        pose: PoseWithCovariance,
+        _frame_id: &str,
+    ) -> Result<(), arci::Error> {
+        send_robot_origin(&self.0.base_url, pose.pose.into())
+    }
 }
 
 impl Navigation for UrdfVizWebClient {
@@ -73,3 +73,22 @@ fn test_set_get_pose_no_wait() {
     assert_approx_eq!(pose.translation.y, 2.0);
     assert_approx_eq!(pose.rotation.angle(), 3.0);
 }
+
+#[test]
+fn test_set_initial_pose() {
+    const PORT: u16 = 8891;
+    let web_server = WebServer::new(PORT);
+    web_server.start_background();
+    let c =
+        UrdfVizWebClient::new(Url::parse(&format!("http://127.0.0.1:{}", PORT)).unwrap()).unwrap();
+    c.set_initial_pose(
+        nalgebra::Isometry2::new(nalgebra::Vector2::new(-1.0, 0.5), 1.0).into(),
+        "",
+    )
+    .unwrap();
+    let pose = c.current_pose_with_covariance("").unwrap();
+    assert_approx_eq!(pose.pose.translation.x, -1.0);
+    assert_approx_eq!(pose.pose.translation.y, 0.5);
+    assert_approx_eq!(pose.pose.rotation.angle(), 1.0);
+    assert_approx_eq!(pose.position_std_dev(), 0.0);
+}
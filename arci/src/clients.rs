@@ -1,3 +1,4 @@
+mod base_velocity_limiter;
 mod dummy_gamepad;
 mod dummy_localization;
 mod dummy_move_base;
@@ -5,12 +6,15 @@ mod dummy_navigation;
 mod dummy_speaker;
 mod dummy_trajectory_client;
 mod dummy_transform_resolver;
+mod geofence;
 mod joint_position_limiter;
 mod joint_trajectory_clients_container;
 mod joint_velocity_limiter;
 mod lazy;
+mod move_base_mux;
 mod partial_joint_trajectory_client;
 
+pub use base_velocity_limiter::*;
 pub use dummy_gamepad::*;
 pub use dummy_localization::*;
 pub use dummy_move_base::*;
@@ -18,8 +22,10 @@ pub use dummy_navigation::*;
 pub use dummy_speaker::*;
 pub use dummy_trajectory_client::*;
 pub use dummy_transform_resolver::*;
+pub use geofence::*;
 pub use joint_position_limiter::*;
 pub use joint_trajectory_clients_container::*;
 pub use joint_velocity_limiter::*;
 pub use lazy::*;
+pub use move_base_mux::*;
 pub use partial_joint_trajectory_client::*;
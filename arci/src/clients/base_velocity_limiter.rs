@@ -0,0 +1,398 @@
+use std::{
+    sync::{Arc, Mutex, Weak},
+    thread,
+    time::{Duration, Instant},
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    error::Error,
+    traits::{BaseVelocity, MoveBase},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BaseVelocityLimiterConfig {
+    /// Maximum absolute velocity of x \[m/s\].
+    pub max_velocity_x: f64,
+    /// Maximum absolute velocity of y \[m/s\].
+    pub max_velocity_y: f64,
+    /// Maximum absolute velocity of theta \[rad/s\].
+    pub max_velocity_theta: f64,
+    /// Maximum absolute acceleration of x \[m/s^2\].
+    pub max_acceleration_x: f64,
+    /// Maximum absolute acceleration of y \[m/s^2\].
+    pub max_acceleration_y: f64,
+    /// Maximum absolute acceleration of theta \[rad/s^2\].
+    pub max_acceleration_theta: f64,
+    /// The base is stopped if no command arrives within this duration.
+    #[serde(default = "default_watchdog_timeout_secs")]
+    pub watchdog_timeout_secs: f64,
+    /// Frequency to send the ramped velocity to the underlying client.
+    #[serde(default = "default_control_frequency")]
+    pub control_frequency: f64,
+}
+
+fn default_watchdog_timeout_secs() -> f64 {
+    0.5
+}
+
+fn default_control_frequency() -> f64 {
+    20.0
+}
+
+impl BaseVelocityLimiterConfig {
+    /// Returns an error if any of the limits is negative or NaN, or if the
+    /// watchdog timeout or the control frequency is not positive.
+    pub fn validate(&self) -> Result<(), Error> {
+        for (name, value) in [
+            ("max_velocity_x", self.max_velocity_x),
+            ("max_velocity_y", self.max_velocity_y),
+            ("max_velocity_theta", self.max_velocity_theta),
+            ("max_acceleration_x", self.max_acceleration_x),
+            ("max_acceleration_y", self.max_acceleration_y),
+            ("max_acceleration_theta", self.max_acceleration_theta),
+        ] {
+            if value.is_nan() || value < 0.0 {
+                return Err(anyhow::format_err!(
+                    "BaseVelocityLimiterConfig: {} must be non-negative, but got {}",
+                    name,
+                    value
+                )
+                .into());
+            }
+        }
+        if self.watchdog_timeout_secs.is_nan() || self.watchdog_timeout_secs <= 0.0 {
+            return Err(anyhow::format_err!(
+                "BaseVelocityLimiterConfig: watchdog_timeout_secs must be positive, but got {}",
+                self.watchdog_timeout_secs
+            )
+            .into());
+        }
+        if !self.control_frequency.is_finite() || self.control_frequency <= 0.0 {
+            return Err(anyhow::format_err!(
+                "BaseVelocityLimiterConfig: control_frequency must be positive, but got {}",
+                self.control_frequency
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn clamp_velocity(&self, velocity: &BaseVelocity) -> BaseVelocity {
+        BaseVelocity::new(
+            velocity.x.clamp(-self.max_velocity_x, self.max_velocity_x),
+            velocity.y.clamp(-self.max_velocity_y, self.max_velocity_y),
+            velocity
+                .theta
+                .clamp(-self.max_velocity_theta, self.max_velocity_theta),
+        )
+    }
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    target: BaseVelocity,
+    output: BaseVelocity,
+    last_command: Option<Instant>,
+    last_update: Instant,
+}
+
+#[derive(Debug)]
+struct LimiterInner<M>
+where
+    M: MoveBase,
+{
+    client: M,
+    config: BaseVelocityLimiterConfig,
+    state: Mutex<LimiterState>,
+}
+
+impl<M> LimiterInner<M>
+where
+    M: MoveBase,
+{
+    fn update(&self, now: Instant, command: Option<&BaseVelocity>) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(command) = command {
+            state.target = self.config.clamp_velocity(command);
+            state.last_command = Some(now);
+        }
+        let timed_out = state.last_command.map_or(false, |last_command| {
+            now.saturating_duration_since(last_command).as_secs_f64()
+                > self.config.watchdog_timeout_secs
+        });
+        if timed_out {
+            state.target = BaseVelocity::default();
+            state.last_command = None;
+        }
+        let dt = now
+            .saturating_duration_since(state.last_update)
+            .as_secs_f64();
+        state.last_update = now;
+
+        let output = BaseVelocity::new(
+            ramp(
+                state.output.x,
+                state.target.x,
+                self.config.max_acceleration_x * dt,
+            ),
+            ramp(
+                state.output.y,
+                state.target.y,
+                self.config.max_acceleration_y * dt,
+            ),
+            ramp(
+                state.output.theta,
+                state.target.theta,
+                self.config.max_acceleration_theta * dt,
+            ),
+        );
+        // Do not send anything while the base is stopped and no command arrives.
+        if command.is_none() && is_zero(&output) && is_zero(&state.output) {
+            return Ok(());
+        }
+        state.output = output;
+        self.client.send_velocity(&output)
+    }
+}
+
+fn ramp(current: f64, target: f64, max_change: f64) -> f64 {
+    current + (target - current).clamp(-max_change, max_change)
+}
+
+fn is_zero(velocity: &BaseVelocity) -> bool {
+    velocity.x == 0.0 && velocity.y == 0.0 && velocity.theta == 0.0
+}
+
+/// BaseVelocityLimiter limits the velocities and the accelerations of the
+/// base, and stops the base if no command arrives within the watchdog timeout.
+///
+/// The commanded velocity is clamped and the velocity sent to the underlying
+/// client approaches it at the limited acceleration. The ramped velocity is
+/// sent periodically at `control_frequency` by a background thread, which
+/// also ramps down the base to zero when the watchdog times out.
+#[derive(Debug)]
+pub struct BaseVelocityLimiter<M>
+where
+    M: MoveBase,
+{
+    inner: Arc<LimiterInner<M>>,
+}
+
+impl<M> BaseVelocityLimiter<M>
+where
+    M: MoveBase + 'static,
+{
+    /// Creates a new `BaseVelocityLimiter` and starts the background thread.
+    ///
+    /// Returns an error if the config is invalid. See [`BaseVelocityLimiterConfig::validate`].
+    pub fn new(client: M, config: BaseVelocityLimiterConfig) -> Result<Self, Error> {
+        let limiter = Self::new_without_control_loop(client, config)?;
+        let period = Duration::from_secs_f64(1.0 / limiter.inner.config.control_frequency);
+        let weak = Arc::downgrade(&limiter.inner);
+        thread::spawn(move || run_control_loop(weak, period));
+        Ok(limiter)
+    }
+}
+
+impl<M> BaseVelocityLimiter<M>
+where
+    M: MoveBase,
+{
+    fn new_without_control_loop(
+        client: M,
+        config: BaseVelocityLimiterConfig,
+    ) -> Result<Self, Error> {
+        config.validate()?;
+        let inner = Arc::new(LimiterInner {
+            client,
+            config,
+            state: Mutex::new(LimiterState {
+                target: BaseVelocity::default(),
+                output: BaseVelocity::default(),
+                last_command: None,
+                last_update: Instant::now(),
+            }),
+        });
+        Ok(Self { inner })
+    }
+}
+
+fn run_control_loop<M>(inner: Weak<LimiterInner<M>>, period: Duration)
+where
+    M: MoveBase,
+{
+    loop {
+        thread::sleep(period);
+        // The thread ends when the limiter is dropped.
+        let inner = match inner.upgrade() {
+            Some(inner) => inner,
+            None => break,
+        };
+        if let Err(e) = inner.update(Instant::now(), None) {
+            warn!("BaseVelocityLimiter: failed to send velocity: {}", e);
+        }
+    }
+}
+
+impl<M> MoveBase for BaseVelocityLimiter<M>
+where
+    M: MoveBase,
+{
+    fn send_velocity(&self, velocity: &BaseVelocity) -> Result<(), Error> {
+        self.inner.update(Instant::now(), Some(velocity))
+    }
+
+    fn current_velocity(&self) -> Result<BaseVelocity, Error> {
+        self.inner.client.current_velocity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+    use nalgebra::{Isometry2, Vector2};
+
+    use super::*;
+    use crate::{
+        DummyLocalization, DummyMoveBase, Geofence, GeofenceClient, GeofenceConfig, GeofenceZone,
+        Localization,
+    };
+
+    fn config() -> BaseVelocityLimiterConfig {
+        BaseVelocityLimiterConfig {
+            max_velocity_x: 1.0,
+            max_velocity_y: 0.5,
+            max_velocity_theta: 2.0,
+            max_acceleration_x: 2.0,
+            max_acceleration_y: 1.0,
+            max_acceleration_theta: 4.0,
+            watchdog_timeout_secs: 0.5,
+            control_frequency: 20.0,
+        }
+    }
+
+    // Without the background thread, so the tests can drive the limiter with
+    // the given times.
+    fn new_limiter(base: Arc<DummyMoveBase>) -> BaseVelocityLimiter<Arc<DummyMoveBase>> {
+        BaseVelocityLimiter::new_without_control_loop(base, config()).unwrap()
+    }
+
+    #[test]
+    fn test_ramp_and_watchdog() {
+        let base = Arc::new(DummyMoveBase::new());
+        let limiter = new_limiter(base.clone());
+        let inner = &limiter.inner;
+        let start = inner.state.lock().unwrap().last_update;
+        let at = |secs: f64| start + Duration::from_secs_f64(secs);
+
+        let command = BaseVelocity::new(3.0, -3.0, 1.0);
+        inner.update(at(0.1), Some(&command)).unwrap();
+        let vel = base.current_velocity().unwrap();
+        assert_approx_eq!(vel.x, 0.2);
+        assert_approx_eq!(vel.y, -0.1);
+        assert_approx_eq!(vel.theta, 0.4);
+
+        for i in 2..=10 {
+            inner.update(at(0.1 * i as f64), Some(&command)).unwrap();
+        }
+        // Limited by the maximum velocities.
+        let vel = base.current_velocity().unwrap();
+        assert_approx_eq!(vel.x, 1.0);
+        assert_approx_eq!(vel.y, -0.5);
+        assert_approx_eq!(vel.theta, 1.0);
+
+        inner.update(at(1.45), None).unwrap();
+        assert_approx_eq!(base.current_velocity().unwrap().x, 1.0);
+        // No command arrives within the watchdog timeout.
+        inner.update(at(1.55), None).unwrap();
+        let vel = base.current_velocity().unwrap();
+        assert_approx_eq!(vel.x, 0.8);
+        assert_approx_eq!(vel.y, -0.4);
+        assert_approx_eq!(vel.theta, 0.6);
+        inner.update(at(3.0), None).unwrap();
+        let vel = base.current_velocity().unwrap();
+        assert_approx_eq!(vel.x, 0.0);
+        assert_approx_eq!(vel.y, 0.0);
+        assert_approx_eq!(vel.theta, 0.0);
+    }
+
+    #[test]
+    fn test_stopped_base_is_not_commanded() {
+        let base = Arc::new(DummyMoveBase::new());
+        let limiter = new_limiter(base.clone());
+        *base.current_velocity.lock().unwrap() = BaseVelocity::new(0.1, 0.0, 0.0);
+        let start = limiter.inner.state.lock().unwrap().last_update;
+        limiter
+            .inner
+            .update(start + Duration::from_secs(1), None)
+            .unwrap();
+        assert_approx_eq!(base.current_velocity().unwrap().x, 0.1);
+    }
+
+    #[test]
+    fn test_geofence_inside_limiter() {
+        let base = Arc::new(DummyMoveBase::new());
+        let localization = Arc::new(DummyLocalization::new());
+        let geofence = Geofence::new(GeofenceConfig {
+            frame_id: "map".to_owned(),
+            lookahead_secs: 0.5,
+            footprint: vec![[0.3, 0.3], [-0.3, 0.3], [-0.3, -0.3], [0.3, -0.3]],
+            zones_path: None,
+            keep_in_zones: vec![],
+            keep_out_zones: vec![GeofenceZone {
+                name: "wall".to_owned(),
+                polygon: vec![[2.0, -1.0], [2.1, -1.0], [2.1, 1.0], [2.0, 1.0]],
+            }],
+        });
+        let mut config = config();
+        config.max_velocity_x = 3.0;
+        let limiter = BaseVelocityLimiter::new_without_control_loop(
+            GeofenceClient::new(base.clone(), localization.clone(), geofence),
+            config,
+        )
+        .unwrap();
+        let start = limiter.inner.state.lock().unwrap().last_update;
+
+        // The base approaches the wall at full speed, and then the commands stop.
+        let dt = 0.05;
+        let mut x = 0.0;
+        for i in 1..=100 {
+            let command = BaseVelocity::new(3.0, 0.0, 0.0);
+            let now = start + Duration::from_secs_f64(dt * i as f64);
+            limiter
+                .inner
+                .update(now, if i <= 40 { Some(&command) } else { None })
+                .unwrap();
+            x += base.current_velocity().unwrap().x * dt;
+            localization
+                .set_initial_pose(Isometry2::new(Vector2::new(x, 0.0), 0.0).into(), "map")
+                .unwrap();
+            // The front of the footprint does not reach the wall.
+            assert!(x + 0.3 < 2.0, "{}", x);
+        }
+        assert!(x > 1.0, "{}", x);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let base = Arc::new(DummyMoveBase::new());
+        let mut invalid = config();
+        invalid.max_velocity_x = -1.0;
+        assert!(BaseVelocityLimiter::new(base.clone(), invalid).is_err());
+        let mut invalid = config();
+        invalid.max_acceleration_theta = f64::NAN;
+        assert!(BaseVelocityLimiter::new(base.clone(), invalid).is_err());
+        let mut invalid = config();
+        invalid.control_frequency = 0.0;
+        assert!(BaseVelocityLimiter::new(base.clone(), invalid).is_err());
+        let mut invalid = config();
+        invalid.watchdog_timeout_secs = -0.5;
+        assert!(BaseVelocityLimiter::new(base.clone(), invalid).is_err());
+        assert!(BaseVelocityLimiter::new(base, config()).is_ok());
+    }
+}
@@ -1,11 +1,18 @@
+use std::sync::Mutex;
+
 use nalgebra::{Isometry2, Vector2};
 
-use crate::{error::Error, traits::Localization};
+use crate::{
+    error::Error,
+    traits::{Localization, PoseWithCovariance},
+};
 
 /// Dummy Localization for debug or tests.
 #[derive(Debug)]
 pub struct DummyLocalization {
     pub current_pose: Isometry2<f64>,
+    /// Pose given by [`Localization::set_initial_pose`], which overrides `current_pose`.
+    initial_pose: Mutex<Option<PoseWithCovariance>>,
 }
 
 impl DummyLocalization {
@@ -13,6 +20,7 @@ impl DummyLocalization {
     pub fn new() -> Self {
         Self {
             current_pose: Isometry2::new(Vector2::new(0.0, 0.0), 0.0),
+            initial_pose: Mutex::new(None),
         }
     }
 }
@@ -24,8 +32,21 @@ impl Default for DummyLocalization {
 }
 
 impl Localization for DummyLocalization {
-    fn current_pose(&self, _frame_id: &str) -> Result<Isometry2<f64>, Error> {
-        Ok(self.current_pose)
+    fn current_pose(&self, frame_id: &str) -> Result<Isometry2<f64>, Error> {
+        Ok(self.current_pose_with_covariance(frame_id)?.pose)
+    }
+
+    fn current_pose_with_covariance(&self, _frame_id: &str) -> Result<PoseWithCovariance, Error> {
+        Ok(self
+            .initial_pose
+            .lock()
+            .unwrap()
+            .unwrap_or_else(|| self.current_pose.into()))
+    }
+
+    fn set_initial_pose(&self, pose: PoseWithCovariance, _frame_id: &str) -> Result<(), Error> {
+        *self.initial_pose.lock().unwrap() = Some(pose);
+        Ok(())
     }
 }
 
@@ -38,4 +59,17 @@ mod tests {
         let current_pose = loc.current_pose("").unwrap();
         assert_eq!(current_pose, current_pose.inverse()); // only identity mapping satisfies this
     }
+
+    #[test]
+    fn test_set_initial_pose() {
+        let loc = DummyLocalization::new();
+        let pose = PoseWithCovariance::from_std_dev(
+            Isometry2::new(Vector2::new(1.0, -2.0), 0.5),
+            0.2,
+            0.1,
+        );
+        loc.set_initial_pose(pose, "map").unwrap();
+        assert_eq!(loc.current_pose("map").unwrap(), pose.pose);
+        assert_eq!(loc.current_pose_with_covariance("map").unwrap(), pose);
+    }
 }
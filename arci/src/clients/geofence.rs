@@ -0,0 +1,563 @@
+use std::path::PathBuf;
+
+use nalgebra::{Isometry2, Point2, Vector2};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    traits::{BaseVelocity, Localization, MoveBase, Navigation},
+    waits::WaitFuture,
+};
+
+const MIN_PREDICTION_STEPS: usize = 10;
+const MAX_PREDICTION_STEPS: usize = 10000;
+const CLIPPING_ITERATIONS: usize = 10;
+
+/// Polygonal zone on the floor.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GeofenceZone {
+    pub name: String,
+    /// Vertices `[x, y]` of the polygon.
+    pub polygon: Vec<[f64; 2]>,
+}
+
+fn polygon_contains(polygon: &[[f64; 2]], point: &Point2<f64>) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for (i, vi) in polygon.iter().enumerate() {
+        let vj = polygon[j];
+        if (vi[1] > point.y) != (vj[1] > point.y)
+            && point.x < (vj[0] - vi[0]) * (point.y - vi[1]) / (vj[1] - vi[1]) + vi[0]
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn polygon_edges(polygon: &[[f64; 2]]) -> impl Iterator<Item = ([f64; 2], [f64; 2])> + '_ {
+    let len = polygon.len();
+    (0..len).map(move |i| (polygon[i], polygon[(i + 1) % len]))
+}
+
+// Returns true if the segments cross each other.
+fn segments_cross(a: ([f64; 2], [f64; 2]), b: ([f64; 2], [f64; 2])) -> bool {
+    let side = |p: [f64; 2], q: [f64; 2], r: [f64; 2]| {
+        ((q[0] - p[0]) * (r[1] - p[1]) - (q[1] - p[1]) * (r[0] - p[0])).signum()
+    };
+    side(a.0, a.1, b.0) * side(a.0, a.1, b.1) < 0.0
+        && side(b.0, b.1, a.0) * side(b.0, b.1, a.1) < 0.0
+}
+
+fn edges_cross(a: &[[f64; 2]], b: &[[f64; 2]]) -> bool {
+    polygon_edges(a).any(|ea| polygon_edges(b).any(|eb| segments_cross(ea, eb)))
+}
+
+impl GeofenceZone {
+    /// Returns true if `point` is inside the polygon.
+    pub fn contains(&self, point: &Point2<f64>) -> bool {
+        polygon_contains(&self.polygon, point)
+    }
+
+    /// Returns true if the polygon of the zone and `polygon` share any area.
+    pub fn overlaps(&self, polygon: &[[f64; 2]]) -> bool {
+        polygon
+            .iter()
+            .any(|v| self.contains(&Point2::new(v[0], v[1])))
+            || self
+                .polygon
+                .iter()
+                .any(|v| polygon_contains(polygon, &Point2::new(v[0], v[1])))
+            || edges_cross(&self.polygon, polygon)
+    }
+
+    /// Returns true if `polygon` is entirely inside the polygon of the zone.
+    pub fn encloses(&self, polygon: &[[f64; 2]]) -> bool {
+        polygon
+            .iter()
+            .all(|v| self.contains(&Point2::new(v[0], v[1])))
+            && !edges_cross(&self.polygon, polygon)
+    }
+
+    /// Width of the polygon, i.e. the minimum over the edges of the distance
+    /// between the edge and the farthest vertex from it.
+    pub fn thickness(&self) -> f64 {
+        let len = self.polygon.len();
+        (0..len)
+            .map(|i| {
+                let start = Vector2::new(self.polygon[i][0], self.polygon[i][1]);
+                let end = self.polygon[(i + 1) % len];
+                let edge = Vector2::new(end[0], end[1]) - start;
+                let length = edge.norm();
+                if length == 0.0 {
+                    return f64::INFINITY;
+                }
+                self.polygon
+                    .iter()
+                    .map(|v| {
+                        let d = Vector2::new(v[0], v[1]) - start;
+                        (edge.x * d.y - edge.y * d.x).abs() / length
+                    })
+                    .fold(0.0, f64::max)
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+}
+
+/// Keep-in and keep-out zones.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GeofenceZones {
+    /// The base must stay inside one of these zones. No restriction if empty.
+    #[serde(default)]
+    // https://github.com/alexcrichton/toml-rs/issues/258
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub keep_in_zones: Vec<GeofenceZone>,
+    /// The base must not enter any of these zones.
+    #[serde(default)]
+    // https://github.com/alexcrichton/toml-rs/issues/258
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub keep_out_zones: Vec<GeofenceZone>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GeofenceConfig {
+    /// Frame of the zones, which is also used to get the current pose.
+    #[serde(default = "default_frame_id")]
+    pub frame_id: String,
+    /// The velocity is clipped if the base would violate the zones within this duration.
+    #[serde(default = "default_lookahead_secs")]
+    pub lookahead_secs: f64,
+    /// Vertices `[x, y]` of the polygon of the base in the frame of the base.
+    /// The footprint must not overlap the keep-out zones and must fit in one
+    /// of the keep-in zones. Only the origin of the base is checked if empty.
+    #[serde(default)]
+    // https://github.com/alexcrichton/toml-rs/issues/258
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub footprint: Vec<[f64; 2]>,
+    /// Path to the file of [`GeofenceZones`], e.g. created from the map.
+    /// The zones in the file are added to the zones in this config.
+    pub zones_path: Option<PathBuf>,
+    /// The base must stay inside one of these zones. No restriction if empty.
+    #[serde(default)]
+    // https://github.com/alexcrichton/toml-rs/issues/258
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub keep_in_zones: Vec<GeofenceZone>,
+    /// The base must not enter any of these zones.
+    #[serde(default)]
+    // https://github.com/alexcrichton/toml-rs/issues/258
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub keep_out_zones: Vec<GeofenceZone>,
+}
+
+impl GeofenceConfig {
+    /// Adds the zones, e.g. loaded from `zones_path`.
+    pub fn extend_zones(&mut self, zones: GeofenceZones) {
+        self.keep_in_zones.extend(zones.keep_in_zones);
+        self.keep_out_zones.extend(zones.keep_out_zones);
+    }
+}
+
+fn default_frame_id() -> String {
+    "map".to_owned()
+}
+
+fn default_lookahead_secs() -> f64 {
+    1.0
+}
+
+/// Checks positions and velocities of the base against the keep-in and keep-out zones.
+#[derive(Debug, Clone)]
+pub struct Geofence {
+    config: GeofenceConfig,
+    // Thickness of the thinnest keep-out zone, which the predicted positions
+    // must not jump over.
+    min_keep_out_thickness: f64,
+    // Distance from the origin of the base to the farthest vertex of the footprint.
+    footprint_radius: f64,
+}
+
+impl Geofence {
+    pub fn new(config: GeofenceConfig) -> Self {
+        let min_keep_out_thickness = config
+            .keep_out_zones
+            .iter()
+            .map(GeofenceZone::thickness)
+            .fold(f64::INFINITY, f64::min);
+        let footprint_radius = config
+            .footprint
+            .iter()
+            .map(|v| v[0].hypot(v[1]))
+            .fold(0.0, f64::max);
+        Self {
+            config,
+            min_keep_out_thickness,
+            footprint_radius,
+        }
+    }
+
+    pub fn frame_id(&self) -> &str {
+        &self.config.frame_id
+    }
+
+    /// Returns an error if the base is not allowed to be at `position`.
+    pub fn check_position(&self, position: &Point2<f64>) -> Result<(), Error> {
+        let config = &self.config;
+        if let Some(zone) = config.keep_out_zones.iter().find(|z| z.contains(position)) {
+            return Err(Error::InKeepOutZone {
+                zone: zone.name.clone(),
+                x: position.x,
+                y: position.y,
+            });
+        }
+        if !config.keep_in_zones.is_empty()
+            && !config.keep_in_zones.iter().any(|z| z.contains(position))
+        {
+            return Err(Error::OutOfKeepInZones {
+                x: position.x,
+                y: position.y,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns an error if the footprint of the base at `pose` violates the zones.
+    ///
+    /// Only the position of the base is checked if the footprint is not configured.
+    pub fn check_pose(&self, pose: &Isometry2<f64>) -> Result<(), Error> {
+        let position = Point2::from(pose.translation.vector);
+        let config = &self.config;
+        if config.footprint.is_empty() {
+            return self.check_position(&position);
+        }
+        let footprint: Vec<[f64; 2]> = config
+            .footprint
+            .iter()
+            .map(|v| {
+                let v = pose * Point2::new(v[0], v[1]);
+                [v.x, v.y]
+            })
+            .collect();
+        if let Some(zone) = config
+            .keep_out_zones
+            .iter()
+            .find(|z| z.overlaps(&footprint))
+        {
+            return Err(Error::InKeepOutZone {
+                zone: zone.name.clone(),
+                x: position.x,
+                y: position.y,
+            });
+        }
+        if !config.keep_in_zones.is_empty()
+            && !config.keep_in_zones.iter().any(|z| z.encloses(&footprint))
+        {
+            return Err(Error::OutOfKeepInZones {
+                x: position.x,
+                y: position.y,
+            });
+        }
+        Ok(())
+    }
+
+    fn is_allowed(&self, pose: &Isometry2<f64>) -> bool {
+        self.check_pose(pose).is_ok()
+    }
+
+    // Poses of the base moving at `velocity` during the lookahead duration.
+    //
+    // The poses are sampled at intervals where any point of the footprint
+    // moves less than half of the thickness of the thinnest keep-out zone, so
+    // that the base does not tunnel through it at high speed.
+    fn predicted_poses(
+        &self,
+        pose: &Isometry2<f64>,
+        velocity: &BaseVelocity,
+    ) -> impl Iterator<Item = Isometry2<f64>> {
+        let distance = (velocity.x.hypot(velocity.y)
+            + velocity.theta.abs() * self.footprint_radius)
+            * self.config.lookahead_secs;
+        let steps = ((2.0 * distance / self.min_keep_out_thickness).ceil() as usize)
+            .clamp(MIN_PREDICTION_STEPS, MAX_PREDICTION_STEPS);
+        let dt = self.config.lookahead_secs / steps as f64;
+        let step = Isometry2::new(
+            Vector2::new(velocity.x * dt, velocity.y * dt),
+            velocity.theta * dt,
+        );
+        let mut pose = *pose;
+        (0..steps).map(move |_| {
+            pose *= step;
+            pose
+        })
+    }
+
+    /// Scales down the translational velocity so that the base does not cross
+    /// the boundaries of the zones within the lookahead duration. The
+    /// rotational velocity is also scaled down if the footprint is configured,
+    /// because the rotation moves the footprint.
+    ///
+    /// If the base is already in a forbidden area, the velocity is kept only
+    /// if it brings the base to an allowed pose.
+    pub fn clip_velocity(&self, pose: &Isometry2<f64>, velocity: &BaseVelocity) -> BaseVelocity {
+        let theta_scaled = !self.config.footprint.is_empty();
+        let scaled = |scale: f64| {
+            BaseVelocity::new(
+                velocity.x * scale,
+                velocity.y * scale,
+                if theta_scaled {
+                    velocity.theta * scale
+                } else {
+                    velocity.theta
+                },
+            )
+        };
+        let is_safe = |scale: f64| {
+            self.predicted_poses(pose, &scaled(scale))
+                .all(|pose| self.is_allowed(&pose))
+        };
+        if is_safe(1.0) {
+            return *velocity;
+        }
+        if !self.is_allowed(pose) {
+            let reaches_allowed_pose = self
+                .predicted_poses(pose, velocity)
+                .last()
+                .map_or(false, |pose| self.is_allowed(&pose));
+            return if reaches_allowed_pose {
+                *velocity
+            } else {
+                scaled(0.0)
+            };
+        }
+        let mut safe_scale = 0.0;
+        let mut unsafe_scale = 1.0;
+        for _ in 0..CLIPPING_ITERATIONS {
+            let scale = (safe_scale + unsafe_scale) / 2.0;
+            if is_safe(scale) {
+                safe_scale = scale;
+            } else {
+                unsafe_scale = scale;
+            }
+        }
+        scaled(safe_scale)
+    }
+}
+
+/// GeofenceClient rejects navigation goals in forbidden areas and clips
+/// velocities that would drive the base across the boundaries of the zones.
+#[derive(Debug)]
+pub struct GeofenceClient<C, L>
+where
+    L: Localization,
+{
+    client: C,
+    localization: L,
+    geofence: Geofence,
+}
+
+impl<C, L> GeofenceClient<C, L>
+where
+    L: Localization,
+{
+    pub fn new(client: C, localization: L, geofence: Geofence) -> Self {
+        Self {
+            client,
+            localization,
+            geofence,
+        }
+    }
+
+    pub fn geofence(&self) -> &Geofence {
+        &self.geofence
+    }
+}
+
+impl<C, L> MoveBase for GeofenceClient<C, L>
+where
+    C: MoveBase,
+    L: Localization,
+{
+    fn send_velocity(&self, velocity: &BaseVelocity) -> Result<(), Error> {
+        let pose = self.localization.current_pose(self.geofence.frame_id())?;
+        self.client
+            .send_velocity(&self.geofence.clip_velocity(&pose, velocity))
+    }
+
+    fn current_velocity(&self) -> Result<BaseVelocity, Error> {
+        self.client.current_velocity()
+    }
+}
+
+impl<C, L> Navigation for GeofenceClient<C, L>
+where
+    C: Navigation,
+    L: Localization,
+{
+    fn send_goal_pose(
+        &self,
+        goal: Isometry2<f64>,
+        frame_id: &str,
+        timeout: std::time::Duration,
+    ) -> Result<WaitFuture, Error> {
+        if frame_id != self.geofence.frame_id() {
+            return Err(Error::FrameMismatch {
+                expected: self.geofence.frame_id().to_owned(),
+                actual: frame_id.to_owned(),
+            });
+        }
+        self.geofence.check_pose(&goal)?;
+        self.client.send_goal_pose(goal, frame_id, timeout)
+    }
+
+    fn cancel(&self) -> Result<(), Error> {
+        self.client.cancel()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+
+    use super::*;
+    use crate::{DummyLocalization, DummyMoveBase, DummyNavigation};
+
+    fn square(name: &str, min: f64, max: f64) -> GeofenceZone {
+        GeofenceZone {
+            name: name.to_owned(),
+            polygon: vec![[min, min], [max, min], [max, max], [min, max]],
+        }
+    }
+
+    fn geofence() -> Geofence {
+        Geofence::new(GeofenceConfig {
+            frame_id: "map".to_owned(),
+            lookahead_secs: 1.0,
+            footprint: vec![],
+            zones_path: None,
+            keep_in_zones: vec![square("room", -5.0, 5.0)],
+            keep_out_zones: vec![square("stairs", 2.0, 3.0)],
+        })
+    }
+
+    #[test]
+    fn test_check_position() {
+        let geofence = geofence();
+        assert!(geofence.check_position(&Point2::new(0.0, 0.0)).is_ok());
+        assert!(matches!(
+            geofence.check_position(&Point2::new(2.5, 2.5)),
+            Err(Error::InKeepOutZone { zone, .. }) if zone == "stairs"
+        ));
+        assert!(matches!(
+            geofence.check_position(&Point2::new(6.0, 0.0)),
+            Err(Error::OutOfKeepInZones { .. })
+        ));
+    }
+
+    #[test]
+    fn test_clip_velocity() {
+        let geofence = geofence();
+        let pose = Isometry2::new(Vector2::new(4.0, 0.0), 0.0);
+        let clipped = geofence.clip_velocity(&pose, &BaseVelocity::new(0.5, 0.0, 0.3));
+        assert_approx_eq!(clipped.x, 0.5);
+        assert_approx_eq!(clipped.theta, 0.3);
+        // Would cross the boundary of the room within 1 sec.
+        let clipped = geofence.clip_velocity(&pose, &BaseVelocity::new(2.0, 0.0, 0.0));
+        assert!(clipped.x <= 1.0 && clipped.x > 0.9, "{:?}", clipped);
+        // Moving away from the boundary is not clipped.
+        let clipped = geofence.clip_velocity(&pose, &BaseVelocity::new(-2.0, 0.0, 0.0));
+        assert_approx_eq!(clipped.x, -2.0);
+    }
+
+    #[test]
+    fn test_thin_keep_out_zone() {
+        let wall = GeofenceZone {
+            name: "wall".to_owned(),
+            polygon: vec![[1.0, -1.0], [1.02, -1.0], [1.02, 1.0], [1.0, 1.0]],
+        };
+        assert_approx_eq!(wall.thickness(), 0.02);
+        let geofence = Geofence::new(GeofenceConfig {
+            frame_id: "map".to_owned(),
+            lookahead_secs: 1.0,
+            footprint: vec![],
+            zones_path: None,
+            keep_in_zones: vec![],
+            keep_out_zones: vec![wall],
+        });
+        // 10 steps of 0.5 m would jump over the wall.
+        let clipped =
+            geofence.clip_velocity(&Isometry2::identity(), &BaseVelocity::new(5.0, 0.0, 0.0));
+        assert!(clipped.x <= 1.0, "{:?}", clipped);
+    }
+
+    #[test]
+    fn test_footprint() {
+        let geofence = Geofence::new(GeofenceConfig {
+            frame_id: "map".to_owned(),
+            lookahead_secs: 1.0,
+            footprint: vec![[0.3, 0.3], [-0.3, 0.3], [-0.3, -0.3], [0.3, -0.3]],
+            zones_path: None,
+            keep_in_zones: vec![square("room", -5.0, 5.0)],
+            keep_out_zones: vec![square("stairs", 2.0, 3.0), square("pole", -3.05, -2.95)],
+        });
+        let pose = |x: f64, y: f64| Isometry2::new(Vector2::new(x, y), 0.0);
+        assert!(geofence.check_pose(&pose(1.5, 2.5)).is_ok());
+        // The origin is outside the stairs, but the footprint overlaps them.
+        assert!(geofence.check_position(&Point2::new(1.8, 2.5)).is_ok());
+        assert!(matches!(
+            geofence.check_pose(&pose(1.8, 2.5)),
+            Err(Error::InKeepOutZone { zone, .. }) if zone == "stairs"
+        ));
+        // The pole is inside the footprint.
+        assert!(matches!(
+            geofence.check_pose(&pose(-2.8, -3.0)),
+            Err(Error::InKeepOutZone { zone, .. }) if zone == "pole"
+        ));
+        assert!(matches!(
+            geofence.check_pose(&pose(4.8, 0.0)),
+            Err(Error::OutOfKeepInZones { .. })
+        ));
+        // Rotating in front of the stairs makes the footprint overlap them.
+        assert!(geofence.check_pose(&pose(1.65, 2.5)).is_ok());
+        assert!(geofence
+            .check_pose(&Isometry2::new(
+                Vector2::new(1.65, 2.5),
+                std::f64::consts::FRAC_PI_4
+            ))
+            .is_err());
+
+        // The front of the footprint is 0.2 m away from the stairs.
+        let clipped = geofence.clip_velocity(&pose(1.5, 2.5), &BaseVelocity::new(1.0, 0.0, 0.0));
+        assert!(clipped.x <= 0.2 && clipped.x > 0.1, "{:?}", clipped);
+    }
+
+    #[test]
+    fn test_geofence_client() {
+        let localization = DummyLocalization::new();
+        let client = GeofenceClient::new(DummyNavigation::new(), localization, geofence());
+        let goal = Isometry2::new(Vector2::new(2.5, 2.5), 0.0);
+        assert!(client
+            .send_goal_pose(goal, "map", std::time::Duration::from_secs(1))
+            .is_err());
+        let goal = Isometry2::new(Vector2::new(1.0, 1.0), 0.0);
+        assert!(client
+            .send_goal_pose(goal, "odom", std::time::Duration::from_secs(1))
+            .is_err());
+        assert!(client
+            .send_goal_pose(goal, "map", std::time::Duration::from_secs(1))
+            .is_ok());
+        assert_eq!(client.client.current_goal_pose().unwrap(), goal);
+
+        let mut localization = DummyLocalization::new();
+        localization.current_pose = Isometry2::new(Vector2::new(1.5, 2.5), 0.0);
+        let client = GeofenceClient::new(DummyMoveBase::new(), localization, geofence());
+        client
+            .send_velocity(&BaseVelocity::new(1.0, 0.0, 0.0))
+            .unwrap();
+        let velocity = client.current_velocity().unwrap();
+        assert!(velocity.x < 0.5, "{:?}", velocity);
+    }
+}
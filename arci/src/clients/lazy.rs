@@ -10,8 +10,8 @@ use crate::{
     error::Error,
     gamepad::GamepadEvent,
     traits::{
-        BaseVelocity, Gamepad, JointTrajectoryClient, Localization, MoveBase, Navigation, Speaker,
-        TrajectoryPoint, TransformResolver,
+        BaseVelocity, Gamepad, JointTrajectoryClient, Localization, MoveBase, Navigation,
+        PoseWithCovariance, Speaker, TrajectoryPoint, TransformResolver,
     },
     waits::WaitFuture,
 };
@@ -95,6 +95,14 @@ where
     fn current_pose(&self, frame_id: &str) -> Result<Isometry2<f64>, Error> {
         self.get_ref()?.current_pose(frame_id)
     }
+
+    fn current_pose_with_covariance(&self, frame_id: &str) -> Result<PoseWithCovariance, Error> {
+        self.get_ref()?.current_pose_with_covariance(frame_id)
+    }
+
+    fn set_initial_pose(&self, pose: PoseWithCovariance, frame_id: &str) -> Result<(), Error> {
+        self.get_ref()?.set_initial_pose(pose, frame_id)
+    }
 }
 
 impl<T> MoveBase for Lazy<'_, T>
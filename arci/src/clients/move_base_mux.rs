@@ -0,0 +1,474 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{
+    error::Error,
+    traits::{BaseVelocity, MoveBase},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MoveBaseMuxInputConfig {
+    pub name: String,
+    /// Commands from the input with the higher priority take precedence.
+    pub priority: u32,
+    /// The input is regarded as inactive if no command arrives within this
+    /// duration. It must be positive and finite.
+    pub timeout_secs: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MoveBaseMuxLockConfig {
+    pub name: String,
+    /// While locked, commands from the inputs whose priorities are lower than
+    /// or equal to this priority are ignored.
+    pub priority: u32,
+    /// The lock is released if it is not locked again within this duration.
+    /// `0` means that the lock never expires. It must be non-negative and finite.
+    #[serde(default)]
+    pub timeout_secs: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MoveBaseMuxConfig {
+    pub inputs: Vec<MoveBaseMuxInputConfig>,
+    #[serde(default)]
+    // https://github.com/alexcrichton/toml-rs/issues/258
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub locks: Vec<MoveBaseMuxLockConfig>,
+}
+
+impl MoveBaseMuxConfig {
+    /// Returns an error if the names are duplicated or the timeouts are invalid.
+    pub fn validate(&self) -> Result<(), Error> {
+        let names = self
+            .inputs
+            .iter()
+            .map(|input| &input.name)
+            .chain(self.locks.iter().map(|lock| &lock.name));
+        let mut checked = vec![];
+        for name in names {
+            if checked.contains(&name) {
+                return Err(anyhow::format_err!(
+                    "MoveBaseMuxConfig: name {:?} is duplicated",
+                    name
+                )
+                .into());
+            }
+            checked.push(name);
+        }
+        for input in &self.inputs {
+            if !input.timeout_secs.is_finite() || input.timeout_secs <= 0.0 {
+                return Err(anyhow::format_err!(
+                    "MoveBaseMuxConfig: timeout_secs of input {:?} must be positive and finite, but got {}",
+                    input.name,
+                    input.timeout_secs
+                )
+                .into());
+            }
+        }
+        for lock in &self.locks {
+            if !lock.timeout_secs.is_finite() || lock.timeout_secs < 0.0 {
+                return Err(anyhow::format_err!(
+                    "MoveBaseMuxConfig: timeout_secs of lock {:?} must be non-negative and finite, but got {}",
+                    lock.name,
+                    lock.timeout_secs
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct MuxState {
+    last_commands: Vec<Option<Instant>>,
+    locked_at: Vec<Option<Instant>>,
+}
+
+#[derive(Debug)]
+struct MuxInner<M>
+where
+    M: MoveBase,
+{
+    client: M,
+    config: MoveBaseMuxConfig,
+    state: Mutex<MuxState>,
+}
+
+fn is_within(time: Option<Instant>, timeout_secs: f64, now: Instant) -> bool {
+    time.map_or(false, |time| {
+        now.saturating_duration_since(time).as_secs_f64() <= timeout_secs
+    })
+}
+
+impl<M> MuxInner<M>
+where
+    M: MoveBase,
+{
+    fn is_lock_active(&self, state: &MuxState, index: usize, now: Instant) -> bool {
+        let timeout_secs = self.config.locks[index].timeout_secs;
+        if timeout_secs == 0.0 {
+            state.locked_at[index].is_some()
+        } else {
+            is_within(state.locked_at[index], timeout_secs, now)
+        }
+    }
+
+    fn is_blocked(&self, state: &MuxState, input_index: usize, now: Instant) -> bool {
+        let priority = self.config.inputs[input_index].priority;
+        let locked = (0..self.config.locks.len()).any(|i| {
+            self.is_lock_active(state, i, now) && self.config.locks[i].priority >= priority
+        });
+        let preempted = self.config.inputs.iter().enumerate().any(|(i, input)| {
+            i != input_index
+                && input.priority > priority
+                && is_within(state.last_commands[i], input.timeout_secs, now)
+        });
+        locked || preempted
+    }
+
+    fn send_velocity(&self, input_index: usize, velocity: &BaseVelocity) -> Result<(), Error> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.last_commands[input_index] = Some(now);
+        if self.is_blocked(&state, input_index, now) {
+            debug!(
+                "MoveBaseMux: ignored command from {:?}",
+                self.config.inputs[input_index].name
+            );
+            return Ok(());
+        }
+        self.client.send_velocity(velocity)
+    }
+
+    fn set_locked(&self, lock_index: usize, locked: bool) -> Result<(), Error> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let was_active = self.is_lock_active(&state, lock_index, now);
+        state.locked_at[lock_index] = if locked { Some(now) } else { None };
+        if locked && !was_active {
+            // Stop the base driven by the inputs that are now blocked.
+            self.client.send_velocity(&BaseVelocity::default())?;
+        }
+        Ok(())
+    }
+}
+
+/// MoveBaseMux multiplexes velocity commands from multiple inputs into one
+/// MoveBase by their priorities, like `twist_mux` of ROS.
+///
+/// The command from an input is forwarded only if no input with a higher
+/// priority is active and no active lock blocks it. Otherwise it is ignored
+/// silently.
+///
+/// The arbitration happens between the handles of the same `MoveBaseMux`, so
+/// the inputs and the locks have to be used in the process owning it.
+#[derive(Debug)]
+pub struct MoveBaseMux<M>
+where
+    M: MoveBase,
+{
+    inner: Arc<MuxInner<M>>,
+}
+
+impl<M> MoveBaseMux<M>
+where
+    M: MoveBase,
+{
+    /// Creates a new `MoveBaseMux`.
+    ///
+    /// Returns an error if the config is invalid. See [`MoveBaseMuxConfig::validate`].
+    pub fn new(client: M, config: MoveBaseMuxConfig) -> Result<Self, Error> {
+        config.validate()?;
+        let state = MuxState {
+            last_commands: vec![None; config.inputs.len()],
+            locked_at: vec![None; config.locks.len()],
+        };
+        Ok(Self {
+            inner: Arc::new(MuxInner {
+                client,
+                config,
+                state: Mutex::new(state),
+            }),
+        })
+    }
+
+    /// Returns the input handles in the order of the config.
+    pub fn inputs(&self) -> Vec<MoveBaseMuxInput<M>> {
+        (0..self.inner.config.inputs.len())
+            .map(|index| MoveBaseMuxInput {
+                inner: self.inner.clone(),
+                index,
+            })
+            .collect()
+    }
+
+    /// Returns the lock handles in the order of the config.
+    pub fn locks(&self) -> Vec<MoveBaseMuxLock<M>> {
+        (0..self.inner.config.locks.len())
+            .map(|index| MoveBaseMuxLock {
+                inner: self.inner.clone(),
+                index,
+            })
+            .collect()
+    }
+
+    /// Returns the input handle of the given name.
+    pub fn input(&self, name: &str) -> Option<MoveBaseMuxInput<M>> {
+        let index = self
+            .inner
+            .config
+            .inputs
+            .iter()
+            .position(|input| input.name == name)?;
+        Some(MoveBaseMuxInput {
+            inner: self.inner.clone(),
+            index,
+        })
+    }
+
+    /// Returns the lock handle of the given name.
+    pub fn lock(&self, name: &str) -> Option<MoveBaseMuxLock<M>> {
+        let index = self
+            .inner
+            .config
+            .locks
+            .iter()
+            .position(|lock| lock.name == name)?;
+        Some(MoveBaseMuxLock {
+            inner: self.inner.clone(),
+            index,
+        })
+    }
+}
+
+/// Input of [`MoveBaseMux`].
+#[derive(Debug)]
+pub struct MoveBaseMuxInput<M>
+where
+    M: MoveBase,
+{
+    inner: Arc<MuxInner<M>>,
+    index: usize,
+}
+
+impl<M> MoveBaseMuxInput<M>
+where
+    M: MoveBase,
+{
+    pub fn name(&self) -> &str {
+        &self.inner.config.inputs[self.index].name
+    }
+}
+
+impl<M> Clone for MoveBaseMuxInput<M>
+where
+    M: MoveBase,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            index: self.index,
+        }
+    }
+}
+
+impl<M> MoveBase for MoveBaseMuxInput<M>
+where
+    M: MoveBase,
+{
+    fn send_velocity(&self, velocity: &BaseVelocity) -> Result<(), Error> {
+        self.inner.send_velocity(self.index, velocity)
+    }
+
+    fn current_velocity(&self) -> Result<BaseVelocity, Error> {
+        self.inner.client.current_velocity()
+    }
+}
+
+/// Lock of [`MoveBaseMux`], which blocks the inputs with lower priorities.
+#[derive(Debug)]
+pub struct MoveBaseMuxLock<M>
+where
+    M: MoveBase,
+{
+    inner: Arc<MuxInner<M>>,
+    index: usize,
+}
+
+impl<M> MoveBaseMuxLock<M>
+where
+    M: MoveBase,
+{
+    pub fn name(&self) -> &str {
+        &self.inner.config.locks[self.index].name
+    }
+
+    /// Locks, or extends the lock. The base is stopped when the lock becomes active.
+    pub fn lock(&self) -> Result<(), Error> {
+        self.inner.set_locked(self.index, true)
+    }
+
+    pub fn unlock(&self) -> Result<(), Error> {
+        self.inner.set_locked(self.index, false)
+    }
+
+    pub fn is_locked(&self) -> bool {
+        let state = self.inner.state.lock().unwrap();
+        self.inner
+            .is_lock_active(&state, self.index, Instant::now())
+    }
+}
+
+impl<M> Clone for MoveBaseMuxLock<M>
+where
+    M: MoveBase,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            index: self.index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use assert_approx_eq::assert_approx_eq;
+
+    use super::*;
+    use crate::DummyMoveBase;
+
+    fn config() -> MoveBaseMuxConfig {
+        MoveBaseMuxConfig {
+            inputs: vec![
+                MoveBaseMuxInputConfig {
+                    name: "navigation".to_owned(),
+                    priority: 10,
+                    timeout_secs: 0.5,
+                },
+                MoveBaseMuxInputConfig {
+                    name: "teleop".to_owned(),
+                    priority: 100,
+                    timeout_secs: 0.1,
+                },
+            ],
+            locks: vec![MoveBaseMuxLockConfig {
+                name: "pause".to_owned(),
+                priority: 50,
+                timeout_secs: 0.0,
+            }],
+        }
+    }
+
+    fn new_mux() -> (Arc<DummyMoveBase>, MoveBaseMux<Arc<DummyMoveBase>>) {
+        let base = Arc::new(DummyMoveBase::new());
+        let mux = MoveBaseMux::new(base.clone(), config()).unwrap();
+        (base, mux)
+    }
+
+    #[test]
+    fn test_priority() {
+        let (base, mux) = new_mux();
+        let navigation = mux.input("navigation").unwrap();
+        let teleop = mux.input("teleop").unwrap();
+        assert!(mux.input("unknown").is_none());
+        assert_eq!(teleop.name(), "teleop");
+        let names: Vec<_> = mux
+            .inputs()
+            .iter()
+            .map(|input| input.name().to_owned())
+            .collect();
+        assert_eq!(names, vec!["navigation", "teleop"]);
+        assert_eq!(mux.locks()[0].name(), "pause");
+
+        navigation
+            .send_velocity(&BaseVelocity::new(1.0, 0.0, 0.0))
+            .unwrap();
+        assert_approx_eq!(base.current_velocity().unwrap().x, 1.0);
+        teleop
+            .send_velocity(&BaseVelocity::new(2.0, 0.0, 0.0))
+            .unwrap();
+        assert_approx_eq!(base.current_velocity().unwrap().x, 2.0);
+        // teleop is active.
+        navigation
+            .send_velocity(&BaseVelocity::new(1.0, 0.0, 0.0))
+            .unwrap();
+        assert_approx_eq!(base.current_velocity().unwrap().x, 2.0);
+
+        // teleop is timed out.
+        std::thread::sleep(Duration::from_millis(200));
+        navigation
+            .send_velocity(&BaseVelocity::new(1.0, 0.0, 0.0))
+            .unwrap();
+        assert_approx_eq!(base.current_velocity().unwrap().x, 1.0);
+    }
+
+    #[test]
+    fn test_lock() {
+        let (base, mux) = new_mux();
+        let navigation = mux.input("navigation").unwrap();
+        let teleop = mux.input("teleop").unwrap();
+        let pause = mux.lock("pause").unwrap();
+
+        navigation
+            .send_velocity(&BaseVelocity::new(1.0, 0.0, 0.0))
+            .unwrap();
+        pause.lock().unwrap();
+        assert!(pause.is_locked());
+        // The base is stopped by the lock.
+        assert_approx_eq!(base.current_velocity().unwrap().x, 0.0);
+        navigation
+            .send_velocity(&BaseVelocity::new(1.0, 0.0, 0.0))
+            .unwrap();
+        assert_approx_eq!(base.current_velocity().unwrap().x, 0.0);
+        // Higher priority than the lock.
+        teleop
+            .send_velocity(&BaseVelocity::new(2.0, 0.0, 0.0))
+            .unwrap();
+        assert_approx_eq!(base.current_velocity().unwrap().x, 2.0);
+
+        pause.unlock().unwrap();
+        assert!(!pause.is_locked());
+        std::thread::sleep(Duration::from_millis(200));
+        navigation
+            .send_velocity(&BaseVelocity::new(1.0, 0.0, 0.0))
+            .unwrap();
+        assert_approx_eq!(base.current_velocity().unwrap().x, 1.0);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let base = Arc::new(DummyMoveBase::new());
+        let mut invalid = config();
+        invalid.inputs[0].timeout_secs = 0.0;
+        assert!(MoveBaseMux::new(base.clone(), invalid).is_err());
+        let mut invalid = config();
+        invalid.inputs[1].timeout_secs = f64::NAN;
+        assert!(MoveBaseMux::new(base.clone(), invalid).is_err());
+        let mut invalid = config();
+        invalid.inputs[1].timeout_secs = f64::INFINITY;
+        assert!(MoveBaseMux::new(base.clone(), invalid).is_err());
+        let mut invalid = config();
+        invalid.locks[0].timeout_secs = f64::INFINITY;
+        assert!(MoveBaseMux::new(base.clone(), invalid).is_err());
+        let mut invalid = config();
+        invalid.locks[0].timeout_secs = -1.0;
+        assert!(MoveBaseMux::new(base.clone(), invalid).is_err());
+        let mut invalid = config();
+        invalid.locks[0].name = "teleop".to_owned();
+        assert!(MoveBaseMux::new(base, invalid).is_err());
+    }
+}
@@ -63,6 +63,29 @@ pub enum Error {
         position: f64,
         limit: RangeInclusive<f64>,
     },
+    #[error(
+        "arci: Over torque limit: joint={}, time_from_start={:?}, torque={}, limit={}",
+        name,
+        time_from_start,
+        torque,
+        limit
+    )]
+    OverTorqueLimit {
+        name: String,
+        time_from_start: std::time::Duration,
+        torque: f64,
+        limit: f64,
+    },
+    #[error("arci: Position ({}, {}) is in the keep-out zone {:?}.", x, y, zone)]
+    InKeepOutZone { zone: String, x: f64, y: f64 },
+    #[error("arci: Position ({}, {}) is out of all the keep-in zones.", x, y)]
+    OutOfKeepInZones { x: f64, y: f64 },
+    #[error(
+        "arci: Frame mismatch : expected = {:?}, actual = {:?}",
+        expected,
+        actual
+    )]
+    FrameMismatch { expected: String, actual: String },
     #[error("arci: Failed to construct instance: {}", .0)]
     Lazy(Arc<Error>),
     #[error("arci: urdf: {:?}", .0)]
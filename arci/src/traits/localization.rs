@@ -1,9 +1,123 @@
 use auto_impl::auto_impl;
-use nalgebra::Isometry2;
+use nalgebra::{Isometry2, Matrix3, Vector3};
 
 use crate::error::Error;
 
+/// 2D pose with its uncertainty.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PoseWithCovariance {
+    pub pose: Isometry2<f64>,
+    /// Covariance of (x, y, theta).
+    pub covariance: Matrix3<f64>,
+}
+
+impl PoseWithCovariance {
+    pub fn new(pose: Isometry2<f64>, covariance: Matrix3<f64>) -> Self {
+        Self { pose, covariance }
+    }
+
+    /// Creates a new `PoseWithCovariance` from standard deviations of
+    /// position and orientation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use assert_approx_eq::assert_approx_eq;
+    /// use arci::{Isometry2, PoseWithCovariance, Vector2};
+    ///
+    /// let pose = PoseWithCovariance::from_std_dev(Isometry2::new(Vector2::new(1.0, 2.0), 0.5), 0.5, 0.1);
+    /// assert_approx_eq!(pose.covariance[(0, 0)], 0.25);
+    /// assert_approx_eq!(pose.covariance[(1, 1)], 0.25);
+    /// assert_approx_eq!(pose.covariance[(2, 2)], 0.01);
+    /// assert_approx_eq!(pose.covariance[(0, 1)], 0.0);
+    /// ```
+    pub fn from_std_dev(pose: Isometry2<f64>, position_std_dev: f64, theta_std_dev: f64) -> Self {
+        let position_variance = position_std_dev.powi(2);
+        Self {
+            pose,
+            covariance: Matrix3::from_diagonal(&Vector3::new(
+                position_variance,
+                position_variance,
+                theta_std_dev.powi(2),
+            )),
+        }
+    }
+
+    /// Returns the standard deviation of the position, i.e. the square root of
+    /// the larger eigenvalue of the x-y covariance.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use assert_approx_eq::assert_approx_eq;
+    /// use arci::{Isometry2, PoseWithCovariance};
+    ///
+    /// let pose = PoseWithCovariance::from_std_dev(Isometry2::identity(), 0.3, 0.1);
+    /// assert_approx_eq!(pose.position_std_dev(), 0.3);
+    /// ```
+    pub fn position_std_dev(&self) -> f64 {
+        let xx = self.covariance[(0, 0)];
+        let yy = self.covariance[(1, 1)];
+        let xy = self.covariance[(0, 1)];
+        let half_trace = (xx + yy) / 2.0;
+        let d = ((xx - yy).powi(2) / 4.0 + xy.powi(2)).sqrt();
+        (half_trace + d).max(0.0).sqrt()
+    }
+
+    /// Returns the standard deviation of the orientation.
+    pub fn theta_std_dev(&self) -> f64 {
+        self.covariance[(2, 2)].max(0.0).sqrt()
+    }
+}
+
+impl From<Isometry2<f64>> for PoseWithCovariance {
+    /// Creates a new `PoseWithCovariance` with zero covariance.
+    fn from(pose: Isometry2<f64>) -> Self {
+        Self::new(pose, Matrix3::zeros())
+    }
+}
+
 #[auto_impl(Box, Arc)]
 pub trait Localization: Send + Sync {
     fn current_pose(&self, frame_id: &str) -> Result<Isometry2<f64>, Error>;
+
+    /// Returns the current pose with its covariance.
+    ///
+    /// The default implementation returns [`Self::current_pose`] with zero covariance.
+    fn current_pose_with_covariance(&self, frame_id: &str) -> Result<PoseWithCovariance, Error> {
+        Ok(self.current_pose(frame_id)?.into())
+    }
+
+    /// Resets the estimated pose to the specified pose.
+    ///
+    /// The default implementation returns an error because resetting the pose is not supported.
+    fn set_initial_pose(&self, _pose: PoseWithCovariance, _frame_id: &str) -> Result<(), Error> {
+        Err(Error::Other(anyhow::format_err!(
+            "set_initial_pose is not supported"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector2;
+
+    use super::*;
+
+    struct PoseOnlyLocalization;
+
+    impl Localization for PoseOnlyLocalization {
+        fn current_pose(&self, _frame_id: &str) -> Result<Isometry2<f64>, Error> {
+            Ok(Isometry2::new(Vector2::new(1.0, 2.0), 0.5))
+        }
+    }
+
+    #[test]
+    fn test_default_methods() {
+        let loc = PoseOnlyLocalization;
+        let pose = loc.current_pose_with_covariance("map").unwrap();
+        assert_eq!(pose.pose, loc.current_pose("map").unwrap());
+        assert_eq!(pose.covariance, Matrix3::zeros());
+        assert!(loc.set_initial_pose(pose, "map").is_err());
+    }
 }
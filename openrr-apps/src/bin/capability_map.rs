@@ -0,0 +1,171 @@
+use std::path::PathBuf;
+
+use anyhow::{format_err, Result};
+use k::nalgebra as na;
+use openrr_apps::utils::init_tracing;
+use openrr_planner::{CapabilityMap, JacobianIkSolver};
+use structopt::StructOpt;
+use tracing::{debug, info};
+
+/// Generates the capability map of the arm and finds the base placements to reach the target.
+#[derive(Debug, StructOpt)]
+#[structopt(name = env!("CARGO_BIN_NAME"), rename_all = "snake_case")]
+enum Args {
+    /// Generate the map and save it as JSON.
+    Generate {
+        /// Path to the URDF or xacro file.
+        #[structopt(parse(from_os_str))]
+        urdf_path: PathBuf,
+        /// Name of the end of the arm.
+        #[structopt(short, long)]
+        end_name: String,
+        /// Name of the root of the arm. The root of the robot is used if not specified.
+        /// Only the joints between the root and the end are moved, and the map is
+        /// still in the frame of the root of the robot.
+        #[structopt(short, long)]
+        root_name: Option<String>,
+        /// Minimum corner of the grid in the frame of the root of the robot [m].
+        #[structopt(
+            long,
+            number_of_values = 3,
+            allow_hyphen_values = true,
+            required = true
+        )]
+        min: Vec<f64>,
+        /// Maximum corner of the grid in the frame of the root of the robot [m].
+        #[structopt(
+            long,
+            number_of_values = 3,
+            allow_hyphen_values = true,
+            required = true
+        )]
+        max: Vec<f64>,
+        /// Length of the edges of the voxels [m].
+        #[structopt(short, long, default_value = "0.1")]
+        voxel_size: f64,
+        /// Number of the sampled directions of the x axis of the end.
+        #[structopt(long, default_value = "26")]
+        num_directions: usize,
+        /// Number of the sampled rotations around the x axis of the end.
+        #[structopt(long, default_value = "4")]
+        num_rolls: usize,
+        /// Max number of the iterations of IK.
+        #[structopt(long, default_value = "100")]
+        num_max_try: usize,
+        /// Output path of the map.
+        #[structopt(short, long, parse(from_os_str))]
+        output: PathBuf,
+        /// Output path of the voxels as CSV for the visualization.
+        #[structopt(long, parse(from_os_str))]
+        csv: Option<PathBuf>,
+    },
+    /// Print the best base placements to reach the target pose.
+    BasePlacement {
+        /// Path to the map created by `generate`.
+        #[structopt(parse(from_os_str))]
+        map_path: PathBuf,
+        x: f64,
+        y: f64,
+        z: f64,
+        #[structopt(long, default_value = "0.0", allow_hyphen_values = true)]
+        roll: f64,
+        #[structopt(long, default_value = "0.0", allow_hyphen_values = true)]
+        pitch: f64,
+        #[structopt(long, default_value = "0.0", allow_hyphen_values = true)]
+        yaw: f64,
+        /// Number of the sampled yaw angles of the base.
+        #[structopt(long, default_value = "16")]
+        num_yaws: usize,
+        /// Number of the printed placements.
+        #[structopt(short, long, default_value = "5")]
+        num_results: usize,
+    },
+}
+
+fn main() -> Result<()> {
+    init_tracing();
+    let args = Args::from_args();
+    debug!(?args);
+
+    match args {
+        Args::Generate {
+            urdf_path,
+            end_name,
+            root_name,
+            min,
+            max,
+            voxel_size,
+            num_directions,
+            num_rolls,
+            num_max_try,
+            output,
+            csv,
+        } => {
+            let urdf_robot = urdf_rs::utils::read_urdf_or_xacro(&urdf_path)?;
+            let robot = k::Chain::<f64>::from(&urdf_robot);
+            let find = |name: &str| {
+                robot
+                    .find(name)
+                    .ok_or_else(|| format_err!("{} is not found", name))
+            };
+            let arm = match &root_name {
+                Some(root_name) => {
+                    k::SerialChain::from_end_to_root(find(&end_name)?, find(root_name)?)
+                }
+                None => k::SerialChain::from_end(find(&end_name)?),
+            };
+            let solver = JacobianIkSolver::new(0.005, 0.005, 0.5, num_max_try);
+            let map = CapabilityMap::generate(
+                &solver,
+                &arm,
+                na::Vector3::from_column_slice(&min),
+                na::Vector3::from_column_slice(&max),
+                voxel_size,
+                num_directions,
+                num_rolls,
+            )?;
+            info!(
+                "{} of {} voxels are reachable",
+                map.voxels.len(),
+                map.num_voxels.iter().product::<usize>()
+            );
+            map.save(&output)?;
+            if let Some(csv) = csv {
+                std::fs::write(csv, map.to_csv())?;
+            }
+        }
+        Args::BasePlacement {
+            map_path,
+            x,
+            y,
+            z,
+            roll,
+            pitch,
+            yaw,
+            num_yaws,
+            num_results,
+        } => {
+            let map = CapabilityMap::<f64>::load(&map_path)?;
+            let target_pose = na::Isometry3::from_parts(
+                na::Translation3::new(x, y, z),
+                na::UnitQuaternion::from_euler_angles(roll, pitch, yaw),
+            );
+            let placements = map.base_placements(&target_pose, num_yaws);
+            if placements.is_empty() {
+                return Err(format_err!("no base placements are found"));
+            }
+            for placement in placements.iter().take(num_results) {
+                println!(
+                    "x: {:.3}, y: {:.3}, yaw: {:.3}, reachability: {:.3}, manipulability: {:.4}",
+                    placement.pose.translation.x,
+                    placement.pose.translation.y,
+                    placement.pose.rotation.angle(),
+                    placement.reachability,
+                    placement.manipulability
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
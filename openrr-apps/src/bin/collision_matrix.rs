@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use openrr_apps::utils::init_tracing;
+use openrr_planner::{collision::CollisionMatrix, CollisionDetector};
+use serde::Serialize;
+use structopt::{clap::arg_enum, StructOpt};
+use tracing::{debug, info};
+
+/// Generates the self collision pairs by sampling random configurations of the robot.
+#[derive(Debug, StructOpt)]
+#[structopt(name = env!("CARGO_BIN_NAME"))]
+struct Args {
+    /// Path to the URDF or xacro file.
+    #[structopt(parse(from_os_str))]
+    urdf_path: PathBuf,
+    /// Number of the random configurations.
+    #[structopt(short, long, default_value = "10000")]
+    num_samples: usize,
+    /// Output format: `self_collision_check_pairs` of the robot config (toml)
+    /// or `disable_collisions` of SRDF (srdf).
+    #[structopt(
+        short,
+        long,
+        possible_values = &OutputFormat::variants(),
+        case_insensitive = true,
+        default_value = "toml"
+    )]
+    format: OutputFormat,
+}
+
+arg_enum! {
+    #[derive(Debug)]
+    enum OutputFormat {
+        Toml,
+        Srdf,
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SelfCollisionCheckPairs {
+    self_collision_check_pairs: Vec<String>,
+}
+
+fn main() -> Result<()> {
+    init_tracing();
+    let args = Args::from_args();
+    debug!(?args);
+
+    let urdf_robot = urdf_rs::utils::read_urdf_or_xacro(&args.urdf_path)?;
+    let robot = k::Chain::<f64>::from(&urdf_robot);
+    let detector =
+        CollisionDetector::from_urdf_robot_with_base_dir(&urdf_robot, args.urdf_path.parent(), 0.0);
+    let matrix = CollisionMatrix::generate(&robot, &detector, args.num_samples)?;
+    info!(
+        "{} pairs to check, {} pairs disabled",
+        matrix.check_pairs.len(),
+        matrix.disabled_collisions.len()
+    );
+
+    match args.format {
+        OutputFormat::Toml => {
+            let pairs = SelfCollisionCheckPairs {
+                self_collision_check_pairs: matrix.colon_separated_check_pairs(),
+            };
+            print!("{}", toml::to_string_pretty(&pairs)?);
+        }
+        OutputFormat::Srdf => print!("{}", matrix.to_srdf(&urdf_robot)),
+    }
+
+    Ok(())
+}
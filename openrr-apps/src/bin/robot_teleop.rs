@@ -103,7 +103,7 @@ async fn main() -> Result<()> {
             client.joint_trajectory_clients(),
             client.ik_solvers(),
             Some(client.clone()),
-            robot_config.openrr_clients_config.joints_poses,
+            client.joints_poses(),
         )
         .unwrap();
     if nodes.is_empty() {
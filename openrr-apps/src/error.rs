@@ -21,6 +21,10 @@ pub enum Error {
     NoPluginInstance { kind: String, name: String },
     #[error("openrr-apps: {}", .0)]
     DuplicateInstance(String),
+    #[error("openrr-apps: No MoveBase multiplexer input {:?} is found.", .0)]
+    NoMoveBaseMuxInput(String),
+    #[error("openrr-apps: Geofence requires Localization.")]
+    NoLocalizationForGeofence,
     #[error("openrr-apps: Config {:?} requires ros feature.", .0)]
     ConfigRequireRos(String),
     #[error("openrr-apps: urdf: {:?}", .0)]
@@ -7,7 +7,11 @@ use std::{
 };
 
 use anyhow::format_err;
-use arci::{JointTrajectoryClient, Localization, MoveBase, Navigation, Speaker};
+use arci::{
+    BaseVelocityLimiter, BaseVelocityLimiterConfig, Geofence, GeofenceClient, GeofenceConfig,
+    GeofenceZones, JointTrajectoryClient, Localization, MoveBase, MoveBaseMux, MoveBaseMuxConfig,
+    Navigation, Speaker,
+};
 #[cfg(feature = "ros")]
 use arci_ros::{
     RosCmdVelMoveBase, RosCmdVelMoveBaseConfig, RosControlClientConfig, RosEspeakClient,
@@ -25,6 +29,9 @@ use tracing::{debug, error, info};
 
 use crate::Error;
 
+/// MoveBase multiplexer returned by [`RobotConfig::create_robot_client_with_move_base_mux`].
+pub type BoxMoveBaseMux = MoveBaseMux<Box<dyn MoveBase>>;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum BuiltinClient {
@@ -355,6 +362,9 @@ pub struct RobotConfig {
     /// Navigation to be used. `"ros"`, `"urdf-viz"`, `false`, or plugin instance name.
     #[serde(default)]
     pub navigation: ClientKind,
+    /// Input of the MoveBase multiplexer to be used by the robot client. The
+    /// first input is used if not specified.
+    pub move_base_mux_input: Option<String>,
 
     #[cfg(feature = "ros")]
     #[serde(default)]
@@ -394,6 +404,15 @@ pub struct RobotConfig {
     #[schemars(schema_with = "unimplemented_schema")]
     ros_localization_client_config: Option<toml::Value>,
 
+    /// Limits the velocities and the accelerations of the MoveBase.
+    pub base_velocity_limiter_config: Option<BaseVelocityLimiterConfig>,
+
+    /// Multiplexes the commands to the MoveBase by priorities.
+    pub move_base_mux_config: Option<MoveBaseMuxConfig>,
+
+    /// Keep-in and keep-out zones enforced on the MoveBase and the Navigation.
+    pub geofence_config: Option<GeofenceConfig>,
+
     #[serde(default)]
     pub openrr_clients_config: OpenrrClientsConfig,
 
@@ -428,7 +447,24 @@ impl RobotConfig {
 
     pub fn create_robot_client<L, M, N>(&self) -> Result<RobotClient<L, M, N>, Error>
     where
-        L: Localization + From<Box<dyn Localization>>,
+        L: Localization + From<Box<dyn Localization>> + 'static,
+        M: MoveBase + From<Box<dyn MoveBase>>,
+        N: Navigation + From<Box<dyn Navigation>>,
+    {
+        Ok(self.create_robot_client_with_move_base_mux()?.0)
+    }
+
+    /// Creates the robot client, and returns the MoveBase multiplexer too if
+    /// `move_base_mux_config` is set.
+    ///
+    /// The MoveBase of the robot client is the input `move_base_mux_input` of
+    /// the multiplexer. The other inputs and the locks of the multiplexer can
+    /// be used by the other components in the same process.
+    pub fn create_robot_client_with_move_base_mux<L, M, N>(
+        &self,
+    ) -> Result<(RobotClient<L, M, N>, Option<BoxMoveBaseMux>), Error>
+    where
+        L: Localization + From<Box<dyn Localization>> + 'static,
         M: MoveBase + From<Box<dyn MoveBase>>,
         N: Navigation + From<Box<dyn Navigation>>,
     {
@@ -440,18 +476,56 @@ impl RobotConfig {
 
         let joint_trajectory_clients = self.create_raw_joint_trajectory_clients(&mut plugins)?;
         let speakers = self.create_speakers(&mut plugins)?;
-        let localization = self.create_localization(&mut plugins)?;
-        let move_base = self.create_move_base(&mut plugins)?;
-        let navigation = self.create_navigation(&mut plugins)?;
+        let localization: Option<Arc<dyn Localization>> =
+            self.create_localization(&mut plugins)?.map(Arc::from);
+        let geofence = match self.create_geofence()? {
+            Some(geofence) => Some((
+                geofence,
+                localization
+                    .clone()
+                    .ok_or(Error::NoLocalizationForGeofence)?,
+            )),
+            None => None,
+        };
+        let (move_base, move_base_mux) = self.create_move_base(&mut plugins, geofence.as_ref())?;
+        let mut navigation = self.create_navigation(&mut plugins)?;
+        if let Some((geofence, localization)) = &geofence {
+            navigation = navigation.map(|navigation| -> Box<dyn Navigation> {
+                Box::new(GeofenceClient::new(
+                    navigation,
+                    localization.clone(),
+                    geofence.clone(),
+                ))
+            });
+        }
 
-        Ok(RobotClient::new(
+        let robot_client = RobotClient::new(
             self.openrr_clients_config.clone(),
             joint_trajectory_clients,
             speakers,
-            localization.map(L::from),
+            localization.map(|localization| {
+                let localization: Box<dyn Localization> = Box::new(localization);
+                L::from(localization)
+            }),
             move_base.map(M::from),
             navigation.map(N::from),
-        )?)
+        )?;
+        Ok((robot_client, move_base_mux))
+    }
+
+    fn create_geofence(&self) -> Result<Option<Geofence>, Error> {
+        let mut config = match &self.geofence_config {
+            Some(config) => config.clone(),
+            None => return Ok(None),
+        };
+        if let Some(path) = config.zones_path.clone() {
+            let zones: GeofenceZones = toml::from_str(
+                &fs::read_to_string(&path).map_err(|e| Error::NoFile(path.to_owned(), e))?,
+            )
+            .map_err(|e| Error::TomlParseFailure(path.to_owned(), e))?;
+            config.extend_zones(zones);
+        }
+        Ok(Some(Geofence::new(config)))
     }
 
     fn create_localization_urdf_viz(&self) -> Box<dyn Localization> {
@@ -466,7 +540,7 @@ impl RobotConfig {
         let config = self.ros_localization_client_config.clone()?;
         Some(Box::new(arci::Lazy::new(move || {
             debug!("create_localization_ros: creating RosLocalizationClient");
-            Ok(RosLocalizationClient::new_from_config(config))
+            RosLocalizationClient::new_from_config(config)
         })))
     }
 
@@ -617,6 +691,50 @@ impl RobotConfig {
     fn create_move_base(
         &self,
         plugins: &mut PluginMap,
+        geofence: Option<&(Geofence, Arc<dyn Localization>)>,
+    ) -> Result<(Option<Box<dyn MoveBase>>, Option<BoxMoveBaseMux>), Error> {
+        let mut move_base = self.create_raw_move_base(plugins)?;
+        // The geofence is inside the limiter, so that it also clips the
+        // velocities ramped by the limiter, e.g. while the base decelerates.
+        if let Some((geofence, localization)) = geofence {
+            move_base = move_base.map(|move_base| -> Box<dyn MoveBase> {
+                Box::new(GeofenceClient::new(
+                    move_base,
+                    localization.clone(),
+                    geofence.clone(),
+                ))
+            });
+        }
+        if let Some(config) = &self.base_velocity_limiter_config {
+            move_base = move_base
+                .map(|move_base| -> Result<Box<dyn MoveBase>, Error> {
+                    Ok(Box::new(BaseVelocityLimiter::new(
+                        move_base,
+                        config.clone(),
+                    )?))
+                })
+                .transpose()?;
+        }
+        match (&self.move_base_mux_config, move_base) {
+            (Some(config), Some(move_base)) => {
+                let input_name = self
+                    .move_base_mux_input
+                    .as_deref()
+                    .or_else(|| config.inputs.first().map(|input| input.name.as_str()))
+                    .unwrap_or_default();
+                let mux = MoveBaseMux::new(move_base, config.clone())?;
+                let input = mux
+                    .input(input_name)
+                    .ok_or_else(|| Error::NoMoveBaseMuxInput(input_name.to_owned()))?;
+                Ok((Some(Box::new(input)), Some(mux)))
+            }
+            (_, move_base) => Ok((move_base, None)),
+        }
+    }
+
+    fn create_raw_move_base(
+        &self,
+        plugins: &mut PluginMap,
     ) -> Result<Option<Box<dyn MoveBase>>, Error> {
         let (plugin_name, instance) = match &self.move_base {
             ClientKind::Auto(false) => return Ok(None),
@@ -915,12 +1033,22 @@ impl RobotConfig {
 
         if config.openrr_clients_config.urdf_path.is_some() {
             config.openrr_clients_config.resolve_path(path)?;
+        } else {
+            config
+                .openrr_clients_config
+                .resolve_joints_poses_path(path)?;
         }
         for speak_config in config.speak_configs.values_mut() {
             if let SpeakConfig::Audio { ref mut map } = speak_config {
                 resolve_audio_file_path(path, map)?;
             }
         }
+        if let Some(geofence_config) = &mut config.geofence_config {
+            if let Some(zones_path) = geofence_config.zones_path.take() {
+                geofence_config.zones_path =
+                    Some(openrr_client::resolve_relative_path(path, &zones_path)?);
+            }
+        }
         for plugin_config in config.plugins.values_mut() {
             resolve_plugin_path(&mut plugin_config.path, path)?;
             for instance in &mut plugin_config.instances {
@@ -1,4 +1,6 @@
+use arci::{Localization, MoveBase, Navigation};
 use openrr_apps::RobotConfig;
+use openrr_client::BoxRobotClient;
 
 #[test]
 fn verify_sample_configs() {
@@ -31,3 +33,47 @@ fn verify_sample_configs() {
 fn ser_default_config() {
     toml::to_string(&RobotConfig::default()).unwrap();
 }
+
+#[test]
+fn create_robot_client_with_move_base_mux() {
+    let mut config: RobotConfig = toml::from_str(
+        r#"
+localization = false
+move_base = "urdf-viz"
+navigation = false
+move_base_mux_input = "teleop"
+
+[move_base_mux_config]
+inputs = [
+    { name = "navigation", priority = 10, timeout_secs = 0.5 },
+    { name = "teleop", priority = 100, timeout_secs = 0.1 },
+]
+locks = [{ name = "pause", priority = 50 }]
+"#,
+    )
+    .unwrap();
+    let (_client, mux): (BoxRobotClient, _) =
+        config.create_robot_client_with_move_base_mux().unwrap();
+    let mux = mux.unwrap();
+    let names: Vec<_> = mux
+        .inputs()
+        .iter()
+        .map(|input| input.name().to_owned())
+        .collect();
+    assert_eq!(names, vec!["navigation", "teleop"]);
+    assert_eq!(mux.locks()[0].name(), "pause");
+
+    config.move_base_mux_input = Some("unknown".to_owned());
+    assert!(matches!(
+        config
+            .create_robot_client::<Box<dyn Localization>, Box<dyn MoveBase>, Box<dyn Navigation>>(),
+        Err(openrr_apps::Error::NoMoveBaseMuxInput(..))
+    ));
+
+    // The timeouts of the inputs must be positive.
+    config.move_base_mux_input = None;
+    config.move_base_mux_config.as_mut().unwrap().inputs[0].timeout_secs = 0.0;
+    assert!(config
+        .create_robot_client_with_move_base_mux::<Box<dyn Localization>, Box<dyn MoveBase>, Box<dyn Navigation>>()
+        .is_err());
+}
@@ -1,13 +1,19 @@
 mod chain_wrapper;
 mod collision_avoidance_client;
 mod collision_check_client;
+mod dual_arm_client;
 mod ik_client;
 mod local_move;
 mod print_speaker;
+mod torque_limit_client;
+mod whole_body_ik_client;
 
 pub use chain_wrapper::*;
 pub use collision_avoidance_client::*;
 pub use collision_check_client::*;
+pub use dual_arm_client::*;
 pub use ik_client::*;
 pub use local_move::*;
 pub use print_speaker::*;
+pub use torque_limit_client::*;
+pub use whole_body_ik_client::*;
@@ -1,10 +1,10 @@
 use std::{path::Path, sync::Arc};
 
 use arci::{Error, JointTrajectoryClient, TrajectoryPoint, WaitFuture};
-use openrr_planner::JointPathPlannerBuilder;
+use openrr_planner::{collision::AttachedObject, JointPathPlannerBuilder};
 
 // TODO: speed limit
-fn trajectory_from_positions(
+pub(crate) fn trajectory_from_positions(
     positions: &[Vec<f64>],
     total_duration: std::time::Duration,
 ) -> Vec<TrajectoryPoint> {
@@ -45,6 +45,23 @@ where
             planner,
         }
     }
+
+    /// Attaches the object to the link(joint) in the collision checking of the planner
+    pub fn attach_object(&self, name: &str, object: AttachedObject<f64>) {
+        self.planner
+            .robot_collision_detector
+            .collision_detector
+            .attach_object(name, object);
+    }
+
+    /// Detaches the object, and returns false if it is not attached.
+    pub fn detach_object(&self, name: &str) -> bool {
+        self.planner
+            .robot_collision_detector
+            .collision_detector
+            .detach_object(name)
+            .is_some()
+    }
 }
 
 impl<T> JointTrajectoryClient for CollisionAvoidanceClient<T>
@@ -169,5 +186,27 @@ mod tests {
                 std::time::Duration::new(1, 0),
             )
             .is_err());
+
+        // Ball at the elbow
+        robot.update_transforms();
+        let object = AttachedObject::from_urdf_geometry(
+            "root",
+            &urdf_rs::Geometry::Sphere { radius: 0.05 },
+            robot
+                .find("l_elbow_pitch")
+                .unwrap()
+                .world_transform()
+                .unwrap(),
+        )
+        .unwrap();
+        collision_avoidance_client.attach_object("ball", object);
+        assert!(collision_avoidance_client
+            .send_joint_positions(vec![0.0; 8], std::time::Duration::new(1, 0))
+            .is_err());
+        assert!(collision_avoidance_client.detach_object("ball"));
+        assert!(!collision_avoidance_client.detach_object("ball"));
+        assert!(collision_avoidance_client
+            .send_joint_positions(vec![0.0; 8], std::time::Duration::new(1, 0))
+            .is_ok());
     }
 }
@@ -0,0 +1,304 @@
+use std::time::Duration;
+
+use arci::{Error, JointTrajectoryClient, WaitFuture};
+use k::Isometry3;
+use ncollide3d::shape::Compound;
+use openrr_planner::{
+    collision::AttachedObject, DualArmPlanner, JacobianIkSolver, JointPathPlannerBuilder,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::collision_avoidance_client::trajectory_from_positions;
+use crate::{create_jacobian_ik_solver, IkSolverConfig, IkSolverParameters};
+
+/// Plans the motions of both of the arms at once and sends them as one trajectory
+///
+/// The collisions between the arms are avoided. If `objects` of the methods
+/// are given, the collisions with them are also avoided.
+pub struct DualArmClient<T>
+where
+    T: JointTrajectoryClient,
+{
+    /// Client of the joints of both of the arms, e.g. the container of the
+    /// clients of the left and the right arm. The joints of the left arm come first.
+    pub client: T,
+    pub planner: DualArmPlanner<f64, JacobianIkSolver<f64>>,
+}
+
+impl<T> DualArmClient<T>
+where
+    T: JointTrajectoryClient,
+{
+    pub fn new(client: T, planner: DualArmPlanner<f64, JacobianIkSolver<f64>>) -> Self {
+        if planner.joint_names() != client.joint_names() {
+            panic!(
+                "Invalid configuration : dual arm joints {:?} != joint_names {:?}",
+                planner.joint_names(),
+                client.joint_names()
+            );
+        }
+        Self { client, planner }
+    }
+
+    /// Attaches the object to the link(joint) in the collision checking of the planner
+    pub fn attach_object(&self, name: &str, object: AttachedObject<f64>) {
+        self.planner
+            .path_planner
+            .robot_collision_detector
+            .collision_detector
+            .attach_object(name, object);
+    }
+
+    /// Detaches the object, and returns false if it is not attached.
+    pub fn detach_object(&self, name: &str) -> bool {
+        self.planner
+            .path_planner
+            .robot_collision_detector
+            .collision_detector
+            .detach_object(name)
+            .is_some()
+    }
+
+    fn set_current_joint_positions(&self) -> Result<(), Error> {
+        self.planner
+            .arms
+            .set_joint_positions_clamped(&self.client.current_joint_positions()?);
+        Ok(())
+    }
+
+    /// Current poses of the ends of the left and the right arm
+    pub fn current_end_transforms(&self) -> Result<(Isometry3<f64>, Isometry3<f64>), Error> {
+        self.set_current_joint_positions()?;
+        Ok((
+            self.planner.left_arm.end_transform(),
+            self.planner.right_arm.end_transform(),
+        ))
+    }
+
+    /// Moves both of the arms to the positions without the collisions
+    pub fn move_joints(
+        &self,
+        positions: &[f64],
+        duration_sec: f64,
+        objects: Option<&Compound<f64>>,
+    ) -> Result<WaitFuture, Error> {
+        self.set_current_joint_positions()?;
+        let path = self
+            .planner
+            .plan(positions, objects)
+            .map_err(|e| Error::Other(e.into()))?;
+        self.send_path(&path, duration_sec)
+    }
+
+    /// Moves the ends of the arms to the target poses
+    pub fn move_ik(
+        &self,
+        left_target_pose: &Isometry3<f64>,
+        right_target_pose: &Isometry3<f64>,
+        duration_sec: f64,
+        objects: Option<&Compound<f64>>,
+    ) -> Result<WaitFuture, Error> {
+        self.set_current_joint_positions()?;
+        let path = self
+            .planner
+            .plan_with_ik(left_target_pose, right_target_pose, objects)
+            .map_err(|e| Error::Other(e.into()))?;
+        self.send_path(&path, duration_sec)
+    }
+
+    /// Moves the end of the left arm to the target pose keeping the current
+    /// relative pose of the ends, e.g. while both of the arms hold one object
+    pub fn move_holding_ik(
+        &self,
+        left_target_pose: &Isometry3<f64>,
+        duration_sec: f64,
+        objects: Option<&Compound<f64>>,
+    ) -> Result<WaitFuture, Error> {
+        self.set_current_joint_positions()?;
+        let path = self
+            .planner
+            .plan_holding_with_ik(left_target_pose, objects)
+            .map_err(|e| Error::Other(e.into()))?;
+        self.send_path(&path, duration_sec)
+    }
+
+    fn send_path(&self, path: &[Vec<f64>], duration_sec: f64) -> Result<WaitFuture, Error> {
+        // Both of the arms follow the same timing.
+        self.client.send_joint_trajectory(trajectory_from_positions(
+            path,
+            Duration::from_secs_f64(duration_sec),
+        ))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DualArmClientConfig {
+    pub name: String,
+    /// Client of the joints of both of the arms. The joints of the left arm come first.
+    pub client_name: String,
+    /// IK solver whose tolerances are used for both of the arms.
+    /// `root_node_name`, `ik_target`, `use_random_ik` and `constraints` of the solver are not used.
+    pub solver_name: String,
+    pub left_ik_target: String,
+    pub right_ik_target: String,
+}
+
+pub fn create_dual_arm_client<T>(
+    urdf_robot: urdf_rs::Robot,
+    self_collision_check_pairs: &[String],
+    solver_config: &IkSolverConfig,
+    config: &DualArmClientConfig,
+    client: T,
+) -> Result<DualArmClient<T>, Error>
+where
+    T: JointTrajectoryClient,
+{
+    let pairs = openrr_planner::collision::parse_colon_separated_pairs(self_collision_check_pairs)
+        .map_err(|e| Error::Other(e.into()))?;
+    let path_planner = JointPathPlannerBuilder::from_urdf_robot(urdf_robot)
+        .self_collision_pairs(pairs)
+        .finalize();
+    let planner = DualArmPlanner::new(
+        path_planner,
+        create_jacobian_ik_solver(&IkSolverParameters {
+            allowable_position_error: solver_config.allowable_position_error_m,
+            allowable_angle_error: solver_config.allowable_angle_error_rad,
+            jacobian_multiplier: solver_config.jacobian_multiplier,
+            num_max_try: solver_config.num_max_try,
+        }),
+        &config.left_ik_target,
+        &config.right_ik_target,
+    )
+    .map_err(|e| Error::Other(e.into()))?;
+    Ok(DualArmClient::new(client, planner))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arci::{DummyJointTrajectoryClient, JointTrajectoryClientsContainer};
+    use assert_approx_eq::assert_approx_eq;
+
+    use super::*;
+
+    fn two_arms_urdf() -> String {
+        let arm = |prefix: &str, y: f64| {
+            format!(
+                r#"<link name="{p}_yaw_link"/>
+<joint name="{p}_yaw" type="revolute">
+  <origin xyz="0 {y} 0.5"/>
+  <parent link="base"/>
+  <child link="{p}_yaw_link"/>
+  <axis xyz="0 0 1"/>
+  <limit lower="-2" upper="2" effort="1" velocity="1"/>
+</joint>
+<link name="{p}_pitch_link"/>
+<joint name="{p}_pitch" type="revolute">
+  <origin xyz="0 0 0.1"/>
+  <parent link="{p}_yaw_link"/>
+  <child link="{p}_pitch_link"/>
+  <axis xyz="0 1 0"/>
+  <limit lower="-2" upper="2" effort="1" velocity="1"/>
+</joint>
+<link name="{p}_end_link"/>
+<joint name="{p}_end" type="fixed">
+  <origin xyz="0.2 0 0"/>
+  <parent link="{p}_pitch_link"/>
+  <child link="{p}_end_link"/>
+</joint>
+"#,
+                p = prefix,
+                y = y
+            )
+        };
+        format!(
+            r#"<robot name="two_arms">
+<link name="base"/>
+{}{}</robot>"#,
+            arm("l", 0.2),
+            arm("r", -0.2)
+        )
+    }
+
+    #[test]
+    fn test_create_dual_arm_client() {
+        let urdf_robot = urdf_rs::read_file("sample.urdf").unwrap();
+        let solver_config = IkSolverConfig::new(None, "l_tool_fixed".to_owned());
+        let config = DualArmClientConfig {
+            name: "arms".to_owned(),
+            client_name: "arms".to_owned(),
+            solver_name: "arm".to_owned(),
+            left_ik_target: "l_gripper_linear1".to_owned(),
+            right_ik_target: "l_gripper_linear2".to_owned(),
+        };
+        let client = arci::DummyJointTrajectoryClient::new(vec![
+            "l_shoulder_yaw".to_owned(),
+            "l_shoulder_pitch".to_owned(),
+            "l_shoulder_roll".to_owned(),
+            "l_elbow_pitch".to_owned(),
+            "l_wrist_yaw".to_owned(),
+            "l_wrist_pitch".to_owned(),
+            "l_gripper_linear1".to_owned(),
+            "l_gripper_linear2".to_owned(),
+        ]);
+        // The fingers share the joints of the arm.
+        assert!(
+            create_dual_arm_client(urdf_robot.clone(), &[], &solver_config, &config, client)
+                .is_err()
+        );
+
+        let config = DualArmClientConfig {
+            right_ik_target: "no_such_link".to_owned(),
+            ..config
+        };
+        let client = arci::DummyJointTrajectoryClient::new(vec![]);
+        assert!(create_dual_arm_client(urdf_robot, &[], &solver_config, &config, client).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dual_arm_client_move_joints() {
+        let urdf_robot = urdf_rs::read_from_string(&two_arms_urdf()).unwrap();
+        let solver_config = IkSolverConfig::new(None, "l_end".to_owned());
+        let config = DualArmClientConfig {
+            name: "arms".to_owned(),
+            client_name: "arms".to_owned(),
+            solver_name: "arm".to_owned(),
+            left_ik_target: "l_end".to_owned(),
+            right_ik_target: "r_end".to_owned(),
+        };
+        let left = Arc::new(DummyJointTrajectoryClient::new(vec![
+            "l_yaw".to_owned(),
+            "l_pitch".to_owned(),
+        ]));
+        let right = Arc::new(DummyJointTrajectoryClient::new(vec![
+            "r_yaw".to_owned(),
+            "r_pitch".to_owned(),
+        ]));
+        let client = create_dual_arm_client(
+            urdf_robot,
+            &[],
+            &solver_config,
+            &config,
+            JointTrajectoryClientsContainer::new(vec![left.clone(), right.clone()]),
+        )
+        .unwrap();
+
+        client
+            .move_joints(&[0.5, 0.3, -0.5, -0.3], 0.1, None)
+            .unwrap()
+            .await
+            .unwrap();
+        // The trajectory is split into the clients of the arms.
+        let left_positions = left.current_joint_positions().unwrap();
+        assert_approx_eq!(left_positions[0], 0.5);
+        assert_approx_eq!(left_positions[1], 0.3);
+        let right_positions = right.current_joint_positions().unwrap();
+        assert_approx_eq!(right_positions[0], -0.5);
+        assert_approx_eq!(right_positions[1], -0.3);
+        assert!(!left.last_trajectory.lock().unwrap().is_empty());
+        assert!(!right.last_trajectory.lock().unwrap().is_empty());
+    }
+}
@@ -1,10 +1,16 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
 
 use arci::{Error, JointTrajectoryClient, TrajectoryPoint, WaitFuture};
 use k::{nalgebra as na, Constraints, Isometry3};
+use openrr_planner::KinematicAnalysis;
 use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
 use serde::{Deserialize, Serialize};
 
+use crate::VelocityProfile;
+
 pub fn isometry(x: f64, y: f64, z: f64, roll: f64, pitch: f64, yaw: f64) -> k::Isometry3<f64> {
     k::Isometry3::from_parts(
         k::Translation3::new(x, y, z),
@@ -52,6 +58,13 @@ impl IkSolverWithChain {
         self.ik_arm.joint_positions()
     }
 
+    /// Jacobian and the measures of the singularity at the current joint positions of the chain
+    ///
+    /// Only the rows and the joints used by the constraints of the solver are analyzed.
+    pub fn kinematic_analysis(&self) -> KinematicAnalysis<f64> {
+        KinematicAnalysis::with_constraints(&self.ik_arm, &self.constraints)
+    }
+
     pub fn solve_with_constraints(
         &self,
         target_pose: &k::Isometry3<f64>,
@@ -113,31 +126,118 @@ impl IkSolverWithChain {
         max_resolution: f64,
         min_number_of_points: i32,
     ) -> Result<Vec<TrajectoryPoint>, Error> {
+        self.generate_trajectory_with_velocity_profile(
+            current_pose,
+            target_pose,
+            constraints,
+            VelocityProfile::Linear,
+            duration_sec,
+            max_resolution,
+            min_number_of_points,
+        )
+    }
+
+    /// Interpolates the poses with the velocity profile, and fills the velocities of the trajectory.
+    ///
+    /// The velocities are not filled with [`VelocityProfile::Linear`], which
+    /// starts and stops abruptly, as [`Self::generate_trajectory_with_interpolation`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_trajectory_with_velocity_profile(
+        &self,
+        current_pose: &Isometry3<f64>,
+        target_pose: &Isometry3<f64>,
+        constraints: &Constraints,
+        velocity_profile: VelocityProfile,
+        duration_sec: f64,
+        max_resolution: f64,
+        min_number_of_points: i32,
+    ) -> Result<Vec<TrajectoryPoint>, Error> {
+        self.generate_trajectory_with_end_offset(
+            current_pose,
+            target_pose,
+            &Isometry3::identity(),
+            constraints,
+            velocity_profile,
+            duration_sec,
+            max_resolution,
+            min_number_of_points,
+        )
+    }
+
+    /// Interpolates the poses of the frame at `end_offset` from the end of the chain
+    #[allow(clippy::too_many_arguments)]
+    fn generate_trajectory_with_end_offset(
+        &self,
+        current_pose: &Isometry3<f64>,
+        target_pose: &Isometry3<f64>,
+        end_offset: &Isometry3<f64>,
+        constraints: &Constraints,
+        velocity_profile: VelocityProfile,
+        duration_sec: f64,
+        max_resolution: f64,
+        min_number_of_points: i32,
+    ) -> Result<Vec<TrajectoryPoint>, Error> {
+        let end_offset_inverse = end_offset.inverse();
         let target_position = target_pose.translation.vector;
         let target_rotation = target_pose.rotation;
         let current_position = current_pose.translation.vector;
         let current_rotation = current_pose.rotation;
 
         let position_diff = target_position - current_position;
+        // The points are denser than the resolution where the speed is lower than the peak.
         let n = std::cmp::max(
             min_number_of_points,
-            (position_diff.norm() / max_resolution) as i32,
+            (position_diff.norm() * velocity_profile.max_velocity() / max_resolution) as i32,
         );
+        // Progresses and joint positions of the points including the current positions
+        let mut progresses = vec![0.0];
+        let mut positions = vec![self.joint_positions()];
         let mut traj = vec![];
         for i in 1..n + 1 {
             let t = i as f64 / n as f64;
-            let tar_pos = current_position.lerp(&target_position, t);
-            let tar_rot = current_rotation.slerp(&target_rotation, t);
+            let s = velocity_profile.position(t);
+            let tar_pos = current_position.lerp(&target_position, s);
+            let tar_rot = openrr_planner::slerp_rotation(&current_rotation, &target_rotation, s);
             self.solve_with_constraints(
-                &k::Isometry3::from_parts(na::Translation3::from(tar_pos), tar_rot),
+                &(k::Isometry3::from_parts(na::Translation3::from(tar_pos), tar_rot)
+                    * end_offset_inverse),
                 constraints,
             )?;
+            progresses.push(s);
+            positions.push(self.joint_positions());
             let trajectory = TrajectoryPoint::new(
                 self.joint_positions(),
                 std::time::Duration::from_secs_f64(t * duration_sec),
             );
             traj.push(trajectory);
         }
+        if velocity_profile == VelocityProfile::Linear {
+            return Ok(traj);
+        }
+        // The joint velocities are the derivatives of the positions with respect to
+        // the progress, which are approximated by the differences, times the speed of the progress.
+        for (i, point) in traj.iter_mut().enumerate() {
+            let t = (i + 1) as f64 / n as f64;
+            let ds_dt = if duration_sec > 0.0 {
+                velocity_profile.velocity(t) / duration_sec
+            } else {
+                0.0
+            };
+            let prev = i;
+            let next = std::cmp::min(i + 2, n as usize);
+            let ds = progresses[next] - progresses[prev];
+            point.velocities = Some(
+                positions[prev]
+                    .iter()
+                    .zip(&positions[next])
+                    .map(|(p, q)| if ds > 0.0 { (q - p) / ds * ds_dt } else { 0.0 })
+                    .collect(),
+            );
+        }
+        // Stops at the target.
+        if let Some(last) = traj.last_mut() {
+            last.velocities = Some(vec![0.0; self.ik_arm.dof()]);
+        }
         Ok(traj)
     }
 }
@@ -148,6 +248,12 @@ where
 {
     pub client: T,
     pub ik_solver_with_chain: Arc<IkSolverWithChain>,
+    /// Offsets of the named frames, e.g. tool center points, from the end of the chain
+    end_frames: HashMap<String, Isometry3<f64>>,
+    /// Frame used by IK. `None` means the end of the chain.
+    current_end_frame: RwLock<Option<String>>,
+    /// Velocity profile of the interpolated motions
+    velocity_profile: RwLock<VelocityProfile>,
 }
 
 impl<T> IkClient<T>
@@ -171,13 +277,98 @@ where
         Self {
             client,
             ik_solver_with_chain,
+            end_frames: HashMap::new(),
+            current_end_frame: RwLock::new(None),
+            velocity_profile: RwLock::new(VelocityProfile::default()),
+        }
+    }
+
+    /// Adds the frame at `offset` from the end of the chain, which can be used by [`Self::set_end_frame`]
+    pub fn add_end_frame(&mut self, name: &str, offset: Isometry3<f64>) {
+        self.end_frames.insert(name.to_owned(), offset);
+    }
+
+    pub fn end_frame_names(&self) -> Vec<String> {
+        self.end_frames.keys().map(|k| k.to_owned()).collect()
+    }
+
+    /// Frame used by IK and [`Self::current_end_transform`]. `None` means the end of the chain.
+    pub fn end_frame(&self) -> Option<String> {
+        self.current_end_frame.read().unwrap().clone()
+    }
+
+    /// Switches the frame used by IK and [`Self::current_end_transform`]. `None` means the end of the chain.
+    pub fn set_end_frame(&self, name: Option<&str>) -> Result<(), crate::Error> {
+        if let Some(name) = name {
+            if !self.end_frames.contains_key(name) {
+                return Err(crate::Error::NoEndFrame(name.to_owned()));
+            }
         }
+        *self.current_end_frame.write().unwrap() = name.map(|n| n.to_owned());
+        Ok(())
+    }
+
+    /// Offset of the current end frame from the end of the chain
+    pub fn end_frame_offset(&self) -> Isometry3<f64> {
+        match &*self.current_end_frame.read().unwrap() {
+            Some(name) => self.end_frames[name],
+            None => Isometry3::identity(),
+        }
+    }
+
+    /// Velocity profile used by the interpolated motions, e.g. [`Self::move_ik_with_interpolation`]
+    pub fn velocity_profile(&self) -> VelocityProfile {
+        *self.velocity_profile.read().unwrap()
+    }
+
+    pub fn set_velocity_profile(&self, velocity_profile: VelocityProfile) {
+        *self.velocity_profile.write().unwrap() = velocity_profile;
+    }
+
+    /// Offset of the current end frame, which can be used with `constraints`.
+    ///
+    /// IK solves for the end of the chain, so the end frame follows the target
+    /// only if the rotation is fully constrained when the end frame is
+    /// translated from the end of the chain.
+    fn end_frame_offset_with_constraints(
+        &self,
+        constraints: &Constraints,
+    ) -> Result<Isometry3<f64>, Error> {
+        let offset = self.end_frame_offset();
+        if offset.translation.vector.norm() > 0.0
+            && !(constraints.rotation_x && constraints.rotation_y && constraints.rotation_z)
+        {
+            return Err(Error::Other(anyhow::format_err!(
+                "End frame {:?} is translated from the end of the chain, which requires all the rotations to be constrained",
+                self.end_frame(),
+            )));
+        }
+        Ok(offset)
+    }
+
+    /// Pose of the end of the chain to move the current end frame to `target_pose`
+    fn chain_end_pose(
+        &self,
+        target_pose: &Isometry3<f64>,
+        constraints: &Constraints,
+    ) -> Result<Isometry3<f64>, Error> {
+        Ok(target_pose
+            * self
+                .end_frame_offset_with_constraints(constraints)?
+                .inverse())
     }
 
     pub fn current_end_transform(&self) -> Result<k::Isometry3<f64>, Error> {
         let current_joint_angles = self.client.current_joint_positions()?;
         self.set_joint_positions_clamped(&current_joint_angles);
-        Ok(self.ik_solver_with_chain.end_transform())
+        Ok(self.ik_solver_with_chain.end_transform() * self.end_frame_offset())
+    }
+
+    /// Jacobian and the measures of the singularity at the current joint positions
+    pub fn current_kinematic_analysis(&self) -> Result<KinematicAnalysis<f64>, Error> {
+        let current_joint_angles = self.client.current_joint_positions()?;
+        self.set_joint_positions_clamped(&current_joint_angles);
+        Ok(self.ik_solver_with_chain.kinematic_analysis())
     }
 
     pub fn move_ik_with_constraints(
@@ -187,7 +378,7 @@ where
         duration_sec: f64,
     ) -> Result<WaitFuture, Error> {
         self.ik_solver_with_chain
-            .solve_with_constraints(target_pose, constraints)?;
+            .solve_with_constraints(&self.chain_end_pose(target_pose, constraints)?, constraints)?;
 
         let positions = self.ik_solver_with_chain.joint_positions();
         let duration = std::time::Duration::from_secs_f64(duration_sec);
@@ -200,19 +391,63 @@ where
         constraints: &Constraints,
         duration_sec: f64,
     ) -> Result<WaitFuture, Error> {
-        let mut traj = self
+        let current_pose = self.current_end_transform()?;
+        self.move_ik_with_interpolation_from(&current_pose, target_pose, constraints, duration_sec)
+    }
+
+    /// Moves with the interpolation taking the shortest duration in which the
+    /// linear speed \[m/s\] and the angular speed \[rad/s\] of the end frame do
+    /// not exceed the limits.
+    ///
+    /// The speeds must be positive. [`f64::INFINITY`] means no limit, but at
+    /// least one of the speeds must be finite.
+    pub fn move_ik_with_max_speed(
+        &self,
+        target_pose: &k::Isometry3<f64>,
+        max_linear_speed: f64,
+        max_angular_speed: f64,
+    ) -> Result<WaitFuture, Error> {
+        if max_linear_speed.is_infinite() && max_angular_speed.is_infinite() {
+            return Err(Error::Other(anyhow::format_err!(
+                "Either the max linear speed or the max angular speed must be finite"
+            )));
+        }
+        let current_pose = self.current_end_transform()?;
+        let velocity_profile = self.velocity_profile();
+        let distance = (target_pose.translation.vector - current_pose.translation.vector).norm();
+        let angle = current_pose.rotation.angle_to(&target_pose.rotation);
+        let duration_sec = f64::max(
+            velocity_profile.min_duration(distance, max_linear_speed)?,
+            velocity_profile.min_duration(angle, max_angular_speed)?,
+        );
+        self.move_ik_with_interpolation_from(
+            &current_pose,
+            target_pose,
+            self.constraints(),
+            duration_sec,
+        )
+    }
+
+    fn move_ik_with_interpolation_from(
+        &self,
+        current_pose: &k::Isometry3<f64>,
+        target_pose: &k::Isometry3<f64>,
+        constraints: &Constraints,
+        duration_sec: f64,
+    ) -> Result<WaitFuture, Error> {
+        let end_offset = self.end_frame_offset_with_constraints(constraints)?;
+        let traj = self
             .ik_solver_with_chain
-            .generate_trajectory_with_interpolation_and_constraints(
-                &self.current_end_transform()?,
+            .generate_trajectory_with_end_offset(
+                current_pose,
                 target_pose,
+                &end_offset,
                 constraints,
+                self.velocity_profile(),
                 duration_sec,
                 0.05,
                 10,
             )?;
-        let dof = self.client.joint_names().len();
-        traj.first_mut().unwrap().velocities = Some(vec![0.0; dof]);
-        traj.last_mut().unwrap().velocities = Some(vec![0.0; dof]);
         self.client.send_joint_trajectory(traj)
     }
 
@@ -221,7 +456,8 @@ where
         target_pose: &k::Isometry3<f64>,
         duration_sec: f64,
     ) -> Result<WaitFuture, Error> {
-        self.ik_solver_with_chain.solve(target_pose)?;
+        self.ik_solver_with_chain
+            .solve(&self.chain_end_pose(target_pose, self.constraints())?)?;
 
         let positions = self.ik_solver_with_chain.joint_positions();
         let duration = std::time::Duration::from_secs_f64(duration_sec);
@@ -233,19 +469,11 @@ where
         target_pose: &k::Isometry3<f64>,
         duration_sec: f64,
     ) -> Result<WaitFuture, Error> {
-        let mut traj = self
-            .ik_solver_with_chain
-            .generate_trajectory_with_interpolation(
-                &self.current_end_transform()?,
-                target_pose,
-                duration_sec,
-                0.05,
-                10,
-            )?;
-        let dof = self.client.joint_names().len();
-        traj.first_mut().unwrap().velocities = Some(vec![0.0; dof]);
-        traj.last_mut().unwrap().velocities = Some(vec![0.0; dof]);
-        self.client.send_joint_trajectory(traj)
+        self.move_ik_with_interpolation_and_constraints(
+            target_pose,
+            self.constraints(),
+            duration_sec,
+        )
     }
 
     /// Get relative pose from current pose of the IK target
@@ -318,6 +546,22 @@ pub struct IkSolverConfig {
     pub constraints: Constraints,
 }
 
+impl IkSolverConfig {
+    /// Creates the config with the default parameters.
+    pub fn new(root_node_name: Option<String>, ik_target: String) -> Self {
+        Self {
+            root_node_name,
+            ik_target,
+            use_random_ik: false,
+            allowable_position_error_m: default_allowable_position_error_m(),
+            allowable_angle_error_rad: default_allowable_angle_error_rad(),
+            jacobian_multiplier: default_jacobian_multiplier(),
+            num_max_try: default_num_max_try(),
+            constraints: Constraints::default(),
+        }
+    }
+}
+
 fn default_allowable_position_error_m() -> f64 {
     0.005
 }
@@ -401,3 +645,125 @@ pub fn create_ik_solver_with_chain(
         config.constraints.clone(),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use arci::DummyJointTrajectoryClient;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_end_frame() {
+        let chain = k::Chain::<f64>::from_urdf_file("sample.urdf").unwrap();
+        let mut solver_config =
+            IkSolverConfig::new(Some("l_shoulder_yaw".to_owned()), "l_tool_fixed".to_owned());
+        solver_config.num_max_try = 1000;
+        let solver = Arc::new(create_ik_solver_with_chain(&chain, &solver_config));
+        let joint_names = chain
+            .iter_joints()
+            .take(6)
+            .map(|j| j.name.clone())
+            .collect();
+        let mut client = IkClient::new(DummyJointTrajectoryClient::new(joint_names), solver);
+        let offset = isometry(0.0, 0.0, 0.1, 0.0, 0.0, 0.0);
+        client.add_end_frame("tip", offset);
+        assert!(client.set_end_frame(Some("no_frame")).is_err());
+        assert_eq!(client.end_frame(), None);
+
+        client
+            .send_joint_positions(
+                vec![0.2, 0.2, 0.0, -1.0, 0.0, 0.0],
+                std::time::Duration::from_secs(0),
+            )
+            .unwrap()
+            .await
+            .unwrap();
+        let end_pose = client.current_end_transform().unwrap();
+        client.set_end_frame(Some("tip")).unwrap();
+        assert_eq!(client.end_frame().as_deref(), Some("tip"));
+        let tip_pose = client.current_end_transform().unwrap();
+        assert!(
+            (tip_pose.translation.vector - (end_pose * offset).translation.vector).norm() < 1e-6
+        );
+
+        // Moves the tip to the current pose of the end of the chain.
+        client.move_ik(&end_pose, 0.1).unwrap().await.unwrap();
+        let tip_pose = client.current_end_transform().unwrap();
+        assert!((tip_pose.translation.vector - end_pose.translation.vector).norm() < 0.01);
+
+        // The tip does not follow the target if the rotation of the chain is free.
+        let constraints = Constraints {
+            rotation_z: false,
+            ..Default::default()
+        };
+        assert!(client
+            .move_ik_with_constraints(&end_pose, &constraints, 0.1)
+            .is_err());
+        assert!(client
+            .move_ik_with_interpolation_and_constraints(&end_pose, &constraints, 0.1)
+            .is_err());
+
+        client.set_end_frame(None).unwrap();
+        assert_eq!(client.end_frame(), None);
+    }
+
+    #[tokio::test]
+    async fn test_move_ik_with_max_speed() {
+        let chain = k::Chain::<f64>::from_urdf_file("sample.urdf").unwrap();
+        let mut solver_config =
+            IkSolverConfig::new(Some("l_shoulder_yaw".to_owned()), "l_tool_fixed".to_owned());
+        solver_config.num_max_try = 1000;
+        let solver = Arc::new(create_ik_solver_with_chain(&chain, &solver_config));
+        let joint_names = chain
+            .iter_joints()
+            .take(6)
+            .map(|j| j.name.clone())
+            .collect();
+        let client = IkClient::new(DummyJointTrajectoryClient::new(joint_names), solver);
+        client.set_velocity_profile(VelocityProfile::MinimumJerk);
+        assert_eq!(client.velocity_profile(), VelocityProfile::MinimumJerk);
+
+        client
+            .send_joint_positions(
+                vec![0.2, 0.2, 0.0, -1.0, 0.0, 0.0],
+                std::time::Duration::from_secs(0),
+            )
+            .unwrap()
+            .await
+            .unwrap();
+        let current_pose = client.current_end_transform().unwrap();
+        let target_pose = Isometry3::translation(0.0, 0.0, 0.05) * current_pose;
+        client
+            .move_ik_with_max_speed(&target_pose, 0.1, f64::INFINITY)
+            .unwrap()
+            .await
+            .unwrap();
+
+        let traj = client.client.last_trajectory.lock().unwrap().clone();
+        let last = traj.last().unwrap();
+        // 0.05 [m] * 1.875 / 0.1 [m/s]
+        assert!((last.time_from_start.as_secs_f64() - 0.9375).abs() < 1e-6);
+        assert_eq!(last.velocities, Some(vec![0.0; 6]));
+        // The motion starts slowly and is the fastest at the middle.
+        let speed = |point: &TrajectoryPoint| {
+            point
+                .velocities
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|v| v * v)
+                .sum::<f64>()
+                .sqrt()
+        };
+        assert!(speed(&traj[0]) < speed(&traj[traj.len() / 2]));
+        let tool_pose = client.current_end_transform().unwrap();
+        assert!((tool_pose.translation.vector - target_pose.translation.vector).norm() < 0.01);
+
+        assert!(client
+            .move_ik_with_max_speed(&target_pose, 0.0, f64::INFINITY)
+            .is_err());
+        assert!(client
+            .move_ik_with_max_speed(&target_pose, f64::INFINITY, f64::INFINITY)
+            .is_err());
+    }
+}
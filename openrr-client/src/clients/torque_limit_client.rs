@@ -0,0 +1,409 @@
+use std::{
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+use arci::{Error, JointTrajectoryClient, TrajectoryPoint, WaitFuture};
+use openrr_planner::{RigidBodyInertia, RobotDynamics};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Checks the joint torques required by the trajectories against the effort limits of URDF before sending them
+///
+/// The torques are estimated by the inverse dynamics from the current
+/// positions, assuming that the joints which are not of the client stay at
+/// their positions in the full chain. The trajectories which exceed the limits
+/// are rejected, or slowed down if `slow_down` is true.
+///
+/// Only the torques at the points of the trajectories are checked, not between
+/// them, so the trajectories should have enough points, e.g. by interpolation.
+pub struct TorqueLimitClient<T>
+where
+    T: JointTrajectoryClient,
+{
+    pub client: T,
+    /// Shared chain to read the positions of the other joints, which is not modified
+    full_chain: Arc<k::Chain<f64>>,
+    /// Private chain of the robot to calculate the dynamics
+    chain: Mutex<k::Chain<f64>>,
+    dynamics: RwLock<RobotDynamics<f64>>,
+    /// Indices of the joints of the client in the movable joints of the full chain
+    joint_indices: Vec<usize>,
+    /// Effort limits of the joints of the client. The non-positive values mean no limits.
+    effort_limits: Vec<f64>,
+    slow_down: bool,
+}
+
+impl<T> TorqueLimitClient<T>
+where
+    T: JointTrajectoryClient,
+{
+    pub fn new(
+        client: T,
+        urdf_robot: &urdf_rs::Robot,
+        full_chain: Arc<k::Chain<f64>>,
+        slow_down: bool,
+    ) -> Result<Self, Error> {
+        let full_joint_names: Vec<String> =
+            full_chain.iter_joints().map(|j| j.name.clone()).collect();
+        let mut joint_indices = vec![];
+        let mut effort_limits = vec![];
+        for name in client.joint_names() {
+            joint_indices.push(
+                full_joint_names
+                    .iter()
+                    .position(|n| *n == name)
+                    .ok_or_else(|| Error::NoJoint(name.clone()))?,
+            );
+            effort_limits.push(
+                urdf_robot
+                    .joints
+                    .iter()
+                    .find(|j| j.name == name)
+                    .map_or(0.0, |j| j.limit.effort),
+            );
+        }
+        Ok(Self {
+            client,
+            full_chain,
+            chain: Mutex::new(k::Chain::from(urdf_robot)),
+            dynamics: RwLock::new(RobotDynamics::from_urdf_robot(urdf_robot)),
+            joint_indices,
+            effort_limits,
+            slow_down,
+        })
+    }
+
+    /// Attaches the payload to the link moved by the joint, e.g. the object in the gripper
+    pub fn set_payload(&self, joint_name: &str, payload: RigidBodyInertia<f64>) {
+        self.dynamics
+            .write()
+            .unwrap()
+            .set_payload(joint_name, payload);
+    }
+
+    pub fn remove_payload(&self, joint_name: &str) -> Option<RigidBodyInertia<f64>> {
+        self.dynamics.write().unwrap().remove_payload(joint_name)
+    }
+
+    /// Torques of the joints of the client at the current positions and the points of `trajectory`
+    ///
+    /// The torques between the points are not calculated.
+    ///
+    /// The robot is assumed to be at rest at the current positions and the end
+    /// of the trajectory. The velocities not given in the trajectory are
+    /// estimated by the central differences of the positions, and the
+    /// accelerations by the differences of the average velocities of the segments.
+    /// Returns the torques and the gravity torques of each point.
+    pub fn required_torques(
+        &self,
+        trajectory: &[TrajectoryPoint],
+    ) -> Result<Vec<(Vec<f64>, Vec<f64>)>, Error> {
+        let dof = self.joint_indices.len();
+        let mut points = vec![TrajectoryPoint {
+            positions: self.client.current_joint_positions()?,
+            velocities: Some(vec![0.0; dof]),
+            time_from_start: Duration::from_secs(0),
+        }];
+        points.extend(trajectory.iter().cloned());
+        for point in &points {
+            if point.positions.len() != dof {
+                return Err(Error::LengthMismatch {
+                    model: dof,
+                    input: point.positions.len(),
+                });
+            }
+        }
+        let times: Vec<f64> = points
+            .iter()
+            .map(|p| p.time_from_start.as_secs_f64())
+            .collect();
+        let last = points.len() - 1;
+
+        let velocities: Vec<Vec<f64>> = (0..points.len())
+            .map(|i| match &points[i].velocities {
+                Some(velocities) => velocities.clone(),
+                None if i == last => vec![0.0; dof],
+                None => difference(
+                    &points[i - 1].positions,
+                    &points[i + 1].positions,
+                    times[i + 1] - times[i - 1],
+                ),
+            })
+            .collect();
+        // Average velocities of the segments between the points, with the rests before and after the trajectory
+        let mut segment_velocities = vec![vec![0.0; dof]];
+        for i in 0..last {
+            segment_velocities.push(difference(
+                &points[i].positions,
+                &points[i + 1].positions,
+                times[i + 1] - times[i],
+            ));
+        }
+        segment_velocities.push(vec![0.0; dof]);
+
+        let chain = self.chain.lock().unwrap();
+        let full_dof = chain.dof();
+        let initial_full_positions = self.full_chain.joint_positions();
+        if initial_full_positions.len() != full_dof {
+            return Err(Error::LengthMismatch {
+                model: full_dof,
+                input: initial_full_positions.len(),
+            });
+        }
+        let dynamics = self.dynamics.read().unwrap();
+        let mut torques = vec![];
+        for i in 0..points.len() {
+            let prev_time = if i == 0 { times[0] } else { times[i - 1] };
+            let next_time = if i == last { times[last] } else { times[i + 1] };
+            let accelerations = difference(
+                &segment_velocities[i],
+                &segment_velocities[i + 1],
+                (next_time - prev_time) / 2.0,
+            );
+            let mut full_positions = initial_full_positions.clone();
+            let mut full_velocities = vec![0.0; full_dof];
+            let mut full_accelerations = vec![0.0; full_dof];
+            for (j, &index) in self.joint_indices.iter().enumerate() {
+                full_positions[index] = points[i].positions[j];
+                full_velocities[index] = velocities[i][j];
+                full_accelerations[index] = accelerations[j];
+            }
+            chain.set_joint_positions_clamped(&full_positions);
+            let total = dynamics
+                .inverse_dynamics(&chain, &full_velocities, &full_accelerations)
+                .map_err(|e| Error::Other(e.into()))?;
+            let gravity = dynamics.gravity_torques(&chain);
+            torques.push((
+                self.joint_indices.iter().map(|&j| total[j]).collect(),
+                self.joint_indices.iter().map(|&j| gravity[j]).collect(),
+            ));
+        }
+        Ok(torques)
+    }
+
+    /// Returns the trajectory itself if the torques are within the limits,
+    /// the slowed down trajectory if `slow_down` is true, or the error of the
+    /// first point and joint which exceed the limit
+    pub fn check_joint_trajectory(
+        &self,
+        trajectory: Vec<TrajectoryPoint>,
+    ) -> Result<Vec<TrajectoryPoint>, Error> {
+        let torques = self.required_torques(&trajectory)?;
+        let joint_names = self.client.joint_names();
+        // The velocities become 1/s and the accelerations become 1/s^2 when
+        // the trajectory is s times as long, so the torques except the gravity
+        // torques become 1/s^2.
+        let mut scale: f64 = 1.0;
+        for (i, (total, gravity)) in torques.iter().enumerate() {
+            for (j, &limit) in self.effort_limits.iter().enumerate() {
+                if limit <= 0.0 || total[j].abs() <= limit {
+                    continue;
+                }
+                if !self.slow_down || gravity[j].abs() >= limit {
+                    return Err(Error::OverTorqueLimit {
+                        name: joint_names[j].clone(),
+                        time_from_start: if i == 0 {
+                            Duration::from_secs(0)
+                        } else {
+                            trajectory[i - 1].time_from_start
+                        },
+                        torque: total[j],
+                        limit,
+                    });
+                }
+                let dynamic_torque = (total[j] - gravity[j]).abs();
+                scale = scale.max((dynamic_torque / (limit - gravity[j].abs())).sqrt());
+            }
+        }
+        if scale <= 1.0 {
+            return Ok(trajectory);
+        }
+        debug!("The trajectory is slowed down by {}", scale);
+        Ok(trajectory
+            .into_iter()
+            .map(|point| TrajectoryPoint {
+                velocities: point
+                    .velocities
+                    .map(|v| v.iter().map(|v| v / scale).collect()),
+                time_from_start: point.time_from_start.mul_f64(scale),
+                positions: point.positions,
+            })
+            .collect())
+    }
+}
+
+/// (to - from) / dt, or zeros if dt is not positive
+fn difference(from: &[f64], to: &[f64], dt: f64) -> Vec<f64> {
+    if dt <= 0.0 {
+        return vec![0.0; from.len()];
+    }
+    from.iter().zip(to).map(|(f, t)| (t - f) / dt).collect()
+}
+
+impl<T> JointTrajectoryClient for TorqueLimitClient<T>
+where
+    T: JointTrajectoryClient,
+{
+    fn joint_names(&self) -> Vec<String> {
+        self.client.joint_names()
+    }
+
+    fn current_joint_positions(&self) -> Result<Vec<f64>, Error> {
+        self.client.current_joint_positions()
+    }
+
+    fn send_joint_positions(
+        &self,
+        positions: Vec<f64>,
+        duration: Duration,
+    ) -> Result<WaitFuture, Error> {
+        let mut trajectory =
+            self.check_joint_trajectory(vec![TrajectoryPoint::new(positions, duration)])?;
+        let point = trajectory.pop().unwrap();
+        self.client
+            .send_joint_positions(point.positions, point.time_from_start)
+    }
+
+    fn send_joint_trajectory(&self, trajectory: Vec<TrajectoryPoint>) -> Result<WaitFuture, Error> {
+        let trajectory = self.check_joint_trajectory(trajectory)?;
+        self.client.send_joint_trajectory(trajectory)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PayloadConfig {
+    /// Joint which moves the link holding the payload
+    pub joint_name: String,
+    /// Mass of the payload \[kg\]
+    pub mass: f64,
+    /// Center of mass in the frame of the joint \[m\]
+    #[serde(default)]
+    pub center_of_mass: [f64; 3],
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TorqueLimitClientConfig {
+    pub name: String,
+    pub client_name: String,
+    /// Slows down the trajectories which exceed the effort limits instead of rejecting them.
+    #[serde(default)]
+    pub slow_down: bool,
+    #[serde(default)]
+    // https://github.com/alexcrichton/toml-rs/issues/258
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub payloads: Vec<PayloadConfig>,
+}
+
+pub fn create_torque_limit_client<T>(
+    urdf_robot: &urdf_rs::Robot,
+    config: &TorqueLimitClientConfig,
+    client: T,
+    full_chain: Arc<k::Chain<f64>>,
+) -> Result<TorqueLimitClient<T>, Error>
+where
+    T: JointTrajectoryClient,
+{
+    let torque_limit_client =
+        TorqueLimitClient::new(client, urdf_robot, full_chain, config.slow_down)?;
+    for payload in &config.payloads {
+        torque_limit_client.set_payload(
+            &payload.joint_name,
+            RigidBodyInertia::point_mass(payload.mass, payload.center_of_mass.into()),
+        );
+    }
+    Ok(torque_limit_client)
+}
+
+#[cfg(test)]
+mod tests {
+    use arci::DummyJointTrajectoryClient;
+    use k::nalgebra as na;
+
+    use super::*;
+
+    fn urdf_robot() -> urdf_rs::Robot {
+        urdf_rs::read_from_string(
+            r#"<robot name="pendulum">
+<link name="base"/>
+<link name="link1">
+  <inertial>
+    <origin xyz="1 0 0"/>
+    <mass value="1"/>
+    <inertia ixx="0" ixy="0" ixz="0" iyy="0" iyz="0" izz="0"/>
+  </inertial>
+</link>
+<joint name="joint1" type="revolute">
+  <parent link="base"/>
+  <child link="link1"/>
+  <axis xyz="0 1 0"/>
+  <limit lower="-3" upper="3" effort="15" velocity="1"/>
+</joint>
+</robot>"#,
+        )
+        .unwrap()
+    }
+
+    fn create_client(slow_down: bool) -> TorqueLimitClient<DummyJointTrajectoryClient> {
+        let urdf_robot = urdf_robot();
+        let config = TorqueLimitClientConfig {
+            name: "torque".to_owned(),
+            client_name: "arm".to_owned(),
+            slow_down,
+            payloads: vec![],
+        };
+        create_torque_limit_client(
+            &urdf_robot,
+            &config,
+            DummyJointTrajectoryClient::new(vec!["joint1".to_owned()]),
+            Arc::new(k::Chain::from(&urdf_robot)),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_torque_limit_client() {
+        let client = create_client(false);
+        // The gravity torque of the horizontal link is about 9.8Nm.
+        client
+            .send_joint_positions(vec![0.1], Duration::from_secs(1))
+            .unwrap()
+            .await
+            .unwrap();
+        // Too fast
+        match client.send_joint_positions(vec![1.5], Duration::from_millis(100)) {
+            Err(Error::OverTorqueLimit { name, limit, .. }) => {
+                assert_eq!(name, "joint1");
+                assert_eq!(limit, 15.0);
+            }
+            r => panic!("{:?}", r.map(|_| ())),
+        }
+        assert_eq!(client.current_joint_positions().unwrap(), vec![0.1]);
+
+        // Too heavy
+        client.set_payload(
+            "joint1",
+            RigidBodyInertia::point_mass(1.0, na::Vector3::new(1.0, 0.0, 0.0)),
+        );
+        assert!(client
+            .send_joint_positions(vec![0.0], Duration::from_secs(10))
+            .is_err());
+        assert!(client.remove_payload("joint1").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_torque_limit_client_slow_down() {
+        let client = create_client(true);
+        let trajectory = vec![TrajectoryPoint::new(vec![1.5], Duration::from_millis(100))];
+        let slowed = client.check_joint_trajectory(trajectory).unwrap();
+        assert!(slowed[0].time_from_start > Duration::from_millis(100));
+        for (total, _) in client.required_torques(&slowed).unwrap() {
+            assert!(total[0].abs() <= 15.0 + 1e-6, "{}", total[0]);
+        }
+        client.send_joint_trajectory(slowed).unwrap().await.unwrap();
+        assert_eq!(client.current_joint_positions().unwrap(), vec![1.5]);
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use arci::{Error, JointTrajectoryClient, Localization, Navigation, WaitFuture};
+use k::{nalgebra::Isometry2, Constraints, Isometry3};
+use openrr_planner::{
+    add_virtual_base_joints, base_pose_from_virtual_joint_positions, InverseKinematicsSolver,
+    WeightedJacobianIkSolver, VIRTUAL_BASE_JOINT_NAMES,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::IkSolverConfig;
+
+/// Result of the whole-body IK
+#[derive(Debug, Clone, PartialEq)]
+pub struct WholeBodyIkSolution {
+    /// Goal of the base relative to the current pose of the base
+    pub base_pose: Isometry2<f64>,
+    /// Positions of the joints of the arm
+    pub joint_positions: Vec<f64>,
+}
+
+/// IK solver of the chain from the virtual joints of the base to the IK target
+pub struct WholeBodyIkSolver {
+    /// Private chain with the virtual joints, which is modified while solving
+    chain: Mutex<k::SerialChain<f64>>,
+    solver: WeightedJacobianIkSolver<f64>,
+    constraints: Constraints,
+}
+
+impl WholeBodyIkSolver {
+    pub fn new(
+        chain: k::SerialChain<f64>,
+        solver: WeightedJacobianIkSolver<f64>,
+        constraints: Constraints,
+    ) -> Self {
+        Self {
+            chain: Mutex::new(chain),
+            solver,
+            constraints,
+        }
+    }
+
+    /// Names of the joints of the arm, without the virtual joints of the base
+    pub fn arm_joint_names(&self) -> Vec<String> {
+        self.chain
+            .lock()
+            .unwrap()
+            .iter_joints()
+            .skip(VIRTUAL_BASE_JOINT_NAMES.len())
+            .map(|j| j.name.clone())
+            .collect()
+    }
+
+    /// Solves the IK from the current joint positions of the arm
+    ///
+    /// `target_pose` is relative to the current pose of the base.
+    pub fn solve(
+        &self,
+        arm_positions: &[f64],
+        target_pose: &Isometry3<f64>,
+    ) -> Result<WholeBodyIkSolution, Error> {
+        let mut positions = vec![0.0; VIRTUAL_BASE_JOINT_NAMES.len()];
+        positions.extend_from_slice(arm_positions);
+        let chain = self.chain.lock().unwrap();
+        chain.set_joint_positions_clamped(&positions);
+        self.solver
+            .solve_with_constraints(&chain, target_pose, &self.constraints)
+            .map_err(|e| Error::Other(e.into()))?;
+        let positions = chain.joint_positions();
+        Ok(WholeBodyIkSolution {
+            base_pose: base_pose_from_virtual_joint_positions(&positions),
+            joint_positions: positions[VIRTUAL_BASE_JOINT_NAMES.len()..].to_vec(),
+        })
+    }
+}
+
+/// Moves the base and the arm to reach the targets out of the range of the arm
+pub struct WholeBodyIkClient<T>
+where
+    T: JointTrajectoryClient,
+{
+    pub client: T,
+    pub whole_body_ik_solver: Arc<WholeBodyIkSolver>,
+}
+
+impl<T> WholeBodyIkClient<T>
+where
+    T: JointTrajectoryClient + Clone + 'static,
+{
+    pub fn new(client: T, whole_body_ik_solver: Arc<WholeBodyIkSolver>) -> Self {
+        if whole_body_ik_solver.arm_joint_names() != client.joint_names() {
+            panic!(
+                "Invalid configuration : whole body ik arm joints {:?} != joint_names {:?}",
+                whole_body_ik_solver.arm_joint_names(),
+                client.joint_names()
+            );
+        }
+        Self {
+            client,
+            whole_body_ik_solver,
+        }
+    }
+
+    /// Solves the IK from the current joint positions
+    ///
+    /// `target_pose` is relative to the current pose of the base.
+    pub fn solve(&self, target_pose: &Isometry3<f64>) -> Result<WholeBodyIkSolution, Error> {
+        self.whole_body_ik_solver
+            .solve(&self.client.current_joint_positions()?, target_pose)
+    }
+
+    /// Sends the goal of the base to `navigation`, then moves the arm after the base reaches the goal
+    ///
+    /// The goal is calculated from the current pose in `frame_id` given by `localization`.
+    pub fn move_whole_body_ik<L, N>(
+        &self,
+        localization: &L,
+        navigation: &N,
+        frame_id: &str,
+        target_pose: &Isometry3<f64>,
+        duration_sec: f64,
+        navigation_timeout: Duration,
+    ) -> Result<WaitFuture, Error>
+    where
+        L: Localization,
+        N: Navigation,
+    {
+        let solution = self.solve(target_pose)?;
+        let base_goal = localization.current_pose(frame_id)? * solution.base_pose;
+        let base_wait = navigation.send_goal_pose(base_goal, frame_id, navigation_timeout)?;
+        let client = self.client.clone();
+        Ok(WaitFuture::new(async move {
+            base_wait.await?;
+            client
+                .send_joint_positions(
+                    solution.joint_positions,
+                    Duration::from_secs_f64(duration_sec),
+                )?
+                .await
+        }))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WholeBodyIkClientConfig {
+    pub name: String,
+    /// Client of all of the movable joints from the root of the robot to the IK target
+    pub client_name: String,
+    /// IK solver whose target, tolerances and constraints are used.
+    /// `root_node_name` and `use_random_ik` of the solver are not used.
+    pub solver_name: String,
+    /// Weight of the translation of the base, which must be positive and finite.
+    /// The larger weights move the base less than the arm.
+    #[serde(default = "default_base_weight")]
+    pub base_translation_weight: f64,
+    /// Weight of the rotation of the base, which must be positive and finite
+    #[serde(default = "default_base_weight")]
+    pub base_rotation_weight: f64,
+}
+
+fn default_base_weight() -> f64 {
+    10.0
+}
+
+pub fn create_whole_body_ik_solver(
+    urdf_robot: &urdf_rs::Robot,
+    solver_config: &IkSolverConfig,
+    config: &WholeBodyIkClientConfig,
+) -> Result<WholeBodyIkSolver, Error> {
+    // The chain for the whole body IK has its own virtual joints, so it is not shared.
+    let chain = add_virtual_base_joints(k::Chain::<f64>::from(urdf_robot));
+    let solver = WeightedJacobianIkSolver::new(
+        solver_config.allowable_position_error_m,
+        solver_config.allowable_angle_error_rad,
+        solver_config.jacobian_multiplier,
+        solver_config.num_max_try,
+    )
+    .joint_weight(VIRTUAL_BASE_JOINT_NAMES[0], config.base_translation_weight)
+    .joint_weight(VIRTUAL_BASE_JOINT_NAMES[1], config.base_translation_weight)
+    .joint_weight(VIRTUAL_BASE_JOINT_NAMES[2], config.base_rotation_weight);
+    solver.validate().map_err(|e| Error::Other(e.into()))?;
+    Ok(WholeBodyIkSolver::new(
+        k::SerialChain::from_end(chain.find(&solver_config.ik_target).unwrap()),
+        solver,
+        solver_config.constraints.clone(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use arci::{DummyJointTrajectoryClient, DummyLocalization, DummyNavigation};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_whole_body_ik_client() {
+        let urdf_robot = urdf_rs::read_file("sample.urdf").unwrap();
+        let mut solver_config = IkSolverConfig::new(None, "l_tool_fixed".to_owned());
+        solver_config.jacobian_multiplier = 0.5;
+        solver_config.num_max_try = 1000;
+        let config = WholeBodyIkClientConfig {
+            name: "whole_body".to_owned(),
+            client_name: "arm".to_owned(),
+            solver_name: "arm".to_owned(),
+            base_translation_weight: default_base_weight(),
+            base_rotation_weight: default_base_weight(),
+        };
+        for weight in &[0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let mut invalid_config = config.clone();
+            invalid_config.base_translation_weight = *weight;
+            assert!(
+                create_whole_body_ik_solver(&urdf_robot, &solver_config, &invalid_config).is_err()
+            );
+            let mut invalid_config = config.clone();
+            invalid_config.base_rotation_weight = *weight;
+            assert!(
+                create_whole_body_ik_solver(&urdf_robot, &solver_config, &invalid_config).is_err()
+            );
+        }
+        let solver =
+            Arc::new(create_whole_body_ik_solver(&urdf_robot, &solver_config, &config).unwrap());
+        let joint_names = solver.arm_joint_names();
+        assert_eq!(joint_names.len(), 6);
+        let client = WholeBodyIkClient::new(
+            Arc::new(DummyJointTrajectoryClient::new(joint_names)),
+            solver,
+        );
+
+        let chain = k::Chain::<f64>::from(&urdf_robot);
+        chain.update_transforms();
+        let mut target_pose = chain
+            .find("l_tool_fixed")
+            .unwrap()
+            .world_transform()
+            .unwrap();
+        target_pose.translation.vector.x += 2.0;
+
+        let solution = client.solve(&target_pose).unwrap();
+        assert!(
+            solution.base_pose.translation.vector.x > 1.0,
+            "{}",
+            solution.base_pose
+        );
+
+        let localization = DummyLocalization::new();
+        let navigation = DummyNavigation::new();
+        client
+            .move_whole_body_ik(
+                &localization,
+                &navigation,
+                "map",
+                &target_pose,
+                0.1,
+                Duration::from_secs(1),
+            )
+            .unwrap()
+            .await
+            .unwrap();
+        // The current pose of the base is the origin.
+        assert!(
+            (navigation.current_goal_pose().unwrap().translation.vector
+                - solution.base_pose.translation.vector)
+                .norm()
+                < 1e-6
+        );
+        assert_eq!(
+            client.client.current_joint_positions().unwrap(),
+            solution.joint_positions
+        );
+    }
+}
@@ -14,16 +14,30 @@ pub enum Error {
     NoFile(PathBuf, #[source] std::io::Error),
     #[error("openrr-client: No IkClient={} is found.", .0)]
     NoIkClient(String),
+    #[error("openrr-client: No end frame={} is found.", .0)]
+    NoEndFrame(String),
+    #[error("openrr-client: No WholeBodyIkClient={} is found.", .0)]
+    NoWholeBodyIkClient(String),
+    #[error("openrr-client: No DualArmClient={} is found.", .0)]
+    NoDualArmClient(String),
     #[error("openrr-client: No JointsPose {} {} is found.", .0, .1)]
     NoJointsPose(String, String),
     #[error("openrr-client: No HashMap item is found. HashMap={}, Key={}", .0, .1)]
     NoMapKey(String, String),
+    #[error("openrr-client: No Link={} is found.", .0)]
+    NoLink(String),
     #[error("openrr-client: No JointTrajectoryClient={} is found.", .0)]
     NoJointTrajectoryClient(String),
     #[error("openrr-client: No ParentDirectory {:?} is found.", .0)]
     NoParentDirectory(PathBuf),
     #[error("openrr-client: No UrdfPath is specified.")]
     NoUrdfPath,
+    #[error("openrr-client: No TransformBuffer is configured.")]
+    NoTransformBuffer,
+    #[error("openrr-client: No Localization is configured.")]
+    NoLocalization,
+    #[error("openrr-client: No Navigation is configured.")]
+    NoNavigation,
     #[error("openrr-client: Failed to parse {:?} as toml ({}).", .0, .1)]
     TomlParseFailure(PathBuf, #[source] toml::de::Error),
     #[error("openrr-client: urdf-rs: {:?}", .0)]
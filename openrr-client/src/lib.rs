@@ -7,7 +7,9 @@
 mod clients;
 mod error;
 mod robot_client;
+mod transform_buffer;
+mod velocity_profile;
 
 pub mod utils;
 
-pub use crate::{clients::*, error::*, robot_client::*};
+pub use crate::{clients::*, error::*, robot_client::*, transform_buffer::*, velocity_profile::*};
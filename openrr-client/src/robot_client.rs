@@ -1,26 +1,40 @@
 use std::{
     collections::HashMap,
+    fs,
     path::{Path, PathBuf},
-    sync::Arc,
-    time::Duration,
+    sync::{Arc, RwLock, Weak},
+    thread,
+    time::{Duration, SystemTime},
 };
 
 use arci::{
     BaseVelocity, Error as ArciError, JointTrajectoryClient, JointTrajectoryClientsContainer,
-    Localization, MoveBase, Navigation, Speaker, WaitFuture,
+    Localization, MoveBase, Navigation, PoseWithCovariance, Speaker, TransformResolver, WaitFuture,
 };
 use k::{nalgebra::Isometry2, Chain, Isometry3};
-use openrr_planner::{SelfCollisionChecker, SelfCollisionCheckerConfig};
+use ncollide3d::shape::Compound;
+use openrr_planner::{
+    collision::AttachedObject,
+    srdf::{self, Srdf},
+    KinematicAnalysis, SelfCollisionChecker, SelfCollisionCheckerConfig,
+};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use tracing::{debug, warn};
 
 use crate::{
-    create_collision_check_client, create_ik_solver_with_chain, CollisionCheckClient, Error,
-    IkClient, IkSolverConfig, IkSolverWithChain,
+    create_collision_check_client, create_dual_arm_client, create_ik_solver_with_chain,
+    create_torque_limit_client, create_whole_body_ik_solver, interpolate_joint_positions, isometry,
+    isometry3_from_isometry2, CollisionCheckClient, DualArmClient, DualArmClientConfig, Error,
+    IkClient, IkSolverConfig, IkSolverWithChain, JointLinks, LocalizationTransformConfig,
+    TorqueLimitClient, TorqueLimitClientConfig, TransformBuffer, TransformBufferConfig,
+    VelocityProfile, WholeBodyIkClient, WholeBodyIkClientConfig, WholeBodyIkSolution,
 };
 
+type ArcTorqueLimitClient = Arc<TorqueLimitClient<Arc<dyn JointTrajectoryClient>>>;
 type ArcIkClient = Arc<IkClient<Arc<dyn JointTrajectoryClient>>>;
+type ArcWholeBodyIkClient = Arc<WholeBodyIkClient<Arc<dyn JointTrajectoryClient>>>;
+type ArcDualArmClient = Arc<DualArmClient<Arc<dyn JointTrajectoryClient>>>;
 pub type ArcRobotClient =
     RobotClient<Arc<dyn Localization>, Arc<dyn MoveBase>, Arc<dyn Navigation>>;
 pub type BoxRobotClient =
@@ -39,24 +53,30 @@ where
     all_joint_trajectory_clients: HashMap<String, Arc<dyn JointTrajectoryClient>>,
     collision_check_clients:
         HashMap<String, Arc<CollisionCheckClient<Arc<dyn JointTrajectoryClient>>>>,
+    torque_limit_clients: HashMap<String, ArcTorqueLimitClient>,
     ik_clients: HashMap<String, ArcIkClient>,
+    whole_body_ik_clients: HashMap<String, ArcWholeBodyIkClient>,
+    dual_arm_clients: HashMap<String, ArcDualArmClient>,
     self_collision_checkers: HashMap<String, Arc<SelfCollisionChecker<f64>>>,
     ik_solvers: HashMap<String, Arc<IkSolverWithChain>>,
     speakers: HashMap<String, Arc<dyn Speaker>>,
-    localization: Option<L>,
+    localization: Option<Arc<L>>,
     move_base: Option<M>,
     navigation: Option<N>,
-    joints_poses: HashMap<String, HashMap<String, Vec<f64>>>,
+    joints_poses: RwLock<HashMap<String, HashMap<String, Vec<f64>>>>,
+    joints_poses_full_path: Option<PathBuf>,
+    transform_buffer_updater: Option<Arc<TransformBufferUpdater<L>>>,
+    urdf_joint_links: Vec<JointLinks>,
 }
 
 impl<L, M, N> RobotClient<L, M, N>
 where
-    L: Localization,
+    L: Localization + 'static,
     M: MoveBase,
     N: Navigation,
 {
     pub fn new(
-        config: OpenrrClientsConfig,
+        mut config: OpenrrClientsConfig,
         raw_joint_trajectory_clients: HashMap<String, Arc<dyn JointTrajectoryClient>>,
         speakers: HashMap<String, Arc<dyn Speaker>>,
         localization: Option<L>,
@@ -65,6 +85,19 @@ where
     ) -> Result<Self, Error> {
         debug!("{:?}", config);
 
+        if let Some(srdf_full_path) = config.srdf_full_path().map(Path::to_owned) {
+            debug!("Loading {:?}", srdf_full_path);
+            let urdf_full_path = config.urdf_full_path().ok_or(Error::NoUrdfPath)?;
+            let urdf_robot = urdf_rs::read_file(urdf_full_path)?;
+            let srdf = srdf::read_file(&srdf_full_path).map_err(|e| Error::Other(e.into()))?;
+            let client_joint_names = raw_joint_trajectory_clients
+                .iter()
+                .map(|(name, client)| (name.to_owned(), client.joint_names()))
+                .collect();
+            config.import_srdf(&srdf, &urdf_robot, &client_joint_names)?;
+        }
+        config.load_joints_poses()?;
+
         let mut all_joint_trajectory_clients = HashMap::new();
         for (name, client) in &raw_joint_trajectory_clients {
             all_joint_trajectory_clients.insert(name.to_owned(), client.clone());
@@ -83,9 +116,13 @@ where
         let (
             full_chain_for_collision_checker,
             collision_check_clients,
+            torque_limit_clients,
             ik_clients,
+            whole_body_ik_clients,
+            dual_arm_clients,
             self_collision_checkers,
             ik_solvers,
+            urdf_joint_links,
         ) = if let Some(urdf_full_path) = config.urdf_full_path() {
             debug!("Loading {:?}", urdf_full_path);
             let full_chain_for_collision_checker =
@@ -106,6 +143,20 @@ where
                 all_joint_trajectory_clients.insert(name.to_owned(), client.clone());
             }
 
+            let torque_limit_clients = if config.torque_limit_clients_configs.is_empty() {
+                HashMap::new()
+            } else {
+                create_torque_limit_clients(
+                    &urdf_rs::read_file(&urdf_full_path)?,
+                    &config.torque_limit_clients_configs,
+                    &all_joint_trajectory_clients,
+                    full_chain_for_collision_checker.clone(),
+                )?
+            };
+            for (name, client) in &torque_limit_clients {
+                all_joint_trajectory_clients.insert(name.to_owned(), client.clone());
+            }
+
             let mut ik_solvers = HashMap::new();
             for (k, c) in &config.ik_solvers_configs {
                 ik_solvers.insert(
@@ -126,12 +177,44 @@ where
             for (name, client) in &ik_clients {
                 all_joint_trajectory_clients.insert(name.to_owned(), client.clone());
             }
+
+            let whole_body_ik_clients = if config.whole_body_ik_clients_configs.is_empty() {
+                HashMap::new()
+            } else {
+                create_whole_body_ik_clients(
+                    &config.whole_body_ik_clients_configs,
+                    &urdf_rs::read_file(&urdf_full_path)?,
+                    &config.ik_solvers_configs,
+                    &all_joint_trajectory_clients,
+                )?
+            };
+
+            let mut dual_arm_clients = HashMap::new();
+            for c in &config.dual_arm_clients_configs {
+                dual_arm_clients.insert(
+                    c.name.to_owned(),
+                    Arc::new(create_dual_arm_client(
+                        urdf_rs::read_file(&urdf_full_path)?,
+                        &config.self_collision_check_pairs,
+                        &config.ik_solvers_configs[&c.solver_name],
+                        c,
+                        all_joint_trajectory_clients[&c.client_name].clone(),
+                    )?),
+                );
+            }
+
+            let urdf_joint_links =
+                JointLinks::from_urdf_robot(&urdf_rs::read_file(&urdf_full_path)?);
             (
                 Some(full_chain_for_collision_checker),
                 collision_check_clients,
+                torque_limit_clients,
                 ik_clients,
+                whole_body_ik_clients,
+                dual_arm_clients,
                 self_collision_checkers,
                 ik_solvers,
+                urdf_joint_links,
             )
         } else {
             (
@@ -140,6 +223,10 @@ where
                 HashMap::new(),
                 HashMap::new(),
                 HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+                vec![],
             )
         };
         let mut joints_poses: HashMap<String, HashMap<String, Vec<f64>>> = HashMap::new();
@@ -152,49 +239,49 @@ where
                     joints_pose.positions.to_owned(),
                 );
         }
+        let localization = localization.map(Arc::new);
+        let transform_buffer_updater = match &config.transform_buffer_config {
+            Some(transform_buffer_config) => Some(TransformBufferUpdater::spawn(
+                transform_buffer_config,
+                raw_joint_trajectory_clients.values().cloned().collect(),
+                config
+                    .urdf_full_path()
+                    .map(Chain::<f64>::from_urdf_file)
+                    .transpose()?,
+                urdf_joint_links.clone(),
+                localization.clone(),
+            )?),
+            None => None,
+        };
         Ok(Self {
             full_chain_for_collision_checker,
             raw_joint_trajectory_clients,
             all_joint_trajectory_clients,
             collision_check_clients,
+            torque_limit_clients,
             ik_clients,
+            whole_body_ik_clients,
+            dual_arm_clients,
             self_collision_checkers,
             ik_solvers,
             speakers,
             localization,
             move_base,
             navigation,
-            joints_poses,
+            joints_poses: RwLock::new(joints_poses),
+            joints_poses_full_path: config.joints_poses_full_path,
+            transform_buffer_updater,
+            urdf_joint_links,
         })
     }
 
     pub fn set_raw_clients_joint_positions_to_full_chain_for_collision_checker(
         &self,
     ) -> Result<(), Error> {
-        for client in self.raw_joint_trajectory_clients.values() {
-            let positions = client.current_joint_positions()?;
-            let joint_names = client.joint_names();
-            if positions.len() != joint_names.len() {
-                return Err(Error::MismatchedLength(positions.len(), joint_names.len()));
-            }
-            for (index, joint_name) in joint_names.iter().enumerate() {
-                if let Some(joint) = self
-                    .full_chain_for_collision_checker
-                    .as_ref()
-                    .unwrap()
-                    .find(joint_name)
-                {
-                    joint.set_joint_position_clamped(positions[index])
-                } else {
-                    return Err(ArciError::NoJoint(joint_name.to_owned()).into());
-                }
-            }
-        }
-        self.full_chain_for_collision_checker
-            .as_ref()
-            .unwrap()
-            .update_transforms();
-        Ok(())
+        set_joint_positions_to_chain(
+            self.raw_joint_trajectory_clients.values(),
+            self.full_chain_for_collision_checker.as_ref().unwrap(),
+        )
     }
 
     pub fn is_raw_joint_trajectory_client(&self, name: &str) -> bool {
@@ -209,10 +296,22 @@ where
         self.collision_check_clients.contains_key(name)
     }
 
+    pub fn is_torque_limit_client(&self, name: &str) -> bool {
+        self.torque_limit_clients.contains_key(name)
+    }
+
     pub fn is_ik_client(&self, name: &str) -> bool {
         self.ik_clients.contains_key(name)
     }
 
+    pub fn is_whole_body_ik_client(&self, name: &str) -> bool {
+        self.whole_body_ik_clients.contains_key(name)
+    }
+
+    pub fn is_dual_arm_client(&self, name: &str) -> bool {
+        self.dual_arm_clients.contains_key(name)
+    }
+
     fn joint_trajectory_client(
         &self,
         name: &str,
@@ -232,6 +331,22 @@ where
         }
     }
 
+    fn whole_body_ik_client(&self, name: &str) -> Result<&ArcWholeBodyIkClient, Error> {
+        if self.is_whole_body_ik_client(name) {
+            Ok(&self.whole_body_ik_clients[name])
+        } else {
+            Err(Error::NoWholeBodyIkClient(name.to_owned()))
+        }
+    }
+
+    fn dual_arm_client(&self, name: &str) -> Result<&ArcDualArmClient, Error> {
+        if self.is_dual_arm_client(name) {
+            Ok(&self.dual_arm_clients[name])
+        } else {
+            Err(Error::NoDualArmClient(name.to_owned()))
+        }
+    }
+
     pub fn joint_trajectory_clients(&self) -> &HashMap<String, Arc<dyn JointTrajectoryClient>> {
         &self.all_joint_trajectory_clients
     }
@@ -244,10 +359,51 @@ where
         &self.ik_solvers
     }
 
+    pub fn torque_limit_clients(&self) -> &HashMap<String, ArcTorqueLimitClient> {
+        &self.torque_limit_clients
+    }
+
     pub fn ik_clients(&self) -> &HashMap<String, ArcIkClient> {
         &self.ik_clients
     }
 
+    pub fn whole_body_ik_clients(&self) -> &HashMap<String, ArcWholeBodyIkClient> {
+        &self.whole_body_ik_clients
+    }
+
+    pub fn dual_arm_clients(&self) -> &HashMap<String, ArcDualArmClient> {
+        &self.dual_arm_clients
+    }
+
+    /// Attaches the object to the link(joint) in the collision checking of all
+    /// of the collision check clients and the planners of the dual arm clients,
+    /// e.g. while the robot holds the object.
+    pub fn attach_object(&self, name: &str, object: AttachedObject<f64>) {
+        for checker in self.self_collision_checkers.values() {
+            checker
+                .collision_detector
+                .attach_object(name, object.clone());
+        }
+        for client in self.dual_arm_clients.values() {
+            client.attach_object(name, object.clone());
+        }
+    }
+
+    /// Detaches the object, and returns false if it is not attached.
+    pub fn detach_object(&self, name: &str) -> bool {
+        let num_checkers = self
+            .self_collision_checkers
+            .values()
+            .filter(|checker| checker.collision_detector.detach_object(name).is_some())
+            .count();
+        let num_dual_arm_clients = self
+            .dual_arm_clients
+            .values()
+            .filter(|client| client.detach_object(name))
+            .count();
+        num_checkers + num_dual_arm_clients > 0
+    }
+
     pub fn send_joint_positions(
         &self,
         name: &str,
@@ -268,6 +424,33 @@ where
         }
     }
 
+    /// Sends the trajectory interpolated in the joint space with the velocity profile
+    pub fn send_joint_positions_with_velocity_profile(
+        &self,
+        name: &str,
+        positions: &[f64],
+        velocity_profile: VelocityProfile,
+        duration_sec: f64,
+    ) -> Result<WaitFuture, Error> {
+        self.set_raw_clients_joint_positions_to_full_chain_for_collision_checker()?;
+        let current_positions = self.current_joint_positions(name)?;
+        let traj = interpolate_joint_positions(
+            &current_positions,
+            positions,
+            velocity_profile,
+            duration_sec,
+            0.05,
+            10,
+        )?;
+        if self.is_ik_client(name) {
+            Ok(self.ik_client(name)?.client.send_joint_trajectory(traj)?)
+        } else {
+            Ok(self
+                .joint_trajectory_client(name)?
+                .send_joint_trajectory(traj)?)
+        }
+    }
+
     pub fn current_joint_positions(&self, name: &str) -> Result<Vec<f64>, Error> {
         if self.is_ik_client(name) {
             self.set_raw_clients_joint_positions_to_full_chain_for_collision_checker()?;
@@ -285,15 +468,86 @@ where
         pose_name: &str,
         duration_sec: f64,
     ) -> Result<WaitFuture, Error> {
-        if self.joints_poses.contains_key(name) && self.joints_poses[name].contains_key(pose_name) {
-            Ok(self.send_joint_positions(
-                name,
-                &self.joints_poses[name][pose_name],
-                duration_sec,
-            )?)
-        } else {
-            Err(Error::NoJointsPose(name.to_owned(), pose_name.to_owned()))
+        let positions = self
+            .joints_poses
+            .read()
+            .unwrap()
+            .get(name)
+            .and_then(|poses| poses.get(pose_name))
+            .cloned()
+            .ok_or_else(|| Error::NoJointsPose(name.to_owned(), pose_name.to_owned()))?;
+        self.send_joint_positions(name, &positions, duration_sec)
+    }
+
+    /// Joints poses sorted by the client names and the pose names
+    pub fn joints_poses(&self) -> Vec<JointsPose> {
+        let mut joints_poses: Vec<_> = self
+            .joints_poses
+            .read()
+            .unwrap()
+            .iter()
+            .flat_map(|(client_name, poses)| {
+                poses.iter().map(move |(pose_name, positions)| JointsPose {
+                    pose_name: pose_name.to_owned(),
+                    client_name: client_name.to_owned(),
+                    positions: positions.to_owned(),
+                })
+            })
+            .collect();
+        joints_poses
+            .sort_by(|a, b| (&a.client_name, &a.pose_name).cmp(&(&b.client_name, &b.pose_name)));
+        joints_poses
+    }
+
+    /// File to save the joints poses to. See [`OpenrrClientsConfig::joints_poses_path`].
+    pub fn joints_poses_full_path(&self) -> Option<&Path> {
+        self.joints_poses_full_path.as_deref()
+    }
+
+    /// Saves the current joint positions of the client as the joints pose.
+    ///
+    /// The pose of the same name is replaced. The pose is also written to the
+    /// joints poses file if it is configured.
+    pub fn save_joints_pose(&self, name: &str, pose_name: &str) -> Result<JointsPose, Error> {
+        let joints_pose = JointsPose {
+            pose_name: pose_name.to_owned(),
+            client_name: name.to_owned(),
+            positions: self.current_joint_positions(name)?,
+        };
+        if let Some(path) = &self.joints_poses_full_path {
+            save_joints_pose_to_file(path, &joints_pose)?;
+        }
+        self.joints_poses
+            .write()
+            .unwrap()
+            .entry(name.to_owned())
+            .or_insert_with(HashMap::new)
+            .insert(pose_name.to_owned(), joints_pose.positions.clone());
+        Ok(joints_pose)
+    }
+
+    /// Deletes the joints pose, and also from the joints poses file if it is configured.
+    ///
+    /// The poses written in the config file itself are not modified, so they
+    /// come back when the config is loaded again.
+    pub fn delete_joints_pose(&self, name: &str, pose_name: &str) -> Result<(), Error> {
+        let exists = self
+            .joints_poses
+            .read()
+            .unwrap()
+            .get(name)
+            .map_or(false, |poses| poses.contains_key(pose_name));
+        if !exists {
+            return Err(Error::NoJointsPose(name.to_owned(), pose_name.to_owned()));
         }
+        // The pose is kept if the file can not be updated, as in `save_joints_pose`.
+        if let Some(path) = &self.joints_poses_full_path {
+            delete_joints_pose_from_file(path, name, pose_name)?;
+        }
+        if let Some(poses) = self.joints_poses.write().unwrap().get_mut(name) {
+            poses.remove(pose_name);
+        }
+        Ok(())
     }
 
     pub fn current_end_transform(&self, name: &str) -> Result<Isometry3<f64>, Error> {
@@ -301,6 +555,39 @@ where
         Ok(self.ik_client(name)?.current_end_transform()?)
     }
 
+    /// Jacobian and the measures of the singularity of the IK client at the current joint positions
+    pub fn kinematic_analysis(&self, name: &str) -> Result<KinematicAnalysis<f64>, Error> {
+        self.set_raw_clients_joint_positions_to_full_chain_for_collision_checker()?;
+        Ok(self.ik_client(name)?.current_kinematic_analysis()?)
+    }
+
+    /// Switches the frame used by the IK client. `None` means the IK target of the solver.
+    pub fn set_end_frame(&self, name: &str, frame: Option<&str>) -> Result<(), Error> {
+        self.ik_client(name)?.set_end_frame(frame)
+    }
+
+    pub fn end_frame(&self, name: &str) -> Result<Option<String>, Error> {
+        Ok(self.ik_client(name)?.end_frame())
+    }
+
+    pub fn end_frame_names(&self, name: &str) -> Result<Vec<String>, Error> {
+        Ok(self.ik_client(name)?.end_frame_names())
+    }
+
+    /// Switches the velocity profile of the interpolated motions of the IK client.
+    pub fn set_velocity_profile(
+        &self,
+        name: &str,
+        velocity_profile: VelocityProfile,
+    ) -> Result<(), Error> {
+        self.ik_client(name)?.set_velocity_profile(velocity_profile);
+        Ok(())
+    }
+
+    pub fn velocity_profile(&self, name: &str) -> Result<VelocityProfile, Error> {
+        Ok(self.ik_client(name)?.velocity_profile())
+    }
+
     pub fn transform(&self, name: &str, pose: &Isometry3<f64>) -> Result<Isometry3<f64>, Error> {
         self.set_raw_clients_joint_positions_to_full_chain_for_collision_checker()?;
         Ok(self.ik_client(name)?.transform(pose)?)
@@ -328,6 +615,24 @@ where
             .move_ik_with_interpolation(target_pose, duration_sec)?)
     }
 
+    /// Moves with the interpolation taking the shortest duration in which the
+    /// speeds of the end frame do not exceed `max_linear_speed` \[m/s\] and
+    /// `max_angular_speed` \[rad/s\].
+    pub fn move_ik_with_max_speed(
+        &self,
+        name: &str,
+        target_pose: &Isometry3<f64>,
+        max_linear_speed: f64,
+        max_angular_speed: f64,
+    ) -> Result<WaitFuture, Error> {
+        self.set_raw_clients_joint_positions_to_full_chain_for_collision_checker()?;
+        Ok(self.ik_client(name)?.move_ik_with_max_speed(
+            target_pose,
+            max_linear_speed,
+            max_angular_speed,
+        )?)
+    }
+
     pub fn send_joint_positions_with_pose_interpolation(
         &self,
         name: &str,
@@ -343,6 +648,73 @@ where
         self.move_ik_with_interpolation(name, &target_pose, duration_sec)
     }
 
+    /// Solves the IK including the motion of the base.
+    ///
+    /// `target_pose` is relative to the current pose of the base.
+    pub fn solve_whole_body_ik(
+        &self,
+        name: &str,
+        target_pose: &Isometry3<f64>,
+    ) -> Result<WholeBodyIkSolution, Error> {
+        Ok(self.whole_body_ik_client(name)?.solve(target_pose)?)
+    }
+
+    /// Moves the base by the navigation, then moves the arm to reach the target.
+    ///
+    /// `target_pose` is relative to the current pose of the base, and the goal
+    /// of the base is sent in `frame_id`.
+    pub fn move_whole_body_ik(
+        &self,
+        name: &str,
+        target_pose: &Isometry3<f64>,
+        frame_id: &str,
+        duration_sec: f64,
+        navigation_timeout: Duration,
+    ) -> Result<WaitFuture, Error> {
+        let localization = self.localization.as_deref().ok_or(Error::NoLocalization)?;
+        let navigation = self.navigation.as_ref().ok_or(Error::NoNavigation)?;
+        Ok(self.whole_body_ik_client(name)?.move_whole_body_ik(
+            localization,
+            navigation,
+            frame_id,
+            target_pose,
+            duration_sec,
+            navigation_timeout,
+        )?)
+    }
+
+    /// Moves the ends of both of the arms to the target poses avoiding the collisions between the arms,
+    /// and with `objects` if given.
+    pub fn move_dual_arm_ik(
+        &self,
+        name: &str,
+        left_target_pose: &Isometry3<f64>,
+        right_target_pose: &Isometry3<f64>,
+        duration_sec: f64,
+        objects: Option<&Compound<f64>>,
+    ) -> Result<WaitFuture, Error> {
+        Ok(self.dual_arm_client(name)?.move_ik(
+            left_target_pose,
+            right_target_pose,
+            duration_sec,
+            objects,
+        )?)
+    }
+
+    /// Moves the end of the left arm to the target pose while both of the arms
+    /// hold one object, i.e. the relative pose of the ends is kept.
+    pub fn move_dual_arm_holding_ik(
+        &self,
+        name: &str,
+        left_target_pose: &Isometry3<f64>,
+        duration_sec: f64,
+        objects: Option<&Compound<f64>>,
+    ) -> Result<WaitFuture, Error> {
+        Ok(self
+            .dual_arm_client(name)?
+            .move_holding_ik(left_target_pose, duration_sec, objects)?)
+    }
+
     pub fn raw_joint_trajectory_clients_names(&self) -> Vec<String> {
         self.raw_joint_trajectory_clients
             .keys()
@@ -357,6 +729,13 @@ where
             .collect::<Vec<String>>()
     }
 
+    pub fn torque_limit_clients_names(&self) -> Vec<String> {
+        self.torque_limit_clients
+            .keys()
+            .map(|k| k.to_owned())
+            .collect::<Vec<String>>()
+    }
+
     pub fn collision_check_clients_names(&self) -> Vec<String> {
         self.collision_check_clients
             .keys()
@@ -371,6 +750,20 @@ where
             .collect::<Vec<String>>()
     }
 
+    pub fn whole_body_ik_clients_names(&self) -> Vec<String> {
+        self.whole_body_ik_clients
+            .keys()
+            .map(|k| k.to_owned())
+            .collect::<Vec<String>>()
+    }
+
+    pub fn dual_arm_clients_names(&self) -> Vec<String> {
+        self.dual_arm_clients
+            .keys()
+            .map(|k| k.to_owned())
+            .collect::<Vec<String>>()
+    }
+
     pub fn full_chain_for_collision_checker(&self) -> &Option<Arc<Chain<f64>>> {
         &self.full_chain_for_collision_checker
     }
@@ -385,6 +778,194 @@ where
             _ => Err(anyhow::format_err!("Speaker \"{}\" is not found.", name).into()),
         }
     }
+
+    /// Pose of the link relative to the root link with the current joint positions of all of the raw clients
+    pub fn link_transform(&self, link_name: &str) -> Result<Isometry3<f64>, Error> {
+        self.set_raw_clients_joint_positions_to_full_chain()?;
+        self.link_transform_in_full_chain(link_name)
+    }
+
+    /// Pose of `to` link in `from` link with the current joint positions of all of the raw clients
+    pub fn relative_link_transform(&self, from: &str, to: &str) -> Result<Isometry3<f64>, Error> {
+        self.set_raw_clients_joint_positions_to_full_chain()?;
+        Ok(self.link_transform_in_full_chain(from)?.inverse()
+            * self.link_transform_in_full_chain(to)?)
+    }
+
+    fn set_raw_clients_joint_positions_to_full_chain(&self) -> Result<(), Error> {
+        if self.full_chain_for_collision_checker.is_none() {
+            return Err(Error::NoUrdfPath);
+        }
+        self.set_raw_clients_joint_positions_to_full_chain_for_collision_checker()
+    }
+
+    fn link_transform_in_full_chain(&self, link_name: &str) -> Result<Isometry3<f64>, Error> {
+        let full_chain = self
+            .full_chain_for_collision_checker
+            .as_ref()
+            .ok_or(Error::NoUrdfPath)?;
+        match self
+            .urdf_joint_links
+            .iter()
+            .find(|j| j.child_link_name == link_name)
+        {
+            Some(joint_links) => full_chain
+                .find(&joint_links.joint_name)
+                .and_then(|node| node.world_transform())
+                .ok_or_else(|| Error::NoLink(link_name.to_owned())),
+            // The root link is not the child of any joints.
+            None if self
+                .urdf_joint_links
+                .iter()
+                .any(|j| j.parent_link_name == link_name) =>
+            {
+                Ok(Isometry3::identity())
+            }
+            None => Err(Error::NoLink(link_name.to_owned())),
+        }
+    }
+
+    pub fn transform_buffer(&self) -> Option<&TransformBuffer> {
+        self.transform_buffer_updater
+            .as_ref()
+            .map(|updater| &updater.transform_buffer)
+    }
+
+    /// Updates the transform buffer with the current joint positions and the
+    /// current pose from the localization immediately. The transform buffer is
+    /// also updated periodically by the background thread.
+    pub fn update_transform_buffer(&self) -> Result<(), Error> {
+        self.transform_buffer_updater
+            .as_ref()
+            .ok_or(Error::NoTransformBuffer)?
+            .update()
+    }
+}
+
+fn set_joint_positions_to_chain<'a>(
+    clients: impl IntoIterator<Item = &'a Arc<dyn JointTrajectoryClient>>,
+    chain: &Chain<f64>,
+) -> Result<(), Error> {
+    for client in clients {
+        let positions = client.current_joint_positions()?;
+        let joint_names = client.joint_names();
+        if positions.len() != joint_names.len() {
+            return Err(Error::MismatchedLength(positions.len(), joint_names.len()));
+        }
+        for (index, joint_name) in joint_names.iter().enumerate() {
+            if let Some(joint) = chain.find(joint_name) {
+                joint.set_joint_position_clamped(positions[index])
+            } else {
+                return Err(ArciError::NoJoint(joint_name.to_owned()).into());
+            }
+        }
+    }
+    chain.update_transforms();
+    Ok(())
+}
+
+/// Keeps the transform buffer updated with the current joint positions and
+/// the current pose from the localization.
+struct TransformBufferUpdater<L> {
+    transform_buffer: TransformBuffer,
+    raw_joint_trajectory_clients: Vec<Arc<dyn JointTrajectoryClient>>,
+    // Not the full chain for the collision checker, so that updating it from
+    // the background thread does not disturb the collision checking.
+    chain: Option<Chain<f64>>,
+    joint_links: Vec<JointLinks>,
+    localization: Option<(LocalizationTransformConfig, Arc<L>)>,
+}
+
+impl<L> TransformBufferUpdater<L>
+where
+    L: Localization + 'static,
+{
+    /// Creates a new `TransformBufferUpdater` and starts the background thread
+    /// which updates the transform buffer at `config.update_frequency`.
+    fn spawn(
+        config: &TransformBufferConfig,
+        raw_joint_trajectory_clients: Vec<Arc<dyn JointTrajectoryClient>>,
+        chain: Option<Chain<f64>>,
+        joint_links: Vec<JointLinks>,
+        localization: Option<Arc<L>>,
+    ) -> Result<Arc<Self>, Error> {
+        for (name, value) in [
+            ("cache_duration_secs", config.cache_duration_secs),
+            ("time_tolerance_secs", config.time_tolerance_secs),
+        ] {
+            if !(value >= 0.0 && value.is_finite()) {
+                return Err(anyhow::format_err!(
+                    "{} of transform_buffer_config must be non-negative, but got {}",
+                    name,
+                    value
+                )
+                .into());
+            }
+        }
+        if !(config.update_frequency > 0.0 && config.update_frequency.is_finite()) {
+            return Err(anyhow::format_err!(
+                "update_frequency of transform_buffer_config must be positive, but got {}",
+                config.update_frequency
+            )
+            .into());
+        }
+        let updater = Arc::new(Self {
+            transform_buffer: TransformBuffer::new_from_config(config),
+            raw_joint_trajectory_clients,
+            chain,
+            joint_links,
+            localization: config.localization.clone().zip(localization),
+        });
+        // Makes the transformations available right after the creation.
+        if let Err(e) = updater.update() {
+            warn!("Failed to update the transform buffer: {}", e);
+        }
+        let weak = Arc::downgrade(&updater);
+        let period = Duration::from_secs_f64(1.0 / config.update_frequency);
+        thread::spawn(move || run_transform_buffer_update_loop(weak, period));
+        Ok(updater)
+    }
+}
+
+impl<L> TransformBufferUpdater<L>
+where
+    L: Localization,
+{
+    fn update(&self) -> Result<(), Error> {
+        let now = SystemTime::now();
+        if let Some(chain) = &self.chain {
+            set_joint_positions_to_chain(&self.raw_joint_trajectory_clients, chain)?;
+            self.transform_buffer
+                .set_transforms_from_chain(&self.joint_links, chain, now)?;
+        }
+        if let Some((config, localization)) = &self.localization {
+            let pose = localization.current_pose(&config.parent_frame)?;
+            self.transform_buffer.set_transform(
+                &config.parent_frame,
+                &config.child_frame,
+                isometry3_from_isometry2(&pose),
+                now,
+            );
+        }
+        Ok(())
+    }
+}
+
+fn run_transform_buffer_update_loop<L>(updater: Weak<TransformBufferUpdater<L>>, period: Duration)
+where
+    L: Localization,
+{
+    loop {
+        thread::sleep(period);
+        // The thread ends when the robot client is dropped.
+        let updater = match updater.upgrade() {
+            Some(updater) => updater,
+            None => break,
+        };
+        if let Err(e) = updater.update() {
+            warn!("Failed to update the transform buffer: {}", e);
+        }
+    }
 }
 
 impl<L, M, N> Localization for RobotClient<L, M, N>
@@ -396,6 +977,23 @@ where
     fn current_pose(&self, frame_id: &str) -> Result<Isometry2<f64>, ArciError> {
         self.localization.as_ref().unwrap().current_pose(frame_id)
     }
+
+    fn current_pose_with_covariance(
+        &self,
+        frame_id: &str,
+    ) -> Result<PoseWithCovariance, ArciError> {
+        self.localization
+            .as_ref()
+            .unwrap()
+            .current_pose_with_covariance(frame_id)
+    }
+
+    fn set_initial_pose(&self, pose: PoseWithCovariance, frame_id: &str) -> Result<(), ArciError> {
+        self.localization
+            .as_ref()
+            .unwrap()
+            .set_initial_pose(pose, frame_id)
+    }
 }
 
 impl<L, M, N> Navigation for RobotClient<L, M, N>
@@ -436,6 +1034,33 @@ where
     }
 }
 
+/// Resolves the transformations by the transform buffer if it is configured,
+/// otherwise by the forward kinematics of the links of URDF with the current
+/// joint positions, ignoring `time`.
+///
+/// The transform buffer is filled by the background thread, so `time` must be
+/// within the cached duration.
+impl<L, M, N> TransformResolver for RobotClient<L, M, N>
+where
+    L: Localization + 'static,
+    M: MoveBase,
+    N: Navigation,
+{
+    fn resolve_transformation(
+        &self,
+        from: &str,
+        to: &str,
+        time: SystemTime,
+    ) -> Result<Isometry3<f64>, ArciError> {
+        match self.transform_buffer() {
+            Some(transform_buffer) => transform_buffer.resolve_transformation(from, to, time),
+            None => self
+                .relative_link_transform(from, to)
+                .map_err(|e| ArciError::Other(e.into())),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct JointTrajectoryClientsContainerConfig {
@@ -459,17 +1084,44 @@ pub struct OpenrrClientsConfig {
     // https://github.com/alexcrichton/toml-rs/issues/258
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub collision_check_clients_configs: Vec<CollisionCheckClientConfig>,
+    /// Clients which check the torques of the trajectories. They can wrap the collision check clients.
+    #[serde(default)]
+    // https://github.com/alexcrichton/toml-rs/issues/258
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub torque_limit_clients_configs: Vec<TorqueLimitClientConfig>,
     #[serde(default)]
     // https://github.com/alexcrichton/toml-rs/issues/258
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub ik_clients_configs: Vec<IkClientConfig>,
     #[serde(default)]
     pub ik_solvers_configs: HashMap<String, IkSolverConfig>,
+    #[serde(default)]
+    // https://github.com/alexcrichton/toml-rs/issues/258
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub whole_body_ik_clients_configs: Vec<WholeBodyIkClientConfig>,
+    #[serde(default)]
+    // https://github.com/alexcrichton/toml-rs/issues/258
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dual_arm_clients_configs: Vec<DualArmClientConfig>,
 
     #[serde(default)]
     // https://github.com/alexcrichton/toml-rs/issues/258
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub joints_poses: Vec<JointsPose>,
+
+    /// Enables the transform buffer used by [`TransformResolver`] implementation.
+    pub transform_buffer_config: Option<TransformBufferConfig>,
+
+    /// Path to the SRDF file to import the planning groups, the group states
+    /// and the disabled collisions from. See [`OpenrrClientsConfig::import_srdf`].
+    pub srdf_path: Option<String>,
+    srdf_full_path: Option<PathBuf>,
+
+    /// Path to the file to save the joints poses to, e.g. by
+    /// [`RobotClient::save_joints_pose`]. The poses in the file are merged into
+    /// `joints_poses` by [`OpenrrClientsConfig::load_joints_poses`].
+    pub joints_poses_path: Option<String>,
+    joints_poses_full_path: Option<PathBuf>,
 }
 
 /// Make relative path into absolute path from base file (not base dir).
@@ -493,17 +1145,221 @@ pub fn resolve_relative_path<B: AsRef<Path>, P: AsRef<Path>>(
 
 impl OpenrrClientsConfig {
     pub fn resolve_path<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        self.resolve_joints_poses_path(&path)?;
         if let Some(urdf_path) = self.urdf_path.as_ref() {
-            self.urdf_full_path = Some(resolve_relative_path(path, &urdf_path)?);
+            self.urdf_full_path = Some(resolve_relative_path(&path, &urdf_path)?);
         } else {
             return Err(Error::NoUrdfPath);
         }
+        if let Some(srdf_path) = self.srdf_path.as_ref() {
+            self.srdf_full_path = Some(resolve_relative_path(&path, &srdf_path)?);
+        }
         Ok(())
     }
 
     pub fn urdf_full_path(&self) -> Option<&Path> {
         self.urdf_full_path.as_deref()
     }
+
+    pub fn srdf_full_path(&self) -> Option<&Path> {
+        self.srdf_full_path.as_deref()
+    }
+
+    /// Resolves only `joints_poses_path`, which does not need URDF unlike [`Self::resolve_path`].
+    pub fn resolve_joints_poses_path<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        if let Some(joints_poses_path) = self.joints_poses_path.as_ref() {
+            self.joints_poses_full_path = Some(resolve_relative_path(&path, &joints_poses_path)?);
+        }
+        Ok(())
+    }
+
+    pub fn joints_poses_full_path(&self) -> Option<&Path> {
+        self.joints_poses_full_path.as_deref()
+    }
+
+    /// Merges the poses in the joints poses file into `joints_poses`.
+    ///
+    /// The poses in the file take precedence over the poses of the same names.
+    /// It is not an error that the file does not exist yet.
+    pub fn load_joints_poses(&mut self) -> Result<(), Error> {
+        let path = match &self.joints_poses_full_path {
+            Some(path) if path.exists() => path,
+            _ => return Ok(()),
+        };
+        debug!("Loading {:?}", path);
+        for joints_pose in read_joints_poses_file(path)? {
+            self.joints_poses.retain(|p| {
+                p.client_name != joints_pose.client_name || p.pose_name != joints_pose.pose_name
+            });
+            self.joints_poses.push(joints_pose);
+        }
+        Ok(())
+    }
+
+    /// Imports the planning groups, the group states and the disabled collisions of SRDF.
+    ///
+    /// - Each group becomes a container of the clients whose joints are all in
+    ///   the group. A group of a single chain also gets an IK solver named after
+    ///   the group and an IK client named `<group>_ik`.
+    /// - Each group state becomes a joints pose of the container of the group.
+    /// - The disabled pairs are removed from `self_collision_check_pairs`. If it
+    ///   is empty, all of the other pairs of the links with collision models are checked.
+    ///
+    /// The settings already in the config take precedence.
+    ///
+    /// client_joint_names: joint names of each raw joint trajectory client
+    pub fn import_srdf(
+        &mut self,
+        srdf: &Srdf,
+        urdf_robot: &urdf_rs::Robot,
+        client_joint_names: &HashMap<String, Vec<String>>,
+    ) -> Result<(), Error> {
+        let mut container_joint_names = HashMap::new();
+        for group in &srdf.groups {
+            if client_joint_names.contains_key(&group.name)
+                || self
+                    .joint_trajectory_clients_container_configs
+                    .iter()
+                    .any(|c| c.name == group.name)
+            {
+                warn!("SRDF group {} is skipped: the client exists", group.name);
+                continue;
+            }
+            let group_joints = srdf
+                .group_joint_names(group, urdf_robot)
+                .map_err(|e| Error::Other(e.into()))?;
+            let mut clients_names: Vec<_> = client_joint_names
+                .iter()
+                .filter(|(_, joints)| {
+                    !joints.is_empty() && joints.iter().all(|j| group_joints.contains(j))
+                })
+                .map(|(name, _)| name.to_owned())
+                .collect();
+            // Same order as the joints of the group.
+            clients_names.sort_by_key(|name| {
+                group_joints
+                    .iter()
+                    .position(|j| *j == client_joint_names[name][0])
+            });
+            let joint_names: Vec<String> = clients_names
+                .iter()
+                .flat_map(|name| client_joint_names[name].iter().cloned())
+                .collect();
+            if joint_names.len() != group_joints.len() {
+                warn!(
+                    "SRDF group {} is skipped: no clients for some of the joints {:?}",
+                    group.name, group_joints
+                );
+                continue;
+            }
+            self.joint_trajectory_clients_container_configs.push(
+                JointTrajectoryClientsContainerConfig {
+                    name: group.name.clone(),
+                    clients_names,
+                },
+            );
+
+            let is_single_chain =
+                group.chains.len() == 1 && group.joints.is_empty() && group.links.is_empty();
+            let ik_client_name = format!("{}_ik", group.name);
+            if is_single_chain
+                && !self.ik_solvers_configs.contains_key(&group.name)
+                && !self
+                    .ik_clients_configs
+                    .iter()
+                    .any(|c| c.name == ik_client_name)
+            {
+                let chain_joints = group.chains[0]
+                    .joints(urdf_robot)
+                    .map_err(|e| Error::Other(e.into()))?;
+                if let (Some(root), Some(end)) = (chain_joints.first(), chain_joints.last()) {
+                    self.ik_solvers_configs.insert(
+                        group.name.clone(),
+                        IkSolverConfig::new(Some(root.name.clone()), end.name.clone()),
+                    );
+                    self.ik_clients_configs.push(IkClientConfig {
+                        name: ik_client_name,
+                        client_name: group.name.clone(),
+                        solver_name: group.name.clone(),
+                        end_frames: vec![],
+                        velocity_profile: VelocityProfile::default(),
+                    });
+                }
+            }
+            container_joint_names.insert(group.name.clone(), joint_names);
+        }
+
+        for state in &srdf.group_states {
+            let joint_names = match container_joint_names.get(&state.group) {
+                Some(joint_names) => joint_names,
+                None => continue,
+            };
+            if self
+                .joints_poses
+                .iter()
+                .any(|p| p.client_name == state.group && p.pose_name == state.name)
+            {
+                continue;
+            }
+            let positions: Option<Vec<f64>> = joint_names
+                .iter()
+                .map(|name| {
+                    state
+                        .joints
+                        .iter()
+                        .find(|j| j.name == *name)
+                        .map(|j| j.value)
+                })
+                .collect();
+            match positions {
+                Some(positions) => self.joints_poses.push(JointsPose {
+                    pose_name: state.name.clone(),
+                    client_name: state.group.clone(),
+                    positions,
+                }),
+                None => warn!(
+                    "SRDF group state {} is skipped: some of the joints {:?} are missing",
+                    state.name, joint_names
+                ),
+            }
+        }
+
+        let disabled_pairs = srdf
+            .disabled_collision_joint_pairs(urdf_robot)
+            .map_err(|e| Error::Other(e.into()))?;
+        let is_disabled = |j1: &str, j2: &str| {
+            disabled_pairs
+                .iter()
+                .any(|(d1, d2)| (d1 == j1 && d2 == j2) || (d1 == j2 && d2 == j1))
+        };
+        if self.self_collision_check_pairs.is_empty() {
+            let link_joint_map = k::urdf::link_to_joint_map(urdf_robot);
+            let mut names: Vec<_> = urdf_robot
+                .links
+                .iter()
+                .filter(|l| !l.collision.is_empty())
+                .filter_map(|l| link_joint_map.get(&l.name).cloned())
+                .collect();
+            names.sort();
+            for (i, name1) in names.iter().enumerate() {
+                for name2 in &names[i + 1..] {
+                    if !is_disabled(name1, name2) {
+                        self.self_collision_check_pairs
+                            .push(format!("{}:{}", name1, name2));
+                    }
+                }
+            }
+        } else {
+            self.self_collision_check_pairs.retain(|pair| {
+                let mut sp = pair.split(':');
+                match (sp.next(), sp.next()) {
+                    (Some(j1), Some(j2)) => !is_disabled(j1, j2),
+                    _ => true,
+                }
+            });
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
@@ -514,12 +1370,129 @@ pub struct JointsPose {
     pub positions: Vec<f64>,
 }
 
+/// Contents of the joints poses file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JointsPosesFile {
+    #[serde(default)]
+    joints_poses: Vec<JointsPose>,
+}
+
+fn read_joints_poses_file(path: &Path) -> Result<Vec<JointsPose>, Error> {
+    let s = fs::read_to_string(path).map_err(|e| Error::NoFile(path.to_owned(), e))?;
+    let file: JointsPosesFile =
+        toml::from_str(&s).map_err(|e| Error::TomlParseFailure(path.to_owned(), e))?;
+    Ok(file.joints_poses)
+}
+
+/// Reads the joints poses file as the poses and the TOML document to modify.
+/// The document is empty if the file does not exist yet.
+fn read_joints_poses_document(path: &Path) -> Result<(Vec<JointsPose>, toml::Value), Error> {
+    let mut doc = if path.exists() {
+        let s = fs::read_to_string(path).map_err(|e| Error::NoFile(path.to_owned(), e))?;
+        toml::from_str(&s).map_err(|e| Error::TomlParseFailure(path.to_owned(), e))?
+    } else {
+        toml::Value::Table(toml::value::Table::new())
+    };
+    let file: JointsPosesFile = doc
+        .clone()
+        .try_into()
+        .map_err(|e| Error::TomlParseFailure(path.to_owned(), e))?;
+    if let toml::Value::Table(table) = &mut doc {
+        if !table.contains_key("joints_poses") {
+            table.insert("joints_poses".to_owned(), toml::Value::Array(vec![]));
+        }
+    }
+    Ok((file.joints_poses, doc))
+}
+
+fn write_joints_poses_document(path: &Path, doc: &toml::Value) -> Result<(), Error> {
+    let s = toml::to_string(doc).map_err(|e| Error::Other(e.into()))?;
+    fs::write(path, s).map_err(|e| Error::Other(e.into()))
+}
+
+/// Index of the pose in the joints poses file
+fn joints_pose_index(
+    joints_poses: &[JointsPose],
+    client_name: &str,
+    pose_name: &str,
+) -> Option<usize> {
+    joints_poses
+        .iter()
+        .position(|p| p.client_name == client_name && p.pose_name == pose_name)
+}
+
+/// Adds the pose to the joints poses file, or replaces the pose of the same name.
+fn save_joints_pose_to_file(path: &Path, joints_pose: &JointsPose) -> Result<(), Error> {
+    let (joints_poses, mut doc) = read_joints_poses_document(path)?;
+    // The index out of the array appends the pose.
+    let index = joints_pose_index(
+        &joints_poses,
+        &joints_pose.client_name,
+        &joints_pose.pose_name,
+    )
+    .unwrap_or(joints_poses.len());
+    let script = format!(
+        "joints_poses[{}] = {{ pose_name = {}, client_name = {}, positions = {} }}",
+        index,
+        toml::Value::String(joints_pose.pose_name.clone()),
+        toml::Value::String(joints_pose.client_name.clone()),
+        toml::Value::Array(
+            joints_pose
+                .positions
+                .iter()
+                .map(|p| toml::Value::Float(*p))
+                .collect()
+        ),
+    );
+    openrr_config::overwrite(&mut doc, &script)?;
+    write_joints_poses_document(path, &doc)
+}
+
+/// Deletes the pose from the joints poses file if the pose is in the file.
+fn delete_joints_pose_from_file(
+    path: &Path,
+    client_name: &str,
+    pose_name: &str,
+) -> Result<(), Error> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let (joints_poses, mut doc) = read_joints_poses_document(path)?;
+    if let Some(index) = joints_pose_index(&joints_poses, client_name, pose_name) {
+        openrr_config::overwrite(&mut doc, &format!("joints_poses[{}] =", index))?;
+        write_joints_poses_document(path, &doc)?;
+    }
+    Ok(())
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct IkClientConfig {
     pub name: String,
     pub client_name: String,
     pub solver_name: String,
+    /// Named frames, e.g. tool center points, which can be used instead of the IK target of the solver
+    #[serde(default)]
+    // https://github.com/alexcrichton/toml-rs/issues/258
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub end_frames: Vec<EndFrameConfig>,
+    /// Velocity profile of the interpolated motions
+    #[serde(default)]
+    pub velocity_profile: VelocityProfile,
+}
+
+/// Frame fixed to the IK target of the solver
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EndFrameConfig {
+    pub name: String,
+    /// Translation from the IK target \[m\]
+    #[serde(default)]
+    pub xyz: [f64; 3],
+    /// Rotation from the IK target in roll, pitch and yaw \[rad\]
+    #[serde(default)]
+    pub rpy: [f64; 3],
 }
 
 pub fn create_ik_clients(
@@ -527,17 +1500,52 @@ pub fn create_ik_clients(
     name_to_joint_trajectory_client: &HashMap<String, ArcJointTrajectoryClient>,
     name_to_ik_solvers: &HashMap<String, Arc<IkSolverWithChain>>,
 ) -> HashMap<String, Arc<IkClient<ArcJointTrajectoryClient>>> {
+    let mut clients = HashMap::new();
+    for config in configs {
+        let mut client = IkClient::new(
+            name_to_joint_trajectory_client[&config.client_name].clone(),
+            name_to_ik_solvers[&config.solver_name].clone(),
+        );
+        client.set_velocity_profile(config.velocity_profile);
+        for frame in &config.end_frames {
+            client.add_end_frame(
+                &frame.name,
+                isometry(
+                    frame.xyz[0],
+                    frame.xyz[1],
+                    frame.xyz[2],
+                    frame.rpy[0],
+                    frame.rpy[1],
+                    frame.rpy[2],
+                ),
+            );
+        }
+        clients.insert(config.name.clone(), Arc::new(client));
+    }
+    clients
+}
+
+pub fn create_whole_body_ik_clients(
+    configs: &[WholeBodyIkClientConfig],
+    urdf_robot: &urdf_rs::Robot,
+    name_to_ik_solvers_configs: &HashMap<String, IkSolverConfig>,
+    name_to_joint_trajectory_client: &HashMap<String, ArcJointTrajectoryClient>,
+) -> Result<HashMap<String, ArcWholeBodyIkClient>, Error> {
     let mut clients = HashMap::new();
     for config in configs {
         clients.insert(
             config.name.clone(),
-            Arc::new(IkClient::new(
+            Arc::new(WholeBodyIkClient::new(
                 name_to_joint_trajectory_client[&config.client_name].clone(),
-                name_to_ik_solvers[&config.solver_name].clone(),
+                Arc::new(create_whole_body_ik_solver(
+                    urdf_robot,
+                    &name_to_ik_solvers_configs[&config.solver_name],
+                    config,
+                )?),
             )),
         );
     }
-    clients
+    Ok(clients)
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
@@ -572,6 +1580,30 @@ pub fn create_collision_check_clients<P: AsRef<Path>>(
     clients
 }
 
+pub fn create_torque_limit_clients(
+    urdf_robot: &urdf_rs::Robot,
+    configs: &[TorqueLimitClientConfig],
+    name_to_joint_trajectory_client: &HashMap<String, Arc<dyn JointTrajectoryClient>>,
+    full_chain: Arc<k::Chain<f64>>,
+) -> Result<HashMap<String, ArcTorqueLimitClient>, Error> {
+    let mut clients = HashMap::new();
+    for config in configs {
+        let client = name_to_joint_trajectory_client
+            .get(&config.client_name)
+            .ok_or_else(|| Error::NoJointTrajectoryClient(config.client_name.clone()))?;
+        clients.insert(
+            config.name.clone(),
+            Arc::new(create_torque_limit_client(
+                urdf_robot,
+                config,
+                client.clone(),
+                full_chain.clone(),
+            )?),
+        );
+    }
+    Ok(clients)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -638,6 +1670,29 @@ mod tests {
         fn current_pose(&self, frame_id: &str) -> Result<arci::Isometry2<f64>, arci::Error> {
             panic!("PanicLocalization::current_pose frame_id={:?}", frame_id)
         }
+
+        #[track_caller]
+        fn current_pose_with_covariance(
+            &self,
+            frame_id: &str,
+        ) -> Result<PoseWithCovariance, arci::Error> {
+            panic!(
+                "PanicLocalization::current_pose_with_covariance frame_id={:?}",
+                frame_id
+            )
+        }
+
+        #[track_caller]
+        fn set_initial_pose(
+            &self,
+            pose: PoseWithCovariance,
+            frame_id: &str,
+        ) -> Result<(), arci::Error> {
+            panic!(
+                "PanicLocalization::set_initial_pose pose={:?}, frame_id={:?}",
+                pose, frame_id
+            )
+        }
     }
 
     struct PanicMoveBase;
@@ -676,6 +1731,84 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_import_srdf() {
+        let mut root_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        root_dir.pop();
+        let urdf_robot = urdf_rs::read_file(root_dir.join("openrr-planner/sample.urdf")).unwrap();
+        let srdf = srdf::read_from_string(
+            r#"<robot name="robo">
+  <group name="l_arm">
+    <chain base_link="root" tip_link="l_tool" />
+  </group>
+  <group name="l_arm_with_gripper">
+    <group name="l_arm" />
+    <joint name="l_gripper_linear1" />
+    <joint name="l_gripper_linear2" />
+  </group>
+  <group_state name="open" group="l_arm_with_gripper">
+    <joint name="l_shoulder_yaw" value="0.1" />
+    <joint name="l_shoulder_pitch" value="0.2" />
+    <joint name="l_shoulder_roll" value="0.3" />
+    <joint name="l_elbow_pitch" value="0.4" />
+    <joint name="l_wrist_yaw" value="0.5" />
+    <joint name="l_wrist_pitch" value="0.6" />
+    <joint name="l_gripper_linear2" value="-0.02" />
+    <joint name="l_gripper_linear1" value="0.02" />
+  </group_state>
+  <disable_collisions link1="l_gripper1" link2="l_gripper2" reason="Never" />
+</robot>"#,
+        )
+        .unwrap();
+        let mut client_joint_names = HashMap::new();
+        client_joint_names.insert("arm".to_owned(), PanicJointTrajectoryClient.joint_names());
+        client_joint_names.insert(
+            "gripper".to_owned(),
+            vec![
+                "l_gripper_linear1".to_owned(),
+                "l_gripper_linear2".to_owned(),
+            ],
+        );
+
+        let mut config = OpenrrClientsConfig::default();
+        config
+            .import_srdf(&srdf, &urdf_robot, &client_joint_names)
+            .unwrap();
+        let containers = &config.joint_trajectory_clients_container_configs;
+        assert_eq!(containers.len(), 2);
+        assert_eq!(containers[0].name, "l_arm");
+        assert_eq!(containers[0].clients_names, vec!["arm"]);
+        assert_eq!(containers[1].clients_names, vec!["arm", "gripper"]);
+        let solver = &config.ik_solvers_configs["l_arm"];
+        assert_eq!(solver.root_node_name.as_deref(), Some("l_shoulder_yaw"));
+        assert_eq!(solver.ik_target, "l_tool_fixed");
+        assert_eq!(config.ik_clients_configs.len(), 1);
+        assert_eq!(config.ik_clients_configs[0].name, "l_arm_ik");
+        assert_eq!(config.joints_poses.len(), 1);
+        assert_eq!(
+            config.joints_poses[0].positions,
+            vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.02, -0.02]
+        );
+        let pairs = &config.self_collision_check_pairs;
+        assert!(pairs.contains(&"l_gripper_linear1:l_shoulder_yaw".to_owned()));
+        assert!(!pairs.contains(&"l_gripper_linear1:l_gripper_linear2".to_owned()));
+
+        let mut config = OpenrrClientsConfig {
+            self_collision_check_pairs: vec![
+                "l_shoulder_yaw:l_gripper_linear1".to_owned(),
+                "l_gripper_linear2:l_gripper_linear1".to_owned(),
+            ],
+            ..Default::default()
+        };
+        config
+            .import_srdf(&srdf, &urdf_robot, &client_joint_names)
+            .unwrap();
+        assert_eq!(
+            config.self_collision_check_pairs,
+            vec!["l_shoulder_yaw:l_gripper_linear1"]
+        );
+    }
+
     #[test]
     fn lazy() {
         let mut root_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -736,4 +1869,168 @@ positions = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0]
         )
         .unwrap();
     }
+
+    #[test]
+    fn test_attach_object() {
+        let mut root_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        root_dir.pop();
+        let mut config: OpenrrClientsConfig = toml::from_str(
+            r#"
+self_collision_check_pairs = ["l_shoulder_yaw:l_gripper_linear1"]
+
+[[collision_check_clients_configs]]
+name = "arm_collision_checked"
+client_name = "arm"
+"#,
+        )
+        .unwrap();
+        config.urdf_full_path = Some(root_dir.join("openrr-planner/sample.urdf"));
+        let arm = arci::DummyJointTrajectoryClient::new(PanicJointTrajectoryClient.joint_names());
+        let client = BoxRobotClient::new(
+            config,
+            {
+                let mut map = HashMap::new();
+                map.insert(
+                    "arm".to_string(),
+                    Arc::new(arm) as Arc<dyn JointTrajectoryClient>,
+                );
+                map
+            },
+            HashMap::new(),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let object = AttachedObject::from_urdf_geometry(
+            "l_wrist_pitch",
+            &urdf_rs::Geometry::Sphere { radius: 0.05 },
+            Isometry3::translation(0.1, 0.0, 0.0),
+        )
+        .unwrap();
+        client.attach_object("ball", object);
+        assert_eq!(
+            client.self_collision_checkers()["arm_collision_checked"]
+                .collision_detector
+                .attached_object_names(),
+            vec!["ball"]
+        );
+        assert!(client.detach_object("ball"));
+        assert!(!client.detach_object("ball"));
+    }
+
+    #[test]
+    fn test_link_transform() {
+        let mut root_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        root_dir.pop();
+        let mut config = OpenrrClientsConfig::default();
+        config.urdf_full_path = Some(root_dir.join("openrr-planner/sample.urdf"));
+        let chain = Chain::<f64>::from_urdf_file(config.urdf_full_path().unwrap()).unwrap();
+        let joint_names: Vec<String> = chain.iter_joints().map(|j| j.name.clone()).collect();
+        let arm = arci::DummyJointTrajectoryClient::new(joint_names);
+        *arm.positions.lock().unwrap() = vec![0.2, 0.2, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0];
+        let client = BoxRobotClient::new(
+            config,
+            {
+                let mut map = HashMap::new();
+                map.insert(
+                    "arm".to_string(),
+                    Arc::new(arm) as Arc<dyn JointTrajectoryClient>,
+                );
+                map
+            },
+            HashMap::new(),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            client.link_transform("root").unwrap(),
+            Isometry3::identity()
+        );
+        let tool = client.link_transform("l_tool").unwrap();
+        chain
+            .set_joint_positions(&[0.2, 0.2, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0])
+            .unwrap();
+        chain.update_transforms();
+        let expected = chain
+            .find("l_tool_fixed")
+            .unwrap()
+            .world_transform()
+            .unwrap();
+        assert!((tool.translation.vector - expected.translation.vector).norm() < 1e-6);
+
+        let wrist = client.link_transform("l_wrist1").unwrap();
+        let relative = client
+            .relative_link_transform("l_wrist1", "l_tool")
+            .unwrap();
+        assert!(((wrist * relative).translation.vector - tool.translation.vector).norm() < 1e-6);
+        // Without the transform buffer, the transformations are resolved by the forward kinematics.
+        let resolved = client
+            .resolve_transformation("l_wrist1", "l_tool", SystemTime::now())
+            .unwrap();
+        assert!((resolved.translation.vector - relative.translation.vector).norm() < 1e-6);
+        assert!(client.link_transform("no_link").is_err());
+    }
+
+    #[test]
+    fn test_save_joints_pose() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("joints_poses.toml");
+        let mut config: OpenrrClientsConfig = toml::from_str(
+            r#"
+[[joints_poses]]
+pose_name = "zero"
+client_name = "arm"
+positions = [0.0, 0.0]
+"#,
+        )
+        .unwrap();
+        config.joints_poses_full_path = Some(path.clone());
+        let arm = Arc::new(arci::DummyJointTrajectoryClient::new(vec![
+            "a".to_owned(),
+            "b".to_owned(),
+        ]));
+        *arm.positions.lock().unwrap() = vec![0.5, -0.5];
+        let new_client = |config: OpenrrClientsConfig| {
+            let mut map = HashMap::new();
+            map.insert(
+                "arm".to_string(),
+                arm.clone() as Arc<dyn JointTrajectoryClient>,
+            );
+            BoxRobotClient::new(config, map, HashMap::new(), None, None, None).unwrap()
+        };
+
+        let client = new_client(config.clone());
+        client.save_joints_pose("arm", "zero").unwrap();
+        client.save_joints_pose("arm", "up").unwrap();
+        assert!(client.save_joints_pose("no_client", "up").is_err());
+        let names = |client: &BoxRobotClient| {
+            client
+                .joints_poses()
+                .into_iter()
+                .map(|p| p.pose_name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&client), vec!["up", "zero"]);
+
+        // The saved poses take precedence over the config.
+        let client = new_client(config.clone());
+        assert_eq!(names(&client), vec!["up", "zero"]);
+        assert_eq!(client.joints_poses()[1].positions, vec![0.5, -0.5]);
+
+        client.delete_joints_pose("arm", "up").unwrap();
+        assert!(client.delete_joints_pose("arm", "up").is_err());
+        let client = new_client(config);
+        assert_eq!(names(&client), vec!["zero"]);
+        assert_eq!(read_joints_poses_file(&path).unwrap().len(), 1);
+
+        // The pose is kept if the file can not be updated.
+        std::fs::write(&path, "joints_poses = [").unwrap();
+        assert!(client.delete_joints_pose("arm", "zero").is_err());
+        assert_eq!(names(&client), vec!["zero"]);
+    }
 }
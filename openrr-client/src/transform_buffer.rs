@@ -0,0 +1,584 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use arci::{Error as ArciError, TransformResolver};
+use k::{
+    nalgebra::{Isometry2, Translation3, UnitQuaternion},
+    Chain, Isometry3,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Transformation between two frames that never changes.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct StaticTransformConfig {
+    pub parent_frame: String,
+    pub child_frame: String,
+    /// Translation `[x, y, z]` of the child frame in the parent frame.
+    #[serde(default)]
+    pub translation: [f64; 3],
+    /// Rotation `[roll, pitch, yaw]` of the child frame in the parent frame.
+    #[serde(default)]
+    pub rotation: [f64; 3],
+}
+
+impl StaticTransformConfig {
+    pub fn transform(&self) -> Isometry3<f64> {
+        Isometry3::from_parts(
+            Translation3::new(
+                self.translation[0],
+                self.translation[1],
+                self.translation[2],
+            ),
+            UnitQuaternion::from_euler_angles(self.rotation[0], self.rotation[1], self.rotation[2]),
+        )
+    }
+}
+
+/// Frames connected by the pose from [`arci::Localization`].
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LocalizationTransformConfig {
+    /// Frame in which the robot is localized, e.g. `map` or `odom`.
+    pub parent_frame: String,
+    /// Frame of the robot base. Use the root link of the URDF to connect the
+    /// localized pose to the kinematic chain.
+    pub child_frame: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TransformBufferConfig {
+    /// How long the past transformations are kept for interpolation.
+    #[serde(default = "default_cache_duration_secs")]
+    pub cache_duration_secs: f64,
+    /// Lookups up to this duration before the oldest or after the latest
+    /// transformation return that transformation. It should be longer than
+    /// the update period.
+    #[serde(default = "default_time_tolerance_secs")]
+    pub time_tolerance_secs: f64,
+    /// Frequency to update the transformations of the links and the
+    /// localization by the background thread.
+    #[serde(default = "default_update_frequency")]
+    pub update_frequency: f64,
+    #[serde(default)]
+    // https://github.com/alexcrichton/toml-rs/issues/258
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub static_transforms: Vec<StaticTransformConfig>,
+    pub localization: Option<LocalizationTransformConfig>,
+}
+
+fn default_cache_duration_secs() -> f64 {
+    10.0
+}
+
+fn default_time_tolerance_secs() -> f64 {
+    0.2
+}
+
+fn default_update_frequency() -> f64 {
+    20.0
+}
+
+impl Default for TransformBufferConfig {
+    fn default() -> Self {
+        Self {
+            cache_duration_secs: default_cache_duration_secs(),
+            time_tolerance_secs: default_time_tolerance_secs(),
+            update_frequency: default_update_frequency(),
+            static_transforms: vec![],
+            localization: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TransformHistory {
+    parent_frame: String,
+    is_static: bool,
+    // Sorted by the time stamp.
+    samples: VecDeque<(SystemTime, Isometry3<f64>)>,
+}
+
+impl TransformHistory {
+    fn new(parent_frame: &str, is_static: bool) -> Self {
+        Self {
+            parent_frame: parent_frame.to_owned(),
+            is_static,
+            samples: VecDeque::new(),
+        }
+    }
+
+    fn insert(&mut self, time: SystemTime, transform: Isometry3<f64>, cache_duration: Duration) {
+        let index = self
+            .samples
+            .iter()
+            .rposition(|(stamp, _)| *stamp <= time)
+            .map_or(0, |i| i + 1);
+        if index > 0 && self.samples[index - 1].0 == time {
+            self.samples[index - 1].1 = transform;
+        } else {
+            self.samples.insert(index, (time, transform));
+        }
+        let latest = self.samples.back().unwrap().0;
+        while let Some((oldest, _)) = self.samples.front() {
+            match latest.duration_since(*oldest) {
+                Ok(age) if age > cache_duration => {
+                    self.samples.pop_front();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Returns the nearest sample if `time` is out of the stored range within
+    /// `tolerance`, and `None` if it is further.
+    fn transform_at(
+        &self,
+        time: Option<SystemTime>,
+        tolerance: Duration,
+    ) -> Option<Isometry3<f64>> {
+        let (latest_time, latest) = self.samples.back()?;
+        let time = match time {
+            Some(time) if !self.is_static => time,
+            _ => return Some(*latest),
+        };
+        if let Ok(after_latest) = time.duration_since(*latest_time) {
+            return if after_latest <= tolerance {
+                Some(*latest)
+            } else {
+                None
+            };
+        }
+        let (oldest_time, oldest) = self.samples.front()?;
+        if let Ok(before_oldest) = oldest_time.duration_since(time) {
+            return if before_oldest <= tolerance {
+                Some(*oldest)
+            } else {
+                None
+            };
+        }
+        // `time` is between the oldest and the latest samples.
+        let index = self.samples.iter().position(|(stamp, _)| *stamp >= time)?;
+        let (t1, transform1) = &self.samples[index];
+        let (t0, transform0) = &self.samples[index - 1];
+        let ratio = time.duration_since(*t0).ok()?.as_secs_f64()
+            / t1.duration_since(*t0).ok()?.as_secs_f64();
+        Some(interpolate(transform0, transform1, ratio))
+    }
+}
+
+fn interpolate(from: &Isometry3<f64>, to: &Isometry3<f64>, ratio: f64) -> Isometry3<f64> {
+    let translation =
+        from.translation.vector + (to.translation.vector - from.translation.vector) * ratio;
+    let rotation = from
+        .rotation
+        .try_slerp(&to.rotation, ratio, f64::EPSILON)
+        .unwrap_or(from.rotation);
+    Isometry3::from_parts(translation.into(), rotation)
+}
+
+/// Converts a pose on the floor into 3D.
+pub fn isometry3_from_isometry2(pose: &Isometry2<f64>) -> Isometry3<f64> {
+    Isometry3::from_parts(
+        Translation3::new(pose.translation.x, pose.translation.y, 0.0),
+        UnitQuaternion::from_euler_angles(0.0, 0.0, pose.rotation.angle()),
+    )
+}
+
+/// In-process tree of time-stamped transformations between frames.
+///
+/// Each frame has at most one parent frame. Transformations are interpolated
+/// between the stored samples, and [`UNIX_EPOCH`] as the time means the latest
+/// available transformation. Times slightly out of the stored range are
+/// resolved to the nearest sample within `time_tolerance`.
+#[derive(Debug)]
+pub struct TransformBuffer {
+    cache_duration: Duration,
+    time_tolerance: Duration,
+    // Child frame to its transformations from the parent frame.
+    histories: Mutex<HashMap<String, TransformHistory>>,
+}
+
+impl TransformBuffer {
+    pub fn new(cache_duration: Duration, time_tolerance: Duration) -> Self {
+        Self {
+            cache_duration,
+            time_tolerance,
+            histories: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Creates a new `TransformBuffer` with the static transformations in the config.
+    pub fn new_from_config(config: &TransformBufferConfig) -> Self {
+        let buffer = Self::new(
+            Duration::from_secs_f64(config.cache_duration_secs),
+            Duration::from_secs_f64(config.time_tolerance_secs),
+        );
+        for static_transform in &config.static_transforms {
+            buffer.set_static_transform(
+                &static_transform.parent_frame,
+                &static_transform.child_frame,
+                static_transform.transform(),
+            );
+        }
+        buffer
+    }
+
+    /// Sets the pose of `child_frame` in `parent_frame`, which is valid at any time.
+    pub fn set_static_transform(
+        &self,
+        parent_frame: &str,
+        child_frame: &str,
+        transform: Isometry3<f64>,
+    ) {
+        let mut history = TransformHistory::new(parent_frame, true);
+        history.samples.push_back((UNIX_EPOCH, transform));
+        self.histories
+            .lock()
+            .unwrap()
+            .insert(child_frame.to_owned(), history);
+    }
+
+    /// Sets the pose of `child_frame` in `parent_frame` at `time`.
+    ///
+    /// The past transformations of `child_frame` are discarded if its parent
+    /// frame is changed.
+    pub fn set_transform(
+        &self,
+        parent_frame: &str,
+        child_frame: &str,
+        transform: Isometry3<f64>,
+        time: SystemTime,
+    ) {
+        let mut histories = self.histories.lock().unwrap();
+        let history = histories
+            .entry(child_frame.to_owned())
+            .or_insert_with(|| TransformHistory::new(parent_frame, false));
+        if history.is_static || history.parent_frame != parent_frame {
+            *history = TransformHistory::new(parent_frame, false);
+        }
+        history.insert(time, transform, self.cache_duration);
+    }
+
+    /// Sets the transformations between the links connected by `joints`,
+    /// using the current joint positions of `chain`. The transformations of
+    /// the fixed joints are set as static.
+    pub fn set_transforms_from_chain(
+        &self,
+        joints: &[JointLinks],
+        chain: &Chain<f64>,
+        time: SystemTime,
+    ) -> Result<(), ArciError> {
+        let world_transform = |joint_name: &str| {
+            chain
+                .find(joint_name)
+                .and_then(|node| node.world_transform())
+                .ok_or_else(|| ArciError::NoJoint(joint_name.to_owned()))
+        };
+        for joint in joints {
+            let parent_transform = match &joint.parent_joint_name {
+                Some(parent_joint_name) => world_transform(parent_joint_name)?,
+                None => Isometry3::identity(),
+            };
+            let child_transform = world_transform(&joint.joint_name)?;
+            let transform = parent_transform.inverse() * child_transform;
+            if joint.is_fixed {
+                self.set_static_transform(
+                    &joint.parent_link_name,
+                    &joint.child_link_name,
+                    transform,
+                );
+            } else {
+                self.set_transform(
+                    &joint.parent_link_name,
+                    &joint.child_link_name,
+                    transform,
+                    time,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the names of all the known frames.
+    pub fn frame_names(&self) -> Vec<String> {
+        let histories = self.histories.lock().unwrap();
+        let mut names: HashSet<&str> = HashSet::new();
+        for (child_frame, history) in histories.iter() {
+            names.insert(child_frame);
+            names.insert(&history.parent_frame);
+        }
+        names.into_iter().map(|name| name.to_owned()).collect()
+    }
+
+    /// Returns the pose of `to` in `from` at `time`.
+    pub fn lookup_transform(
+        &self,
+        from: &str,
+        to: &str,
+        time: SystemTime,
+    ) -> Result<Isometry3<f64>, ArciError> {
+        let histories = self.histories.lock().unwrap();
+        let time = if time == UNIX_EPOCH { None } else { Some(time) };
+        let from_ancestors = ancestors(&histories, from)?;
+        let to_ancestors = ancestors(&histories, to)?;
+        let common_ancestor = from_ancestors
+            .iter()
+            .find(|frame| to_ancestors.contains(frame))
+            .ok_or_else(|| {
+                anyhow::format_err!("Frames {:?} and {:?} are not connected", from, to)
+            })?;
+        let from_transform = transform_from_ancestor(
+            &histories,
+            &from_ancestors,
+            common_ancestor,
+            time,
+            self.time_tolerance,
+        )?;
+        let to_transform = transform_from_ancestor(
+            &histories,
+            &to_ancestors,
+            common_ancestor,
+            time,
+            self.time_tolerance,
+        )?;
+        Ok(from_transform.inverse() * to_transform)
+    }
+}
+
+// Returns `frame` and its ancestors, from `frame` to the root.
+fn ancestors<'a>(
+    histories: &'a HashMap<String, TransformHistory>,
+    frame: &'a str,
+) -> Result<Vec<&'a str>, ArciError> {
+    if !histories.contains_key(frame) && !histories.values().any(|h| h.parent_frame == frame) {
+        return Err(anyhow::format_err!("Frame {:?} does not exist", frame).into());
+    }
+    let mut ancestors = vec![frame];
+    let mut current = frame;
+    while let Some(history) = histories.get(current) {
+        current = &history.parent_frame;
+        if ancestors.contains(&current) {
+            return Err(
+                anyhow::format_err!("Frame {:?} has a loop at {:?}", frame, current).into(),
+            );
+        }
+        ancestors.push(current);
+    }
+    Ok(ancestors)
+}
+
+// Returns the pose of `ancestors[0]` in `ancestor`.
+fn transform_from_ancestor(
+    histories: &HashMap<String, TransformHistory>,
+    ancestors: &[&str],
+    ancestor: &str,
+    time: Option<SystemTime>,
+    tolerance: Duration,
+) -> Result<Isometry3<f64>, ArciError> {
+    let mut transform = Isometry3::identity();
+    for frame in ancestors.iter().take_while(|frame| **frame != ancestor) {
+        let history = &histories[*frame];
+        let parent_transform = history.transform_at(time, tolerance).ok_or_else(|| {
+            anyhow::format_err!(
+                "Transformation from {:?} to {:?} is not available at {:?}",
+                history.parent_frame,
+                frame,
+                time
+            )
+        })?;
+        transform = parent_transform * transform;
+    }
+    Ok(transform)
+}
+
+impl TransformResolver for TransformBuffer {
+    fn resolve_transformation(
+        &self,
+        from: &str,
+        to: &str,
+        time: SystemTime,
+    ) -> Result<Isometry3<f64>, ArciError> {
+        self.lookup_transform(from, to, time)
+    }
+}
+
+/// Parent and child links of a joint of URDF.
+#[derive(Debug, Clone)]
+pub struct JointLinks {
+    pub joint_name: String,
+    pub parent_link_name: String,
+    pub child_link_name: String,
+    /// Joint whose child link is the parent link of this joint. `None` if the
+    /// parent link is the root.
+    pub parent_joint_name: Option<String>,
+    pub is_fixed: bool,
+}
+
+impl JointLinks {
+    pub fn from_urdf_robot(robot: &urdf_rs::Robot) -> Vec<Self> {
+        robot
+            .joints
+            .iter()
+            .map(|joint| Self {
+                joint_name: joint.name.clone(),
+                parent_link_name: joint.parent.link.clone(),
+                child_link_name: joint.child.link.clone(),
+                parent_joint_name: robot
+                    .joints
+                    .iter()
+                    .find(|parent| parent.child.link == joint.parent.link)
+                    .map(|parent| parent.name.clone()),
+                is_fixed: matches!(joint.joint_type, urdf_rs::JointType::Fixed),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+
+    use super::*;
+
+    fn translation(x: f64, y: f64, z: f64) -> Isometry3<f64> {
+        Isometry3::translation(x, y, z)
+    }
+
+    fn assert_translation(transform: &Isometry3<f64>, expected: [f64; 3]) {
+        for (actual, expected) in transform.translation.vector.iter().zip(expected.iter()) {
+            assert_approx_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_static_transforms() {
+        let buffer = TransformBuffer::new_from_config(&TransformBufferConfig {
+            static_transforms: vec![
+                StaticTransformConfig {
+                    parent_frame: "base".to_owned(),
+                    child_frame: "arm".to_owned(),
+                    translation: [0.0, 0.0, 1.0],
+                    rotation: [0.0, 0.0, std::f64::consts::FRAC_PI_2],
+                },
+                StaticTransformConfig {
+                    parent_frame: "arm".to_owned(),
+                    child_frame: "hand".to_owned(),
+                    translation: [1.0, 0.0, 0.0],
+                    rotation: [0.0, 0.0, 0.0],
+                },
+                StaticTransformConfig {
+                    parent_frame: "base".to_owned(),
+                    child_frame: "camera".to_owned(),
+                    translation: [0.5, 0.0, 0.0],
+                    rotation: [0.0, 0.0, 0.0],
+                },
+            ],
+            ..Default::default()
+        });
+        let now = SystemTime::now();
+        let hand = buffer.lookup_transform("base", "hand", now).unwrap();
+        assert_translation(&hand, [0.0, 1.0, 1.0]);
+
+        let hand = buffer.lookup_transform("camera", "hand", now).unwrap();
+        assert_translation(&hand, [-0.5, 1.0, 1.0]);
+        let camera = buffer.lookup_transform("hand", "camera", now).unwrap();
+        assert_translation(&(camera * hand), [0.0, 0.0, 0.0]);
+        assert_approx_eq!((camera * hand).rotation.angle(), 0.0);
+
+        assert!(buffer.lookup_transform("base", "unknown", now).is_err());
+        buffer.set_static_transform("world", "floor", Isometry3::identity());
+        assert!(buffer.lookup_transform("base", "floor", now).is_err());
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let buffer = TransformBuffer::new(Duration::from_secs(10), Duration::from_millis(500));
+        let t0 = UNIX_EPOCH + Duration::from_secs(100);
+        buffer.set_transform("map", "base", translation(0.0, 0.0, 0.0), t0);
+        buffer.set_transform(
+            "map",
+            "base",
+            translation(2.0, 0.0, 0.0),
+            t0 + Duration::from_secs(2),
+        );
+        buffer.set_static_transform("base", "arm", translation(0.0, 0.0, 1.0));
+
+        let arm = buffer
+            .lookup_transform("map", "arm", t0 + Duration::from_millis(500))
+            .unwrap();
+        assert_translation(&arm, [0.5, 0.0, 1.0]);
+        // UNIX_EPOCH means the latest.
+        let arm = buffer.lookup_transform("map", "arm", UNIX_EPOCH).unwrap();
+        assert_translation(&arm, [2.0, 0.0, 1.0]);
+
+        // Slightly out of the stored range.
+        let arm = buffer
+            .lookup_transform("map", "arm", t0 - Duration::from_millis(300))
+            .unwrap();
+        assert_translation(&arm, [0.0, 0.0, 1.0]);
+        let arm = buffer
+            .lookup_transform("map", "arm", t0 + Duration::from_millis(2300))
+            .unwrap();
+        assert_translation(&arm, [2.0, 0.0, 1.0]);
+        // Out of the stored range.
+        assert!(buffer
+            .lookup_transform("map", "arm", t0 - Duration::from_secs(1))
+            .is_err());
+        assert!(buffer
+            .lookup_transform("map", "arm", t0 + Duration::from_secs(3))
+            .is_err());
+        // Static transformations are available at any time.
+        assert!(buffer
+            .lookup_transform("base", "arm", t0 + Duration::from_secs(3))
+            .is_ok());
+
+        // Old samples are discarded.
+        buffer.set_transform(
+            "map",
+            "base",
+            translation(4.0, 0.0, 0.0),
+            t0 + Duration::from_secs(12),
+        );
+        assert!(buffer.lookup_transform("map", "base", t0).is_err());
+        let base = buffer
+            .lookup_transform("map", "base", t0 + Duration::from_secs(7))
+            .unwrap();
+        assert_translation(&base, [3.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_fixed_joints() {
+        let mut root_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        root_dir.pop();
+        let urdf_path = root_dir.join("openrr-planner/sample.urdf");
+        let joints = JointLinks::from_urdf_robot(&urdf_rs::read_file(&urdf_path).unwrap());
+        let chain = Chain::<f64>::from_urdf_file(&urdf_path).unwrap();
+        chain.update_transforms();
+
+        let buffer = TransformBuffer::new(Duration::from_secs(10), Duration::from_secs(1));
+        let t0 = UNIX_EPOCH + Duration::from_secs(100);
+        buffer
+            .set_transforms_from_chain(&joints, &chain, t0)
+            .unwrap();
+        let later = t0 + Duration::from_secs(5);
+        // The fixed joint is valid at any time.
+        let tool = buffer
+            .lookup_transform("l_wrist2", "l_tool", later)
+            .unwrap();
+        assert_translation(&tool, [0.2, 0.0, 0.0]);
+        // The movable joint is not.
+        assert!(buffer
+            .lookup_transform("l_elbow1", "l_wrist1", later)
+            .is_err());
+        let wrist = buffer
+            .lookup_transform("l_elbow1", "l_wrist1", t0 + Duration::from_millis(500))
+            .unwrap();
+        assert_translation(&wrist, [0.25, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,287 @@
+use std::{f64::consts::PI, str::FromStr, time::Duration};
+
+use arci::TrajectoryPoint;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Ratio of the time of the acceleration (and the deceleration) to the whole
+/// duration in [`VelocityProfile::Trapezoidal`] and [`VelocityProfile::SCurve`].
+const ACCELERATION_TIME_RATIO: f64 = 0.25;
+
+/// Time scaling of the motion from the start to the goal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum VelocityProfile {
+    /// Constant velocity. The motion starts and stops abruptly.
+    Linear,
+    /// Constant acceleration, constant velocity and constant deceleration.
+    Trapezoidal,
+    /// Trapezoidal velocity with the sinusoidal acceleration, so the jerk is bounded.
+    SCurve,
+    /// Fifth order polynomial which minimizes the jerk.
+    MinimumJerk,
+}
+
+impl Default for VelocityProfile {
+    fn default() -> Self {
+        VelocityProfile::Linear
+    }
+}
+
+impl FromStr for VelocityProfile {
+    type Err = Error;
+
+    /// Accepts both `MinimumJerk` and `minimum_jerk` style names.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.replace(&['_', '-'][..], "").to_lowercase().as_str() {
+            "linear" => Ok(VelocityProfile::Linear),
+            "trapezoidal" => Ok(VelocityProfile::Trapezoidal),
+            "scurve" => Ok(VelocityProfile::SCurve),
+            "minimumjerk" => Ok(VelocityProfile::MinimumJerk),
+            _ => Err(anyhow::format_err!(
+                "Invalid velocity profile \"{}\". Use linear, trapezoidal, s_curve or minimum_jerk.",
+                s
+            )
+            .into()),
+        }
+    }
+}
+
+impl VelocityProfile {
+    /// Progress of the motion in `[0, 1]` at the normalized time `tau` in `[0, 1]`
+    pub fn position(&self, tau: f64) -> f64 {
+        let tau = tau.clamp(0.0, 1.0);
+        let ta = ACCELERATION_TIME_RATIO;
+        match self {
+            VelocityProfile::Linear => tau,
+            VelocityProfile::Trapezoidal | VelocityProfile::SCurve if tau > 0.5 => {
+                1.0 - self.position(1.0 - tau)
+            }
+            VelocityProfile::Trapezoidal if tau < ta => {
+                self.max_velocity() * tau * tau / (2.0 * ta)
+            }
+            VelocityProfile::SCurve if tau < ta => {
+                self.max_velocity() * (tau / 2.0 - ta / (2.0 * PI) * (PI * tau / ta).sin())
+            }
+            VelocityProfile::Trapezoidal | VelocityProfile::SCurve => {
+                self.max_velocity() * (tau - ta / 2.0)
+            }
+            VelocityProfile::MinimumJerk => tau.powi(3) * (10.0 - 15.0 * tau + 6.0 * tau * tau),
+        }
+    }
+
+    /// Derivative of [`Self::position`] with respect to the normalized time
+    pub fn velocity(&self, tau: f64) -> f64 {
+        let tau = tau.clamp(0.0, 1.0);
+        let ta = ACCELERATION_TIME_RATIO;
+        match self {
+            VelocityProfile::Linear => 1.0,
+            VelocityProfile::Trapezoidal | VelocityProfile::SCurve if tau > 0.5 => {
+                self.velocity(1.0 - tau)
+            }
+            VelocityProfile::Trapezoidal if tau < ta => self.max_velocity() * tau / ta,
+            VelocityProfile::SCurve if tau < ta => {
+                self.max_velocity() * (1.0 - (PI * tau / ta).cos()) / 2.0
+            }
+            VelocityProfile::Trapezoidal | VelocityProfile::SCurve => self.max_velocity(),
+            VelocityProfile::MinimumJerk => 30.0 * tau * tau * (1.0 - tau) * (1.0 - tau),
+        }
+    }
+
+    /// Maximum of [`Self::velocity`], i.e. the ratio of the peak speed to the average speed
+    pub fn max_velocity(&self) -> f64 {
+        match self {
+            VelocityProfile::Linear => 1.0,
+            VelocityProfile::Trapezoidal | VelocityProfile::SCurve => {
+                1.0 / (1.0 - ACCELERATION_TIME_RATIO)
+            }
+            VelocityProfile::MinimumJerk => 1.875,
+        }
+    }
+
+    /// Shortest duration \[s\] to move `distance` without exceeding `max_speed`.
+    ///
+    /// `max_speed` must be positive. [`f64::INFINITY`] means no limit.
+    pub fn min_duration(&self, distance: f64, max_speed: f64) -> Result<f64, arci::Error> {
+        if max_speed.is_nan() || max_speed <= 0.0 {
+            return Err(anyhow::format_err!(
+                "Invalid max speed {}, which must be positive",
+                max_speed
+            )
+            .into());
+        }
+        Ok(distance.abs() * self.max_velocity() / max_speed)
+    }
+}
+
+/// Interpolates the joint positions from `start` to `goal` with the velocity profile.
+///
+/// `start` is not included in the trajectory. The velocities are filled except
+/// with [`VelocityProfile::Linear`], which leaves them to the client as the
+/// interpolation of the IK clients does.
+pub fn interpolate_joint_positions(
+    start: &[f64],
+    goal: &[f64],
+    velocity_profile: VelocityProfile,
+    duration_sec: f64,
+    max_resolution: f64,
+    min_number_of_points: i32,
+) -> Result<Vec<TrajectoryPoint>, Error> {
+    if start.len() != goal.len() {
+        return Err(Error::MismatchedLength(start.len(), goal.len()));
+    }
+    let max_diff = start
+        .iter()
+        .zip(goal)
+        .map(|(s, g)| (g - s).abs())
+        .fold(0.0, f64::max);
+    let n = std::cmp::max(
+        min_number_of_points,
+        (max_diff * velocity_profile.max_velocity() / max_resolution) as i32,
+    );
+    let mut traj = vec![];
+    for i in 1..n + 1 {
+        let tau = i as f64 / n as f64;
+        let s = velocity_profile.position(tau);
+        let ds_dt = if duration_sec > 0.0 {
+            velocity_profile.velocity(tau) / duration_sec
+        } else {
+            0.0
+        };
+        let mut point = TrajectoryPoint::new(
+            start
+                .iter()
+                .zip(goal)
+                .map(|(start, goal)| start + (goal - start) * s)
+                .collect(),
+            Duration::from_secs_f64(tau * duration_sec),
+        );
+        if velocity_profile != VelocityProfile::Linear {
+            point.velocities = Some(
+                start
+                    .iter()
+                    .zip(goal)
+                    .map(|(start, goal)| (goal - start) * ds_dt)
+                    .collect(),
+            );
+        }
+        traj.push(point);
+    }
+    if velocity_profile == VelocityProfile::Linear {
+        return Ok(traj);
+    }
+    // Stops at the goal.
+    if let Some(last) = traj.last_mut() {
+        last.velocities = Some(vec![0.0; goal.len()]);
+    }
+    Ok(traj)
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+
+    use super::*;
+
+    const PROFILES: [VelocityProfile; 4] = [
+        VelocityProfile::Linear,
+        VelocityProfile::Trapezoidal,
+        VelocityProfile::SCurve,
+        VelocityProfile::MinimumJerk,
+    ];
+
+    #[test]
+    fn test_velocity_profile() {
+        for profile in &PROFILES {
+            assert_approx_eq!(profile.position(0.0), 0.0);
+            assert_approx_eq!(profile.position(0.5), 0.5);
+            assert_approx_eq!(profile.position(1.0), 1.0);
+            let dt = 1e-6;
+            let mut max_velocity = 0.0;
+            for i in 1..100 {
+                let tau = i as f64 / 100.0;
+                // The velocity is the derivative of the position.
+                let velocity =
+                    (profile.position(tau + dt) - profile.position(tau - dt)) / (2.0 * dt);
+                assert_approx_eq!(profile.velocity(tau), velocity, 1e-4);
+                assert!(profile.position(tau) <= profile.position(tau + 0.01));
+                max_velocity = f64::max(max_velocity, profile.velocity(tau));
+            }
+            assert_approx_eq!(max_velocity, profile.max_velocity(), 1e-3);
+        }
+        for profile in &PROFILES[1..] {
+            assert_approx_eq!(profile.velocity(0.0), 0.0);
+            assert_approx_eq!(profile.velocity(1.0), 0.0);
+        }
+        assert_approx_eq!(
+            VelocityProfile::MinimumJerk
+                .min_duration(-0.2, 0.1)
+                .unwrap(),
+            3.75
+        );
+        assert_approx_eq!(
+            VelocityProfile::Linear
+                .min_duration(0.2, f64::INFINITY)
+                .unwrap(),
+            0.0
+        );
+        assert!(VelocityProfile::Linear.min_duration(0.2, 0.0).is_err());
+        assert!(VelocityProfile::Linear.min_duration(0.2, -0.1).is_err());
+        assert!(VelocityProfile::Linear.min_duration(0.2, f64::NAN).is_err());
+        assert_eq!(
+            "minimum_jerk".parse::<VelocityProfile>().unwrap(),
+            VelocityProfile::MinimumJerk
+        );
+        assert_eq!(
+            "SCurve".parse::<VelocityProfile>().unwrap(),
+            VelocityProfile::SCurve
+        );
+        assert!("cubic".parse::<VelocityProfile>().is_err());
+    }
+
+    #[test]
+    fn test_interpolate_joint_positions() {
+        let traj = interpolate_joint_positions(
+            &[0.0, 1.0],
+            &[1.0, -1.0],
+            VelocityProfile::Trapezoidal,
+            2.0,
+            0.05,
+            10,
+        )
+        .unwrap();
+        // 2.0 [rad] * 4/3 / 0.05
+        assert_eq!(traj.len(), 53);
+        assert_eq!(traj.last().unwrap().positions, vec![1.0, -1.0]);
+        assert_eq!(traj.last().unwrap().time_from_start, Duration::from_secs(2));
+        assert_eq!(traj.last().unwrap().velocities, Some(vec![0.0, 0.0]));
+        let middle = &traj[traj.len() / 2 - 1];
+        let velocities = middle.velocities.as_ref().unwrap();
+        // Peak speed is 4/3 of the average speed.
+        assert_approx_eq!(velocities[0], 0.5 * 4.0 / 3.0);
+        assert_approx_eq!(velocities[1], -1.0 * 4.0 / 3.0);
+
+        let traj = interpolate_joint_positions(
+            &[0.0, 1.0],
+            &[1.0, -1.0],
+            VelocityProfile::Linear,
+            2.0,
+            0.05,
+            10,
+        )
+        .unwrap();
+        assert_eq!(traj.last().unwrap().positions, vec![1.0, -1.0]);
+        assert!(traj.iter().all(|point| point.velocities.is_none()));
+
+        assert!(interpolate_joint_positions(
+            &[0.0],
+            &[1.0, -1.0],
+            VelocityProfile::Linear,
+            2.0,
+            0.05,
+            10
+        )
+        .is_err());
+    }
+}
@@ -222,6 +222,7 @@ fn test_ik_solver_with_chain_generate_trajectory_with_interpolation() {
         .generate_trajectory_with_interpolation(&current, &target, 1.0, 0.05, 10)
         .unwrap();
     assert!(!result.is_empty());
+    assert!(result.iter().all(|p| p.velocities.is_none()));
 }
 
 #[test]
@@ -261,3 +262,35 @@ fn test_ik_solver_with_chain_generate_trajectory_with_interpolation_and_constrai
         .unwrap();
     assert!(!result.is_empty());
 }
+
+#[test]
+fn test_ik_solver_with_chain_generate_trajectory_with_velocity_profile() {
+    let chain = k::Chain::<f64>::from_urdf_file("../openrr-planner/sample.urdf").unwrap();
+    let end_link = chain.find("l_tool_fixed").unwrap();
+    let arm = k::SerialChain::from_end(end_link);
+    let positions = vec![0.1, 0.2, 0.0, -0.5, 0.0, -0.3];
+    arm.set_joint_positions(&positions).unwrap();
+    let params = ik_solver_parameters(0.01, 0.02, 0.1, 100);
+    let ik_solver = create_jacobian_ik_solver(&params);
+    let constraints = k::Constraints::default();
+    let ik_solver_with_chain = IkSolverWithChain::new(arm, Arc::new(ik_solver), constraints);
+
+    let current = ik_solver_with_chain.end_transform();
+    let mut target = current;
+    target.translation.vector.z += 0.1;
+    let result = ik_solver_with_chain
+        .generate_trajectory_with_velocity_profile(
+            &current,
+            &target,
+            &k::Constraints::default(),
+            VelocityProfile::Trapezoidal,
+            2.0,
+            0.05,
+            10,
+        )
+        .unwrap();
+    assert_eq!(result.len(), 10);
+    assert_approx_eq!(result.last().unwrap().time_from_start.as_secs_f64(), 2.0);
+    assert!(result.iter().all(|p| p.velocities.is_some()));
+    assert_eq!(result.last().unwrap().velocities, Some(vec![0.0; 6]));
+}
@@ -5,15 +5,17 @@ use std::{
     path::PathBuf,
     process::Command,
     thread::sleep,
-    time::{Duration, Instant},
+    time::{Duration, Instant, UNIX_EPOCH},
 };
 
-use arci::{BaseVelocity, Localization, MoveBase, Navigation};
+use arci::{
+    BaseVelocity, Localization, MoveBase, Navigation, PoseWithCovariance, TransformResolver,
+};
 use async_recursion::async_recursion;
 use k::nalgebra::{Isometry2, Vector2};
-use openrr_client::{isometry, RobotClient};
+use openrr_client::{isometry, RobotClient, VelocityProfile};
 use structopt::StructOpt;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::Error as OpenrrCommandError;
 
@@ -42,6 +44,10 @@ pub enum RobotCommand {
         /// If you use this flag, joint values are not used as references but used in forward kinematics.
         #[structopt(name = "interpolate", short, long)]
         use_interpolation: bool,
+        /// Velocity profile of the interpolation: linear, trapezoidal, s_curve or minimum_jerk.
+        /// Without `--interpolate`, joint values are interpolated in joint space.
+        #[structopt(long)]
+        velocity_profile: Option<VelocityProfile>,
         #[structopt(short, parse(try_from_str=parse_joints))]
         joint: Vec<(usize, f64)>,
     },
@@ -52,6 +58,13 @@ pub enum RobotCommand {
         #[structopt(short, long, default_value = "3.0")]
         duration: f64,
     },
+    /// Save the current joint positions as the predefined joint positions.
+    /// They are written to the joints poses file if it is configured.
+    SaveJointsPose { name: String, pose_name: String },
+    /// Delete the predefined joint positions.
+    DeleteJointsPose { name: String, pose_name: String },
+    /// List the predefined joint positions.
+    ListJointsPoses,
     /// Move with ik
     MoveIk {
         name: String,
@@ -72,11 +85,32 @@ pub enum RobotCommand {
         /// Interpolate target in cartesian space.
         #[structopt(name = "interpolate", short, long)]
         use_interpolation: bool,
+        /// Velocity profile of the interpolation: linear, trapezoidal, s_curve or minimum_jerk.
+        /// The profile is used until another profile is given.
+        #[structopt(long)]
+        velocity_profile: Option<VelocityProfile>,
+        /// Maximum linear speed [m/s] of the interpolation.
+        /// If this or `--max_angular_speed` is given, the duration is computed from the speeds
+        /// instead of `--duration`.
+        #[structopt(long)]
+        max_linear_speed: Option<f64>,
+        /// Maximum angular speed [rad/s] of the interpolation.
+        #[structopt(long)]
+        max_angular_speed: Option<f64>,
         #[structopt(name = "local", short, long)]
         is_local: bool,
+        /// Named end frame of the IK client to move, e.g. a tool center point.
+        /// The frame is used until another frame is given or `--reset_frame` is given.
+        #[structopt(long)]
+        frame: Option<String>,
+        /// Move the end of the chain of the IK client instead of the named end frame.
+        #[structopt(long, conflicts_with = "frame")]
+        reset_frame: bool,
     },
     /// Get joint positions and end pose if applicable.
     GetState { name: String },
+    /// Get the pose of `to` frame in `from` frame, e.g. the links of URDF.
+    GetTransform { from: String, to: String },
     /// Load commands from file and execute them.
     LoadCommands {
         #[structopt(parse(from_os_str))]
@@ -90,6 +124,20 @@ pub enum RobotCommand {
     ExecuteCommand { command: Vec<String> },
     /// Get navigation current pose.
     GetNavigationCurrentPose,
+    /// Set initial pose of localization.
+    SetInitialPose {
+        x: f64,
+        y: f64,
+        yaw: f64,
+        #[structopt(short, long, default_value = "map")]
+        frame_id: String,
+        /// Standard deviation of the position.
+        #[structopt(long, default_value = "0.5")]
+        position_std_dev: f64,
+        /// Standard deviation of the yaw angle.
+        #[structopt(long, default_value = "0.26")]
+        yaw_std_dev: f64,
+    },
     /// Send navigation goal pose.
     SendNavigationGoal {
         x: f64,
@@ -122,7 +170,7 @@ impl RobotCommandExecutor {
         command: &RobotCommand,
     ) -> Result<(), OpenrrCommandError>
     where
-        L: Localization,
+        L: Localization + 'static,
         M: MoveBase,
         N: Navigation,
     {
@@ -131,6 +179,7 @@ impl RobotCommandExecutor {
                 name,
                 duration,
                 use_interpolation,
+                velocity_profile,
                 joint,
             } => {
                 let mut positions = client.current_joint_positions(name)?;
@@ -146,9 +195,21 @@ impl RobotCommandExecutor {
                     return Ok(());
                 }
                 if *use_interpolation {
+                    if let Some(velocity_profile) = velocity_profile {
+                        client.set_velocity_profile(name, *velocity_profile)?;
+                    }
                     client
                         .send_joint_positions_with_pose_interpolation(name, &positions, *duration)?
                         .await?;
+                } else if let Some(velocity_profile) = velocity_profile {
+                    client
+                        .send_joint_positions_with_velocity_profile(
+                            name,
+                            &positions,
+                            *velocity_profile,
+                            *duration,
+                        )?
+                        .await?;
                 } else {
                     client
                         .send_joint_positions(name, &positions, *duration)?
@@ -162,6 +223,28 @@ impl RobotCommandExecutor {
             } => {
                 client.send_joints_pose(name, pose_name, *duration)?.await?;
             }
+            RobotCommand::SaveJointsPose { name, pose_name } => {
+                let joints_pose = client.save_joints_pose(name, pose_name)?;
+                println!(
+                    "Saved {} {} : {:?}",
+                    joints_pose.client_name, joints_pose.pose_name, joints_pose.positions
+                );
+                match client.joints_poses_full_path() {
+                    Some(path) => println!("to {:?}", path),
+                    None => warn!("No joints_poses_path is configured, so the pose is not kept"),
+                }
+            }
+            RobotCommand::DeleteJointsPose { name, pose_name } => {
+                client.delete_joints_pose(name, pose_name)?;
+            }
+            RobotCommand::ListJointsPoses => {
+                for joints_pose in client.joints_poses() {
+                    println!(
+                        "{} {} : {:?}",
+                        joints_pose.client_name, joints_pose.pose_name, joints_pose.positions
+                    );
+                }
+            }
             RobotCommand::MoveIk {
                 name,
                 x,
@@ -172,11 +255,24 @@ impl RobotCommandExecutor {
                 roll,
                 duration,
                 use_interpolation,
+                velocity_profile,
+                max_linear_speed,
+                max_angular_speed,
                 is_local,
+                frame,
+                reset_frame,
             } => {
                 if !client.is_ik_client(name) {
                     return Err(OpenrrCommandError::NoIkClient(name.clone()));
                 }
+                if *reset_frame {
+                    client.set_end_frame(name, None)?;
+                } else if let Some(frame) = frame {
+                    client.set_end_frame(name, Some(frame))?;
+                }
+                if let Some(velocity_profile) = velocity_profile {
+                    client.set_velocity_profile(name, *velocity_profile)?;
+                }
                 let mut should_send = false;
                 let current_pose = client.current_end_transform(name)?;
                 let target_pose = [
@@ -246,7 +342,16 @@ impl RobotCommandExecutor {
                 } else {
                     target_pose
                 };
-                if *use_interpolation {
+                if max_linear_speed.is_some() || max_angular_speed.is_some() {
+                    client
+                        .move_ik_with_max_speed(
+                            name,
+                            &target_pose,
+                            max_linear_speed.unwrap_or(f64::INFINITY),
+                            max_angular_speed.unwrap_or(f64::INFINITY),
+                        )?
+                        .await?
+                } else if *use_interpolation {
                     client
                         .move_ik_with_interpolation(name, &target_pose, *duration)?
                         .await?
@@ -262,10 +367,24 @@ impl RobotCommandExecutor {
                 if client.is_ik_client(name) {
                     let pose = client.current_end_transform(name)?;
                     println!("End pose");
+                    if let Some(frame) = client.end_frame(name)? {
+                        println!(" frame = {}", frame);
+                    }
                     println!(" translation = {:?}", pose.translation.vector.data);
                     println!(" rotation = {:?}", pose.rotation.euler_angles());
+                    let analysis = client.kinematic_analysis(name)?;
+                    println!("Kinematics");
+                    println!(" manipulability = {}", analysis.manipulability());
+                    println!(" condition number = {}", analysis.condition_number());
+                    println!(" min singular value = {}", analysis.min_singular_value());
                 }
             }
+            RobotCommand::GetTransform { from, to } => {
+                // UNIX_EPOCH means the latest transformation.
+                let pose = client.resolve_transformation(from, to, UNIX_EPOCH)?;
+                println!(" translation = {:?}", pose.translation.vector.data);
+                println!(" rotation = {:?}", pose.rotation.euler_angles());
+            }
             RobotCommand::LoadCommands { command_file_path } => {
                 for command in load_command_file_and_filter(command_file_path.clone())? {
                     let command_parsed_iter = command.split_whitespace();
@@ -289,6 +408,10 @@ impl RobotCommandExecutor {
                 for name in client.collision_check_clients_names() {
                     println!(" {}", name);
                 }
+                println!("Torque limit clients");
+                for name in client.torque_limit_clients_names() {
+                    println!(" {}", name);
+                }
                 println!("Ik clients");
                 for name in client.ik_clients_names() {
                     println!(" {}", name);
@@ -322,6 +445,23 @@ impl RobotCommandExecutor {
             RobotCommand::GetNavigationCurrentPose => {
                 println!("Base Pose {}", client.current_pose("")?);
             }
+            RobotCommand::SetInitialPose {
+                x,
+                y,
+                yaw,
+                frame_id,
+                position_std_dev,
+                yaw_std_dev,
+            } => {
+                client.set_initial_pose(
+                    PoseWithCovariance::from_std_dev(
+                        Isometry2::new(Vector2::new(*x, *y), *yaw),
+                        *position_std_dev,
+                        *yaw_std_dev,
+                    ),
+                    frame_id,
+                )?;
+            }
             RobotCommand::SendNavigationGoal {
                 x,
                 y,
@@ -0,0 +1,90 @@
+/*
+This benchmark measures the collision checking of benches/meshed.urdf, whose collision
+models are meshes, with each mesh approximation, with and without the broadphase.
+
+$ cargo bench -p openrr-planner
+*/
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use ncollide3d::shape::Compound;
+use openrr_planner::{
+    collision::{
+        create_robot_collision_detector, MeshApproximation, RobotCollisionDetector,
+        RobotCollisionDetectorConfig,
+    },
+    FromUrdf,
+};
+
+/// Robot whose collision models are meshes, so that the approximations make a difference
+const URDF_PATH: &str = "benches/meshed.urdf";
+
+const BROADPHASE: &[bool] = &[true, false];
+
+const APPROXIMATIONS: &[MeshApproximation] = &[
+    MeshApproximation::None,
+    MeshApproximation::BoundingBox,
+    MeshApproximation::Capsule,
+    MeshApproximation::ConvexHull,
+];
+
+fn create_detector(
+    mesh_approximation: MeshApproximation,
+    use_broadphase: bool,
+) -> RobotCollisionDetector<f64> {
+    let config = RobotCollisionDetectorConfig {
+        mesh_approximation,
+        ..Default::default()
+    };
+    let mut detector = create_robot_collision_detector(URDF_PATH, config, vec![]);
+    // The meshes are not loaded if the fixture is not found.
+    assert_eq!(detector.collision_detector.collision_link_names().len(), 5);
+    detector.collision_detector.use_broadphase = use_broadphase;
+    detector
+}
+
+/// e.g. `self_collision_None` and `self_collision_None_no_broadphase` as the baseline
+fn bench_name(prefix: &str, approximation: MeshApproximation, use_broadphase: bool) -> String {
+    if use_broadphase {
+        format!("{}_{:?}", prefix, approximation)
+    } else {
+        format!("{}_{:?}_no_broadphase", prefix, approximation)
+    }
+}
+
+fn self_collision(c: &mut Criterion) {
+    for &approximation in APPROXIMATIONS {
+        for &use_broadphase in BROADPHASE {
+            let mut detector = create_detector(approximation, use_broadphase);
+            let names = detector.collision_detector.collision_link_names();
+            detector.self_collision_pairs = names
+                .iter()
+                .enumerate()
+                .flat_map(|(i, name1)| {
+                    names[i + 1..]
+                        .iter()
+                        .map(move |name2| (name1.clone(), name2.clone()))
+                })
+                .collect();
+            c.bench_function(
+                &bench_name("self_collision", approximation, use_broadphase),
+                |b| b.iter(|| detector.detect_self().count()),
+            );
+        }
+    }
+}
+
+fn env_collision(c: &mut Criterion) {
+    let obstacles = Compound::from_urdf_file("obstacles.urdf").unwrap();
+    for &approximation in APPROXIMATIONS {
+        for &use_broadphase in BROADPHASE {
+            let detector = create_detector(approximation, use_broadphase);
+            c.bench_function(
+                &bench_name("env_collision", approximation, use_broadphase),
+                |b| b.iter(|| detector.env_collision_link_names(&obstacles)),
+            );
+        }
+    }
+}
+
+criterion_group!(benches, self_collision, env_collision);
+criterion_main!(benches);
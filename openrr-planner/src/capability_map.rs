@@ -0,0 +1,444 @@
+use std::{
+    cmp::Ordering,
+    fmt::Write,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use k::{nalgebra as na, InverseKinematicsSolver, SubsetOf};
+use na::RealField;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{errors::*, KinematicAnalysis};
+
+/// Reachable orientations in a voxel of [`CapabilityMap`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Voxel<N> {
+    /// Index of the voxel in the grid
+    pub index: [usize; 3],
+    /// Center of the voxel
+    pub center: [N; 3],
+    /// Indices of the reachable orientations in [`CapabilityMap::orientations`]
+    pub reachable_orientations: Vec<usize>,
+    /// Ratio of the reachable orientations to all of the sampled orientations
+    pub reachability: N,
+    /// Mean manipulability of the reachable orientations
+    pub manipulability: N,
+}
+
+/// Base pose which makes the target reachable, found by [`CapabilityMap::base_placements`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasePlacement<N>
+where
+    N: RealField,
+{
+    /// Pose of the root of the robot on the floor
+    pub pose: na::Isometry2<N>,
+    pub reachability: N,
+    pub manipulability: N,
+}
+
+/// Map of the reachable orientations and the manipulability of the end of the arm in each voxel
+///
+/// The positions and the orientations are in the frame of the root of the
+/// robot, which is the frame of the IK targets, even if the arm starts from
+/// another link. Only the voxels which have at least one reachable
+/// orientation are stored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CapabilityMap<N> {
+    /// Names of the joints of the arm
+    pub joint_names: Vec<String>,
+    /// Minimum corner of the grid
+    pub min_point: [N; 3],
+    /// Length of the edges of the voxels \[m\]
+    pub voxel_size: N,
+    /// Number of the voxels along the x, y and z axes
+    pub num_voxels: [usize; 3],
+    /// Sampled orientations of the end of the arm as roll, pitch and yaw \[rad\]
+    pub orientations: Vec<[N; 3]>,
+    pub voxels: Vec<Voxel<N>>,
+}
+
+impl<N> CapabilityMap<N>
+where
+    N: RealField + SubsetOf<f64>,
+{
+    /// Generate the map by solving IK for the sampled orientations in each voxel
+    ///
+    /// The orientations point the x axis of the end of the arm to
+    /// `num_directions` directions distributed evenly on the sphere, with
+    /// `num_rolls` rotations around the axis. IK is solved from the current
+    /// joint positions, which are restored after the generation.
+    ///
+    /// `min_point` and `max_point` are in the frame of the root of the robot.
+    /// The joints which are not in `arm` are fixed at the current positions.
+    ///
+    /// Returns an error if `voxel_size` is not positive and finite, the
+    /// points are not finite, `max_point` is smaller than `min_point` or
+    /// `num_directions` or `num_rolls` is zero.
+    pub fn generate<I>(
+        ik_solver: &I,
+        arm: &k::SerialChain<N>,
+        min_point: na::Vector3<N>,
+        max_point: na::Vector3<N>,
+        voxel_size: N,
+        num_directions: usize,
+        num_rolls: usize,
+    ) -> Result<Self>
+    where
+        I: InverseKinematicsSolver<N>,
+    {
+        let invalid = |error: String| Err(Error::Other { error });
+        let voxel_size_f64: f64 = voxel_size.to_superset();
+        if !voxel_size_f64.is_finite() || voxel_size_f64 <= 0.0 {
+            return invalid(format!(
+                "voxel_size must be positive and finite, but {}",
+                voxel_size_f64
+            ));
+        }
+        for i in 0..3 {
+            let min: f64 = min_point[i].to_superset();
+            let max: f64 = max_point[i].to_superset();
+            if !min.is_finite() || !max.is_finite() {
+                return invalid(format!(
+                    "the points must be finite, but min={} max={}",
+                    min, max
+                ));
+            }
+            if max < min {
+                return invalid(format!(
+                    "max_point must not be smaller than min_point, but min={} max={}",
+                    min, max
+                ));
+            }
+        }
+        if num_directions == 0 || num_rolls == 0 {
+            return invalid(format!(
+                "num_directions and num_rolls must be positive, but {} and {}",
+                num_directions, num_rolls
+            ));
+        }
+
+        let orientations = sample_orientations(num_directions, num_rolls);
+        let rotations: Vec<_> = orientations.iter().map(rotation_from_rpy).collect();
+        let mut num_voxels = [0; 3];
+        for (i, num) in num_voxels.iter_mut().enumerate() {
+            let length: f64 = ((max_point[i] - min_point[i]) / voxel_size).to_superset();
+            *num = length.ceil().max(1.0) as usize;
+        }
+        let mut map = Self {
+            joint_names: arm.iter_joints().map(|j| j.name.clone()).collect(),
+            min_point: [min_point[0], min_point[1], min_point[2]],
+            voxel_size,
+            num_voxels,
+            orientations,
+            voxels: vec![],
+        };
+
+        let initial_angles = arm.joint_positions();
+        for ix in 0..num_voxels[0] {
+            for iy in 0..num_voxels[1] {
+                for iz in 0..num_voxels[2] {
+                    let index = [ix, iy, iz];
+                    let center = map.center(&index);
+                    let mut reachable_orientations = vec![];
+                    let mut manipulability_sum = N::zero();
+                    for (i, rotation) in rotations.iter().enumerate() {
+                        arm.set_joint_positions_unchecked(&initial_angles);
+                        let target_pose =
+                            na::Isometry3::from_parts(na::Translation3::from(center), *rotation);
+                        if ik_solver.solve(arm, &target_pose).is_ok() {
+                            reachable_orientations.push(i);
+                            manipulability_sum += KinematicAnalysis::new(arm).manipulability();
+                        }
+                    }
+                    if reachable_orientations.is_empty() {
+                        continue;
+                    }
+                    let num_reachable: N = na::convert(reachable_orientations.len() as f64);
+                    let num_orientations: N = na::convert(rotations.len() as f64);
+                    map.voxels.push(Voxel {
+                        index,
+                        center: [center[0], center[1], center[2]],
+                        reachable_orientations,
+                        reachability: num_reachable / num_orientations,
+                        manipulability: manipulability_sum / num_reachable,
+                    });
+                }
+            }
+        }
+        arm.set_joint_positions_unchecked(&initial_angles);
+        arm.update_transforms();
+        Ok(map)
+    }
+
+    fn center(&self, index: &[usize; 3]) -> na::Vector3<N> {
+        let half: N = na::convert(0.5);
+        na::Vector3::from_fn(|i, _| {
+            self.min_point[i] + (na::convert::<f64, N>(index[i] as f64) + half) * self.voxel_size
+        })
+    }
+
+    /// Voxel which contains `position`, or `None` if no orientations are reachable there
+    pub fn voxel(&self, position: &na::Vector3<N>) -> Option<&Voxel<N>> {
+        let mut index = [0; 3];
+        for (i, value) in index.iter_mut().enumerate() {
+            let v: f64 = ((position[i] - self.min_point[i]) / self.voxel_size).to_superset();
+            if v < 0.0 || v >= self.num_voxels[i] as f64 {
+                return None;
+            }
+            *value = v as usize;
+        }
+        self.voxels.iter().find(|voxel| voxel.index == index)
+    }
+
+    /// Index of the sampled orientation nearest to `rotation`
+    pub fn nearest_orientation(&self, rotation: &na::UnitQuaternion<N>) -> Option<usize> {
+        self.orientations
+            .iter()
+            .map(|rpy| rotation_from_rpy(rpy).angle_to(rotation))
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+            .map(|(i, _)| i)
+    }
+
+    /// Returns true if the nearest sampled orientation to the pose is reachable
+    /// in the voxel of the pose
+    pub fn is_reachable(&self, pose: &na::Isometry3<N>) -> bool {
+        match (
+            self.voxel(&pose.translation.vector),
+            self.nearest_orientation(&pose.rotation),
+        ) {
+            (Some(voxel), Some(orientation)) => voxel.reachable_orientations.contains(&orientation),
+            _ => false,
+        }
+    }
+
+    /// Poses of the base on the floor which make `target_pose` reachable, the best first
+    ///
+    /// The yaw angles of the base are sampled `num_yaws` times. The voxels
+    /// at the height of the target are used and the root of the robot is
+    /// assumed to be on the floor (z = 0). The placements are sorted by the
+    /// reachability, then the manipulability.
+    pub fn base_placements(
+        &self,
+        target_pose: &na::Isometry3<N>,
+        num_yaws: usize,
+    ) -> Vec<BasePlacement<N>> {
+        let half_size = self.voxel_size * na::convert(0.5);
+        let target = &target_pose.translation.vector;
+        let mut placements = vec![];
+        for i in 0..num_yaws {
+            let yaw: N = na::convert(std::f64::consts::PI * 2.0 * i as f64 / num_yaws as f64);
+            let base_rotation = na::UnitQuaternion::from_axis_angle(&na::Vector3::z_axis(), yaw);
+            let orientation =
+                match self.nearest_orientation(&(base_rotation.inverse() * target_pose.rotation)) {
+                    Some(orientation) => orientation,
+                    None => continue,
+                };
+            for voxel in &self.voxels {
+                let center = na::Vector3::new(voxel.center[0], voxel.center[1], voxel.center[2]);
+                if (center[2] - target[2]).abs() > half_size
+                    || !voxel.reachable_orientations.contains(&orientation)
+                {
+                    continue;
+                }
+                let base = target - base_rotation * center;
+                placements.push(BasePlacement {
+                    pose: na::Isometry2::new(na::Vector2::new(base[0], base[1]), yaw),
+                    reachability: voxel.reachability,
+                    manipulability: voxel.manipulability,
+                });
+            }
+        }
+        placements.sort_by(|a, b| {
+            b.reachability
+                .partial_cmp(&a.reachability)
+                .unwrap_or(Ordering::Equal)
+                .then(
+                    b.manipulability
+                        .partial_cmp(&a.manipulability)
+                        .unwrap_or(Ordering::Equal),
+                )
+        });
+        placements
+    }
+
+    /// CSV of the voxels for the visualization
+    ///
+    /// The columns are `x,y,z,reachability,manipulability`.
+    pub fn to_csv(&self) -> String {
+        let mut csv = "x,y,z,reachability,manipulability\n".to_owned();
+        for v in &self.voxels {
+            writeln!(
+                csv,
+                "{},{},{},{},{}",
+                v.center[0], v.center[1], v.center[2], v.reachability, v.manipulability
+            )
+            .unwrap();
+        }
+        csv
+    }
+}
+
+impl<N> CapabilityMap<N>
+where
+    N: Serialize,
+{
+    /// Save the map to a file
+    pub fn save<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self).map_err(|e| Error::Other {
+            error: format!("failed to save capability map: {}", e),
+        })
+    }
+}
+
+impl<N> CapabilityMap<N>
+where
+    N: DeserializeOwned,
+{
+    /// Load the map from a file created by [`CapabilityMap::save`]
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let reader = BufReader::new(File::open(path.as_ref())?);
+        serde_json::from_reader(reader)
+            .map_err(|e| Error::ParseError(format!("{:?}: {}", path.as_ref(), e)))
+    }
+}
+
+fn rotation_from_rpy<N>(rpy: &[N; 3]) -> na::UnitQuaternion<N>
+where
+    N: RealField,
+{
+    na::UnitQuaternion::from_euler_angles(rpy[0], rpy[1], rpy[2])
+}
+
+/// Orientations whose x axes point to the directions on the Fibonacci sphere
+fn sample_orientations<N>(num_directions: usize, num_rolls: usize) -> Vec<[N; 3]>
+where
+    N: RealField,
+{
+    let golden_angle = std::f64::consts::PI * (3.0 - 5.0_f64.sqrt());
+    let mut orientations = vec![];
+    for i in 0..num_directions {
+        let z = 1.0 - 2.0 * (i as f64 + 0.5) / num_directions as f64;
+        let radius = (1.0 - z * z).sqrt();
+        let phi = golden_angle * i as f64;
+        let direction = na::Vector3::new(radius * phi.cos(), radius * phi.sin(), z);
+        let rotation = na::UnitQuaternion::rotation_between(&na::Vector3::x(), &direction)
+            .unwrap_or_else(|| {
+                na::UnitQuaternion::from_axis_angle(&na::Vector3::z_axis(), std::f64::consts::PI)
+            });
+        for j in 0..num_rolls {
+            let roll = std::f64::consts::PI * 2.0 * j as f64 / num_rolls as f64;
+            let (r, p, y) = (rotation
+                * na::UnitQuaternion::from_axis_angle(&na::Vector3::x_axis(), roll))
+            .euler_angles();
+            orientations.push([na::convert(r), na::convert(p), na::convert(y)]);
+        }
+    }
+    orientations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capability_map() {
+        let robot = k::Chain::<f64>::from_urdf_file("sample.urdf").unwrap();
+        let arm = k::SerialChain::from_end(robot.find("l_tool_fixed").unwrap());
+        arm.set_joint_positions(&[0.2, 0.2, 0.0, -1.0, 0.0, 0.0])
+            .unwrap();
+        let initial_pose = arm.end_transform();
+        let center = initial_pose.translation.vector;
+        let solver = k::JacobianIkSolver::new(0.01, 0.1, 0.5, 100);
+        let map = CapabilityMap::generate(
+            &solver,
+            &arm,
+            center - na::Vector3::new(0.1, 0.1, 0.1),
+            center + na::Vector3::new(0.1, 0.1, 0.1),
+            0.1,
+            6,
+            2,
+        )
+        .unwrap();
+        assert_eq!(map.num_voxels, [2, 2, 2]);
+        assert_eq!(map.orientations.len(), 12);
+        assert_eq!(map.joint_names.len(), 6);
+        assert!(!map.voxels.is_empty());
+        for voxel in &map.voxels {
+            assert!(voxel.reachability > 0.0 && voxel.reachability <= 1.0);
+            assert!(voxel.manipulability >= 0.0);
+        }
+        assert!(map
+            .voxel(&(center + na::Vector3::new(1.0, 0.0, 0.0)))
+            .is_none());
+        assert_eq!(map.to_csv().lines().count(), map.voxels.len() + 1);
+        // The joint positions are restored.
+        assert!((arm.end_transform().translation.vector - center).norm() < 1e-6);
+
+        // The target is 1m ahead of the reachable pose.
+        let voxel = &map.voxels[0];
+        let target_pose = na::Isometry3::from_parts(
+            na::Translation3::new(voxel.center[0] + 1.0, voxel.center[1], voxel.center[2]),
+            rotation_from_rpy(&map.orientations[voxel.reachable_orientations[0]]),
+        );
+        assert!(!map.is_reachable(&target_pose));
+        let placements = map.base_placements(&target_pose, 8);
+        assert!(!placements.is_empty());
+        for placement in &placements {
+            let base = na::Isometry3::new(
+                na::Vector3::new(
+                    placement.pose.translation.vector[0],
+                    placement.pose.translation.vector[1],
+                    0.0,
+                ),
+                na::Vector3::z() * placement.pose.rotation.angle(),
+            );
+            assert!(map.is_reachable(&(base.inverse() * target_pose)));
+        }
+        assert!(placements[0].reachability >= placements[placements.len() - 1].reachability);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capability_map.json");
+        map.save(&path).unwrap();
+        assert_eq!(CapabilityMap::<f64>::load(&path).unwrap(), map);
+    }
+
+    #[test]
+    fn test_capability_map_invalid_parameters() {
+        let robot = k::Chain::<f64>::from_urdf_file("sample.urdf").unwrap();
+        let arm = k::SerialChain::from_end(robot.find("l_tool_fixed").unwrap());
+        let solver = k::JacobianIkSolver::new(0.01, 0.1, 0.5, 100);
+        let generate = |min: na::Vector3<f64>, voxel_size, num_directions, num_rolls| {
+            CapabilityMap::generate(
+                &solver,
+                &arm,
+                min,
+                na::Vector3::new(0.1, 0.1, 0.1),
+                voxel_size,
+                num_directions,
+                num_rolls,
+            )
+        };
+        let min = na::Vector3::new(-0.1, -0.1, -0.1);
+        for voxel_size in &[0.0, -0.1, f64::NAN, f64::INFINITY] {
+            assert!(generate(min, *voxel_size, 6, 2).is_err());
+        }
+        // max < min
+        assert!(generate(na::Vector3::new(0.2, -0.1, -0.1), 0.1, 6, 2).is_err());
+        assert!(generate(na::Vector3::new(f64::NAN, -0.1, -0.1), 0.1, 6, 2).is_err());
+        assert!(generate(min, 0.1, 0, 2).is_err());
+        assert!(generate(min, 0.1, 6, 0).is_err());
+    }
+}
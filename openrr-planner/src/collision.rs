@@ -1,7 +1,13 @@
 mod collision_detector;
+mod collision_matrix;
+mod continuous;
 mod mesh;
+mod mesh_approximation;
 mod robot_collision_detector;
 mod self_collision_checker;
 mod urdf;
 
-pub use self::{collision_detector::*, robot_collision_detector::*, self_collision_checker::*};
+pub use self::{
+    collision_detector::*, collision_matrix::*, continuous::*, mesh_approximation::*,
+    robot_collision_detector::*, self_collision_checker::*,
+};
@@ -16,18 +16,23 @@ limitations under the License.
 use std::{
     collections::HashMap,
     path::Path,
+    sync::RwLock,
     time::{Duration, Instant},
 };
 
 use k::nalgebra as na;
 use na::RealField;
 use ncollide3d::{
-    query,
+    bounding_volume::{BoundingVolume, AABB},
+    query::{self, ClosestPoints},
     shape::{Compound, Shape, ShapeHandle},
 };
 use tracing::{debug, warn};
 
-use super::urdf::urdf_geometry_to_shape_handle;
+use super::{
+    mesh_approximation::{approximate_mesh, MeshApproximation},
+    urdf::urdf_geometry_to_shape_handle,
+};
 use crate::errors::*;
 
 type NameShapeMap<T> = HashMap<String, Vec<(ShapeHandle<T>, na::Isometry3<T>)>>;
@@ -40,7 +45,9 @@ where
     detector: &'a CollisionDetector<T>,
     target_shape: &'b dyn Shape<T>,
     target_pose: &'b na::Isometry3<T>,
-    joints: Vec<&'b k::Node<T>>,
+    target_aabb: AABB<T>,
+    /// Collision models of the links and the attached objects in the world frame
+    shapes: Vec<(String, Vec<(ShapeHandle<T>, na::Isometry3<T>)>)>,
     index: usize,
 }
 
@@ -55,12 +62,12 @@ where
         target_pose: &'b na::Isometry3<T>,
     ) -> Self {
         robot.update_transforms();
-        let joints = robot.iter().collect();
         Self {
             detector,
             target_shape,
             target_pose,
-            joints,
+            target_aabb: target_shape.aabb(target_pose),
+            shapes: detector.world_shapes(robot),
             index: 0,
         }
     }
@@ -73,34 +80,26 @@ where
     type Item = String;
 
     fn next(&mut self) -> Option<String> {
-        if self.joints.len() <= self.index {
-            return None;
-        }
-        let joint = self.joints[self.index];
-        self.index += 1;
-        let trans = joint.world_transform().unwrap();
-        let joint_name = &joint.joint().name;
-        match self.detector.name_collision_model_map.get(joint_name) {
-            Some(obj_vec) => {
-                for obj in obj_vec {
-                    // proximity and prediction does not work for meshes.
-                    let dist = query::distance(
-                        &(trans * obj.1),
-                        &*obj.0,
-                        self.target_pose,
-                        self.target_shape,
-                    );
-                    if dist < self.detector.prediction {
-                        debug!("name: {}, dist={}", joint_name, dist);
-                        return Some(joint_name.to_owned());
-                    }
+        while let Some((name, obj_vec)) = self.shapes.get(self.index) {
+            self.index += 1;
+            let padding = self.detector.padding(name);
+            for (shape, pose) in obj_vec {
+                if self
+                    .detector
+                    .is_apart(&shape.aabb(pose), &self.target_aabb, padding)
+                {
+                    continue;
+                }
+                // proximity and prediction does not work for meshes.
+                let dist = query::distance(pose, &**shape, self.target_pose, self.target_shape);
+                let dist = dist - padding;
+                if dist < self.detector.prediction {
+                    debug!("name: {}, dist={}", name, dist);
+                    return Some(name.to_owned());
                 }
-            }
-            None => {
-                debug!("collision model {} not found", joint_name);
             }
         }
-        self.next()
+        None
     }
 }
 
@@ -110,10 +109,13 @@ where
     T: RealField,
 {
     detector: &'a CollisionDetector<T>,
-    robot: &'a k::Chain<T>,
     self_collision_pairs: &'a [(String, String)],
+    /// Pairs of the attached objects and the links which are not allowed to touch them
+    attached_object_pairs: Vec<(String, String)>,
     index: usize,
     used_duration: HashMap<String, Duration>,
+    /// Shapes, poses and bounding boxes of the collision models in the world frame
+    link_shapes: HashMap<String, Vec<(ShapeHandle<T>, na::Isometry3<T>, AABB<T>)>>,
 }
 
 impl<'a, T> SelfCollisionPairs<'a, T>
@@ -126,12 +128,27 @@ where
         self_collision_pairs: &'a [(String, String)],
     ) -> Self {
         robot.update_transforms();
+        let link_shapes = detector
+            .world_shapes(robot)
+            .into_iter()
+            .map(|(name, obj_vec)| {
+                let shapes = obj_vec
+                    .into_iter()
+                    .map(|(shape, pose)| {
+                        let aabb = shape.aabb(&pose);
+                        (shape, pose, aabb)
+                    })
+                    .collect();
+                (name, shapes)
+            })
+            .collect();
         Self {
             detector,
-            robot,
             self_collision_pairs,
+            attached_object_pairs: detector.attached_object_pairs(),
             index: 0,
             used_duration: HashMap::new(),
+            link_shapes,
         }
     }
 
@@ -148,64 +165,177 @@ where
     type Item = (String, String);
 
     fn next(&mut self) -> Option<(String, String)> {
-        if self.self_collision_pairs.len() <= self.index {
-            return None;
-        }
-        let (j1, j2) = &self.self_collision_pairs[self.index];
-        self.index += 1;
-        let obj_vec1_opt = self.detector.name_collision_model_map.get(j1);
-        let obj_vec2_opt = self.detector.name_collision_model_map.get(j2);
-        if obj_vec1_opt.is_none() {
-            warn!("Collision model {} not found", j1);
-            return self.next();
-        }
-        if obj_vec2_opt.is_none() {
-            warn!("Collision model {} not found", j2);
-            return self.next();
-        }
-        let node1_opt = self.robot.find(j1);
-        let node2_opt = self.robot.find(j2);
-        if node1_opt.is_none() {
-            warn!("self_colliding: joint {} not found", j1);
-            return self.next();
-        }
-        if node2_opt.is_none() {
-            warn!("self_colliding: joint {} not found", j2);
-            return self.next();
-        }
-        let obj_vec1 = obj_vec1_opt.unwrap();
-        let obj_vec2 = obj_vec2_opt.unwrap();
-        let node1 = node1_opt.unwrap();
-        let node2 = node2_opt.unwrap();
-        let mut last_time = Instant::now();
-        for obj1 in obj_vec1 {
-            for obj2 in obj_vec2 {
-                let trans1 = node1.world_transform().unwrap();
-                let trans2 = node2.world_transform().unwrap();
-                // proximity and predict does not work correctly for mesh
-                let dist =
-                    query::distance(&(trans1 * obj1.1), &*obj1.0, &(trans2 * obj2.1), &*obj2.0);
-                debug!("name: {}, name: {} dist={}", j1, j2, dist);
-                if dist < self.detector.prediction {
-                    return Some((j1.to_owned(), j2.to_owned()));
+        loop {
+            let num_pairs = self.self_collision_pairs.len();
+            let (j1, j2) = if self.index < num_pairs {
+                &self.self_collision_pairs[self.index]
+            } else if self.index < num_pairs + self.attached_object_pairs.len() {
+                &self.attached_object_pairs[self.index - num_pairs]
+            } else {
+                return None;
+            };
+            self.index += 1;
+            if !self.detector.has_collision_model(j1) {
+                warn!("Collision model {} not found", j1);
+                continue;
+            }
+            if !self.detector.has_collision_model(j2) {
+                warn!("Collision model {} not found", j2);
+                continue;
+            }
+            let shapes1_opt = self.link_shapes.get(j1);
+            let shapes2_opt = self.link_shapes.get(j2);
+            if shapes1_opt.is_none() {
+                warn!("self_colliding: joint {} not found", j1);
+                continue;
+            }
+            if shapes2_opt.is_none() {
+                warn!("self_colliding: joint {} not found", j2);
+                continue;
+            }
+            let shapes1 = shapes1_opt.unwrap();
+            let shapes2 = shapes2_opt.unwrap();
+            let padding = self.detector.padding(j1) + self.detector.padding(j2);
+            let mut last_time = Instant::now();
+            for (shape1, pose1, aabb1) in shapes1 {
+                for (shape2, pose2, aabb2) in shapes2 {
+                    if !self.detector.is_apart(aabb1, aabb2, padding) {
+                        // proximity and predict does not work correctly for mesh
+                        let dist = query::distance(pose1, &**shape1, pose2, &**shape2) - padding;
+                        debug!("name: {}, name: {} dist={}", j1, j2, dist);
+                        if dist < self.detector.prediction {
+                            return Some((j1.to_owned(), j2.to_owned()));
+                        }
+                    }
+                    let elapsed = last_time.elapsed();
+                    *self
+                        .used_duration
+                        .entry(j1.to_owned())
+                        .or_insert_with(|| Duration::from_nanos(0)) += elapsed;
+                    *self
+                        .used_duration
+                        .entry(j2.to_owned())
+                        .or_insert_with(|| Duration::from_nanos(0)) += elapsed;
+                    last_time = Instant::now();
                 }
-                let elapsed = last_time.elapsed();
-                *self
-                    .used_duration
-                    .entry(j1.to_owned())
-                    .or_insert_with(|| Duration::from_nanos(0)) += elapsed;
-                *self
-                    .used_duration
-                    .entry(j2.to_owned())
-                    .or_insert_with(|| Duration::from_nanos(0)) += elapsed;
-                last_time = Instant::now();
             }
         }
-        self.next()
     }
 }
 
+/// Shape attached to a link of the robot, e.g. an object grasped by the gripper
+///
+/// The attached object is checked against the environmental objects and the
+/// links of the robot, except for the link which it is attached to and `touch_links`.
 #[derive(Clone)]
+pub struct AttachedObject<T>
+where
+    T: RealField,
+{
+    /// Name of the link(joint) which the object is attached to
+    pub link_name: String,
+    pub shape: ShapeHandle<T>,
+    /// Pose of the object in the frame of the link
+    pub pose: na::Isometry3<T>,
+    /// Names of the links(joints) or the other attached objects which are allowed to touch the object
+    pub touch_links: Vec<String>,
+}
+
+impl<T> AttachedObject<T>
+where
+    T: RealField,
+{
+    pub fn new(link_name: &str, shape: ShapeHandle<T>, pose: na::Isometry3<T>) -> Self {
+        Self {
+            link_name: link_name.to_owned(),
+            shape,
+            pose,
+            touch_links: vec![],
+        }
+    }
+
+    /// Creates the object from the geometry of URDF, e.g. `urdf_rs::Geometry::Box`
+    ///
+    /// Returns `None` if the mesh file can not be loaded.
+    pub fn from_urdf_geometry(
+        link_name: &str,
+        geometry: &urdf_rs::Geometry,
+        pose: na::Isometry3<T>,
+    ) -> Option<Self> {
+        Some(Self::new(
+            link_name,
+            urdf_geometry_to_shape_handle(geometry, None)?,
+            pose,
+        ))
+    }
+
+    /// Allow the links(joints) to touch the object, e.g. the fingers of the gripper
+    pub fn touch_links(mut self, touch_links: Vec<String>) -> Self {
+        self.touch_links = touch_links;
+        self
+    }
+}
+
+/// Distance between a link of the robot and an environmental object
+#[derive(Debug, Clone)]
+pub struct EnvDistance<T>
+where
+    T: RealField,
+{
+    /// Name of the link(joint)
+    pub name: String,
+    /// Distance minus the padding of the link, negative if penetrating
+    pub distance: T,
+    /// Closest point on the link in the world frame
+    pub link_point: na::Point3<T>,
+    /// Closest point on the object in the world frame
+    pub object_point: na::Point3<T>,
+}
+
+/// Distance between a pair of the links of the robot
+#[derive(Debug, Clone)]
+pub struct SelfDistance<T>
+where
+    T: RealField,
+{
+    /// Names of the links(joints)
+    pub names: (String, String),
+    /// Distance minus the paddings of the links, negative if penetrating
+    pub distance: T,
+    /// Closest points on the links in the world frame
+    pub points: (na::Point3<T>, na::Point3<T>),
+}
+
+/// Distance and the closest points of two shapes, negative distance if penetrating
+///
+/// `None` if the closest points are not found, e.g. for the shapes which are
+/// not supported by the queries.
+fn closest_points<T>(
+    m1: &na::Isometry3<T>,
+    g1: &dyn Shape<T>,
+    m2: &na::Isometry3<T>,
+    g2: &dyn Shape<T>,
+) -> Option<(T, na::Point3<T>, na::Point3<T>)>
+where
+    T: RealField,
+{
+    match query::closest_points(m1, g1, m2, g2, na::convert(f64::MAX)) {
+        ClosestPoints::WithinMargin(p1, p2) => Some((na::distance(&p1, &p2), p1, p2)),
+        ClosestPoints::Intersecting | ClosestPoints::Disjoint => {
+            query::contact(m1, g1, m2, g2, T::zero())
+                .map(|contact| (-contact.depth, contact.world1, contact.world2))
+        }
+    }
+}
+
+fn min_distance<T, I>(distances: I) -> Option<(T, na::Point3<T>, na::Point3<T>)>
+where
+    T: RealField,
+    I: Iterator<Item = (T, na::Point3<T>, na::Point3<T>)>,
+{
+    distances.min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
+}
+
 /// Collision detector
 pub struct CollisionDetector<T>
 where
@@ -214,6 +344,27 @@ where
     name_collision_model_map: NameShapeMap<T>,
     /// margin length for collision detection
     pub prediction: T,
+    /// Padding of the shapes of the link(joint), which is subtracted from the distances
+    pub link_padding: HashMap<String, T>,
+    /// Skip the distance queries of the shapes whose bounding boxes are apart (default: true)
+    pub use_broadphase: bool,
+    /// Objects attached at runtime, which can be changed via shared references
+    attached_objects: RwLock<HashMap<String, AttachedObject<T>>>,
+}
+
+impl<T> Clone for CollisionDetector<T>
+where
+    T: RealField,
+{
+    fn clone(&self) -> Self {
+        Self {
+            name_collision_model_map: self.name_collision_model_map.clone(),
+            prediction: self.prediction,
+            link_padding: self.link_padding.clone(),
+            use_broadphase: self.use_broadphase,
+            attached_objects: RwLock::new(self.attached_objects.read().unwrap().clone()),
+        }
+    }
 }
 
 impl<T> CollisionDetector<T>
@@ -225,6 +376,9 @@ where
         CollisionDetector {
             name_collision_model_map,
             prediction,
+            link_padding: HashMap::new(),
+            use_broadphase: true,
+            attached_objects: RwLock::new(HashMap::new()),
         }
     }
 
@@ -262,9 +416,181 @@ where
         CollisionDetector {
             name_collision_model_map,
             prediction,
+            link_padding: HashMap::new(),
+            use_broadphase: true,
+            attached_objects: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn padding(&self, name: &str) -> T {
+        self.link_padding.get(name).copied().unwrap_or_else(T::zero)
+    }
+
+    /// Returns true if the shapes in the bounding boxes can not be closer than
+    /// `prediction` with `padding`, which is the broadphase before the
+    /// expensive distance queries.
+    fn is_apart(&self, aabb1: &AABB<T>, aabb2: &AABB<T>, padding: T) -> bool {
+        if !self.use_broadphase {
+            return false;
+        }
+        let margin = (self.prediction + padding).max(T::zero());
+        !aabb1.loosened(margin).intersects(aabb2)
+    }
+
+    /// Replaces the meshes of the collision models with the primitive shapes
+    pub fn approximate_meshes(&mut self, approximation: MeshApproximation) {
+        if approximation == MeshApproximation::None {
+            return;
+        }
+        for obj_vec in self.name_collision_model_map.values_mut() {
+            for obj in obj_vec.iter_mut() {
+                *obj = approximate_mesh(&obj.0, &obj.1, approximation);
+            }
         }
     }
 
+    /// Names of the links(joints) which have collision models
+    pub fn collision_link_names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.name_collision_model_map.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Radii of the spheres around the origins of the links(joints) which contain
+    /// their collision models and attached objects
+    pub fn link_radii(&self) -> HashMap<String, T> {
+        let radius = |shape: &ShapeHandle<T>, pose: &na::Isometry3<T>| {
+            let aabb = shape.aabb(pose);
+            aabb.mins()
+                .coords
+                .abs()
+                .sup(&aabb.maxs().coords.abs())
+                .norm()
+        };
+        let mut radii: HashMap<String, T> = self
+            .name_collision_model_map
+            .iter()
+            .map(|(name, obj_vec)| {
+                let r = obj_vec
+                    .iter()
+                    .map(|obj| radius(&obj.0, &obj.1))
+                    .fold(T::zero(), |a, b| a.max(b));
+                (name.to_owned(), r)
+            })
+            .collect();
+        for object in self.attached_objects.read().unwrap().values() {
+            let r = radius(&object.shape, &object.pose);
+            let link_radius = radii
+                .entry(object.link_name.clone())
+                .or_insert_with(T::zero);
+            *link_radius = link_radius.max(r);
+        }
+        radii
+    }
+
+    /// Attaches the object to the link(joint), replacing the object of the same name
+    pub fn attach_object(&self, name: &str, object: AttachedObject<T>) {
+        self.attached_objects
+            .write()
+            .unwrap()
+            .insert(name.to_owned(), object);
+    }
+
+    /// Detaches the object and returns it if it was attached
+    pub fn detach_object(&self, name: &str) -> Option<AttachedObject<T>> {
+        self.attached_objects.write().unwrap().remove(name)
+    }
+
+    /// Names of the attached objects
+    pub fn attached_object_names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self
+            .attached_objects
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn has_collision_model(&self, name: &str) -> bool {
+        self.name_collision_model_map.contains_key(name)
+            || self.attached_objects.read().unwrap().contains_key(name)
+    }
+
+    /// Collision models of the links(joints) and the attached objects in the world frame
+    ///
+    /// The transforms of the robot must be updated beforehand.
+    fn world_shapes(
+        &self,
+        robot: &k::Chain<T>,
+    ) -> Vec<(String, Vec<(ShapeHandle<T>, na::Isometry3<T>)>)> {
+        let mut shapes: Vec<_> = robot
+            .iter()
+            .filter_map(|node| {
+                let name = node.joint().name.clone();
+                let obj_vec = self.name_collision_model_map.get(&name)?;
+                let trans = node.world_transform()?;
+                Some((
+                    name,
+                    obj_vec
+                        .iter()
+                        .map(|obj| (obj.0.clone(), trans * obj.1))
+                        .collect(),
+                ))
+            })
+            .collect();
+        let objects = self.attached_objects.read().unwrap();
+        let mut attached_objects: Vec<_> = objects.iter().collect();
+        attached_objects.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, object) in attached_objects {
+            match robot
+                .find(&object.link_name)
+                .and_then(|node| node.world_transform())
+            {
+                Some(trans) => shapes.push((
+                    name.to_owned(),
+                    vec![(object.shape.clone(), trans * object.pose)],
+                )),
+                None => warn!(
+                    "attached object {}: joint {} not found",
+                    name, object.link_name
+                ),
+            }
+        }
+        shapes
+    }
+
+    /// Pairs of the attached objects and the links(joints) or the other attached
+    /// objects which are not allowed to touch them
+    ///
+    /// The objects attached to the same link are not checked against each other.
+    fn attached_object_pairs(&self) -> Vec<(String, String)> {
+        let link_names = self.collision_link_names();
+        let objects = self.attached_objects.read().unwrap();
+        let mut objects: Vec<_> = objects.iter().collect();
+        objects.sort_by(|a, b| a.0.cmp(b.0));
+        let mut pairs = vec![];
+        for (i, &(name, object)) in objects.iter().enumerate() {
+            for link_name in &link_names {
+                if *link_name != object.link_name && !object.touch_links.contains(link_name) {
+                    pairs.push((name.to_owned(), link_name.to_owned()));
+                }
+            }
+            for &(name2, object2) in &objects[i + 1..] {
+                if object.link_name != object2.link_name
+                    && !object.touch_links.contains(name2)
+                    && !object2.touch_links.contains(name)
+                {
+                    pairs.push((name.to_owned(), name2.to_owned()));
+                }
+            }
+        }
+        pairs.sort();
+        pairs
+    }
+
     /// Detects collisions of a robot with an environmental object and returns the names of the link(joint) names
     ///
     /// robot: robot model
@@ -276,7 +602,6 @@ where
         target_shape: &'a dyn Shape<T>,
         target_pose: &'a na::Isometry3<T>,
     ) -> EnvCollisionNames<'a, 'a, T> {
-        robot.update_transforms();
         EnvCollisionNames::new(self, robot, target_shape, target_pose)
     }
 
@@ -289,11 +614,81 @@ where
         robot: &'a k::Chain<T>,
         self_collision_pairs: &'a [(String, String)],
     ) -> SelfCollisionPairs<'a, T> {
-        robot.update_transforms();
         SelfCollisionPairs::new(self, robot, self_collision_pairs)
     }
 }
 
+impl<T> CollisionDetector<T>
+where
+    T: RealField + k::SubsetOf<f64>,
+{
+    /// Calculates the distances between the links of a robot and an environmental object
+    ///
+    /// The result contains the links(joints) which have collision models and the attached objects.
+    ///
+    /// robot: robot model
+    /// target_shape: shape of the environmental object
+    /// target_pose: pose of the environmental object
+    pub fn env_distances(
+        &self,
+        robot: &k::Chain<T>,
+        target_shape: &dyn Shape<T>,
+        target_pose: &na::Isometry3<T>,
+    ) -> Vec<EnvDistance<T>> {
+        robot.update_transforms();
+        self.world_shapes(robot)
+            .into_iter()
+            .filter_map(|(name, obj_vec)| {
+                let (distance, link_point, object_point) =
+                    min_distance(obj_vec.iter().filter_map(|(shape, pose)| {
+                        closest_points(pose, &**shape, target_pose, target_shape)
+                    }))?;
+                Some(EnvDistance {
+                    distance: distance - self.padding(&name),
+                    name,
+                    link_point,
+                    object_point,
+                })
+            })
+            .collect()
+    }
+
+    /// Calculates the distances between the pairs of the links and the attached objects
+    ///
+    /// robot: robot model
+    /// self_collision_pairs: pairs of the names of the link(joint) or the attached objects
+    pub fn self_distances(
+        &self,
+        robot: &k::Chain<T>,
+        self_collision_pairs: &[(String, String)],
+    ) -> Vec<SelfDistance<T>> {
+        robot.update_transforms();
+        let shapes: HashMap<_, _> = self.world_shapes(robot).into_iter().collect();
+        self_collision_pairs
+            .iter()
+            .filter_map(|(j1, j2)| {
+                let (objects1, objects2) = match (shapes.get(j1), shapes.get(j2)) {
+                    (Some(o1), Some(o2)) => (o1, o2),
+                    _ => {
+                        warn!("self_distances: collision model {} or {} not found", j1, j2);
+                        return None;
+                    }
+                };
+                let (distance, p1, p2) = min_distance(objects1.iter().flat_map(|obj1| {
+                    objects2.iter().filter_map(move |obj2| {
+                        closest_points(&obj1.1, &*obj1.0, &obj2.1, &*obj2.0)
+                    })
+                }))?;
+                Some(SelfDistance {
+                    names: (j1.to_owned(), j2.to_owned()),
+                    distance: distance - self.padding(j1) - self.padding(j2),
+                    points: (p1, p2),
+                })
+            })
+            .collect()
+    }
+}
+
 /// Convert urdf object into openrr_planner/ncollide3d object
 pub trait FromUrdf {
     fn from_urdf_robot(robot: &urdf_rs::Robot) -> Self;
@@ -337,7 +732,147 @@ pub fn parse_colon_separated_pairs(pair_strs: &[String]) -> Result<Vec<(String,
 
 #[cfg(test)]
 mod test {
-    use super::parse_colon_separated_pairs;
+    use ncollide3d::shape::Cuboid;
+
+    use super::*;
+
+    #[test]
+    fn test_distances() {
+        let urdf_robot = urdf_rs::read_file("sample.urdf").unwrap();
+        let mut detector = CollisionDetector::from_urdf_robot(&urdf_robot, 0.01);
+        let robot = k::Chain::<f64>::from(&urdf_robot);
+        let target = Cuboid::new(na::Vector3::new(0.1, 0.1, 0.1));
+        let target_pose = na::Isometry3::translation(3.0, 0.0, 0.0);
+
+        let distances = detector.env_distances(&robot, &target, &target_pose);
+        assert!(!distances.is_empty());
+        for d in &distances {
+            assert!(d.distance > 0.0);
+            assert!((na::distance(&d.link_point, &d.object_point) - d.distance).abs() < 1e-6);
+            assert!(d.object_point.x >= 2.9 - 1e-6);
+        }
+        let nearest = distances
+            .iter()
+            .min_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap())
+            .unwrap();
+        detector.link_padding.insert(nearest.name.clone(), 0.05);
+        let padded = detector.env_distances(&robot, &target, &target_pose);
+        let padded = padded.iter().find(|d| d.name == nearest.name).unwrap();
+        assert!((nearest.distance - padded.distance - 0.05).abs() < 1e-6);
+
+        let pairs = vec![("root".to_owned(), "l_shoulder_roll".to_owned())];
+        robot.set_joint_positions_clamped(&[0.0; 8]);
+        let distances = detector.self_distances(&robot, &pairs);
+        assert_eq!(distances.len(), 1);
+        assert!(distances[0].distance > 0.0);
+        robot.set_joint_positions_clamped(&[1.57, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        let distances = detector.self_distances(&robot, &pairs);
+        assert!(distances[0].distance < detector.prediction);
+    }
+
+    #[test]
+    fn test_broadphase() {
+        let urdf_robot = urdf_rs::read_file("sample.urdf").unwrap();
+        let mut detector = CollisionDetector::from_urdf_robot(&urdf_robot, 0.01);
+        let robot = k::Chain::<f64>::from(&urdf_robot);
+        let target = Cuboid::new(na::Vector3::new(0.3, 0.3, 0.3));
+        robot.set_joint_positions_clamped(&[1.57, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        let names = detector.collision_link_names();
+        let pairs: Vec<_> = names
+            .iter()
+            .enumerate()
+            .flat_map(|(i, name1)| {
+                names[i + 1..]
+                    .iter()
+                    .map(move |name2| (name1.clone(), name2.clone()))
+            })
+            .collect();
+        let detect = |detector: &CollisionDetector<f64>| {
+            let env: Vec<Vec<_>> = [0.0, 0.3, 3.0]
+                .iter()
+                .map(|&x| {
+                    let target_pose = na::Isometry3::translation(x, 0.0, 0.0);
+                    let mut names: Vec<_> =
+                        detector.detect_env(&robot, &target, &target_pose).collect();
+                    names.sort();
+                    names
+                })
+                .collect();
+            let mut self_pairs: Vec<_> = detector.detect_self(&robot, &pairs).collect();
+            self_pairs.sort();
+            (env, self_pairs)
+        };
+
+        let with_broadphase = detect(&detector);
+        assert!(!with_broadphase.1.is_empty());
+        detector.use_broadphase = false;
+        assert_eq!(detect(&detector), with_broadphase);
+    }
+
+    #[test]
+    fn test_attached_object() {
+        let urdf_robot = urdf_rs::read_file("sample.urdf").unwrap();
+        let detector = CollisionDetector::from_urdf_robot(&urdf_robot, 0.01);
+        let robot = k::Chain::<f64>::from(&urdf_robot);
+        let target = Cuboid::new(na::Vector3::new(0.1, 0.1, 0.1));
+        let target_pose = na::Isometry3::translation(3.0, 0.0, 0.0);
+        assert_eq!(
+            detector.detect_env(&robot, &target, &target_pose).count(),
+            0
+        );
+
+        let object = AttachedObject::new(
+            "l_wrist_pitch",
+            ShapeHandle::new(Cuboid::new(na::Vector3::new(3.0, 1.0, 1.0))),
+            na::Isometry3::identity(),
+        );
+        detector.attach_object("bar", object.clone());
+        assert_eq!(detector.attached_object_names(), vec!["bar"]);
+        let names: Vec<_> = detector.detect_env(&robot, &target, &target_pose).collect();
+        assert_eq!(names, vec!["bar"]);
+        let pairs: Vec<_> = detector.detect_self(&robot, &[]).collect();
+        assert!(pairs.contains(&("bar".to_owned(), "root".to_owned())));
+        assert!(pairs.iter().all(|(_, link)| link != "l_wrist_pitch"));
+
+        detector.attach_object("bar", object.touch_links(vec!["root".to_owned()]));
+        let pairs: Vec<_> = detector.detect_self(&robot, &[]).collect();
+        assert!(pairs.iter().all(|(_, link)| link != "root"));
+
+        // Objects attached to the different links collide with each other.
+        robot.update_transforms();
+        let ball = AttachedObject::new(
+            "root",
+            ShapeHandle::new(Cuboid::new(na::Vector3::new(0.1, 0.1, 0.1))),
+            robot
+                .find("l_wrist_pitch")
+                .unwrap()
+                .world_transform()
+                .unwrap(),
+        );
+        detector.attach_object("ball", ball.clone());
+        let pairs: Vec<_> = detector.detect_self(&robot, &[]).collect();
+        assert!(pairs.contains(&("ball".to_owned(), "bar".to_owned())));
+        detector.attach_object("ball", ball.touch_links(vec!["bar".to_owned()]));
+        let pairs: Vec<_> = detector.detect_self(&robot, &[]).collect();
+        assert!(!pairs.contains(&("ball".to_owned(), "bar".to_owned())));
+        assert!(detector.detach_object("ball").is_some());
+
+        // The distances to the attached objects are calculated as the links.
+        let bar_pairs = vec![("bar".to_owned(), "root".to_owned())];
+        let distances = detector.self_distances(&robot, &bar_pairs);
+        assert_eq!(distances.len(), 1);
+        assert!(distances[0].distance < detector.prediction);
+
+        assert!(detector.detach_object("bar").is_some());
+        assert!(detector.self_distances(&robot, &bar_pairs).is_empty());
+        assert!(detector.detach_object("bar").is_none());
+        assert_eq!(
+            detector.detect_env(&robot, &target, &target_pose).count(),
+            0
+        );
+        assert_eq!(detector.detect_self(&robot, &[]).count(), 0);
+    }
+
     #[test]
     fn test_parse_colon_separated_pairs() {
         let pairs = parse_colon_separated_pairs(&["j0:j1".to_owned(), "j2:j0".to_owned()]).unwrap();
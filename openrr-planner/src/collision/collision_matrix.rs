@@ -0,0 +1,234 @@
+use std::{collections::HashMap, fmt::Write};
+
+use k::nalgebra as na;
+use na::RealField;
+
+use crate::{errors::*, funcs::set_random_joint_positions, CollisionDetector};
+
+/// Reason why the collision checking of a pair is not needed
+///
+/// The names are the same as the `reason` of `disable_collisions` in SRDF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisabledReason {
+    /// The links are connected by a joint.
+    Adjacent,
+    /// The links are colliding in all of the sampled configurations.
+    Always,
+    /// The links are not colliding in any of the sampled configurations.
+    Never,
+}
+
+/// Pair of the links(joints) whose collision checking is not needed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisabledCollision {
+    pub names: (String, String),
+    pub reason: DisabledReason,
+}
+
+/// Classification of all of the pairs of the links(joints) which have collision models
+#[derive(Debug, Clone, Default)]
+pub struct CollisionMatrix {
+    /// Pairs which need the self collision checking
+    pub check_pairs: Vec<(String, String)>,
+    /// Pairs which can be skipped
+    pub disabled_collisions: Vec<DisabledCollision>,
+}
+
+impl CollisionMatrix {
+    /// Generate the collision matrix by sampling random configurations of the robot
+    ///
+    /// The joint positions of the robot are restored after the sampling.
+    pub fn generate<T>(
+        robot: &k::Chain<T>,
+        detector: &CollisionDetector<T>,
+        num_samples: usize,
+    ) -> Result<Self>
+    where
+        T: RealField + k::SubsetOf<f64>,
+    {
+        let names = detector.collision_link_names();
+        let adjacent_pairs = adjacent_pairs(robot, &names);
+        let mut sampled_pairs = vec![];
+        for (i, name1) in names.iter().enumerate() {
+            for name2 in &names[i + 1..] {
+                let pair = (name1.to_owned(), name2.to_owned());
+                if !adjacent_pairs.contains(&pair) {
+                    sampled_pairs.push(pair);
+                }
+            }
+        }
+
+        let mut counts: HashMap<(String, String), usize> = HashMap::new();
+        let initial_positions = robot.joint_positions();
+        for _ in 0..num_samples {
+            set_random_joint_positions(robot)?;
+            for pair in detector.detect_self(robot, &sampled_pairs) {
+                *counts.entry(pair).or_insert(0) += 1;
+            }
+        }
+        robot.set_joint_positions_unchecked(&initial_positions);
+        robot.update_transforms();
+
+        let mut matrix = Self {
+            check_pairs: vec![],
+            disabled_collisions: adjacent_pairs
+                .into_iter()
+                .map(|names| DisabledCollision {
+                    names,
+                    reason: DisabledReason::Adjacent,
+                })
+                .collect(),
+        };
+        for pair in sampled_pairs {
+            let reason = match counts.get(&pair) {
+                None => DisabledReason::Never,
+                Some(&count) if count == num_samples => DisabledReason::Always,
+                Some(_) => {
+                    matrix.check_pairs.push(pair);
+                    continue;
+                }
+            };
+            matrix.disabled_collisions.push(DisabledCollision {
+                names: pair,
+                reason,
+            });
+        }
+        matrix
+            .disabled_collisions
+            .sort_by(|a, b| a.names.cmp(&b.names));
+        Ok(matrix)
+    }
+
+    /// Pairs to check in the format of `self_collision_check_pairs` of the config,
+    /// which can be parsed by [`parse_colon_separated_pairs`](crate::collision::parse_colon_separated_pairs).
+    pub fn colon_separated_check_pairs(&self) -> Vec<String> {
+        self.check_pairs
+            .iter()
+            .map(|(name1, name2)| format!("{}:{}", name1, name2))
+            .collect()
+    }
+
+    /// SRDF document which has `disable_collisions` elements of the disabled pairs
+    ///
+    /// The names of the joints are converted to the names of their child links.
+    /// The special characters of XML in the names are escaped.
+    pub fn to_srdf(&self, urdf_robot: &urdf_rs::Robot) -> String {
+        let joint_link_map: HashMap<_, _> = k::urdf::link_to_joint_map(urdf_robot)
+            .into_iter()
+            .map(|(link, joint)| (joint, link))
+            .collect();
+        let link_name = |name: &String| escape_xml(joint_link_map.get(name).unwrap_or(name));
+        let mut srdf = format!(
+            "<?xml version=\"1.0\" ?>\n<robot name=\"{}\">\n",
+            escape_xml(&urdf_robot.name)
+        );
+        for disabled in &self.disabled_collisions {
+            writeln!(
+                srdf,
+                "  <disable_collisions link1=\"{}\" link2=\"{}\" reason=\"{}\" />",
+                link_name(&disabled.names.0),
+                link_name(&disabled.names.1),
+                escape_xml(&format!("{:?}", disabled.reason))
+            )
+            .unwrap();
+        }
+        srdf.push_str("</robot>\n");
+        srdf
+    }
+}
+
+/// Escapes the special characters of XML in attribute values and texts
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Pairs of the links(joints) which have collision models and are connected
+/// directly or only via links without collision models
+fn adjacent_pairs<T>(robot: &k::Chain<T>, names: &[String]) -> Vec<(String, String)>
+where
+    T: RealField,
+{
+    let mut pairs = vec![];
+    for name in names {
+        let mut parent = robot.find(name).and_then(|node| node.parent());
+        while let Some(node) = parent {
+            let parent_name = node.joint().name.clone();
+            if names.contains(&parent_name) {
+                pairs.push(if parent_name < *name {
+                    (parent_name, name.to_owned())
+                } else {
+                    (name.to_owned(), parent_name)
+                });
+                break;
+            }
+            parent = node.parent();
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::parse_colon_separated_pairs;
+
+    #[test]
+    fn test_collision_matrix() {
+        let urdf_robot = urdf_rs::read_file("sample.urdf").unwrap();
+        let robot = k::Chain::<f64>::from(&urdf_robot);
+        let detector = CollisionDetector::from_urdf_robot(&urdf_robot, 0.0);
+        let num_links = detector.collision_link_names().len();
+
+        let matrix = CollisionMatrix::generate(&robot, &detector, 100).unwrap();
+        assert_eq!(
+            matrix.check_pairs.len() + matrix.disabled_collisions.len(),
+            num_links * (num_links - 1) / 2
+        );
+        assert!(!matrix.check_pairs.is_empty());
+        // The shoulder and the upper arm are connected by a joint.
+        assert!(matrix.disabled_collisions.contains(&DisabledCollision {
+            names: ("l_shoulder_pitch".to_owned(), "l_shoulder_roll".to_owned()),
+            reason: DisabledReason::Adjacent,
+        }));
+        assert!(robot.joint_positions().iter().all(|&p| p == 0.0));
+
+        let pairs = parse_colon_separated_pairs(&matrix.colon_separated_check_pairs()).unwrap();
+        assert_eq!(pairs, matrix.check_pairs);
+
+        let srdf = matrix.to_srdf(&urdf_robot);
+        assert!(srdf.contains("<robot name=\"robo\">"));
+        assert_eq!(
+            srdf.matches("<disable_collisions ").count(),
+            matrix.disabled_collisions.len()
+        );
+    }
+
+    #[test]
+    fn test_srdf_escape() {
+        let mut urdf_robot = urdf_rs::read_file("sample.urdf").unwrap();
+        urdf_robot.name = "a<b>&\"c'".to_owned();
+        let matrix = CollisionMatrix {
+            check_pairs: vec![],
+            disabled_collisions: vec![DisabledCollision {
+                names: ("x&y".to_owned(), "<z>".to_owned()),
+                reason: DisabledReason::Never,
+            }],
+        };
+        let srdf = matrix.to_srdf(&urdf_robot);
+        assert!(srdf.contains("<robot name=\"a&lt;b&gt;&amp;&quot;c&apos;\">"));
+        assert!(srdf.contains(
+            "<disable_collisions link1=\"x&amp;y\" link2=\"&lt;z&gt;\" reason=\"Never\" />"
+        ));
+    }
+}
@@ -0,0 +1,150 @@
+use k::nalgebra as na;
+use na::RealField;
+
+use crate::CollisionDetector;
+
+/// Checks the motion from `start` to `goal` of `using_joints` by subdividing
+/// it adaptively, until no point on the collision models moves more than
+/// `max_displacement` between the adjacent checked configurations.
+///
+/// `check` is called with the interpolation parameter in `(0, 1)` and the
+/// intermediate joint positions after they are set to `using_joints`, which is
+/// a part of `robot`. `start` and `goal` themselves
+/// are not checked. The joints are left at an arbitrary intermediate position.
+///
+/// The displacement of a link is bounded by the translation of its origin and
+/// the rotation angle multiplied by the radius of its collision models, see
+/// [`CollisionDetector::link_radii`].
+pub fn check_continuous_motion<N, E, F>(
+    detector: &CollisionDetector<N>,
+    robot: &k::Chain<N>,
+    using_joints: &k::Chain<N>,
+    start: &[N],
+    goal: &[N],
+    max_displacement: N,
+    mut check: F,
+) -> Result<(), E>
+where
+    N: RealField + k::SubsetOf<f64>,
+    F: FnMut(N, &[N]) -> Result<(), E>,
+{
+    let radii = detector.link_radii();
+    let nodes: Vec<_> = robot
+        .iter()
+        .filter_map(|node| {
+            let radius = *radii.get(&node.joint().name)?;
+            Some((node, radius))
+        })
+        .collect();
+    let link_poses = |positions: &[N]| {
+        using_joints.set_joint_positions_clamped(positions);
+        robot.update_transforms();
+        nodes
+            .iter()
+            .map(|(node, _)| node.world_transform().unwrap())
+            .collect::<Vec<_>>()
+    };
+    let displacement = |poses1: &[na::Isometry3<N>], poses2: &[na::Isometry3<N>]| {
+        nodes
+            .iter()
+            .zip(poses1.iter().zip(poses2))
+            .map(|((_, radius), (pose1, pose2))| {
+                (pose2.translation.vector - pose1.translation.vector).norm()
+                    + pose1.rotation.angle_to(&pose2.rotation) * *radius
+            })
+            .fold(N::zero(), |a, b| a.max(b))
+    };
+
+    let half: N = na::convert(0.5);
+    let start_poses = link_poses(start);
+    let goal_poses = link_poses(goal);
+    // Depth first from the start, to find the first collision.
+    let mut segments = vec![(
+        N::zero(),
+        start.to_vec(),
+        start_poses,
+        N::one(),
+        goal.to_vec(),
+        goal_poses,
+    )];
+    while let Some((t1, positions1, poses1, t2, positions2, poses2)) = segments.pop() {
+        if displacement(&poses1, &poses2) <= max_displacement {
+            continue;
+        }
+        let middle: Vec<N> = positions1
+            .iter()
+            .zip(&positions2)
+            .map(|(p1, p2)| (*p1 + *p2) * half)
+            .collect();
+        // The segment can not be divided any more, e.g. because of the precision.
+        if middle == positions1 || middle == positions2 {
+            continue;
+        }
+        let t = (t1 + t2) * half;
+        let middle_poses = link_poses(&middle);
+        check(t, &middle)?;
+        segments.push((
+            t,
+            middle.clone(),
+            middle_poses.clone(),
+            t2,
+            positions2,
+            poses2,
+        ));
+        segments.push((t1, positions1, poses1, t, middle, middle_poses));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_continuous_motion() {
+        let urdf_robot = urdf_rs::read_file("sample.urdf").unwrap();
+        let robot = k::Chain::<f64>::from(&urdf_robot);
+        let detector = CollisionDetector::from_urdf_robot(&urdf_robot, 0.0);
+        let start = vec![0.0; robot.dof()];
+        let mut goal = start.clone();
+        goal[0] = 1.0;
+
+        let mut count = 0;
+        let result: Result<(), ()> =
+            check_continuous_motion(&detector, &robot, &robot, &start, &goal, 0.01, |_, _| {
+                count += 1;
+                Ok(())
+            });
+        assert!(result.is_ok());
+        let dense_count = count;
+        assert!(dense_count > 10);
+
+        count = 0;
+        let _ = check_continuous_motion(&detector, &robot, &robot, &start, &goal, 0.1, |_, _| {
+            count += 1;
+            Ok::<(), ()>(())
+        });
+        assert!(count < dense_count);
+
+        // No motion, no checks.
+        let result =
+            check_continuous_motion(&detector, &robot, &robot, &start, &start, 0.01, |_, _| {
+                Err::<(), ()>(())
+            });
+        assert!(result.is_ok());
+        // The first error stops the checking.
+        count = 0;
+        let result =
+            check_continuous_motion(&detector, &robot, &robot, &start, &goal, 0.01, |_, _| {
+                count += 1;
+                Err(count)
+            });
+        assert_eq!(result, Err(1));
+        // The first check is at the middle of the motion.
+        let result =
+            check_continuous_motion(&detector, &robot, &robot, &start, &goal, 0.01, |t, _| {
+                Err(t)
+            });
+        assert_eq!(result, Err(0.5));
+    }
+}
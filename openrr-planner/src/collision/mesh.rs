@@ -5,6 +5,9 @@ use ncollide3d::shape::TriMesh;
 
 use crate::errors::*;
 
+mod collada;
+mod obj;
+
 #[cfg(feature = "assimp")]
 pub(crate) fn load_mesh<P, T>(filename: P, scale: &[f64; 3]) -> Result<TriMesh<T>>
 where
@@ -21,10 +24,7 @@ where
             Err(err) => Err(Error::MeshError(err.to_owned())),
         }
     } else {
-        match filename.extension().and_then(OsStr::to_str) {
-            Some("stl" | "STL") => load_stl(filename, scale),
-            _ => Err(Error::MeshError(format!("failed to parse {:?}", filename))),
-        }
+        load_mesh_without_assimp(filename, scale)
     }
 }
 
@@ -34,11 +34,20 @@ where
     P: AsRef<Path>,
     T: RealField,
 {
-    let filename = filename.as_ref();
+    load_mesh_without_assimp(filename.as_ref(), scale)
+}
+
+/// Loads STL, OBJ and COLLADA files by the pure Rust loaders
+fn load_mesh_without_assimp<T>(filename: &Path, scale: &[f64; 3]) -> Result<TriMesh<T>>
+where
+    T: RealField,
+{
     match filename.extension().and_then(OsStr::to_str) {
         Some("stl" | "STL") => load_stl(filename, scale),
+        Some("obj" | "OBJ") => obj::load_obj(filename, scale),
+        Some("dae" | "DAE") => collada::load_collada(filename, scale),
         _ => Err(Error::MeshError(format!(
-            "assimp feature is disabled: could not parse {:?}",
+            "unsupported mesh format without assimp feature: {:?}",
             filename
         ))),
     }
@@ -0,0 +1,471 @@
+//! Loader of COLLADA (DAE) files
+//!
+//! The triangles, polylists and polygons of the geometries are loaded, and
+//! transformed by the nodes of the visual scene. The unit and the up axis of
+//! the file are converted to meters and Z-up.
+
+use std::{collections::HashMap, path::Path};
+
+use k::{nalgebra as na, RealField};
+use ncollide3d::shape::TriMesh;
+use xml::{reader::XmlEvent, EventReader};
+
+use crate::errors::*;
+
+pub(crate) fn load_collada<P, T>(filename: P, scale: &[f64; 3]) -> Result<TriMesh<T>>
+where
+    P: AsRef<Path>,
+    T: RealField,
+{
+    parse_collada(&std::fs::read_to_string(filename)?, scale)
+}
+
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    fn attribute(&self, name: &str) -> Result<&str> {
+        self.attributes
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| {
+                Error::ParseError(format!("{} attribute of <{}> in COLLADA", name, self.name))
+            })
+    }
+
+    fn numbers<N: std::str::FromStr>(&self) -> Result<Vec<N>> {
+        self.text
+            .split_whitespace()
+            .map(|t| {
+                t.parse().map_err(|_| {
+                    Error::ParseError(format!("{:?} in <{}> in COLLADA", t, self.name))
+                })
+            })
+            .collect()
+    }
+}
+
+fn parse_tree(s: &str) -> Result<Element> {
+    let mut stack = vec![Element::default()];
+    for event in EventReader::from_str(s) {
+        match event.map_err(|e| Error::ParseError(format!("COLLADA: {}", e)))? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => stack.push(Element {
+                name: name.local_name,
+                attributes: attributes
+                    .into_iter()
+                    .map(|a| (a.name.local_name, a.value))
+                    .collect(),
+                ..Default::default()
+            }),
+            XmlEvent::EndElement { .. } => {
+                let element = stack.pop().unwrap();
+                stack.last_mut().unwrap().children.push(element);
+            }
+            XmlEvent::Characters(text) => stack.last_mut().unwrap().text.push_str(&text),
+            _ => {}
+        }
+    }
+    stack
+        .pop()
+        .and_then(|document| document.children.into_iter().next())
+        .ok_or_else(|| Error::ParseError("COLLADA: no root element".to_owned()))
+}
+
+/// Strips `#` of the URL which refers to an element in the same document
+fn local_id(url: &str) -> &str {
+    url.trim_start_matches('#')
+}
+
+fn find_by_id<'a>(element: &'a Element, id: &str) -> Option<&'a Element> {
+    if element.attributes.get("id").map(String::as_str) == Some(id) {
+        return Some(element);
+    }
+    element.children.iter().find_map(|c| find_by_id(c, id))
+}
+
+/// Triangles of a geometry in its local frame
+fn geometry_triangles(root: &Element, geometry: &Element) -> Result<Vec<[na::Point3<f64>; 3]>> {
+    let mesh = match geometry.child("mesh") {
+        Some(mesh) => mesh,
+        // e.g. <spline>
+        None => return Ok(vec![]),
+    };
+    let positions = |vertices_id: &str| -> Result<Vec<na::Point3<f64>>> {
+        let vertices =
+            find_by_id(mesh, vertices_id).ok_or_else(|| Error::NotFound(vertices_id.to_owned()))?;
+        let source_url = vertices
+            .children("input")
+            .find(|input| input.attributes.get("semantic").map(String::as_str) == Some("POSITION"))
+            .ok_or_else(|| Error::NotFound(format!("POSITION of {}", vertices_id)))?
+            .attribute("source")?;
+        let source = find_by_id(root, local_id(source_url))
+            .ok_or_else(|| Error::NotFound(source_url.to_owned()))?;
+        let values: Vec<f64> = source
+            .child("float_array")
+            .ok_or_else(|| Error::NotFound(format!("float_array of {}", source_url)))?
+            .numbers()?;
+        let stride = source
+            .child("technique_common")
+            .and_then(|t| t.child("accessor"))
+            .and_then(|a| a.attributes.get("stride"))
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3usize)
+            .max(3);
+        Ok(values
+            .chunks_exact(stride)
+            .map(|v| na::Point3::new(v[0], v[1], v[2]))
+            .collect())
+    };
+
+    let mut triangles = vec![];
+    for primitive in &mesh.children {
+        if !matches!(
+            primitive.name.as_str(),
+            "triangles" | "polylist" | "polygons"
+        ) {
+            continue;
+        }
+        let mut stride = 1;
+        let mut vertex_input = None;
+        for input in primitive.children("input") {
+            let offset: usize = input
+                .attribute("offset")?
+                .parse()
+                .map_err(|_| Error::ParseError("offset of <input> in COLLADA".to_owned()))?;
+            stride = stride.max(offset + 1);
+            if input.attribute("semantic")? == "VERTEX" {
+                vertex_input = Some((offset, local_id(input.attribute("source")?)));
+            }
+        }
+        let (offset, vertices_id) = vertex_input.ok_or_else(|| {
+            Error::NotFound(format!("VERTEX input of <{}> in COLLADA", primitive.name))
+        })?;
+        let points = positions(vertices_id)?;
+        let point = |index: usize| {
+            points.get(index).copied().ok_or_else(|| {
+                Error::ParseError(format!("index {} of {} in COLLADA", index, vertices_id))
+            })
+        };
+
+        let mut polygons: Vec<Vec<usize>> = vec![];
+        for p in primitive.children("p") {
+            let indices: Vec<usize> = p.numbers()?;
+            let indices: Vec<usize> = indices
+                .chunks_exact(stride)
+                .map(|chunk| chunk[offset])
+                .collect();
+            match primitive.name.as_str() {
+                "triangles" => polygons.extend(indices.chunks_exact(3).map(<[usize]>::to_vec)),
+                "polylist" => {
+                    let vcount: Vec<usize> = match primitive.child("vcount") {
+                        Some(vcount) => vcount.numbers()?,
+                        None => vec![],
+                    };
+                    let mut start = 0;
+                    for count in vcount {
+                        let end = (start + count).min(indices.len());
+                        polygons.push(indices[start..end].to_vec());
+                        start = end;
+                    }
+                }
+                _ => polygons.push(indices),
+            }
+        }
+        for polygon in polygons {
+            for i in 1..polygon.len().saturating_sub(1) {
+                triangles.push([
+                    point(polygon[0])?,
+                    point(polygon[i])?,
+                    point(polygon[i + 1])?,
+                ]);
+            }
+        }
+    }
+    Ok(triangles)
+}
+
+/// Transform of the node, which is the product of its transformation elements in order
+fn node_transform(node: &Element) -> Result<na::Matrix4<f64>> {
+    let mut transform = na::Matrix4::identity();
+    for child in &node.children {
+        let values: Vec<f64> = match child.name.as_str() {
+            "matrix" | "translate" | "rotate" | "scale" => child.numbers()?,
+            _ => continue,
+        };
+        let expected_len = if child.name == "matrix" {
+            16
+        } else if child.name == "rotate" {
+            4
+        } else {
+            3
+        };
+        if values.len() != expected_len {
+            return Err(Error::ParseError(format!(
+                "<{}> in COLLADA: {:?}",
+                child.name, child.text
+            )));
+        }
+        let v = na::Vector3::new(values[0], values[1], values[2]);
+        transform *= match child.name.as_str() {
+            "matrix" => na::Matrix4::from_row_slice(&values),
+            "translate" => na::Matrix4::new_translation(&v),
+            "rotate" => {
+                na::Rotation3::from_axis_angle(&na::Unit::new_normalize(v), values[3].to_radians())
+                    .to_homogeneous()
+            }
+            _ => na::Matrix4::new_nonuniform_scaling(&v),
+        };
+    }
+    Ok(transform)
+}
+
+/// `instances` are the ids of the nodes instantiated by `instance_node` from
+/// the scene to `node`, which are used to reject the cyclic references.
+fn collect_node_triangles<'a>(
+    root: &'a Element,
+    node: &'a Element,
+    parent_transform: &na::Matrix4<f64>,
+    instances: &mut Vec<&'a str>,
+    triangles: &mut Vec<[na::Point3<f64>; 3]>,
+) -> Result<()> {
+    let transform = parent_transform * node_transform(node)?;
+    for child in &node.children {
+        match child.name.as_str() {
+            "instance_geometry" => {
+                let url = child.attribute("url")?;
+                let geometry = find_by_id(root, local_id(url))
+                    .ok_or_else(|| Error::NotFound(url.to_owned()))?;
+                triangles.extend(geometry_triangles(root, geometry)?.into_iter().map(|t| {
+                    [
+                        transform.transform_point(&t[0]),
+                        transform.transform_point(&t[1]),
+                        transform.transform_point(&t[2]),
+                    ]
+                }));
+            }
+            "instance_node" => {
+                let url = child.attribute("url")?;
+                let id = local_id(url);
+                if instances.contains(&id) {
+                    return Err(Error::ParseError(format!(
+                        "cyclic <instance_node> of {}",
+                        url
+                    )));
+                }
+                let instance =
+                    find_by_id(root, id).ok_or_else(|| Error::NotFound(url.to_owned()))?;
+                instances.push(id);
+                collect_node_triangles(root, instance, &transform, instances, triangles)?;
+                instances.pop();
+            }
+            "node" => collect_node_triangles(root, child, &transform, instances, triangles)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn parse_collada<T>(s: &str, scale: &[f64; 3]) -> Result<TriMesh<T>>
+where
+    T: RealField,
+{
+    let root = parse_tree(s)?;
+    if root.name != "COLLADA" {
+        return Err(Error::ParseError(format!(
+            "<{}> is not the root of COLLADA",
+            root.name
+        )));
+    }
+
+    let asset = root.child("asset");
+    let meter = asset
+        .and_then(|a| a.child("unit"))
+        .and_then(|u| u.attributes.get("meter"))
+        .and_then(|m| m.parse().ok())
+        .unwrap_or(1.0);
+    // Y_UP is the default of COLLADA.
+    let up_axis = asset
+        .and_then(|a| a.child("up_axis"))
+        .map_or("Y_UP", |u| u.text.trim());
+    let up_rotation = match up_axis {
+        "X_UP" => {
+            na::Rotation3::from_axis_angle(&na::Vector3::y_axis(), -std::f64::consts::FRAC_PI_2)
+        }
+        "Y_UP" => {
+            na::Rotation3::from_axis_angle(&na::Vector3::x_axis(), std::f64::consts::FRAC_PI_2)
+        }
+        _ => na::Rotation3::identity(),
+    };
+    let file_transform = na::Matrix4::new_nonuniform_scaling(&na::Vector3::from_row_slice(scale))
+        * up_rotation.to_homogeneous()
+        * na::Matrix4::new_scaling(meter);
+
+    let mut triangles = vec![];
+    let scene = root
+        .child("scene")
+        .and_then(|s| s.child("instance_visual_scene"))
+        .and_then(|i| i.attributes.get("url"))
+        .and_then(|url| find_by_id(&root, local_id(url)))
+        .or_else(|| {
+            root.child("library_visual_scenes")
+                .and_then(|l| l.child("visual_scene"))
+        });
+    match scene {
+        Some(scene) => {
+            for node in scene.children("node") {
+                collect_node_triangles(&root, node, &file_transform, &mut vec![], &mut triangles)?;
+            }
+        }
+        // All of the geometries without transforms
+        None => {
+            for geometry in root
+                .children("library_geometries")
+                .flat_map(|l| l.children("geometry"))
+            {
+                triangles.extend(geometry_triangles(&root, geometry)?.into_iter().map(|t| {
+                    [
+                        file_transform.transform_point(&t[0]),
+                        file_transform.transform_point(&t[1]),
+                        file_transform.transform_point(&t[2]),
+                    ]
+                }));
+            }
+        }
+    }
+
+    let vertices = triangles
+        .iter()
+        .flat_map(|t| t.iter())
+        .map(|p| na::Point3::<T>::new(na::convert(p.x), na::convert(p.y), na::convert(p.z)))
+        .collect();
+    let indices = (0..triangles.len())
+        .map(|i| na::Point3::new(i * 3, i * 3 + 1, i * 3 + 2))
+        .collect();
+    Ok(TriMesh::new(vertices, indices, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_COLLADA: &str = r##"<?xml version="1.0" encoding="utf-8"?>
+<COLLADA xmlns="http://www.collada.org/2005/11/COLLADASchema" version="1.4.1">
+  <asset>
+    <unit name="centimeter" meter="0.01"/>
+    <up_axis>Z_UP</up_axis>
+  </asset>
+  <library_geometries>
+    <geometry id="square-mesh" name="square">
+      <mesh>
+        <source id="square-mesh-positions">
+          <float_array id="square-mesh-positions-array" count="12">0 0 0 10 0 0 10 10 0 0 10 0</float_array>
+          <technique_common>
+            <accessor source="#square-mesh-positions-array" count="4" stride="3">
+              <param name="X" type="float"/>
+              <param name="Y" type="float"/>
+              <param name="Z" type="float"/>
+            </accessor>
+          </technique_common>
+        </source>
+        <source id="square-mesh-normals">
+          <float_array id="square-mesh-normals-array" count="3">0 0 1</float_array>
+        </source>
+        <vertices id="square-mesh-vertices">
+          <input semantic="POSITION" source="#square-mesh-positions"/>
+        </vertices>
+        <polylist count="1">
+          <input semantic="VERTEX" source="#square-mesh-vertices" offset="0"/>
+          <input semantic="NORMAL" source="#square-mesh-normals" offset="1"/>
+          <vcount>4</vcount>
+          <p>0 0 1 0 2 0 3 0</p>
+        </polylist>
+      </mesh>
+    </geometry>
+  </library_geometries>
+  <library_visual_scenes>
+    <visual_scene id="Scene" name="Scene">
+      <node id="square" name="square">
+        <translate>0 0 100</translate>
+        <instance_geometry url="#square-mesh"/>
+      </node>
+    </visual_scene>
+  </library_visual_scenes>
+  <scene>
+    <instance_visual_scene url="#Scene"/>
+  </scene>
+</COLLADA>
+"##;
+
+    fn assert_points(mesh: &TriMesh<f64>, expected: &[[f64; 3]]) {
+        assert_eq!(mesh.points().len(), expected.len());
+        for (p, e) in mesh.points().iter().zip(expected) {
+            assert!(
+                (p - na::Point3::from_slice(e)).norm() < 1e-9,
+                "{} {:?}",
+                p,
+                e
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_collada() {
+        let mesh = parse_collada::<f64>(SAMPLE_COLLADA, &[1.0, 2.0, 1.0]).unwrap();
+        assert_eq!(mesh.faces().len(), 2);
+        assert_points(
+            &mesh,
+            &[
+                [0.0, 0.0, 1.0],
+                [0.1, 0.0, 1.0],
+                [0.1, 0.2, 1.0],
+                [0.0, 0.0, 1.0],
+                [0.1, 0.2, 1.0],
+                [0.0, 0.2, 1.0],
+            ],
+        );
+
+        // The Y axis of the file is converted to the Z axis.
+        let y_up = SAMPLE_COLLADA.replace("Z_UP", "Y_UP");
+        let mesh = parse_collada::<f64>(&y_up, &[1.0; 3]).unwrap();
+        assert_points(
+            &mesh,
+            &[
+                [0.0, -1.0, 0.0],
+                [0.1, -1.0, 0.0],
+                [0.1, -1.0, 0.1],
+                [0.0, -1.0, 0.0],
+                [0.1, -1.0, 0.1],
+                [0.0, -1.0, 0.1],
+            ],
+        );
+
+        assert!(parse_collada::<f64>("<robot />", &[1.0; 3]).is_err());
+    }
+
+    #[test]
+    fn test_cyclic_instance_node() {
+        let instance = r##"<instance_geometry url="#square-mesh"/>
+        <instance_node url="#square"/>"##;
+        let cyclic =
+            SAMPLE_COLLADA.replace(r##"<instance_geometry url="#square-mesh"/>"##, instance);
+        assert!(matches!(
+            parse_collada::<f64>(&cyclic, &[1.0; 3]),
+            Err(Error::ParseError(_))
+        ));
+    }
+}
@@ -0,0 +1,110 @@
+//! Loader of Wavefront OBJ files
+//!
+//! Only the vertices and the faces are used. The faces are triangulated as fans.
+
+use std::path::Path;
+
+use k::{nalgebra as na, RealField};
+use ncollide3d::shape::TriMesh;
+
+use crate::errors::*;
+
+pub(crate) fn load_obj<P, T>(filename: P, scale: &[f64; 3]) -> Result<TriMesh<T>>
+where
+    P: AsRef<Path>,
+    T: RealField,
+{
+    parse_obj(&std::fs::read_to_string(filename)?, scale)
+}
+
+fn parse_obj<T>(s: &str, scale: &[f64; 3]) -> Result<TriMesh<T>>
+where
+    T: RealField,
+{
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    for line in s.lines() {
+        let parse_error = || Error::ParseError(format!("{:?} in OBJ", line));
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let mut v = [0.0; 3];
+                for (i, value) in v.iter_mut().enumerate() {
+                    let x: f64 = tokens
+                        .next()
+                        .and_then(|t| t.parse().ok())
+                        .ok_or_else(parse_error)?;
+                    *value = x * scale[i];
+                }
+                vertices.push(na::Point3::<T>::new(
+                    na::convert(v[0]),
+                    na::convert(v[1]),
+                    na::convert(v[2]),
+                ));
+            }
+            Some("f") => {
+                // `v`, `v/vt`, `v//vn` or `v/vt/vn`, where the index starts
+                // from 1 and negative indices are relative to the end.
+                let face = tokens
+                    .map(|t| {
+                        let index: isize = t
+                            .split('/')
+                            .next()
+                            .and_then(|i| i.parse().ok())
+                            .ok_or_else(parse_error)?;
+                        let index = if index < 0 {
+                            vertices.len() as isize + index
+                        } else {
+                            index - 1
+                        };
+                        if index < 0 || index as usize >= vertices.len() {
+                            return Err(parse_error());
+                        }
+                        Ok(index as usize)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                if face.len() < 3 {
+                    return Err(parse_error());
+                }
+                for i in 1..face.len() - 1 {
+                    indices.push(na::Point3::new(face[0], face[i], face[i + 1]));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(TriMesh::new(vertices, indices, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_obj() {
+        let obj = "# comment
+o square
+v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+v 1.0 1.0 0.0
+v 0.0 1.0 0.0 1.0
+vn 0.0 0.0 1.0
+f 1//1 2//1 3//1 4//1
+f -4 -2 -1
+";
+        let mesh = parse_obj::<f64>(obj, &[2.0, 3.0, 1.0]).unwrap();
+        assert_eq!(mesh.points().len(), 4);
+        assert_eq!(mesh.points()[2], na::Point3::new(2.0, 3.0, 0.0));
+        assert_eq!(
+            mesh.faces().iter().map(|f| f.indices).collect::<Vec<_>>(),
+            vec![
+                na::Point3::new(0, 1, 2),
+                na::Point3::new(0, 2, 3),
+                na::Point3::new(0, 2, 3),
+            ]
+        );
+
+        assert!(parse_obj::<f64>("v 0.0 0.0\n", &[1.0; 3]).is_err());
+        assert!(parse_obj::<f64>("v 0 0 0\nv 1 0 0\nf 1 2 3\n", &[1.0; 3]).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use k::nalgebra as na;
+use na::RealField;
+use ncollide3d::shape::{Capsule, ConvexHull, Cuboid, ShapeHandle, TriMesh};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Primitive shape to replace meshes with, for faster collision checking
+///
+/// The approximated shapes contain the original meshes, so they are
+/// conservative for collision checking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum MeshApproximation {
+    /// Use the meshes as they are.
+    None,
+    /// Axis-aligned bounding box in the link frame.
+    BoundingBox,
+    /// Capsule along the longest axis of the bounding box.
+    Capsule,
+    /// Convex hull of the vertices.
+    ConvexHull,
+}
+
+impl Default for MeshApproximation {
+    fn default() -> Self {
+        MeshApproximation::None
+    }
+}
+
+/// Replaces `shape` with the primitive shape if it is a mesh
+///
+/// Returns the new shape and its pose, which are the same as the input if
+/// `shape` is not a mesh or it can not be approximated.
+pub(crate) fn approximate_mesh<T>(
+    shape: &ShapeHandle<T>,
+    pose: &na::Isometry3<T>,
+    approximation: MeshApproximation,
+) -> (ShapeHandle<T>, na::Isometry3<T>)
+where
+    T: RealField,
+{
+    let unchanged = || (shape.clone(), *pose);
+    let mesh = match shape.as_shape::<TriMesh<T>>() {
+        Some(mesh) if !mesh.points().is_empty() => mesh,
+        _ => return unchanged(),
+    };
+    let points = mesh.points();
+    let (mins, maxs) = points
+        .iter()
+        .skip(1)
+        .fold((points[0].coords, points[0].coords), |(mins, maxs), p| {
+            (mins.inf(&p.coords), maxs.sup(&p.coords))
+        });
+    let center = (mins + maxs) * na::convert::<f64, T>(0.5);
+    let half_extents = (maxs - mins) * na::convert::<f64, T>(0.5);
+
+    match approximation {
+        MeshApproximation::None => unchanged(),
+        MeshApproximation::BoundingBox => (
+            ShapeHandle::new(Cuboid::new(half_extents)),
+            *pose * na::Translation3::from(center),
+        ),
+        MeshApproximation::Capsule => {
+            let axis_index = half_extents.imax();
+            let mut axis = na::Vector3::zeros();
+            axis[axis_index] = T::one();
+            // The smallest radius which contains the points around the axis,
+            // then the smallest half height which contains the points.
+            let (radius2, radial2s) = points.iter().fold(
+                (T::zero(), Vec::with_capacity(points.len())),
+                |(max_radius2, mut radial2s), p| {
+                    let v = p.coords - center;
+                    let axial = v[axis_index];
+                    let radial2 = v.norm_squared() - axial * axial;
+                    radial2s.push((axial.abs(), radial2));
+                    (max_radius2.max(radial2), radial2s)
+                },
+            );
+            let half_height = radial2s
+                .into_iter()
+                .map(|(axial, radial2)| axial - (radius2 - radial2).max(T::zero()).sqrt())
+                .fold(T::zero(), |a, b| a.max(b));
+            // Capsule of ncollide3d is along the y axis.
+            let rotation = na::UnitQuaternion::rotation_between(&na::Vector3::y(), &axis)
+                .unwrap_or_else(na::UnitQuaternion::identity);
+            (
+                ShapeHandle::new(Capsule::new(half_height, radius2.sqrt())),
+                *pose * na::Isometry3::from_parts(na::Translation3::from(center), rotation),
+            )
+        }
+        MeshApproximation::ConvexHull => match ConvexHull::try_from_points(points) {
+            Some(hull) => (ShapeHandle::new(hull), *pose),
+            None => unchanged(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ncollide3d::{query, shape::Ball};
+
+    use super::*;
+
+    fn box_mesh() -> ShapeHandle<f64> {
+        let points = vec![
+            na::Point3::new(0.0, 0.0, 0.0),
+            na::Point3::new(0.4, 0.0, 0.0),
+            na::Point3::new(0.4, 0.1, 0.0),
+            na::Point3::new(0.0, 0.1, 0.0),
+            na::Point3::new(0.0, 0.0, 0.1),
+            na::Point3::new(0.4, 0.0, 0.1),
+            na::Point3::new(0.4, 0.1, 0.1),
+            na::Point3::new(0.0, 0.1, 0.1),
+        ];
+        let indices = vec![
+            na::Point3::new(0, 1, 2),
+            na::Point3::new(0, 2, 3),
+            na::Point3::new(4, 6, 5),
+            na::Point3::new(4, 7, 6),
+        ];
+        ShapeHandle::new(TriMesh::new(points, indices, None))
+    }
+
+    #[test]
+    fn test_approximate_mesh() {
+        let mesh = box_mesh();
+        let pose = na::Isometry3::translation(1.0, 0.0, 0.0);
+        let ball = Ball::new(0.01);
+        let ball_pose = na::Isometry3::translation(1.39, 0.09, 0.05);
+
+        let (shape, _) = approximate_mesh(&mesh, &pose, MeshApproximation::None);
+        assert!(shape.as_shape::<TriMesh<f64>>().is_some());
+
+        for approximation in &[
+            MeshApproximation::BoundingBox,
+            MeshApproximation::Capsule,
+            MeshApproximation::ConvexHull,
+        ] {
+            let (shape, shape_pose) = approximate_mesh(&mesh, &pose, *approximation);
+            assert!(shape.as_shape::<TriMesh<f64>>().is_none());
+            // All of the vertices are inside the approximated shape.
+            for p in mesh.as_shape::<TriMesh<f64>>().unwrap().points() {
+                let vertex_pose = pose * na::Translation3::from(p.coords);
+                assert!(
+                    query::distance(&shape_pose, &*shape, &vertex_pose, &Ball::new(1e-6)) < 1e-6,
+                    "{:?} {:?}",
+                    approximation,
+                    p
+                );
+            }
+            // The inner point of the box
+            assert!(query::distance(&shape_pose, &*shape, &ball_pose, &ball) < 1e-6);
+        }
+
+        let (shape, shape_pose) = approximate_mesh(&mesh, &pose, MeshApproximation::Capsule);
+        let capsule = shape.as_shape::<Capsule<f64>>().unwrap();
+        assert!((capsule.radius - 0.5f64.sqrt() * 0.1).abs() < 1e-6);
+        assert!((shape_pose.translation.vector - na::Vector3::new(1.2, 0.05, 0.05)).norm() < 1e-6);
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{cmp::Ordering, collections::HashMap, path::Path};
 
 use k::nalgebra as na;
 use na::RealField;
@@ -6,7 +6,10 @@ use ncollide3d::shape::{Compound, Shape};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::collision::{CollisionDetector, EnvCollisionNames, SelfCollisionPairs};
+use crate::collision::{
+    CollisionDetector, EnvCollisionNames, EnvDistance, MeshApproximation, SelfCollisionPairs,
+    SelfDistance,
+};
 
 pub struct RobotCollisionDetector<N>
 where
@@ -46,8 +49,6 @@ where
         target_shape: &'a dyn Shape<N>,
         target_pose: &'a na::Isometry3<N>,
     ) -> EnvCollisionNames<'a, 'a, N> {
-        self.robot.update_transforms();
-
         EnvCollisionNames::new(
             &self.collision_detector,
             &self.robot,
@@ -58,8 +59,6 @@ where
 
     /// Detects self collisions and returns name pairs of the self-colliding links(joints)
     pub fn detect_self(&self) -> SelfCollisionPairs<'_, N> {
-        self.robot.update_transforms();
-
         SelfCollisionPairs::new(
             &self.collision_detector,
             &self.robot,
@@ -104,6 +103,50 @@ where
     pub fn is_collision_detected(&self, objects: &Compound<N>) -> bool {
         self.is_env_collision_detected(objects) | self.is_self_collision_detected()
     }
+
+    /// Gets the distances from the links to the nearest environmental objects
+    /// objects: environmental objects
+    pub fn env_distances(&self, objects: &Compound<N>) -> Vec<EnvDistance<N>> {
+        let mut distances: Vec<EnvDistance<N>> = Vec::new();
+        for shape in objects.shapes() {
+            for d in self
+                .collision_detector
+                .env_distances(&self.robot, &*shape.1, &shape.0)
+            {
+                match distances.iter_mut().find(|e| e.name == d.name) {
+                    Some(e) if d.distance < e.distance => *e = d,
+                    Some(_) => {}
+                    None => distances.push(d),
+                }
+            }
+        }
+        distances
+    }
+
+    /// Gets the minimum distance between the robot and environmental objects
+    /// objects: environmental objects
+    pub fn min_env_distance(&self, objects: &Compound<N>) -> Option<EnvDistance<N>> {
+        self.env_distances(objects).into_iter().min_by(|a, b| {
+            a.distance
+                .partial_cmp(&b.distance)
+                .unwrap_or(Ordering::Equal)
+        })
+    }
+
+    /// Gets the distances of the self collision pairs
+    pub fn self_distances(&self) -> Vec<SelfDistance<N>> {
+        self.collision_detector
+            .self_distances(&self.robot, &self.self_collision_pairs)
+    }
+
+    /// Gets the minimum distance of the self collision pairs
+    pub fn min_self_distance(&self) -> Option<SelfDistance<N>> {
+        self.self_distances().into_iter().min_by(|a, b| {
+            a.distance
+                .partial_cmp(&b.distance)
+                .unwrap_or(Ordering::Equal)
+        })
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
@@ -112,6 +155,13 @@ where
 pub struct RobotCollisionDetectorConfig {
     #[serde(default = "default_prediction")]
     pub prediction: f64,
+    /// Padding of the shapes of each link(joint) \[m\]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub link_padding: HashMap<String, f64>,
+    /// Replaces the meshes with the primitive shapes for faster checking
+    #[serde(default)]
+    pub mesh_approximation: MeshApproximation,
 }
 
 fn default_prediction() -> f64 {
@@ -120,7 +170,11 @@ fn default_prediction() -> f64 {
 
 impl RobotCollisionDetectorConfig {
     pub fn new(prediction: f64) -> Self {
-        RobotCollisionDetectorConfig { prediction }
+        RobotCollisionDetectorConfig {
+            prediction,
+            link_padding: HashMap::new(),
+            mesh_approximation: MeshApproximation::default(),
+        }
     }
 }
 
@@ -128,6 +182,8 @@ impl Default for RobotCollisionDetectorConfig {
     fn default() -> Self {
         Self {
             prediction: default_prediction(),
+            link_padding: HashMap::new(),
+            mesh_approximation: MeshApproximation::default(),
         }
     }
 }
@@ -139,7 +195,9 @@ pub fn create_robot_collision_detector<P: AsRef<Path>>(
 ) -> RobotCollisionDetector<f64> {
     let urdf_robot = urdf_rs::read_file(urdf_path).unwrap();
     let robot = k::Chain::<f64>::from(&urdf_robot);
-    let collision_detector = CollisionDetector::from_urdf_robot(&urdf_robot, config.prediction);
+    let mut collision_detector = CollisionDetector::from_urdf_robot(&urdf_robot, config.prediction);
+    collision_detector.link_padding = config.link_padding;
+    collision_detector.approximate_meshes(config.mesh_approximation);
 
     RobotCollisionDetector::new(robot, collision_detector, self_collision_pairs)
 }
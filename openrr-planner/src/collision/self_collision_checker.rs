@@ -1,4 +1,4 @@
-use std::{path::Path, sync::Arc, time::Duration};
+use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
 
 use k::nalgebra as na;
 use na::RealField;
@@ -7,8 +7,9 @@ use serde::{Deserialize, Serialize};
 use tracing::debug;
 
 use crate::{
-    collision::parse_colon_separated_pairs, errors::*, interpolate, CollisionDetector,
-    TrajectoryPoint,
+    collision::{check_continuous_motion, parse_colon_separated_pairs, MeshApproximation},
+    errors::*,
+    interpolate, CollisionDetector, TrajectoryPoint,
 };
 
 pub struct SelfCollisionChecker<N>
@@ -20,6 +21,10 @@ where
     pub collision_pairs: Vec<(String, String)>,
 
     pub time_interpolate_rate: N,
+    /// If set, the motions between the points are checked continuously so that
+    /// no link moves more than this between the checked configurations, instead
+    /// of checking the points interpolated by `time_interpolate_rate`.
+    pub max_link_displacement: Option<N>,
 }
 
 impl<N> SelfCollisionChecker<N>
@@ -44,9 +49,53 @@ where
             collision_detector,
             collision_pairs,
             time_interpolate_rate,
+            max_link_displacement: None,
         }
     }
 
+    fn check_current_positions(&self) -> Result<()> {
+        match self
+            .collision_detector
+            .detect_self(&self.collision_check_robot, &self.collision_pairs)
+            .next()
+        {
+            Some(names) => Err(Error::Collision {
+                point: UnfeasibleTrajectory::StartPoint,
+                collision_link_names: vec![names.0, names.1],
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn check_partial_motion(
+        &self,
+        using_joints: &k::Chain<N>,
+        start: &[N],
+        goal: &[N],
+        max_link_displacement: N,
+    ) -> Result<()> {
+        check_continuous_motion(
+            &self.collision_detector,
+            &self.collision_check_robot,
+            using_joints,
+            start,
+            goal,
+            max_link_displacement,
+            |t, _| {
+                self.check_current_positions().map_err(|e| match e {
+                    Error::Collision {
+                        collision_link_names,
+                        ..
+                    } => Error::Collision {
+                        point: UnfeasibleTrajectory::IntermediatePoint(na::convert(t)),
+                        collision_link_names,
+                    },
+                    e => e,
+                })
+            },
+        )
+    }
+
     pub fn check_joint_positions(
         &self,
         current: &[N],
@@ -68,6 +117,18 @@ where
         positions: &[N],
         duration: std::time::Duration,
     ) -> Result<()> {
+        if let Some(max_link_displacement) = self.max_link_displacement {
+            for v in &[current, positions] {
+                using_joints.set_joint_positions_clamped(v);
+                self.check_current_positions()?;
+            }
+            return self.check_partial_motion(
+                using_joints,
+                current,
+                positions,
+                max_link_displacement,
+            );
+        }
         let duration_f64 = num_traits::NumCast::from::<f64>(duration.as_secs_f64()).unwrap();
         match interpolate(
             &[current.to_vec(), positions.to_vec()],
@@ -78,7 +139,6 @@ where
                 debug!("interpolated len={}", interpolated.len());
                 for v in interpolated {
                     using_joints.set_joint_positions_clamped(&v.position);
-                    self.collision_check_robot.update_transforms();
                     let mut self_checker = self
                         .collision_detector
                         .detect_self(&self.collision_check_robot, &self.collision_pairs);
@@ -114,15 +174,16 @@ where
     ) -> Result<()> {
         for v in trajectory {
             using_joints.set_joint_positions(&v.position)?;
-            if let Some(names) = self
-                .collision_detector
-                .detect_self(&self.collision_check_robot, &self.collision_pairs)
-                .next()
-            {
-                return Err(Error::Collision {
-                    point: UnfeasibleTrajectory::StartPoint,
-                    collision_link_names: vec![names.0, names.1],
-                });
+            self.check_current_positions()?;
+        }
+        if let Some(max_link_displacement) = self.max_link_displacement {
+            for points in trajectory.windows(2) {
+                self.check_partial_motion(
+                    using_joints,
+                    &points[0].position,
+                    &points[1].position,
+                    max_link_displacement,
+                )?;
             }
         }
         Ok(())
@@ -136,6 +197,19 @@ pub struct SelfCollisionCheckerConfig {
     pub prediction: f64,
     #[serde(default = "default_time_interpolate_rate")]
     pub time_interpolate_rate: f64,
+    /// Padding of the shapes of each link(joint) \[m\]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub link_padding: HashMap<String, f64>,
+    /// Replaces the meshes with the primitive shapes for faster checking
+    #[serde(default)]
+    pub mesh_approximation: MeshApproximation,
+    /// Checks the motions continuously with this resolution \[m\], instead of
+    /// the points interpolated by `time_interpolate_rate`.
+    ///
+    /// The motions are subdivided until no point on the links moves more than
+    /// this between the checked configurations.
+    pub max_link_displacement: Option<f64>,
 }
 
 fn default_prediction() -> f64 {
@@ -151,6 +225,9 @@ impl Default for SelfCollisionCheckerConfig {
         Self {
             prediction: default_prediction(),
             time_interpolate_rate: default_time_interpolate_rate(),
+            link_padding: HashMap::new(),
+            mesh_approximation: MeshApproximation::default(),
+            max_link_displacement: None,
         }
     }
 }
@@ -161,15 +238,20 @@ pub fn create_self_collision_checker<P: AsRef<Path>>(
     config: &SelfCollisionCheckerConfig,
     full_chain: Arc<k::Chain<f64>>,
 ) -> SelfCollisionChecker<f64> {
-    SelfCollisionChecker::new(
+    let mut collision_detector = CollisionDetector::from_urdf_robot(
+        &urdf_rs::utils::read_urdf_or_xacro(urdf_path).unwrap(),
+        config.prediction,
+    );
+    collision_detector.link_padding = config.link_padding.clone();
+    collision_detector.approximate_meshes(config.mesh_approximation);
+    let mut checker = SelfCollisionChecker::new(
         full_chain,
-        CollisionDetector::from_urdf_robot(
-            &urdf_rs::utils::read_urdf_or_xacro(urdf_path).unwrap(),
-            config.prediction,
-        ),
+        collision_detector,
         parse_colon_separated_pairs(self_collision_check_pairs).unwrap(),
         config.time_interpolate_rate,
-    )
+    );
+    checker.max_link_displacement = config.max_link_displacement;
+    checker
 }
 
 #[test]
@@ -214,3 +296,64 @@ fn test_create_self_collision_checker() {
         )
         .is_err());
 }
+
+#[test]
+fn test_continuous_self_collision_check() {
+    use ncollide3d::shape::{Cuboid, ShapeHandle};
+
+    use crate::collision::AttachedObject;
+
+    let urdf_robot = urdf_rs::read_file("sample.urdf").unwrap();
+    let robot = Arc::new(k::Chain::<f64>::from(&urdf_robot));
+    let mut self_collision_checker = create_self_collision_checker(
+        "sample.urdf",
+        &["root:l_shoulder_roll".into()],
+        &SelfCollisionCheckerConfig::default(),
+        robot,
+    );
+    // Thin wall which the elbow passes through when the shoulder yaw moves
+    // from -0.5 to 0.5.
+    let wall = AttachedObject::new(
+        "root",
+        ShapeHandle::new(Cuboid::new(na::Vector3::new(0.001, 0.03, 0.03))),
+        na::Isometry3::new(
+            na::Vector3::new(0.3, 0.4, 0.5),
+            na::Vector3::z() * 0.3f64.atan2(-0.2),
+        ),
+    );
+    self_collision_checker
+        .collision_detector
+        .attach_object("wall", wall);
+
+    let mut start = vec![0.0; 8];
+    start[0] = -0.5;
+    let mut goal = vec![0.0; 8];
+    goal[0] = 0.5;
+    let trajectory = vec![
+        TrajectoryPoint::new(start.clone(), vec![0.0; 8], vec![0.0; 8]),
+        TrajectoryPoint::new(goal.clone(), vec![0.0; 8], vec![0.0; 8]),
+    ];
+    // Only the points are checked.
+    assert!(self_collision_checker
+        .check_joint_trajectory(&trajectory)
+        .is_ok());
+
+    self_collision_checker.max_link_displacement = Some(0.01);
+    assert!(self_collision_checker
+        .check_joint_trajectory(&trajectory)
+        .is_err());
+    match self_collision_checker.check_joint_positions(
+        &start,
+        &goal,
+        std::time::Duration::new(1, 0),
+    ) {
+        Err(Error::Collision {
+            point: UnfeasibleTrajectory::IntermediatePoint(t),
+            ..
+        }) => assert!(t > 0.0 && t < 1.0),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert!(self_collision_checker
+        .check_joint_positions(&start, &start, std::time::Duration::new(1, 0))
+        .is_ok());
+}
@@ -0,0 +1,518 @@
+use std::collections::HashMap;
+
+use k::nalgebra as na;
+use na::RealField;
+
+use crate::errors::*;
+
+/// Mass properties of a rigid body in the frame of the joint which moves it
+#[derive(Debug, Clone, PartialEq)]
+pub struct RigidBodyInertia<N>
+where
+    N: RealField,
+{
+    pub mass: N,
+    pub center_of_mass: na::Vector3<N>,
+    /// Inertia tensor about the center of mass
+    pub inertia: na::Matrix3<N>,
+}
+
+impl<N> RigidBodyInertia<N>
+where
+    N: RealField,
+{
+    pub fn new(mass: N, center_of_mass: na::Vector3<N>, inertia: na::Matrix3<N>) -> Self {
+        Self {
+            mass,
+            center_of_mass,
+            inertia,
+        }
+    }
+
+    /// Body without the size, e.g. a small object held by the gripper
+    pub fn point_mass(mass: N, center_of_mass: na::Vector3<N>) -> Self {
+        Self::new(mass, center_of_mass, na::Matrix3::zeros())
+    }
+
+    /// Converts the inertial of the URDF link, which is in the frame of its origin
+    pub fn from_urdf_inertial(inertial: &urdf_rs::Inertial) -> Self {
+        let origin: na::Isometry3<N> = k::urdf::isometry_from(&inertial.origin);
+        let i = &inertial.inertia;
+        let inertia = na::Matrix3::new(
+            i.ixx, i.ixy, i.ixz, i.ixy, i.iyy, i.iyz, i.ixz, i.iyz, i.izz,
+        )
+        .map(na::convert::<f64, N>);
+        let rotation = origin.rotation.to_rotation_matrix();
+        Self::new(
+            na::convert(inertial.mass.value),
+            origin.translation.vector,
+            rotation.matrix() * inertia * rotation.matrix().transpose(),
+        )
+    }
+
+    /// Body which consists of `self` and `other` in the same frame
+    pub fn combine(&self, other: &Self) -> Self {
+        let mass = self.mass + other.mass;
+        if mass <= N::zero() {
+            return Self::point_mass(N::zero(), self.center_of_mass);
+        }
+        let center_of_mass =
+            (self.center_of_mass * self.mass + other.center_of_mass * other.mass) / mass;
+        let inertia = self.inertia
+            + parallel_axis_inertia(self.mass, &(self.center_of_mass - center_of_mass))
+            + other.inertia
+            + parallel_axis_inertia(other.mass, &(other.center_of_mass - center_of_mass));
+        Self::new(mass, center_of_mass, inertia)
+    }
+}
+
+/// Inertia of the point mass at `offset` from the center of mass
+fn parallel_axis_inertia<N>(mass: N, offset: &na::Vector3<N>) -> na::Matrix3<N>
+where
+    N: RealField,
+{
+    (na::Matrix3::identity() * offset.norm_squared() - offset * offset.transpose()) * mass
+}
+
+/// Rigid body dynamics of the robot from the inertials of the links of URDF
+///
+/// The bodies are identified by the names of the joints which move them, like
+/// [`CollisionDetector`](crate::CollisionDetector). The torques are calculated
+/// for the current joint positions of the given chain, which must be created
+/// from the same URDF. The chain can be a part of the robot, e.g. an arm, and
+/// then the links out of the chain are treated as the fixed base. The torques
+/// of the prismatic joints are the forces.
+#[derive(Debug, Clone)]
+pub struct RobotDynamics<N>
+where
+    N: RealField,
+{
+    inertias: HashMap<String, RigidBodyInertia<N>>,
+    payloads: HashMap<String, RigidBodyInertia<N>>,
+    gravity: na::Vector3<N>,
+}
+
+impl<N> RobotDynamics<N>
+where
+    N: RealField,
+{
+    pub fn from_urdf_robot(urdf_robot: &urdf_rs::Robot) -> Self {
+        let link_joint_map = k::urdf::link_to_joint_map(urdf_robot);
+        let inertias = urdf_robot
+            .links
+            .iter()
+            .filter_map(|l| {
+                link_joint_map.get(&l.name).map(|joint_name| {
+                    (
+                        joint_name.to_owned(),
+                        RigidBodyInertia::from_urdf_inertial(&l.inertial),
+                    )
+                })
+            })
+            .collect();
+        Self {
+            inertias,
+            payloads: HashMap::new(),
+            gravity: na::Vector3::new(N::zero(), N::zero(), na::convert(-9.80665)),
+        }
+    }
+
+    pub fn from_urdf_file<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        Ok(Self::from_urdf_robot(&urdf_rs::read_file(path)?))
+    }
+
+    /// Gravitational acceleration in the world frame, (0, 0, -9.80665) by default
+    pub fn gravity(&self) -> &na::Vector3<N> {
+        &self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: na::Vector3<N>) {
+        self.gravity = gravity;
+    }
+
+    /// Mass properties of the link moved by the joint, without the payload
+    pub fn inertia(&self, joint_name: &str) -> Option<&RigidBodyInertia<N>> {
+        self.inertias.get(joint_name)
+    }
+
+    /// Attaches the payload to the link moved by the joint, replacing the previous one
+    pub fn set_payload(&mut self, joint_name: &str, payload: RigidBodyInertia<N>) {
+        self.payloads.insert(joint_name.to_owned(), payload);
+    }
+
+    pub fn payload(&self, joint_name: &str) -> Option<&RigidBodyInertia<N>> {
+        self.payloads.get(joint_name)
+    }
+
+    pub fn remove_payload(&mut self, joint_name: &str) -> Option<RigidBodyInertia<N>> {
+        self.payloads.remove(joint_name)
+    }
+
+    /// Joint torques to realize `accelerations` at `velocities` by the recursive Newton-Euler algorithm
+    ///
+    /// The velocities, the accelerations and the torques are in the order of the movable joints of `chain`.
+    pub fn inverse_dynamics(
+        &self,
+        chain: &k::Chain<N>,
+        velocities: &[N],
+        accelerations: &[N],
+    ) -> Result<Vec<N>> {
+        let bodies = self.bodies(None);
+        recursive_newton_euler(chain, &bodies, velocities, accelerations, &self.gravity)
+    }
+
+    /// Joint torques to hold the current joint positions against the gravity
+    pub fn gravity_torques(&self, chain: &k::Chain<N>) -> Vec<N> {
+        let zeros = vec![N::zero(); chain.dof()];
+        self.inverse_dynamics(chain, &zeros, &zeros).unwrap()
+    }
+
+    /// Joint space mass matrix at the current joint positions
+    pub fn mass_matrix(&self, chain: &k::Chain<N>) -> na::DMatrix<N> {
+        let dof = chain.dof();
+        let bodies = self.bodies(None);
+        let zeros = vec![N::zero(); dof];
+        let mut matrix = na::DMatrix::zeros(dof, dof);
+        for i in 0..dof {
+            let mut accelerations = zeros.clone();
+            accelerations[i] = N::one();
+            let column = recursive_newton_euler(
+                chain,
+                &bodies,
+                &zeros,
+                &accelerations,
+                &na::Vector3::zeros(),
+            )
+            .unwrap();
+            matrix.set_column(i, &na::DVector::from_vec(column));
+        }
+        matrix
+    }
+
+    /// Estimates the mass of the payload of the link moved by `joint_name` from the torques measured at rest
+    ///
+    /// The center of mass of the payload is given in the frame of the joint.
+    /// The current payload of the link is ignored and the others are used.
+    pub fn estimate_payload_mass(
+        &self,
+        chain: &k::Chain<N>,
+        joint_name: &str,
+        center_of_mass: na::Vector3<N>,
+        measured_torques: &[N],
+    ) -> Result<N> {
+        if chain.find(joint_name).is_none() {
+            return Err(Error::NotFound(joint_name.to_owned()));
+        }
+        let dof = chain.dof();
+        if measured_torques.len() != dof {
+            return Err(Error::DofMismatch(measured_torques.len(), dof));
+        }
+        let zeros = vec![N::zero(); dof];
+        let without_payload = recursive_newton_euler(
+            chain,
+            &self.bodies(Some(joint_name)),
+            &zeros,
+            &zeros,
+            &self.gravity,
+        )?;
+        let mut unit_payload = HashMap::new();
+        unit_payload.insert(
+            joint_name.to_owned(),
+            RigidBodyInertia::point_mass(N::one(), center_of_mass),
+        );
+        let unit_torques =
+            recursive_newton_euler(chain, &unit_payload, &zeros, &zeros, &self.gravity)?;
+        // Least squares fit of the mass to the residual torques
+        let residual = na::DVector::from_vec(measured_torques.to_vec())
+            - na::DVector::from_vec(without_payload);
+        let unit_torques = na::DVector::from_vec(unit_torques);
+        let norm_squared = unit_torques.norm_squared();
+        if norm_squared <= na::convert(1e-12) {
+            return Err(Error::Other {
+                error: format!(
+                    "The payload of {} does not affect the torques at the current positions",
+                    joint_name
+                ),
+            });
+        }
+        Ok(unit_torques.dot(&residual) / norm_squared)
+    }
+
+    /// Links with their payloads, except the payload of `excluded_payload`
+    fn bodies(&self, excluded_payload: Option<&str>) -> HashMap<String, RigidBodyInertia<N>> {
+        let mut bodies = self.inertias.clone();
+        for (name, payload) in &self.payloads {
+            if Some(name.as_str()) == excluded_payload {
+                continue;
+            }
+            let body = match bodies.get(name) {
+                Some(inertia) => inertia.combine(payload),
+                None => payload.clone(),
+            };
+            bodies.insert(name.to_owned(), body);
+        }
+        bodies
+    }
+}
+
+/// Recursive Newton-Euler algorithm in the world frame
+///
+/// The gravity is handled as the upward acceleration of the root. The nodes
+/// whose parents are not in `chain` are also treated as the roots.
+fn recursive_newton_euler<N>(
+    chain: &k::Chain<N>,
+    bodies: &HashMap<String, RigidBodyInertia<N>>,
+    velocities: &[N],
+    accelerations: &[N],
+    gravity: &na::Vector3<N>,
+) -> Result<Vec<N>>
+where
+    N: RealField,
+{
+    let dof = chain.dof();
+    if velocities.len() != dof {
+        return Err(Error::DofMismatch(velocities.len(), dof));
+    }
+    if accelerations.len() != dof {
+        return Err(Error::DofMismatch(accelerations.len(), dof));
+    }
+    chain.update_transforms();
+    let nodes: Vec<_> = chain.iter().collect();
+    let indices: HashMap<String, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.joint().name.clone(), i))
+        .collect();
+    let num_nodes = nodes.len();
+    let mut parents = vec![None; num_nodes];
+    let mut positions = vec![na::Vector3::zeros(); num_nodes];
+    // Axes of the movable joints in the world frame and whether they are rotational
+    let mut axes: Vec<Option<(na::Vector3<N>, bool)>> = vec![None; num_nodes];
+    let mut angular_velocities = vec![na::Vector3::zeros(); num_nodes];
+    let mut angular_accelerations = vec![na::Vector3::zeros(); num_nodes];
+    let mut linear_accelerations = vec![na::Vector3::zeros(); num_nodes];
+    let mut forces = vec![na::Vector3::zeros(); num_nodes];
+    let mut moments = vec![na::Vector3::zeros(); num_nodes];
+
+    // Forward pass from the root: velocities and accelerations of the origins of the joints
+    let mut dof_index = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        let transform = node.world_transform().unwrap();
+        let position = transform.translation.vector;
+        let parent = node
+            .parent()
+            .and_then(|p| indices.get(&p.joint().name).copied());
+        let (w, w_dot, a, parent_position) = match parent {
+            Some(p) => (
+                angular_velocities[p],
+                angular_accelerations[p],
+                linear_accelerations[p],
+                positions[p],
+            ),
+            None => (
+                na::Vector3::zeros(),
+                na::Vector3::zeros(),
+                -gravity,
+                position,
+            ),
+        };
+        let r = position - parent_position;
+        let mut angular_velocity = w;
+        let mut angular_acceleration = w_dot;
+        let mut linear_acceleration = a + w_dot.cross(&r) + w.cross(&w.cross(&r));
+        match node.joint().joint_type {
+            k::JointType::Rotational { axis } => {
+                let axis = transform.rotation * axis.into_inner();
+                let (v, acc) = (velocities[dof_index], accelerations[dof_index]);
+                angular_velocity += axis * v;
+                angular_acceleration += axis * acc + w.cross(&(axis * v));
+                axes[i] = Some((axis, true));
+                dof_index += 1;
+            }
+            k::JointType::Linear { axis } => {
+                let axis = transform.rotation * axis.into_inner();
+                let (v, acc) = (velocities[dof_index], accelerations[dof_index]);
+                linear_acceleration +=
+                    axis * acc + w.cross(&(axis * v)) * na::convert::<f64, N>(2.0);
+                axes[i] = Some((axis, false));
+                dof_index += 1;
+            }
+            k::JointType::Fixed => {}
+        }
+        parents[i] = parent;
+        positions[i] = position;
+        angular_velocities[i] = angular_velocity;
+        angular_accelerations[i] = angular_acceleration;
+        linear_accelerations[i] = linear_acceleration;
+
+        if let Some(body) = bodies.get(&node.joint().name) {
+            let rotation = transform.rotation.to_rotation_matrix();
+            let c = rotation * body.center_of_mass;
+            let w = angular_velocity;
+            let w_dot = angular_acceleration;
+            let com_acceleration = linear_acceleration + w_dot.cross(&c) + w.cross(&w.cross(&c));
+            let inertia = rotation.matrix() * body.inertia * rotation.matrix().transpose();
+            let force = com_acceleration * body.mass;
+            forces[i] = force;
+            moments[i] = inertia * w_dot + w.cross(&(inertia * w)) + c.cross(&force);
+        }
+    }
+
+    // Backward pass from the leaves: forces and moments about the origins of the joints
+    let mut torques = vec![N::zero(); dof];
+    for i in (0..num_nodes).rev() {
+        if let Some((axis, is_rotational)) = axes[i] {
+            dof_index -= 1;
+            torques[dof_index] = if is_rotational {
+                axis.dot(&moments[i])
+            } else {
+                axis.dot(&forces[i])
+            };
+        }
+        if let Some(p) = parents[i] {
+            let force = forces[i];
+            let moment = moments[i] + (positions[i] - positions[p]).cross(&force);
+            forces[p] += force;
+            moments[p] += moment;
+        }
+    }
+    Ok(torques)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const G: f64 = 9.80665;
+
+    /// Two links of 1m along the x axis rotating around the y axis
+    fn two_link_urdf() -> urdf_rs::Robot {
+        let link = |name: &str, mass: f64| {
+            format!(
+                r#"<link name="{}">
+  <inertial>
+    <origin xyz="0.5 0 0"/>
+    <mass value="{}"/>
+    <inertia ixx="0.1" ixy="0" ixz="0" iyy="0.1" iyz="0" izz="0.1"/>
+  </inertial>
+</link>"#,
+                name, mass
+            )
+        };
+        let urdf = format!(
+            r#"<robot name="two_link">
+<link name="base"/>
+{}
+{}
+<joint name="joint1" type="revolute">
+  <parent link="base"/>
+  <child link="link1"/>
+  <axis xyz="0 1 0"/>
+  <limit lower="-3" upper="3" effort="100" velocity="1"/>
+</joint>
+<joint name="joint2" type="revolute">
+  <origin xyz="1 0 0"/>
+  <parent link="link1"/>
+  <child link="link2"/>
+  <axis xyz="0 1 0"/>
+  <limit lower="-3" upper="3" effort="100" velocity="1"/>
+</joint>
+</robot>"#,
+            link("link1", 2.0),
+            link("link2", 1.0)
+        );
+        urdf_rs::read_from_string(&urdf).unwrap()
+    }
+
+    fn assert_near(a: &[f64], b: &[f64]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).abs() < 1e-6, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_gravity_torques() {
+        let urdf_robot = two_link_urdf();
+        let chain = k::Chain::<f64>::from(&urdf_robot);
+        let dynamics = RobotDynamics::from_urdf_robot(&urdf_robot);
+        assert_eq!(dynamics.inertia("joint1").unwrap().mass, 2.0);
+
+        // The links are horizontal.
+        chain.set_joint_positions(&[0.0, 0.0]).unwrap();
+        assert_near(
+            &dynamics.gravity_torques(&chain),
+            &[-(2.0 * 0.5 + 1.0 * 1.5) * G, -0.5 * G],
+        );
+        // The links hang down.
+        chain
+            .set_joint_positions(&[std::f64::consts::FRAC_PI_2, 0.0])
+            .unwrap();
+        assert_near(&dynamics.gravity_torques(&chain), &[0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_mass_matrix_and_inverse_dynamics() {
+        let urdf_robot = two_link_urdf();
+        let chain = k::Chain::<f64>::from(&urdf_robot);
+        let dynamics = RobotDynamics::from_urdf_robot(&urdf_robot);
+        chain.set_joint_positions(&[0.0, 0.0]).unwrap();
+        let mass_matrix = dynamics.mass_matrix(&chain);
+        assert!((mass_matrix[(0, 0)] - (0.1 + 2.0 * 0.25 + 0.1 + 1.0 * 1.5 * 1.5)).abs() < 1e-6);
+        assert!((mass_matrix[(1, 1)] - (0.1 + 1.0 * 0.25)).abs() < 1e-6);
+        assert!((mass_matrix[(0, 1)] - mass_matrix[(1, 0)]).abs() < 1e-6);
+
+        let accelerations = [0.5, -1.0];
+        let expected = &mass_matrix * na::DVector::from_column_slice(&accelerations)
+            + na::DVector::from_vec(dynamics.gravity_torques(&chain));
+        assert_near(
+            &dynamics
+                .inverse_dynamics(&chain, &[0.0, 0.0], &accelerations)
+                .unwrap(),
+            expected.as_slice(),
+        );
+        assert!(dynamics
+            .inverse_dynamics(&chain, &[0.0], &accelerations)
+            .is_err());
+    }
+
+    #[test]
+    fn test_payload() {
+        let urdf_robot = two_link_urdf();
+        let chain = k::Chain::<f64>::from(&urdf_robot);
+        let mut dynamics = RobotDynamics::from_urdf_robot(&urdf_robot);
+        chain.set_joint_positions(&[0.3, -0.2]).unwrap();
+        let without_payload = dynamics.gravity_torques(&chain);
+
+        let center_of_mass = na::Vector3::new(1.0, 0.0, 0.0);
+        dynamics.set_payload("joint2", RigidBodyInertia::point_mass(0.5, center_of_mass));
+        let with_payload = dynamics.gravity_torques(&chain);
+        assert!(with_payload[1].abs() > without_payload[1].abs());
+        let mass = dynamics
+            .estimate_payload_mass(&chain, "joint2", center_of_mass, &with_payload)
+            .unwrap();
+        assert!((mass - 0.5).abs() < 1e-6, "{}", mass);
+
+        dynamics.remove_payload("joint2");
+        assert_near(&dynamics.gravity_torques(&chain), &without_payload);
+        assert!(dynamics
+            .estimate_payload_mass(&chain, "no_joint", center_of_mass, &with_payload)
+            .is_err());
+    }
+    #[test]
+    fn test_partial_chain() {
+        let urdf_robot = two_link_urdf();
+        let chain = k::Chain::<f64>::from(&urdf_robot);
+        let dynamics = RobotDynamics::from_urdf_robot(&urdf_robot);
+        chain.set_joint_positions(&[0.3, -0.2]).unwrap();
+        chain.update_transforms();
+        let torques = dynamics.gravity_torques(&chain);
+
+        // The parent of joint2 is out of the chain.
+        let link2 = k::Chain::from_nodes(vec![chain.find("joint2").unwrap().clone()]);
+        assert_near(&dynamics.gravity_torques(&link2), &torques[1..]);
+    }
+}
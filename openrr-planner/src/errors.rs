@@ -22,6 +22,9 @@ use thiserror::Error;
 pub enum UnfeasibleTrajectory {
     StartPoint,
     GoalPoint,
+    /// A point between the start and the goal, at the given interpolation
+    /// parameter in `(0, 1)`.
+    IntermediatePoint(f64),
 }
 
 /// Error for `openrr_planner`
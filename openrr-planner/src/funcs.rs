@@ -195,3 +195,32 @@ where
     let limits = robot.iter_joints().map(|j| j.limits).collect();
     robot.set_joint_positions(&generate_random_joint_positions_from_limits(&limits))
 }
+
+/// Spherical linear interpolation of the rotations
+///
+/// Unlike `UnitQuaternion::slerp`, this does not panic if the rotations are
+/// 180 degrees apart. The rotation axis is not unique in that case, and the
+/// axis of the relative rotation is used.
+///
+/// ```
+/// use k::nalgebra::{UnitQuaternion, Vector3};
+///
+/// let from = UnitQuaternion::identity();
+/// let to = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), std::f64::consts::PI);
+/// let middle = openrr_planner::slerp_rotation(&from, &to, 0.5);
+/// assert!((middle.angle() - std::f64::consts::FRAC_PI_2).abs() < 1e-6);
+/// ```
+pub fn slerp_rotation<T>(
+    from: &na::UnitQuaternion<T>,
+    to: &na::UnitQuaternion<T>,
+    t: T,
+) -> na::UnitQuaternion<T>
+where
+    T: RealField,
+{
+    from.try_slerp(to, t, T::default_epsilon())
+        .unwrap_or_else(|| match (from.inverse() * to).axis_angle() {
+            Some((axis, angle)) => from * na::UnitQuaternion::from_axis_angle(&axis, angle * t),
+            None => *from,
+        })
+}
@@ -0,0 +1,161 @@
+use k::{nalgebra as na, SubsetOf};
+use na::RealField;
+
+/// Jacobian and the measures of the singularity of a serial chain at its current joint positions
+#[derive(Debug, Clone, PartialEq)]
+pub struct KinematicAnalysis<N>
+where
+    N: RealField,
+{
+    /// Jacobian of the end, the translation in the first three rows and the rotation in the rest
+    ///
+    /// Only the rows and the columns used by the IK constraints are kept.
+    pub jacobian: na::DMatrix<N>,
+    /// Singular values of the Jacobian in descending order. The number of them is always
+    /// the number of the rows, so the chains with less joints than the rows have zeros.
+    pub singular_values: Vec<N>,
+}
+
+impl<N> KinematicAnalysis<N>
+where
+    N: RealField + SubsetOf<f64>,
+{
+    pub fn new(arm: &k::SerialChain<N>) -> Self {
+        Self::with_constraints(arm, &k::Constraints::default())
+    }
+
+    /// Analysis of the constrained motion, which uses the same rows and
+    /// columns of the Jacobian as the IK solver with `constraints`
+    pub fn with_constraints(arm: &k::SerialChain<N>, constraints: &k::Constraints) -> Self {
+        arm.update_transforms();
+        let full_jacobian = k::jacobian(arm);
+        let rows: Vec<usize> = [
+            constraints.position_x,
+            constraints.position_y,
+            constraints.position_z,
+            constraints.rotation_x,
+            constraints.rotation_y,
+            constraints.rotation_z,
+        ]
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &used)| if used { Some(i) } else { None })
+        .collect();
+        let columns: Vec<usize> = arm
+            .iter_joints()
+            .enumerate()
+            .filter_map(|(i, joint)| {
+                if constraints.ignored_joint_names.contains(&joint.name) {
+                    None
+                } else {
+                    Some(i)
+                }
+            })
+            .collect();
+        let jacobian = na::DMatrix::from_fn(rows.len(), columns.len(), |r, c| {
+            full_jacobian[(rows[r], columns[c])]
+        });
+
+        let mut singular_values: Vec<N> = if jacobian.is_empty() {
+            vec![]
+        } else {
+            na::SVD::new(jacobian.clone(), false, false)
+                .singular_values
+                .iter()
+                .copied()
+                .collect()
+        };
+        // NaN comes last.
+        singular_values.sort_by(|a, b| {
+            let (a, b): (f64, f64) = (a.to_superset(), b.to_superset());
+            b.partial_cmp(&a)
+                .unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+        });
+        singular_values.resize(rows.len(), N::zero());
+        Self {
+            jacobian,
+            singular_values,
+        }
+    }
+
+    /// Manipulability measure of Yoshikawa, sqrt(det(J J^T))
+    pub fn manipulability(&self) -> N {
+        self.singular_values
+            .iter()
+            .fold(N::one(), |product, &value| product * value)
+    }
+
+    /// Ratio of the largest singular value to the smallest one, infinity at the singularity
+    pub fn condition_number(&self) -> N {
+        let min = self.min_singular_value();
+        if min <= N::zero() {
+            na::convert(f64::INFINITY)
+        } else {
+            self.singular_values[0] / min
+        }
+    }
+
+    /// Smallest singular value of the Jacobian, which is the distance to the
+    /// nearest singular Jacobian and goes to zero at the singularity
+    pub fn min_singular_value(&self) -> N {
+        self.singular_values.last().copied().unwrap_or_else(N::zero)
+    }
+
+    /// Returns true if the smallest singular value is less than `threshold`
+    pub fn is_near_singularity(&self, threshold: N) -> bool {
+        self.min_singular_value() < threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kinematic_analysis() {
+        let robot = k::Chain::<f64>::from_urdf_file("sample.urdf").unwrap();
+        let arm = k::SerialChain::from_end(robot.find("l_tool_fixed").unwrap());
+        arm.set_joint_positions(&[0.2, 0.2, 0.0, -1.0, 0.0, 0.0])
+            .unwrap();
+        let analysis = KinematicAnalysis::new(&arm);
+        assert_eq!(analysis.jacobian.ncols(), 6);
+        assert_eq!(analysis.singular_values.len(), 6);
+        assert!(analysis.singular_values[0] >= analysis.min_singular_value());
+        let jacobian = &analysis.jacobian;
+        let determinant = (jacobian * jacobian.transpose()).determinant();
+        assert!((analysis.manipulability() - determinant.sqrt()).abs() < 1e-6);
+        assert!(analysis.condition_number() >= 1.0);
+
+        // The chain with 5 joints is always singular.
+        let arm = k::SerialChain::from_end(robot.find("l_wrist_yaw").unwrap());
+        let analysis = KinematicAnalysis::new(&arm);
+        assert_eq!(analysis.jacobian.ncols(), 5);
+        assert!(analysis.min_singular_value() < 1e-6);
+        assert!(analysis.manipulability() < 1e-6);
+        assert!(analysis.is_near_singularity(1e-3));
+
+        // Only the position is constrained.
+        let arm = k::SerialChain::from_end(robot.find("l_tool_fixed").unwrap());
+        let constraints = k::Constraints {
+            rotation_x: false,
+            rotation_y: false,
+            rotation_z: false,
+            ..Default::default()
+        };
+        let analysis = KinematicAnalysis::with_constraints(&arm, &constraints);
+        assert_eq!(analysis.jacobian.shape(), (3, 6));
+        assert_eq!(analysis.singular_values.len(), 3);
+        let full = KinematicAnalysis::new(&arm);
+        assert_eq!(analysis.jacobian.row(2), full.jacobian.row(2));
+        assert!(!analysis.is_near_singularity(1e-3));
+
+        let constraints = k::Constraints {
+            ignored_joint_names: vec!["l_wrist_pitch".to_owned()],
+            ..Default::default()
+        };
+        let analysis = KinematicAnalysis::with_constraints(&arm, &constraints);
+        assert_eq!(analysis.jacobian.shape(), (6, 5));
+        assert_eq!(analysis.singular_values.len(), 6);
+        assert!(analysis.manipulability() < 1e-6);
+    }
+}
@@ -20,6 +20,10 @@ limitations under the License.
 // https://github.com/rust-lang/rust-clippy/issues/4295
 #![allow(clippy::mutex_atomic)]
 
+mod capability_map;
+
+mod dynamics;
+
 mod errors;
 
 pub mod collision;
@@ -28,15 +32,25 @@ mod funcs;
 
 mod ik;
 
+mod kinematics;
+
 mod planner;
 
+pub mod srdf;
+
+mod whole_body_ik;
+
 // re-export k::IK modules
 pub use k::{InverseKinematicsSolver, JacobianIkSolver};
 
 pub use crate::{
+    capability_map::*,
     collision::{CollisionDetector, FromUrdf, SelfCollisionChecker, SelfCollisionCheckerConfig},
+    dynamics::*,
     errors::Error,
     funcs::*,
     ik::*,
+    kinematics::*,
     planner::*,
+    whole_body_ik::*,
 };
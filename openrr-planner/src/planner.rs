@@ -1,5 +1,16 @@
+mod cartesian_path_planner;
+mod constrained_planner;
+mod dual_arm_planner;
 mod ik_planner;
 mod joint_path_planner;
+mod prm;
+mod rrt_star;
+mod sampling;
 
+pub use cartesian_path_planner::*;
+pub use constrained_planner::*;
+pub use dual_arm_planner::*;
 pub use ik_planner::*;
 pub use joint_path_planner::*;
+pub use prm::*;
+pub use rrt_star::*;
@@ -0,0 +1,368 @@
+use k::nalgebra as na;
+use na::RealField;
+use ncollide3d::shape::Compound;
+
+use super::{joint_path_planner::JointPathPlanner, sampling::is_edge_free};
+use crate::{errors::*, funcs::slerp_rotation};
+
+/// Reason why the Cartesian path is not achieved
+#[derive(Debug, Clone, PartialEq)]
+pub enum CartesianPathFailure {
+    /// The start joint positions are colliding
+    StartCollision,
+    /// Inverse kinematics is not solved for the pose on the path
+    IkFailed,
+    /// The joint moves more than `max_joint_step` between the poses on the path,
+    /// which typically means that the configuration of the arm is flipped
+    ConfigurationFlip { joint_name: String },
+    /// The robot collides between the poses on the path
+    Collision,
+}
+
+/// Result of `CartesianPathPlanner`
+#[derive(Debug, Clone)]
+pub struct CartesianPath<N> {
+    /// Joint positions from the start to the last achieved pose
+    pub joint_positions: Vec<Vec<N>>,
+    /// Ratio of the achieved poses to all of the interpolated poses (0.0 - 1.0)
+    pub fraction: N,
+    /// Number of the waypoints reached
+    pub num_reached_waypoints: usize,
+    /// `None` if the whole path is achieved
+    pub failure: Option<CartesianPathFailure>,
+}
+
+impl<N> CartesianPath<N> {
+    /// Returns true if the whole path is achieved
+    pub fn is_completed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Planner to move the end of the arm along straight lines in Cartesian space
+///
+/// The poses between the waypoints are interpolated linearly (spherically for
+/// the rotation) and inverse kinematics is solved for each of them from the
+/// previous solution. The planning stops at the first pose which can not be
+/// achieved, and the path up to there is returned with the achieved fraction.
+pub struct CartesianPathPlanner<N, I>
+where
+    I: k::InverseKinematicsSolver<N>,
+    N: RealField + k::SubsetOf<f64>,
+{
+    /// Joint Path Planner to be used to check collisions
+    pub path_planner: JointPathPlanner<N>,
+    /// Inverse kinematics solver for the interpolated poses
+    pub ik_solver: I,
+    /// Constraints of the inverse kinematics
+    pub constraints: k::Constraints,
+    /// Max translation between the interpolated poses, which must be positive and finite
+    pub max_step_translation: N,
+    /// Max rotation angle between the interpolated poses, which must be positive and finite
+    pub max_step_rotation: N,
+    /// Max change of each joint between the interpolated poses
+    pub max_joint_step: N,
+}
+
+impl<N, I> CartesianPathPlanner<N, I>
+where
+    N: RealField + k::SubsetOf<f64> + num_traits::Float,
+    I: k::InverseKinematicsSolver<N>,
+{
+    /// Create instance from `JointPathPlanner` and `InverseKinematicsSolver`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let planner = openrr_planner::JointPathPlannerBuilder::from_urdf_file("sample.urdf")
+    ///     .unwrap()
+    ///     .finalize();
+    /// let solver = openrr_planner::JacobianIkSolver::default();
+    /// let _planner = openrr_planner::CartesianPathPlanner::new(planner, solver);
+    /// ```
+    pub fn new(path_planner: JointPathPlanner<N>, ik_solver: I) -> Self {
+        Self {
+            path_planner,
+            ik_solver,
+            constraints: k::Constraints::default(),
+            max_step_translation: na::convert(0.01),
+            max_step_rotation: na::convert(0.05),
+            max_joint_step: na::convert(0.3),
+        }
+    }
+
+    /// Returns an error if the max steps are not positive and finite
+    pub fn validate(&self) -> Result<()> {
+        for (name, value) in [
+            ("max_step_translation", self.max_step_translation),
+            ("max_step_rotation", self.max_step_rotation),
+        ] {
+            if !num_traits::Float::is_finite(value) || value <= N::zero() {
+                return Err(Error::Other {
+                    error: format!("{} must be positive and finite, but got {}", name, value),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Plan the path of `arm` through `waypoints` avoiding `objects` and self collision
+    ///
+    /// The path starts from the current joint positions of `arm`, which must be
+    /// a part of the robot of `path_planner`. The joint positions of `arm` are
+    /// restored after planning. Returns an error if the max steps are invalid.
+    pub fn plan(
+        &self,
+        arm: &k::SerialChain<N>,
+        waypoints: &[na::Isometry3<N>],
+        objects: &Compound<N>,
+    ) -> Result<CartesianPath<N>> {
+        self.plan_with(arm, waypoints, |angles: &[N]| {
+            self.path_planner.is_feasible(arm, angles, objects)
+        })
+    }
+
+    /// Plan the path of `arm` through `waypoints` avoiding self collision
+    pub fn plan_avoid_self_collision(
+        &self,
+        arm: &k::SerialChain<N>,
+        waypoints: &[na::Isometry3<N>],
+    ) -> Result<CartesianPath<N>> {
+        self.plan_with(arm, waypoints, |angles: &[N]| {
+            self.path_planner.is_feasible_with_self(arm, angles)
+        })
+    }
+
+    fn plan_with<F>(
+        &self,
+        arm: &k::SerialChain<N>,
+        waypoints: &[na::Isometry3<N>],
+        is_free: F,
+    ) -> Result<CartesianPath<N>>
+    where
+        F: Fn(&[N]) -> bool,
+    {
+        self.validate()?;
+        let initial_angles = arm.joint_positions();
+        let result = self.interpolate_and_solve(arm, waypoints, is_free, &initial_angles);
+        arm.set_joint_positions(&initial_angles)?;
+        result
+    }
+
+    fn interpolate_and_solve<F>(
+        &self,
+        arm: &k::SerialChain<N>,
+        waypoints: &[na::Isometry3<N>],
+        mut is_free: F,
+        initial_angles: &[N],
+    ) -> Result<CartesianPath<N>>
+    where
+        F: Fn(&[N]) -> bool,
+    {
+        arm.update_transforms();
+        let mut poses = vec![arm.end_transform()];
+        poses.extend_from_slice(waypoints);
+        let num_steps = poses
+            .windows(2)
+            .map(|pair| self.num_interpolation_steps(&pair[0], &pair[1]))
+            .collect::<Result<Vec<_>>>()?;
+        let total_steps = num_steps.iter().sum::<usize>();
+        let mut path = CartesianPath {
+            joint_positions: vec![initial_angles.to_vec()],
+            fraction: N::zero(),
+            num_reached_waypoints: 0,
+            failure: None,
+        };
+        if !is_free(initial_angles) {
+            path.failure = Some(CartesianPathFailure::StartCollision);
+            return Ok(path);
+        }
+        if total_steps == 0 {
+            path.fraction = N::one();
+            path.num_reached_waypoints = waypoints.len();
+            return Ok(path);
+        }
+
+        let mut achieved_steps = 0;
+        for (pair, &n) in poses.windows(2).zip(&num_steps) {
+            for i in 1..=n {
+                let t = na::convert::<f64, N>(i as f64 / n as f64);
+                let target = na::Isometry3::from_parts(
+                    na::Translation3::from(
+                        pair[0]
+                            .translation
+                            .vector
+                            .lerp(&pair[1].translation.vector, t),
+                    ),
+                    slerp_rotation(&pair[0].rotation, &pair[1].rotation, t),
+                );
+                if let Err(failure) = self.solve_next(arm, &target, &mut path, &mut is_free) {
+                    path.failure = Some(failure);
+                    path.fraction = na::convert::<f64, N>(achieved_steps as f64)
+                        / na::convert(total_steps as f64);
+                    return Ok(path);
+                }
+                achieved_steps += 1;
+            }
+            path.num_reached_waypoints += 1;
+        }
+        path.fraction = N::one();
+        Ok(path)
+    }
+
+    fn solve_next<F>(
+        &self,
+        arm: &k::SerialChain<N>,
+        target: &na::Isometry3<N>,
+        path: &mut CartesianPath<N>,
+        is_free: &mut F,
+    ) -> ::std::result::Result<(), CartesianPathFailure>
+    where
+        F: Fn(&[N]) -> bool,
+    {
+        let previous = path.joint_positions.last().unwrap().clone();
+        // `is_free` may have changed the joint positions.
+        arm.set_joint_positions_unchecked(&previous);
+        self.ik_solver
+            .solve_with_constraints(arm, target, &self.constraints)
+            .map_err(|_| CartesianPathFailure::IkFailed)?;
+        let angles = arm.joint_positions();
+        let flipped_joint = arm
+            .iter_joints()
+            .zip(previous.iter().zip(&angles))
+            .find_map(|(joint, (&a, &b))| {
+                if num_traits::Float::abs(b - a) > self.max_joint_step {
+                    Some(joint.name.clone())
+                } else {
+                    None
+                }
+            });
+        if let Some(joint_name) = flipped_joint {
+            return Err(CartesianPathFailure::ConfigurationFlip { joint_name });
+        }
+        if !is_edge_free(&previous, &angles, is_free, self.path_planner.step_length) {
+            return Err(CartesianPathFailure::Collision);
+        }
+        path.joint_positions.push(angles);
+        Ok(())
+    }
+
+    fn num_interpolation_steps(
+        &self,
+        from: &na::Isometry3<N>,
+        to: &na::Isometry3<N>,
+    ) -> Result<usize> {
+        let translation = (to.translation.vector - from.translation.vector).norm();
+        let rotation = from.rotation.angle_to(&to.rotation);
+        let steps = num_traits::Float::max(
+            translation / self.max_step_translation,
+            rotation / self.max_step_rotation,
+        );
+        num_traits::cast::<N, usize>(num_traits::Float::ceil(steps)).ok_or_else(|| {
+            Error::InterpolationError(format!(
+                "invalid number of the interpolated poses between {} and {}",
+                from, to
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JointPathPlannerBuilder;
+
+    fn planner() -> CartesianPathPlanner<f64, k::JacobianIkSolver<f64>> {
+        let path_planner = JointPathPlannerBuilder::from_urdf_file("sample.urdf")
+            .unwrap()
+            .finalize();
+        CartesianPathPlanner::new(path_planner, k::JacobianIkSolver::default())
+    }
+
+    fn arm(planner: &CartesianPathPlanner<f64, k::JacobianIkSolver<f64>>) -> k::SerialChain<f64> {
+        let end = planner
+            .path_planner
+            .robot_collision_detector
+            .robot
+            .find("l_tool_fixed")
+            .unwrap();
+        let arm = k::SerialChain::from_end(end);
+        arm.set_joint_positions(&[0.2, 0.2, 0.0, -1.0, 0.0, 0.0])
+            .unwrap();
+        arm.update_transforms();
+        arm
+    }
+
+    #[test]
+    fn plan_cartesian_path() {
+        let planner = planner();
+        let arm = arm(&planner);
+        let initial_angles = arm.joint_positions();
+        let start = arm.end_transform();
+        let mut waypoint1 = start;
+        waypoint1.translation.vector.z += 0.05;
+        let mut waypoint2 = waypoint1;
+        waypoint2.translation.vector.y += 0.05;
+
+        let path = planner
+            .plan_avoid_self_collision(&arm, &[waypoint1, waypoint2])
+            .unwrap();
+        assert!(path.is_completed());
+        assert_eq!(path.num_reached_waypoints, 2);
+        assert!((path.fraction - 1.0).abs() < 1e-6);
+        // 5cm + 5cm with 1cm steps
+        assert!(path.joint_positions.len() >= 11);
+        assert_eq!(arm.joint_positions(), initial_angles);
+
+        arm.set_joint_positions(path.joint_positions.last().unwrap())
+            .unwrap();
+        arm.update_transforms();
+        let end = arm.end_transform();
+        assert!((end.translation.vector - waypoint2.translation.vector).norm() < 0.01);
+    }
+
+    #[test]
+    fn plan_unreachable_cartesian_path() {
+        let planner = planner();
+        let arm = arm(&planner);
+        let mut waypoint = arm.end_transform();
+        waypoint.translation.vector.x += 10.0;
+
+        let path = planner
+            .plan_avoid_self_collision(&arm, &[waypoint])
+            .unwrap();
+        assert_eq!(path.failure, Some(CartesianPathFailure::IkFailed));
+        assert_eq!(path.num_reached_waypoints, 0);
+        assert!(path.fraction < 0.5);
+        assert!(path.joint_positions.len() > 1);
+    }
+
+    #[test]
+    fn plan_with_invalid_max_steps() {
+        let mut planner = planner();
+        let arm = arm(&planner);
+        let mut waypoint = arm.end_transform();
+        waypoint.translation.vector.z += 0.05;
+        assert!(planner.validate().is_ok());
+
+        for invalid in [0.0, -0.01, f64::NAN, f64::INFINITY] {
+            planner.max_step_translation = invalid;
+            assert!(planner.validate().is_err());
+            assert!(planner
+                .plan_avoid_self_collision(&arm, &[waypoint])
+                .is_err());
+        }
+        planner.max_step_translation = 0.01;
+        planner.max_step_rotation = 0.0;
+        assert!(planner
+            .plan_avoid_self_collision(&arm, &[waypoint])
+            .is_err());
+
+        // A non-finite waypoint is not interpolated.
+        planner.max_step_rotation = 0.05;
+        waypoint.translation.vector.z = f64::INFINITY;
+        assert!(planner
+            .plan_avoid_self_collision(&arm, &[waypoint])
+            .is_err());
+    }
+}
@@ -0,0 +1,375 @@
+use k::nalgebra as na;
+use na::RealField;
+use num_traits::Float;
+
+use super::sampling::*;
+
+/// Limit of the steps of one extension of the tree
+const MAX_EXTEND_STEPS: usize = 1000;
+
+/// Number of the checked points on an edge of the length `extend_length`
+const EDGE_CHECK_DIVISIONS: usize = 4;
+
+/// The end link must keep the orientation within the tolerances
+#[derive(Debug, Clone)]
+pub struct OrientationConstraint<N>
+where
+    N: RealField,
+{
+    /// Reference orientation
+    pub orientation: na::UnitQuaternion<N>,
+    /// Tolerances of roll, pitch and yaw from the reference orientation \[rad\]
+    pub tolerances: na::Vector3<N>,
+}
+
+/// The end link must stay inside the box
+#[derive(Debug, Clone)]
+pub struct PositionConstraint<N>
+where
+    N: RealField,
+{
+    /// Minimum corner of the box
+    pub min: na::Vector3<N>,
+    /// Maximum corner of the box
+    pub max: na::Vector3<N>,
+}
+
+/// The axis of the end link must be aligned with the target axis
+///
+/// Unlike [`OrientationConstraint`], the rotation around the target axis is free.
+#[derive(Debug, Clone)]
+pub struct AxisConstraint<N>
+where
+    N: RealField,
+{
+    /// Axis in the end link frame, e.g. the z axis to keep a tray level
+    pub end_axis: na::Vector3<N>,
+    /// Axis in the world frame
+    pub target_axis: na::Vector3<N>,
+    /// Allowed angle between the axes \[rad\]
+    pub tolerance: N,
+}
+
+/// Constraints on the pose of the end link during the motion
+#[derive(Debug, Clone)]
+pub struct PoseConstraints<N>
+where
+    N: RealField,
+{
+    pub orientation: Option<OrientationConstraint<N>>,
+    pub position: Option<PositionConstraint<N>>,
+    pub axis: Option<AxisConstraint<N>>,
+}
+
+impl<N> Default for PoseConstraints<N>
+where
+    N: RealField,
+{
+    fn default() -> Self {
+        Self {
+            orientation: None,
+            position: None,
+            axis: None,
+        }
+    }
+}
+
+impl<N> PoseConstraints<N>
+where
+    N: RealField,
+{
+    /// Returns true if `pose` satisfies the constraints
+    ///
+    /// `margin` is added to the tolerances, both of the length and the angle.
+    pub fn is_satisfied(&self, pose: &na::Isometry3<N>, margin: N) -> bool {
+        if let Some(c) = &self.orientation {
+            let (roll, pitch, yaw) = (c.orientation.inverse() * pose.rotation).euler_angles();
+            if roll.abs() > c.tolerances[0] + margin
+                || pitch.abs() > c.tolerances[1] + margin
+                || yaw.abs() > c.tolerances[2] + margin
+            {
+                return false;
+            }
+        }
+        if let Some(c) = &self.axis {
+            if (pose.rotation * c.end_axis).angle(&c.target_axis) > c.tolerance + margin {
+                return false;
+            }
+        }
+        if let Some(c) = &self.position {
+            let p = &pose.translation.vector;
+            if (0..3).any(|i| p[i] < c.min[i] - margin || p[i] > c.max[i] + margin) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Returns the pose which satisfies the constraints near `pose`
+    ///
+    /// The orientation and the axis constraints may conflict. Check the
+    /// result with [`PoseConstraints::is_satisfied`].
+    pub fn project(&self, pose: &na::Isometry3<N>) -> na::Isometry3<N> {
+        let mut rotation = pose.rotation;
+        if let Some(c) = &self.orientation {
+            let (roll, pitch, yaw) = (c.orientation.inverse() * rotation).euler_angles();
+            let clamp = |v: N, tolerance: N| v.max(-tolerance).min(tolerance);
+            rotation = c.orientation
+                * na::UnitQuaternion::from_euler_angles(
+                    clamp(roll, c.tolerances[0]),
+                    clamp(pitch, c.tolerances[1]),
+                    clamp(yaw, c.tolerances[2]),
+                );
+        }
+        if let Some(c) = &self.axis {
+            let axis = rotation * c.end_axis;
+            let angle = axis.angle(&c.target_axis);
+            if angle > c.tolerance {
+                if let Some(diff) = na::UnitQuaternion::rotation_between(&axis, &c.target_axis) {
+                    rotation = diff.powf((angle - c.tolerance) / angle) * rotation;
+                }
+            }
+        }
+        let mut translation = pose.translation;
+        if let Some(c) = &self.position {
+            for i in 0..3 {
+                translation.vector[i] = translation.vector[i].max(c.min[i]).min(c.max[i]);
+            }
+        }
+        na::Isometry3::from_parts(translation, rotation)
+    }
+}
+
+struct Tree<N> {
+    points: Vec<Vec<N>>,
+    parents: Vec<Option<usize>>,
+}
+
+impl<N> Tree<N>
+where
+    N: Float,
+{
+    fn new(root: &[N]) -> Self {
+        Self {
+            points: vec![root.to_vec()],
+            parents: vec![None],
+        }
+    }
+
+    fn nearest(&self, point: &[N]) -> usize {
+        sorted_by_distance(&self.points, point)[0].0
+    }
+
+    fn add(&mut self, point: Vec<N>, parent: usize) -> usize {
+        self.points.push(point);
+        self.parents.push(Some(parent));
+        self.points.len() - 1
+    }
+
+    /// Points from the root to `index`
+    fn path_to(&self, index: usize) -> Vec<Vec<N>> {
+        let mut path = vec![self.points[index].clone()];
+        let mut current = index;
+        while let Some(parent) = self.parents[current] {
+            path.push(self.points[parent].clone());
+            current = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Extend the tree toward `target` step by step on the constraint manifold
+    ///
+    /// Returns the index of the node nearest to `target`.
+    fn extend<FF, FP>(
+        &mut self,
+        target: &[N],
+        is_free: &mut FF,
+        project: &mut FP,
+        extend_length: N,
+    ) -> usize
+    where
+        FF: FnMut(&[N]) -> bool,
+        FP: FnMut(&[N]) -> Option<Vec<N>>,
+    {
+        let mut current = self.nearest(target);
+        for _ in 0..MAX_EXTEND_STEPS {
+            let dist = distance(&self.points[current], target);
+            if dist <= N::epsilon() {
+                break;
+            }
+            let next = match project(&steer(&self.points[current], target, extend_length)) {
+                Some(next) => next,
+                None => break,
+            };
+            // Stop if the projection does not make progress or jumps.
+            if distance(&next, target) >= dist
+                || distance(&next, &self.points[current]) > extend_length + extend_length
+                || !is_edge_free(
+                    &self.points[current],
+                    &next,
+                    is_free,
+                    extend_length / N::from(EDGE_CHECK_DIVISIONS).unwrap(),
+                )
+            {
+                break;
+            }
+            current = self.add(next, current);
+        }
+        current
+    }
+}
+
+/// Search the path whose points all satisfy a constraint, like CBiRRT
+///
+/// Both of the random samples and the points between the nodes are projected
+/// onto the constraint manifold by `project`, which returns `None` if the
+/// projection fails. `start` and `goal` must satisfy the constraint.
+///
+/// The distance between the points of the path is `extend_length` at most,
+/// and the path is not smoothed because shortcuts may violate the constraint.
+/// The edges between the points are also checked by `is_free` at the
+/// intervals of `extend_length / 4`, though they may slightly violate the
+/// constraint.
+pub fn constrained_rrt_connect<FF, FP, FR, N>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    mut project: FP,
+    random_sample: FR,
+    extend_length: N,
+    num_max_try: usize,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FP: FnMut(&[N]) -> Option<Vec<N>>,
+    FR: Fn() -> Vec<N>,
+    N: Float,
+{
+    assert_eq!(start.len(), goal.len());
+    let mut start_tree = Tree::new(start);
+    let mut goal_tree = Tree::new(goal);
+    let mut extends_start_tree = true;
+    for _ in 0..num_max_try {
+        let sample = match project(&random_sample()) {
+            Some(sample) => sample,
+            None => continue,
+        };
+        let (tree_a, tree_b) = if extends_start_tree {
+            (&mut start_tree, &mut goal_tree)
+        } else {
+            (&mut goal_tree, &mut start_tree)
+        };
+        let index_a = tree_a.extend(&sample, &mut is_free, &mut project, extend_length);
+        let target = tree_a.points[index_a].clone();
+        let index_b = tree_b.extend(&target, &mut is_free, &mut project, extend_length);
+        if distance(&tree_b.points[index_b], &target) <= N::epsilon() {
+            let (start_index, goal_index) = if extends_start_tree {
+                (index_a, index_b)
+            } else {
+                (index_b, index_a)
+            };
+            let mut path = start_tree.path_to(start_index);
+            let mut rest = goal_tree.path_to(goal_index);
+            rest.reverse();
+            // Both of the trees have the connecting point.
+            path.extend(rest.into_iter().skip(1));
+            return Ok(path);
+        }
+        extends_start_tree = !extends_start_tree;
+    }
+    Err(format!(
+        "failed to find the constrained path in {} tries",
+        num_max_try
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::{Distribution, Uniform};
+
+    use super::*;
+
+    fn random_sample() -> Vec<f64> {
+        let between = Uniform::new(-2.0, 2.0);
+        let mut rng = rand::thread_rng();
+        vec![between.sample(&mut rng), between.sample(&mut rng)]
+    }
+
+    #[test]
+    fn test_constrained_rrt_connect() {
+        // The points must be on the unit circle and the left side is blocked.
+        let project = |p: &[f64]| {
+            let norm = (p[0] * p[0] + p[1] * p[1]).sqrt();
+            if norm < 1e-3 {
+                None
+            } else if (norm - 1.0).abs() < 1e-9 {
+                Some(p.to_vec())
+            } else {
+                Some(vec![p[0] / norm, p[1] / norm])
+            }
+        };
+        let is_free = |p: &[f64]| !(p[0] < -0.5 && p[1].abs() < 0.5);
+        let start = [0.0, 1.0];
+        let goal = [0.0, -1.0];
+        let path =
+            constrained_rrt_connect(&start, &goal, is_free, project, random_sample, 0.1, 1000)
+                .unwrap();
+        assert_eq!(path[0], start);
+        assert_eq!(path[path.len() - 1], goal);
+        for p in &path {
+            assert!(((p[0] * p[0] + p[1] * p[1]).sqrt() - 1.0).abs() < 1e-6);
+            assert!(is_free(p));
+        }
+        for w in path.windows(2) {
+            assert!(distance(&w[0], &w[1]) <= 0.2);
+        }
+        // The path goes around through the right side.
+        assert!(path.iter().any(|p| p[0] > 0.9));
+    }
+
+    #[test]
+    fn test_pose_constraints() {
+        let constraints = PoseConstraints {
+            orientation: None,
+            position: Some(PositionConstraint {
+                min: na::Vector3::new(-1.0, -1.0, 0.5),
+                max: na::Vector3::new(1.0, 1.0, 1.0),
+            }),
+            axis: Some(AxisConstraint {
+                end_axis: na::Vector3::z(),
+                target_axis: na::Vector3::z(),
+                tolerance: 0.1,
+            }),
+        };
+        let pose = na::Isometry3::new(na::Vector3::new(0.0, 0.0, 0.7), na::Vector3::x() * 0.05);
+        assert!(constraints.is_satisfied(&pose, 0.0));
+        // Free around the z axis
+        let pose = na::Isometry3::new(na::Vector3::new(0.0, 0.0, 0.7), na::Vector3::z() * 2.0);
+        assert!(constraints.is_satisfied(&pose, 0.0));
+
+        let pose = na::Isometry3::new(na::Vector3::new(0.0, 2.0, 0.0), na::Vector3::x() * 0.5);
+        assert!(!constraints.is_satisfied(&pose, 0.0));
+        let projected = constraints.project(&pose);
+        assert!(constraints.is_satisfied(&projected, 1e-6));
+        assert!((projected.translation.vector - na::Vector3::new(0.0, 1.0, 0.5)).norm() < 1e-6);
+
+        let constraints = PoseConstraints {
+            orientation: Some(OrientationConstraint {
+                orientation: na::UnitQuaternion::identity(),
+                tolerances: na::Vector3::new(0.1, 0.1, 3.2),
+            }),
+            ..Default::default()
+        };
+        let pose = na::Isometry3::from_parts(
+            na::Translation3::new(0.0, 0.0, 0.0),
+            na::UnitQuaternion::from_euler_angles(0.3, 0.0, 1.0),
+        );
+        assert!(!constraints.is_satisfied(&pose, 0.0));
+        let projected = constraints.project(&pose);
+        assert!(constraints.is_satisfied(&projected, 1e-6));
+        let (roll, _, yaw) = projected.rotation.euler_angles();
+        assert!((roll - 0.1).abs() < 1e-6);
+        assert!((yaw - 1.0).abs() < 1e-6);
+    }
+}
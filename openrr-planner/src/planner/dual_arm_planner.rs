@@ -0,0 +1,409 @@
+use k::nalgebra as na;
+use na::RealField;
+use ncollide3d::shape::Compound;
+
+use super::{constrained_planner::constrained_rrt_connect, joint_path_planner::JointPathPlanner};
+use crate::{errors::*, funcs::generate_random_joint_positions_from_limits};
+
+/// Path planner of two arms, which plans the motions of both of them at once
+///
+/// The collisions between the arms are checked in addition to the self
+/// collision pairs of `path_planner`. The joints of the arms must not be
+/// shared, e.g. the torso joint must be fixed while planning.
+///
+/// If `objects` of the methods are `None`, only the self collisions are checked.
+pub struct DualArmPlanner<T, I>
+where
+    I: k::InverseKinematicsSolver<T>,
+    T: RealField + k::SubsetOf<f64>,
+{
+    /// Joint path planner whose robot has both of the arms
+    pub path_planner: JointPathPlanner<T>,
+    /// Inverse kinematics solver of each arm
+    pub ik_solver: I,
+    pub left_arm: k::SerialChain<T>,
+    pub right_arm: k::SerialChain<T>,
+    /// Joints of both of the arms. The joints of `left_arm` come first.
+    pub arms: k::Chain<T>,
+}
+
+impl<T, I> DualArmPlanner<T, I>
+where
+    T: RealField + k::SubsetOf<f64> + num_traits::Float,
+    I: k::InverseKinematicsSolver<T>,
+{
+    /// Create the planner of the arms which end at `left_end_name` and `right_end_name`
+    pub fn new(
+        mut path_planner: JointPathPlanner<T>,
+        ik_solver: I,
+        left_end_name: &str,
+        right_end_name: &str,
+    ) -> Result<Self> {
+        let robot = &path_planner.robot_collision_detector.robot;
+        let find = |name: &str| {
+            robot
+                .find(name)
+                .map(k::SerialChain::from_end)
+                .ok_or_else(|| Error::NotFound(name.to_owned()))
+        };
+        let left_arm = find(left_end_name)?;
+        let right_arm = find(right_end_name)?;
+
+        let left_names: Vec<String> = left_arm.iter().map(|n| n.joint().name.clone()).collect();
+        let right_names: Vec<String> = right_arm.iter().map(|n| n.joint().name.clone()).collect();
+        let mut nodes: Vec<k::Node<T>> = left_arm.iter().map(|n| (*n).clone()).collect();
+        for node in right_arm.iter() {
+            let joint = node.joint();
+            if !left_names.contains(&joint.name) {
+                nodes.push((*node).clone());
+            } else if joint.is_movable() {
+                return Err(Error::Other {
+                    error: format!("the arms share the movable joint {}", joint.name),
+                });
+            }
+        }
+
+        // Pairs of the links of the different arms which have collision models
+        let collision_link_names = path_planner
+            .robot_collision_detector
+            .collision_detector
+            .collision_link_names();
+        let is_arm_link = |name: &String, other_arm_names: &[String]| {
+            !other_arm_names.contains(name) && collision_link_names.contains(name)
+        };
+        let pairs = &mut path_planner.robot_collision_detector.self_collision_pairs;
+        for left in left_names.iter().filter(|n| is_arm_link(*n, &right_names)) {
+            for right in right_names.iter().filter(|n| is_arm_link(*n, &left_names)) {
+                if !pairs
+                    .iter()
+                    .any(|(a, b)| (a == left && b == right) || (a == right && b == left))
+                {
+                    pairs.push((left.to_owned(), right.to_owned()));
+                }
+            }
+        }
+
+        Ok(Self {
+            path_planner,
+            ik_solver,
+            left_arm,
+            right_arm,
+            arms: k::Chain::from_nodes(nodes),
+        })
+    }
+
+    /// Names of the joints of both of the arms
+    pub fn joint_names(&self) -> Vec<String> {
+        self.arms.iter_joints().map(|j| j.name.clone()).collect()
+    }
+
+    /// Split the positions of `arms` into the ones of the left and the right arm
+    pub fn split_positions(&self, positions: &[T]) -> (Vec<T>, Vec<T>) {
+        let (left, right) = positions.split_at(self.left_arm.dof());
+        (left.to_vec(), right.to_vec())
+    }
+
+    /// Pose of the end of the right arm in the frame of the end of the left arm
+    pub fn relative_end_transform(&self) -> na::Isometry3<T> {
+        self.left_arm.end_transform().inverse() * self.right_arm.end_transform()
+    }
+
+    /// Check if the positions of both of the arms are OK
+    pub fn is_feasible(&self, joint_positions: &[T], objects: Option<&Compound<T>>) -> bool {
+        match objects {
+            Some(objects) => self
+                .path_planner
+                .is_feasible(&self.arms, joint_positions, objects),
+            None => self
+                .path_planner
+                .is_feasible_with_self(&self.arms, joint_positions),
+        }
+    }
+
+    /// Plan the path of both of the arms from the current positions
+    pub fn plan(&self, goal_angles: &[T], objects: Option<&Compound<T>>) -> Result<Vec<Vec<T>>> {
+        let initial = self.arms.joint_positions();
+        match objects {
+            Some(objects) => self
+                .path_planner
+                .plan(&self.arms, &initial, goal_angles, objects),
+            None => self
+                .path_planner
+                .plan_avoid_self_collision(&self.arms, &initial, goal_angles),
+        }
+    }
+
+    /// Solve IK of each arm and plan the path to the solutions
+    pub fn plan_with_ik(
+        &self,
+        left_target_pose: &na::Isometry3<T>,
+        right_target_pose: &na::Isometry3<T>,
+        objects: Option<&Compound<T>>,
+    ) -> Result<Vec<Vec<T>>> {
+        let initial = self.arms.joint_positions();
+        let goal = self
+            .solve_ik(left_target_pose, right_target_pose)
+            .map(|_| self.arms.joint_positions());
+        self.arms.set_joint_positions(&initial)?;
+        self.plan(&goal?, objects)
+    }
+
+    /// Plan the path to `left_target_pose` while both of the arms hold one object
+    ///
+    /// The pose of the end of the right arm relative to the end of the left
+    /// arm is kept as it is now, i.e. the arms form a closed chain. The points
+    /// of the path are projected onto the constraint by solving IK of the
+    /// right arm, and the interval of them is `path_planner.step_length` at most.
+    pub fn plan_holding_with_ik(
+        &self,
+        left_target_pose: &na::Isometry3<T>,
+        objects: Option<&Compound<T>>,
+    ) -> Result<Vec<Vec<T>>> {
+        let relative = self.relative_end_transform();
+        let initial = self.arms.joint_positions();
+        let goal = self
+            .solve_ik(left_target_pose, &(left_target_pose * relative))
+            .map(|_| self.arms.joint_positions());
+        self.arms.set_joint_positions(&initial)?;
+        let goal = goal?;
+
+        let limits = self.arms.iter_joints().map(|j| j.limits).collect();
+        let project = |angles: &[T]| {
+            self.arms.set_joint_positions(angles).ok()?;
+            let right_target_pose = self.left_arm.end_transform() * relative;
+            self.ik_solver
+                .solve(&self.right_arm, &right_target_pose)
+                .ok()?;
+            Some(self.arms.joint_positions())
+        };
+        let result = constrained_rrt_connect(
+            &initial,
+            &goal,
+            |angles: &[T]| self.is_feasible(angles, objects),
+            project,
+            || generate_random_joint_positions_from_limits(&limits),
+            self.path_planner.step_length,
+            self.path_planner.max_try,
+        );
+        self.arms.set_joint_positions(&initial)?;
+        result.map_err(Error::PathPlanFail)
+    }
+
+    fn solve_ik(
+        &self,
+        left_target_pose: &na::Isometry3<T>,
+        right_target_pose: &na::Isometry3<T>,
+    ) -> Result<()> {
+        self.ik_solver.solve(&self.left_arm, left_target_pose)?;
+        self.ik_solver.solve(&self.right_arm, right_target_pose)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use na::{Isometry3, Vector3};
+    use ncollide3d::shape::{Ball, ShapeHandle};
+
+    use super::*;
+    use crate::JointPathPlannerBuilder;
+
+    /// Arm like the one of sample.urdf
+    fn arm_urdf(prefix: &str, y: f64) -> String {
+        let link = |name: &str, length: f64| {
+            format!(
+                r#"<link name="{p}_{n}_link">
+  <collision>
+    <origin xyz="{h} 0 0"/>
+    <geometry><box size="{l} 0.06 0.06"/></geometry>
+  </collision>
+</link>
+"#,
+                p = prefix,
+                n = name,
+                h = length / 2.0,
+                l = length.max(0.06)
+            )
+        };
+        let joint = |name: &str, parent: &str, xyz: &str, axis: &str| {
+            format!(
+                r#"<joint name="{p}_{n}" type="revolute">
+  <origin xyz="{xyz}"/>
+  <parent link="{parent}"/>
+  <child link="{p}_{n}_link"/>
+  <axis xyz="{axis}"/>
+  <limit lower="-2" upper="2" effort="1" velocity="1"/>
+</joint>
+"#,
+                p = prefix,
+                n = name,
+                xyz = xyz,
+                parent = parent,
+                axis = axis
+            )
+        };
+        let names = [
+            ("shoulder_yaw", "0 0 1", 0.0),
+            ("shoulder_pitch", "0 1 0", 0.0),
+            ("shoulder_roll", "1 0 0", 0.2),
+            ("elbow_pitch", "0 1 0", 0.2),
+            ("wrist_yaw", "0 0 1", 0.0),
+            ("wrist_pitch", "0 1 0", 0.1),
+        ];
+        let mut urdf = String::new();
+        let mut parent = "base".to_owned();
+        let mut offset = format!("0 {} 0.5", y);
+        for (name, axis, length) in &names {
+            urdf += &link(name, *length);
+            urdf += &joint(name, &parent, &offset, axis);
+            parent = format!("{}_{}_link", prefix, name);
+            offset = format!("{} 0 0", length);
+        }
+        urdf += &format!(
+            r#"<link name="{p}_end_link"/>
+<joint name="{p}_end" type="fixed">
+  <origin xyz="{o}"/>
+  <parent link="{parent}"/>
+  <child link="{p}_end_link"/>
+</joint>
+"#,
+            p = prefix,
+            o = offset,
+            parent = parent
+        );
+        urdf
+    }
+
+    fn create_planner() -> DualArmPlanner<f64, k::JacobianIkSolver<f64>> {
+        let urdf = format!(
+            r#"<robot name="dual_arm">
+<link name="base"/>
+{}{}</robot>"#,
+            arm_urdf("l", 0.2),
+            arm_urdf("r", -0.2)
+        );
+        let path_planner =
+            JointPathPlannerBuilder::from_urdf_robot(urdf_rs::read_from_string(&urdf).unwrap())
+                .finalize();
+        DualArmPlanner::new(
+            path_planner,
+            k::JacobianIkSolver::new(0.001, 0.005, 0.5, 100),
+            "l_end",
+            "r_end",
+        )
+        .unwrap()
+    }
+
+    fn far_objects() -> Compound<f64> {
+        Compound::new(vec![(
+            Isometry3::translation(10.0, 0.0, 0.0),
+            ShapeHandle::new(Ball::new(0.1)),
+        )])
+    }
+
+    #[test]
+    fn test_dual_arm_plan() {
+        let planner = create_planner();
+        assert_eq!(planner.arms.dof(), 12);
+        assert_eq!(planner.joint_names()[6], "r_shoulder_yaw");
+        assert!(planner
+            .path_planner
+            .robot_collision_detector
+            .self_collision_pairs
+            .contains(&("l_elbow_pitch".to_owned(), "r_elbow_pitch".to_owned())));
+        // The ends have no collision models.
+        assert!(planner
+            .path_planner
+            .robot_collision_detector
+            .self_collision_pairs
+            .iter()
+            .all(|(a, b)| !a.ends_with("_end") && !b.ends_with("_end")));
+
+        // The left arm hits the right arm.
+        let mut positions = vec![0.0; 12];
+        positions[0] = -1.57;
+        assert!(!planner.is_feasible(&positions, None));
+        planner.arms.set_joint_positions(&[0.0; 12]).unwrap();
+
+        let mut goal = vec![0.0; 12];
+        goal[1] = 0.5;
+        goal[7] = -0.5;
+        let path = planner.plan(&goal, Some(&far_objects())).unwrap();
+        assert_eq!(path[path.len() - 1], goal);
+        let (left, right) = planner.split_positions(&goal);
+        assert_eq!(left[1], 0.5);
+        assert_eq!(right[1], -0.5);
+    }
+
+    #[test]
+    fn test_dual_arm_plan_colliding() {
+        let planner = create_planner();
+
+        // The left arm hits the right arm at the goal.
+        let mut goal = vec![0.0; 12];
+        goal[0] = -1.57;
+        assert!(matches!(
+            planner.plan(&goal, None),
+            Err(Error::SelfCollision {
+                point: UnfeasibleTrajectory::GoalPoint,
+                ..
+            })
+        ));
+        assert!(matches!(
+            planner.plan(&goal, Some(&far_objects())),
+            Err(Error::Collision {
+                point: UnfeasibleTrajectory::GoalPoint,
+                ..
+            })
+        ));
+        assert_eq!(planner.arms.joint_positions(), vec![0.0; 12]);
+
+        // The end of the right arm goes into the object.
+        let initial = [0.0, -0.3, 0.0, 1.0, 0.0, 0.5];
+        planner
+            .arms
+            .set_joint_positions(&[initial, initial].concat())
+            .unwrap();
+        let left_target_pose = planner.left_arm.end_transform();
+        let mut right_target_pose = planner.right_arm.end_transform();
+        right_target_pose.translation.vector.z += 0.1;
+        let objects = Compound::new(vec![(right_target_pose, ShapeHandle::new(Ball::new(0.05)))]);
+        assert!(planner
+            .plan_with_ik(&left_target_pose, &right_target_pose, None)
+            .is_ok());
+        assert!(planner
+            .plan_with_ik(&left_target_pose, &right_target_pose, Some(&objects))
+            .is_err());
+    }
+
+    #[test]
+    fn test_dual_arm_plan_holding() {
+        let planner = create_planner();
+        // Bend the arms to avoid the singularity
+        let initial = [0.0, -0.3, 0.0, 1.0, 0.0, 0.5];
+        planner
+            .arms
+            .set_joint_positions(&[initial, initial].concat())
+            .unwrap();
+        let relative = planner.relative_end_transform();
+        let mut target_pose = planner.left_arm.end_transform();
+        target_pose.translation.vector += Vector3::new(-0.05, 0.0, 0.05);
+
+        let path = planner.plan_holding_with_ik(&target_pose, None).unwrap();
+        for positions in &path {
+            planner.arms.set_joint_positions(positions).unwrap();
+            let diff = relative.inverse() * planner.relative_end_transform();
+            assert!(diff.translation.vector.norm() < 0.01, "{}", diff);
+            assert!(diff.rotation.angle() < 0.02, "{}", diff);
+        }
+        planner
+            .arms
+            .set_joint_positions(&path[path.len() - 1])
+            .unwrap();
+        assert!(
+            (planner.left_arm.end_transform().translation.vector - target_pose.translation.vector)
+                .norm()
+                < 0.01
+        );
+    }
+}
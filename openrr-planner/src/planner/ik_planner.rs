@@ -18,8 +18,14 @@ use k::nalgebra as na;
 use na::RealField;
 use ncollide3d::shape::Compound;
 
-use super::joint_path_planner::JointPathPlanner;
-use crate::errors::*;
+use super::{
+    constrained_planner::{constrained_rrt_connect, PoseConstraints},
+    joint_path_planner::JointPathPlanner,
+};
+use crate::{errors::*, funcs::generate_random_joint_positions_from_limits};
+
+/// Margin of the pose constraints for the error of inverse kinematics
+const POSE_CONSTRAINT_MARGIN: f64 = 0.01;
 
 /// Joint path planner which supports inverse kinematics
 pub struct JointPathPlannerWithIk<T, I>
@@ -125,6 +131,79 @@ where
         self.path_planner.plan(&arm, &initial, &goal, objects)
     }
 
+    /// Solve IK and get the path on which the end link satisfies `pose_constraints`
+    ///
+    /// Both of the current pose and `target_pose` must satisfy the constraints,
+    /// and the joint positions of `target_pose` must be collision free.
+    /// The points of the path are projected onto the constraints by solving IK,
+    /// and the interval of them is `path_planner.step_length` at most.
+    pub fn plan_with_ik_with_pose_constraints(
+        &mut self,
+        target_name: &str,
+        target_pose: &na::Isometry3<T>,
+        objects: &Compound<T>,
+        pose_constraints: &PoseConstraints<T>,
+    ) -> Result<Vec<Vec<T>>> {
+        let end_link: &k::Node<T> = self
+            .path_planner
+            .robot_collision_detector
+            .robot
+            .find(target_name)
+            .ok_or_else(|| Error::NotFound(target_name.to_owned()))?;
+        let arm = k::SerialChain::from_end(end_link);
+        let margin = na::convert(POSE_CONSTRAINT_MARGIN);
+        let initial = arm.joint_positions();
+        if !pose_constraints.is_satisfied(&arm.end_transform(), margin) {
+            return Err(Error::PathPlanFail(
+                "the start pose does not satisfy the constraints".to_owned(),
+            ));
+        }
+        if !pose_constraints.is_satisfied(target_pose, margin) {
+            return Err(Error::PathPlanFail(
+                "the target pose does not satisfy the constraints".to_owned(),
+            ));
+        }
+        self.ik_solver.solve(&arm, target_pose)?;
+        let goal = arm.joint_positions();
+        if !self.path_planner.is_feasible(&arm, &goal, objects) {
+            let collision_link_names = self.colliding_link_names(objects);
+            arm.set_joint_positions(&initial)?;
+            return Err(Error::Collision {
+                point: UnfeasibleTrajectory::GoalPoint,
+                collision_link_names,
+            });
+        }
+
+        let limits = arm.iter_joints().map(|j| j.limits).collect();
+        let ik_solver = &self.ik_solver;
+        let project = |angles: &[T]| {
+            arm.set_joint_positions(angles).ok()?;
+            let pose = arm.end_transform();
+            if pose_constraints.is_satisfied(&pose, margin) {
+                return Some(angles.to_vec());
+            }
+            ik_solver
+                .solve(&arm, &pose_constraints.project(&pose))
+                .ok()?;
+            if pose_constraints.is_satisfied(&arm.end_transform(), margin) {
+                Some(arm.joint_positions())
+            } else {
+                None
+            }
+        };
+        let result = constrained_rrt_connect(
+            &initial,
+            &goal,
+            |angles: &[T]| self.path_planner.is_feasible(&arm, angles, objects),
+            project,
+            || generate_random_joint_positions_from_limits(&limits),
+            self.path_planner.step_length,
+            self.path_planner.max_try,
+        );
+        arm.set_joint_positions(&initial)?;
+        result.map_err(Error::PathPlanFail)
+    }
+
     /// Do not solve IK but get the path to the target joint positions
     pub fn plan_joints<K>(
         &mut self,
@@ -147,3 +226,123 @@ where
         self.path_planner.joint_names()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ncollide3d::shape::{Cuboid, ShapeHandle};
+
+    use super::*;
+    use crate::{AxisConstraint, JointPathPlannerBuilder};
+
+    fn planner() -> JointPathPlannerWithIk<f64, k::JacobianIkSolver<f64>> {
+        let path_planner = JointPathPlannerBuilder::from_urdf_file("sample.urdf")
+            .unwrap()
+            .finalize();
+        JointPathPlannerWithIk::new(path_planner, k::JacobianIkSolver::default())
+    }
+
+    fn box_objects(position: na::Vector3<f64>) -> Compound<f64> {
+        Compound::new(vec![(
+            na::Isometry3::translation(position.x, position.y, position.z),
+            ShapeHandle::new(Cuboid::new(na::Vector3::new(0.05, 0.05, 0.05))),
+        )])
+    }
+
+    fn level_constraints() -> PoseConstraints<f64> {
+        PoseConstraints {
+            axis: Some(AxisConstraint {
+                end_axis: na::Vector3::z(),
+                target_axis: na::Vector3::z(),
+                tolerance: 0.1,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn start_and_target(
+        planner: &JointPathPlannerWithIk<f64, k::JacobianIkSolver<f64>>,
+    ) -> (Vec<f64>, na::Isometry3<f64>) {
+        let end = planner
+            .path_planner
+            .robot_collision_detector
+            .robot
+            .find("l_tool_fixed")
+            .unwrap();
+        let arm = k::SerialChain::from_end(end);
+        arm.set_joint_positions(&[0.2, 0.2, 0.0, -1.0, 0.0, 0.0])
+            .unwrap();
+        arm.update_transforms();
+        // Keep the current orientation, which is level enough.
+        let mut target = arm.end_transform();
+        target.translation.vector.y += 0.05;
+        (arm.joint_positions(), target)
+    }
+
+    #[test]
+    fn plan_with_pose_constraints() {
+        let mut planner = planner();
+        let (initial, target) = start_and_target(&planner);
+        let mut constraints = level_constraints();
+        let axis = constraints.axis.as_mut().unwrap();
+        axis.target_axis = target.rotation * na::Vector3::z();
+
+        let path = planner
+            .plan_with_ik_with_pose_constraints(
+                "l_tool_fixed",
+                &target,
+                &box_objects(na::Vector3::new(10.0, 10.0, 10.0)),
+                &constraints,
+            )
+            .unwrap();
+        assert_eq!(path[0], initial);
+
+        let end = planner
+            .path_planner
+            .robot_collision_detector
+            .robot
+            .find("l_tool_fixed")
+            .unwrap();
+        let arm = k::SerialChain::from_end(end);
+        // The arm is restored.
+        assert_eq!(arm.joint_positions(), initial);
+        for angles in &path {
+            arm.set_joint_positions(angles).unwrap();
+            arm.update_transforms();
+            assert!(constraints.is_satisfied(&arm.end_transform(), POSE_CONSTRAINT_MARGIN));
+        }
+        arm.update_transforms();
+        assert!((arm.end_transform().translation.vector - target.translation.vector).norm() < 0.01);
+    }
+
+    #[test]
+    fn plan_with_pose_constraints_to_colliding_target() {
+        let mut planner = planner();
+        let (initial, target) = start_and_target(&planner);
+        let mut constraints = level_constraints();
+        let axis = constraints.axis.as_mut().unwrap();
+        axis.target_axis = target.rotation * na::Vector3::z();
+        // Box around the target
+        let objects = box_objects(target.translation.vector);
+
+        let result = planner.plan_with_ik_with_pose_constraints(
+            "l_tool_fixed",
+            &target,
+            &objects,
+            &constraints,
+        );
+        assert!(matches!(
+            result,
+            Err(Error::Collision {
+                point: UnfeasibleTrajectory::GoalPoint,
+                ..
+            })
+        ));
+        let end = planner
+            .path_planner
+            .robot_collision_detector
+            .robot
+            .find("l_tool_fixed")
+            .unwrap();
+        assert_eq!(k::SerialChain::from_end(end).joint_positions(), initial);
+    }
+}
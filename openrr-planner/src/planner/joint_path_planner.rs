@@ -20,12 +20,38 @@ use na::RealField;
 use ncollide3d::shape::Compound;
 use tracing::*;
 
+use super::{prm::Roadmap, rrt_star::rrt_star, sampling::shortcut_path};
 use crate::{
-    collision::{CollisionDetector, RobotCollisionDetector},
+    collision::{
+        check_continuous_motion, CollisionDetector, MeshApproximation, RobotCollisionDetector,
+    },
     errors::*,
     funcs::*,
 };
 
+/// Algorithm to search the path in `JointPathPlanner`
+#[derive(Debug, Clone)]
+pub enum PlannerAlgorithm<N> {
+    /// RRT-Connect, which is fast but the path is not optimal
+    RrtConnect,
+    /// RRT*, which continues to shorten the path for `max_try` iterations
+    RrtStar {
+        /// Radius to search the nodes to be rewired
+        neighbor_radius: N,
+    },
+    /// PRM on the roadmap built beforehand by [`JointPathPlanner::build_roadmap`]
+    ///
+    /// The path is shortened deterministically instead of the random
+    /// smoothing, so the same query always gives the same path.
+    Prm(Roadmap<N>),
+}
+
+impl<N> Default for PlannerAlgorithm<N> {
+    fn default() -> Self {
+        PlannerAlgorithm::RrtConnect
+    }
+}
+
 /// Collision Avoidance Path Planner
 pub struct JointPathPlanner<N>
 where
@@ -41,6 +67,12 @@ where
     pub max_try: usize,
     /// Num of path smoothing trials
     pub num_smoothing: usize,
+    /// Algorithm to search the path
+    pub algorithm: PlannerAlgorithm<N>,
+    /// If set, the motions between the points of the planned path are
+    /// validated continuously so that no link moves more than this between
+    /// the checked configurations.
+    pub max_link_displacement: Option<N>,
 }
 
 impl<N> JointPathPlanner<N>
@@ -59,6 +91,8 @@ where
             step_length,
             max_try,
             num_smoothing,
+            algorithm: PlannerAlgorithm::default(),
+            max_link_displacement: None,
         }
     }
 
@@ -104,9 +138,6 @@ where
         goal_angles: &[N],
         objects: &Compound<N>,
     ) -> Result<Vec<Vec<N>>> {
-        let limits = using_joints.iter_joints().map(|j| j.limits).collect();
-        let step_length = self.step_length;
-        let max_try = self.max_try;
         let current_angles = using_joints.joint_positions();
 
         if !self.is_feasible(using_joints, start_angles, objects) {
@@ -129,28 +160,13 @@ where
             });
         }
 
-        let mut path = match rrt::dual_rrt_connect(
-            start_angles,
-            goal_angles,
-            |angles: &[N]| self.is_feasible(using_joints, angles, objects),
-            || generate_random_joint_positions_from_limits(&limits),
-            step_length,
-            max_try,
-        ) {
-            Ok(p) => p,
-            Err(error) => {
-                using_joints.set_joint_positions(&current_angles)?;
-                return Err(Error::PathPlanFail(error));
-            }
-        };
-        let num_smoothing = self.num_smoothing;
-        rrt::smooth_path(
-            &mut path,
-            |angles: &[N]| self.is_feasible(using_joints, angles, objects),
-            step_length,
-            num_smoothing,
-        );
-        Ok(path)
+        self.search_path(using_joints, start_angles, goal_angles, |angles: &[N]| {
+            self.is_feasible(using_joints, angles, objects)
+        })
+        .or_else(|error| {
+            using_joints.set_joint_positions(&current_angles)?;
+            Err(error)
+        })
     }
 
     /// Plan the sequence of joint angles of `using_joints` to avoid self collision.
@@ -166,9 +182,6 @@ where
         start_angles: &[N],
         goal_angles: &[N],
     ) -> Result<Vec<Vec<N>>> {
-        let limits = using_joints.iter_joints().map(|j| j.limits).collect();
-        let step_length = self.step_length;
-        let max_try = self.max_try;
         let current_angles = using_joints.joint_positions();
 
         if !self.is_feasible_with_self(using_joints, start_angles) {
@@ -187,30 +200,141 @@ where
             });
         }
 
-        let mut path = match rrt::dual_rrt_connect(
-            start_angles,
-            goal_angles,
-            |angles: &[N]| self.is_feasible_with_self(using_joints, angles),
-            || generate_random_joint_positions_from_limits(&limits),
-            step_length,
-            max_try,
-        ) {
-            Ok(p) => p,
-            Err(error) => {
-                using_joints.set_joint_positions(&current_angles)?;
-                return Err(Error::PathPlanFail(error));
+        self.search_path(using_joints, start_angles, goal_angles, |angles: &[N]| {
+            self.is_feasible_with_self(using_joints, angles)
+        })
+        .or_else(|error| {
+            using_joints.set_joint_positions(&current_angles)?;
+            Err(error)
+        })
+    }
+
+    fn search_path<F>(
+        &self,
+        using_joints: &k::Chain<N>,
+        start_angles: &[N],
+        goal_angles: &[N],
+        is_free: F,
+    ) -> Result<Vec<Vec<N>>>
+    where
+        F: Fn(&[N]) -> bool,
+    {
+        let limits = using_joints.iter_joints().map(|j| j.limits).collect();
+        let mut path = match &self.algorithm {
+            PlannerAlgorithm::RrtConnect => rrt::dual_rrt_connect(
+                start_angles,
+                goal_angles,
+                &is_free,
+                || generate_random_joint_positions_from_limits(&limits),
+                self.step_length,
+                self.max_try,
+            )
+            .map_err(Error::PathPlanFail)?,
+            PlannerAlgorithm::RrtStar { neighbor_radius } => rrt_star(
+                start_angles,
+                goal_angles,
+                &is_free,
+                || generate_random_joint_positions_from_limits(&limits),
+                self.step_length,
+                *neighbor_radius,
+                self.max_try,
+            )
+            .map_err(Error::PathPlanFail)?,
+            PlannerAlgorithm::Prm(roadmap) => {
+                let joint_names = using_joints
+                    .iter_joints()
+                    .map(|j| j.name.clone())
+                    .collect::<Vec<_>>();
+                if roadmap.joint_names() != joint_names.as_slice() {
+                    return Err(Error::Other {
+                        error: format!(
+                            "roadmap is built for {:?}, but {:?} are given",
+                            roadmap.joint_names(),
+                            joint_names
+                        ),
+                    });
+                }
+                let mut path = roadmap.query(start_angles, goal_angles, &is_free)?;
+                shortcut_path(&mut path, &is_free, self.step_length);
+                path
             }
         };
-        let num_smoothing = self.num_smoothing;
-        rrt::smooth_path(
-            &mut path,
-            |angles: &[N]| self.is_feasible_with_self(using_joints, angles),
-            step_length,
-            num_smoothing,
-        );
+        if !matches!(self.algorithm, PlannerAlgorithm::Prm(_)) {
+            rrt::smooth_path(&mut path, &is_free, self.step_length, self.num_smoothing);
+        }
+        if let Some(max_link_displacement) = self.max_link_displacement {
+            self.validate_path(using_joints, &path, max_link_displacement, &is_free)?;
+        }
         Ok(path)
     }
 
+    /// Check the motions between the points of the path continuously
+    fn validate_path<F>(
+        &self,
+        using_joints: &k::Chain<N>,
+        path: &[Vec<N>],
+        max_link_displacement: N,
+        is_free: F,
+    ) -> Result<()>
+    where
+        F: Fn(&[N]) -> bool,
+    {
+        for (i, points) in path.windows(2).enumerate() {
+            check_continuous_motion(
+                &self.robot_collision_detector.collision_detector,
+                &self.robot_collision_detector.robot,
+                using_joints,
+                &points[0],
+                &points[1],
+                max_link_displacement,
+                |_, angles| {
+                    if is_free(angles) {
+                        Ok(())
+                    } else {
+                        Err(Error::PathPlanFail(format!(
+                            "collision between the points {} and {} of the path",
+                            i,
+                            i + 1
+                        )))
+                    }
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Build the roadmap of `using_joints` for [`PlannerAlgorithm::Prm`]
+    ///
+    /// This takes time, but the roadmap can be reused as long as the
+    /// environment is static. Use [`Roadmap::save`] to store it.
+    ///
+    /// # Arguments
+    ///
+    /// - `using_joints`: part of collision_check_robot.
+    /// - `objects`: The collision between `self.collision_check_robot` and `objects` will be checked.
+    /// - `num_nodes`: number of the nodes of the roadmap.
+    /// - `num_neighbors`: each node is connected to this number of the nearest nodes.
+    pub fn build_roadmap(
+        &self,
+        using_joints: &k::Chain<N>,
+        objects: &Compound<N>,
+        num_nodes: usize,
+        num_neighbors: usize,
+    ) -> Result<Roadmap<N>> {
+        let limits = using_joints.iter_joints().map(|j| j.limits).collect();
+        let current_angles = using_joints.joint_positions();
+        let roadmap = Roadmap::build(
+            using_joints.iter_joints().map(|j| j.name.clone()).collect(),
+            |angles: &[N]| self.is_feasible(using_joints, angles, objects),
+            || generate_random_joint_positions_from_limits(&limits),
+            self.step_length,
+            num_nodes,
+            num_neighbors,
+        );
+        using_joints.set_joint_positions(&current_angles)?;
+        Ok(roadmap)
+    }
+
     /// Calculate the transforms of all of the links
     pub fn update_transforms(&self) -> Vec<na::Isometry3<N>> {
         self.robot_collision_detector.robot.update_transforms()
@@ -237,6 +361,9 @@ where
     num_smoothing: usize,
     collision_check_margin: Option<N>,
     self_collision_pairs: Vec<(String, String)>,
+    algorithm: PlannerAlgorithm<N>,
+    mesh_approximation: MeshApproximation,
+    max_link_displacement: Option<N>,
 }
 
 impl<N> JointPathPlannerBuilder<N>
@@ -254,6 +381,9 @@ where
             num_smoothing: 100,
             collision_check_margin: None,
             self_collision_pairs: vec![],
+            algorithm: PlannerAlgorithm::default(),
+            mesh_approximation: MeshApproximation::default(),
+            max_link_displacement: None,
         }
     }
 
@@ -282,10 +412,31 @@ where
         self
     }
 
+    /// Set the algorithm to search the path (default: [`PlannerAlgorithm::RrtConnect`])
+    pub fn algorithm(mut self, algorithm: PlannerAlgorithm<N>) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Replace the meshes of the collision models with the primitive shapes (default: [`MeshApproximation::None`])
+    pub fn mesh_approximation(mut self, mesh_approximation: MeshApproximation) -> Self {
+        self.mesh_approximation = mesh_approximation;
+        self
+    }
+
+    /// Validate the planned path continuously with this resolution \[m\] (default: not validated)
+    pub fn max_link_displacement(mut self, max_link_displacement: N) -> Self {
+        self.max_link_displacement = Some(max_link_displacement);
+        self
+    }
+
     pub fn finalize(mut self) -> JointPathPlanner<N> {
         if let Some(margin) = self.collision_check_margin {
             self.robot_collision_detector.collision_detector.prediction = margin;
         }
+        self.robot_collision_detector
+            .collision_detector
+            .approximate_meshes(self.mesh_approximation);
         let mut planner = JointPathPlanner::new(
             self.robot_collision_detector,
             self.step_length,
@@ -293,6 +444,8 @@ where
             self.num_smoothing,
         );
         planner.robot_collision_detector.self_collision_pairs = self.self_collision_pairs;
+        planner.algorithm = self.algorithm;
+        planner.max_link_displacement = self.max_link_displacement;
         planner
     }
 
@@ -331,6 +484,7 @@ mod tests {
     use ncollide3d::shape::Cuboid;
 
     use super::*;
+    use crate::FromUrdf;
 
     #[test]
     fn collision_check() {
@@ -387,4 +541,135 @@ mod tests {
             .collision_check_margin(0.01)
             .finalize();
     }
+
+    #[test]
+    fn plan_with_algorithms() {
+        let obstacles = Compound::from_urdf_file("obstacles.urdf").unwrap();
+        let mut planner = JointPathPlannerBuilder::from_urdf_file("sample.urdf")
+            .unwrap()
+            .max_try(300)
+            .algorithm(PlannerAlgorithm::RrtStar {
+                neighbor_radius: 0.5,
+            })
+            .finalize();
+        let robot = &planner.robot_collision_detector.robot;
+        let arm = k::SerialChain::from_end(robot.find("l_tool_fixed").unwrap());
+        let elbow = k::SerialChain::from_end(robot.find("l_elbow_pitch").unwrap());
+        let start = vec![0.0; arm.dof()];
+        let mut goal = start.clone();
+        goal[0] = 0.5;
+
+        let path = planner
+            .plan_avoid_self_collision(&arm, &start, &goal)
+            .unwrap();
+        assert_eq!(path[0], start);
+        assert_eq!(path[path.len() - 1], goal);
+
+        let roadmap = planner.build_roadmap(&arm, &obstacles, 30, 5).unwrap();
+        assert_eq!(roadmap.joint_names().len(), arm.dof());
+        planner.algorithm = PlannerAlgorithm::Prm(roadmap);
+        let path = planner
+            .plan_avoid_self_collision(&arm, &start, &goal)
+            .unwrap();
+        assert_eq!(path[0], start);
+        assert_eq!(path[path.len() - 1], goal);
+        // The same query gives the same path.
+        for _ in 0..3 {
+            assert_eq!(
+                planner
+                    .plan_avoid_self_collision(&arm, &start, &goal)
+                    .unwrap(),
+                path
+            );
+        }
+        // The roadmap is built for the other joints.
+        assert!(planner
+            .plan_avoid_self_collision(&elbow, &[0.0; 4], &[0.1; 4])
+            .is_err());
+    }
+
+    #[test]
+    fn plan_with_max_link_displacement() {
+        let planner = JointPathPlannerBuilder::from_urdf_file("sample.urdf")
+            .unwrap()
+            .max_link_displacement(0.01)
+            .finalize();
+        let robot = &planner.robot_collision_detector.robot;
+        let arm = k::SerialChain::from_end(robot.find("l_tool_fixed").unwrap());
+        let start = vec![0.0; arm.dof()];
+        let mut goal = start.clone();
+        goal[0] = 0.5;
+
+        let path = planner
+            .plan_avoid_self_collision(&arm, &start, &goal)
+            .unwrap();
+        assert_eq!(path[path.len() - 1], goal);
+
+        let path = vec![start, goal];
+        assert!(planner
+            .validate_path(&arm, &path, 0.01, |_: &[f64]| true)
+            .is_ok());
+        // The intermediate points are checked.
+        assert!(planner
+            .validate_path(&arm, &path, 0.01, |angles: &[f64]| angles[0] < 0.2)
+            .is_err());
+    }
+
+    #[test]
+    fn plan_with_attached_object() {
+        use ncollide3d::shape::ShapeHandle;
+
+        use crate::collision::AttachedObject;
+
+        let planner = JointPathPlannerBuilder::from_urdf_file("sample.urdf")
+            .unwrap()
+            .finalize();
+        let robot = &planner.robot_collision_detector.robot;
+        let arm = k::SerialChain::from_end(robot.find("l_tool_fixed").unwrap());
+        let start = vec![0.0; arm.dof()];
+        let mut goal = start.clone();
+        goal[1] = -1.0;
+        arm.set_joint_positions(&goal).unwrap();
+        arm.update_transforms();
+        // Box fixed at the goal position of the tool
+        let object = AttachedObject::new(
+            "root",
+            ShapeHandle::new(Cuboid::new(Vector3::new(0.05, 0.05, 0.05))),
+            arm.end_transform(),
+        );
+        arm.set_joint_positions(&start).unwrap();
+        assert!(planner
+            .plan_avoid_self_collision(&arm, &start, &goal)
+            .is_ok());
+
+        planner
+            .robot_collision_detector
+            .collision_detector
+            .attach_object("box", object);
+        assert!(matches!(
+            planner.plan_avoid_self_collision(&arm, &start, &goal),
+            Err(Error::SelfCollision {
+                point: UnfeasibleTrajectory::GoalPoint,
+                ..
+            })
+        ));
+        // The path avoids the box.
+        goal[1] = -0.5;
+        let path = planner
+            .plan_avoid_self_collision(&arm, &start, &goal)
+            .unwrap();
+        for angles in &path {
+            assert!(planner.is_feasible_with_self(&arm, angles));
+        }
+
+        assert!(planner
+            .robot_collision_detector
+            .collision_detector
+            .detach_object("box")
+            .is_some());
+        goal[1] = -1.0;
+        assert!(planner
+            .plan_avoid_self_collision(&arm, &start, &goal)
+            .is_ok());
+    }
 }
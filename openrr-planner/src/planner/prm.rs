@@ -0,0 +1,394 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashSet},
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use num_traits::Float;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::sampling::*;
+use crate::errors::*;
+
+/// Limit of the random samples per node to build the roadmap
+const MAX_SAMPLES_PER_NODE: usize = 100;
+
+/// Roadmap of the Probabilistic Roadmap (PRM) planner
+///
+/// Building the roadmap is slow, but it can be done offline for a static
+/// environment and saved to a file. Queries on the loaded roadmap are fast.
+///
+/// The edges on the found path are checked again with the `is_free` function
+/// of the query, so a roadmap is still usable if some obstacles are added
+/// after it is built.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Roadmap<N> {
+    joint_names: Vec<String>,
+    extend_length: N,
+    num_neighbors: usize,
+    nodes: Vec<Vec<N>>,
+    edges: Vec<Vec<usize>>,
+}
+
+impl<N> Roadmap<N>
+where
+    N: Float,
+{
+    /// Build the roadmap
+    ///
+    /// # Arguments
+    ///
+    /// - `joint_names`: names of the joints which the roadmap is built for.
+    /// - `is_free`: returns true if the joint positions are free from collisions.
+    /// - `random_sample`: returns random joint positions.
+    /// - `extend_length`: interval to check collisions on the edges.
+    /// - `num_nodes`: number of the nodes of the roadmap.
+    /// - `num_neighbors`: each node is connected to this number of the nearest nodes.
+    pub fn build<FF, FR>(
+        joint_names: Vec<String>,
+        mut is_free: FF,
+        random_sample: FR,
+        extend_length: N,
+        num_nodes: usize,
+        num_neighbors: usize,
+    ) -> Self
+    where
+        FF: FnMut(&[N]) -> bool,
+        FR: Fn() -> Vec<N>,
+    {
+        let mut nodes = Vec::with_capacity(num_nodes);
+        for _ in 0..num_nodes * MAX_SAMPLES_PER_NODE {
+            if nodes.len() == num_nodes {
+                break;
+            }
+            let sample = random_sample();
+            if is_free(&sample) {
+                nodes.push(sample);
+            }
+        }
+
+        let mut edges = vec![vec![]; nodes.len()];
+        for i in 0..nodes.len() {
+            for (j, _) in sorted_by_distance(&nodes, &nodes[i])
+                .into_iter()
+                .skip(1)
+                .take(num_neighbors)
+            {
+                if edges[i].contains(&j) {
+                    continue;
+                }
+                if is_edge_free(&nodes[i], &nodes[j], &mut is_free, extend_length) {
+                    edges[i].push(j);
+                    edges[j].push(i);
+                }
+            }
+        }
+        Self {
+            joint_names,
+            extend_length,
+            num_neighbors,
+            nodes,
+            edges,
+        }
+    }
+
+    /// Names of the joints which the roadmap is built for
+    pub fn joint_names(&self) -> &[String] {
+        &self.joint_names
+    }
+
+    /// Joint positions of the nodes
+    pub fn nodes(&self) -> &[Vec<N>] {
+        &self.nodes
+    }
+
+    /// Number of the (undirected) edges
+    pub fn num_edges(&self) -> usize {
+        self.edges.iter().map(|e| e.len()).sum::<usize>() / 2
+    }
+
+    /// Search the path from `start` to `goal` on the roadmap
+    ///
+    /// `start` and `goal` are connected to the nearest nodes, then the
+    /// shortest path is searched by A*.
+    pub fn query<FF>(&self, start: &[N], goal: &[N], mut is_free: FF) -> Result<Vec<Vec<N>>>
+    where
+        FF: FnMut(&[N]) -> bool,
+    {
+        let dof = self.joint_names.len();
+        if start.len() != dof {
+            return Err(Error::DofMismatch(start.len(), dof));
+        }
+        if goal.len() != dof {
+            return Err(Error::DofMismatch(goal.len(), dof));
+        }
+        let extend_length = self.extend_length;
+        if is_edge_free(start, goal, &mut is_free, extend_length) {
+            return Ok(vec![start.to_vec(), goal.to_vec()]);
+        }
+
+        let start_edges = self.connectable_nodes(start, |node| {
+            is_edge_free(start, node, &mut is_free, extend_length)
+        });
+        let goal_edges = self.connectable_nodes(goal, |node| {
+            is_edge_free(node, goal, &mut is_free, extend_length)
+        });
+
+        // The roadmap may be built for another environment, so check the
+        // edges on the found path lazily and search again if blocked.
+        let mut blocked = HashSet::new();
+        let mut checked = HashSet::new();
+        'search: loop {
+            let indices = self
+                .search(start, goal, &start_edges, &goal_edges, &blocked)
+                .ok_or_else(|| Error::PathPlanFail("no path on the roadmap".to_owned()))?;
+            for pair in indices.windows(2) {
+                let edge = (pair[0].min(pair[1]), pair[0].max(pair[1]));
+                if checked.contains(&edge) {
+                    continue;
+                }
+                if !is_edge_free(
+                    &self.nodes[pair[0]],
+                    &self.nodes[pair[1]],
+                    &mut is_free,
+                    extend_length,
+                ) {
+                    blocked.insert(edge);
+                    continue 'search;
+                }
+                checked.insert(edge);
+            }
+            let mut path = vec![start.to_vec()];
+            path.extend(indices.into_iter().map(|i| self.nodes[i].clone()));
+            path.push(goal.to_vec());
+            return Ok(path);
+        }
+    }
+
+    fn connectable_nodes<F>(&self, point: &[N], mut can_connect: F) -> Vec<usize>
+    where
+        F: FnMut(&[N]) -> bool,
+    {
+        sorted_by_distance(&self.nodes, point)
+            .into_iter()
+            .take(self.num_neighbors)
+            .filter(|&(i, _)| can_connect(&self.nodes[i]))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// A* search from `start` to `goal`, returns the indices of the nodes in between
+    fn search(
+        &self,
+        start: &[N],
+        goal: &[N],
+        start_edges: &[usize],
+        goal_edges: &[usize],
+        blocked: &HashSet<(usize, usize)>,
+    ) -> Option<Vec<usize>> {
+        let start_index = self.nodes.len();
+        let goal_index = self.nodes.len() + 1;
+        let point = |i: usize| -> &[N] {
+            if i == start_index {
+                start
+            } else if i == goal_index {
+                goal
+            } else {
+                &self.nodes[i]
+            }
+        };
+        let is_goal_edge = {
+            let mut v = vec![false; self.nodes.len()];
+            for &i in goal_edges {
+                v[i] = true;
+            }
+            v
+        };
+
+        let mut costs = vec![N::infinity(); self.nodes.len() + 2];
+        let mut parents = vec![None; self.nodes.len() + 2];
+        let mut open = BinaryHeap::new();
+        costs[start_index] = N::zero();
+        open.push(OpenNode {
+            score: distance(start, goal),
+            index: start_index,
+        });
+        while let Some(OpenNode { score, index }) = open.pop() {
+            if index == goal_index {
+                let mut indices = vec![];
+                let mut current = parents[goal_index];
+                while let Some(i) = current {
+                    if i == start_index {
+                        break;
+                    }
+                    indices.push(i);
+                    current = parents[i];
+                }
+                indices.reverse();
+                return Some(indices);
+            }
+            if score > costs[index] + distance(point(index), goal) {
+                // Already visited with a lower cost
+                continue;
+            }
+            let neighbors: Box<dyn Iterator<Item = usize> + '_> = if index == start_index {
+                Box::new(start_edges.iter().copied())
+            } else {
+                Box::new(
+                    self.edges[index]
+                        .iter()
+                        .copied()
+                        .filter(|&j| !blocked.contains(&(index.min(j), index.max(j))))
+                        .chain(if is_goal_edge[index] {
+                            Some(goal_index)
+                        } else {
+                            None
+                        }),
+                )
+            };
+            for next in neighbors {
+                let cost = costs[index] + distance(point(index), point(next));
+                if cost < costs[next] {
+                    costs[next] = cost;
+                    parents[next] = Some(index);
+                    open.push(OpenNode {
+                        score: cost + distance(point(next), goal),
+                        index: next,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<N> Roadmap<N>
+where
+    N: Serialize,
+{
+    /// Save the roadmap to a file
+    pub fn save<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self).map_err(|e| Error::Other {
+            error: format!("failed to save roadmap: {}", e),
+        })
+    }
+}
+
+impl<N> Roadmap<N>
+where
+    N: DeserializeOwned,
+{
+    /// Load the roadmap from a file created by [`Roadmap::save`]
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let reader = BufReader::new(File::open(path.as_ref())?);
+        serde_json::from_reader(reader)
+            .map_err(|e| Error::ParseError(format!("{:?}: {}", path.as_ref(), e)))
+    }
+}
+
+/// Node in the open list of A*, ordered by the score in reverse
+struct OpenNode<N> {
+    score: N,
+    index: usize,
+}
+
+impl<N: Float> PartialEq for OpenNode<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<N: Float> Eq for OpenNode<N> {}
+
+impl<N: Float> PartialOrd for OpenNode<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N: Float> Ord for OpenNode<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .score
+            .partial_cmp(&self.score)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::{Distribution, Uniform};
+
+    use super::*;
+
+    fn is_free(p: &[f64]) -> bool {
+        // A wall with a gap at the top
+        !(p[0].abs() < 0.5 && p[1] < 1.0)
+    }
+
+    fn random_sample() -> Vec<f64> {
+        let between = Uniform::new(-2.0, 2.0);
+        let mut rng = rand::thread_rng();
+        vec![between.sample(&mut rng), between.sample(&mut rng)]
+    }
+
+    fn build() -> Roadmap<f64> {
+        Roadmap::build(
+            vec!["x".to_owned(), "y".to_owned()],
+            is_free,
+            random_sample,
+            0.05,
+            500,
+            10,
+        )
+    }
+
+    #[test]
+    fn test_query() {
+        let roadmap = build();
+        assert_eq!(roadmap.nodes().len(), 500);
+        assert!(roadmap.num_edges() > 500);
+
+        let start = [-1.0, 0.0];
+        let goal = [1.0, 0.0];
+        let path = roadmap.query(&start, &goal, is_free).unwrap();
+        assert_eq!(path[0], start);
+        assert_eq!(path[path.len() - 1], goal);
+        let mut free = is_free;
+        for w in path.windows(2) {
+            assert!(is_edge_free(&w[0], &w[1], &mut free, 0.05));
+        }
+
+        assert!(roadmap.query(&[0.0], &goal, is_free).is_err());
+    }
+
+    #[test]
+    fn test_query_with_new_obstacle() {
+        let roadmap = build();
+        // The gap is closed.
+        let is_free = |p: &[f64]| p[0].abs() > 0.5;
+        assert!(roadmap.query(&[-1.0, 0.0], &[1.0, 0.0], is_free).is_err());
+    }
+
+    #[test]
+    fn test_save_load() {
+        let roadmap = build();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("roadmap.json");
+        roadmap.save(&path).unwrap();
+        let loaded = Roadmap::<f64>::load(&path).unwrap();
+        assert_eq!(loaded.joint_names(), roadmap.joint_names());
+        assert_eq!(loaded.nodes(), roadmap.nodes());
+        assert_eq!(loaded.num_edges(), roadmap.num_edges());
+    }
+}
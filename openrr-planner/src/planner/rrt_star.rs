@@ -0,0 +1,230 @@
+use num_traits::Float;
+
+use super::sampling::*;
+
+/// Every `GOAL_SAMPLE_INTERVAL`-th sample is the goal itself.
+const GOAL_SAMPLE_INTERVAL: usize = 10;
+
+struct Tree<N> {
+    points: Vec<Vec<N>>,
+    parents: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    costs: Vec<N>,
+}
+
+impl<N> Tree<N>
+where
+    N: Float,
+{
+    fn new(root: &[N]) -> Self {
+        Self {
+            points: vec![root.to_vec()],
+            parents: vec![None],
+            children: vec![vec![]],
+            costs: vec![N::zero()],
+        }
+    }
+
+    fn nearest(&self, point: &[N]) -> usize {
+        let mut nearest = 0;
+        let mut min_dist = N::infinity();
+        for (i, p) in self.points.iter().enumerate() {
+            let dist = distance(p, point);
+            if dist < min_dist {
+                nearest = i;
+                min_dist = dist;
+            }
+        }
+        nearest
+    }
+
+    fn add(&mut self, point: Vec<N>, parent: usize, cost: N) -> usize {
+        let index = self.points.len();
+        self.points.push(point);
+        self.parents.push(Some(parent));
+        self.children.push(vec![]);
+        self.costs.push(cost);
+        self.children[parent].push(index);
+        index
+    }
+
+    fn change_parent(&mut self, index: usize, new_parent: usize, new_cost: N) {
+        if let Some(old_parent) = self.parents[index] {
+            self.children[old_parent].retain(|&c| c != index);
+        }
+        self.parents[index] = Some(new_parent);
+        self.children[new_parent].push(index);
+        // Propagate the improvement to the descendants.
+        let diff = self.costs[index] - new_cost;
+        let mut stack = vec![index];
+        while let Some(i) = stack.pop() {
+            self.costs[i] = self.costs[i] - diff;
+            stack.extend(self.children[i].iter().copied());
+        }
+    }
+
+    fn path_to(&self, index: usize) -> Vec<Vec<N>> {
+        let mut path = vec![self.points[index].clone()];
+        let mut current = index;
+        while let Some(parent) = self.parents[current] {
+            path.push(self.points[parent].clone());
+            current = parent;
+        }
+        path.reverse();
+        path
+    }
+}
+
+/// Search the path from `start` to `goal` by RRT*.
+///
+/// Unlike RRT-Connect, RRT* continues sampling for `num_max_try` iterations
+/// even after the goal is reached, and rewires the tree within
+/// `neighbor_radius` to shorten the path. The path converges to the optimal
+/// one as `num_max_try` increases.
+pub fn rrt_star<FF, FR, N>(
+    start: &[N],
+    goal: &[N],
+    mut is_free: FF,
+    random_sample: FR,
+    extend_length: N,
+    neighbor_radius: N,
+    num_max_try: usize,
+) -> Result<Vec<Vec<N>>, String>
+where
+    FF: FnMut(&[N]) -> bool,
+    FR: Fn() -> Vec<N>,
+    N: Float,
+{
+    assert_eq!(start.len(), goal.len());
+    let mut tree = Tree::new(start);
+    // Nodes which can be connected to the goal directly
+    let mut goal_parents = vec![];
+    if is_edge_free(start, goal, &mut is_free, extend_length) {
+        goal_parents.push(0);
+    }
+
+    for i in 0..num_max_try {
+        let sample = if i % GOAL_SAMPLE_INTERVAL == 0 {
+            goal.to_vec()
+        } else {
+            random_sample()
+        };
+        let nearest = tree.nearest(&sample);
+        let new_point = steer(&tree.points[nearest], &sample, extend_length);
+        if !is_edge_free(
+            &tree.points[nearest],
+            &new_point,
+            &mut is_free,
+            extend_length,
+        ) {
+            continue;
+        }
+
+        let neighbors = tree
+            .points
+            .iter()
+            .enumerate()
+            .filter_map(|(j, p)| {
+                let dist = distance(p, &new_point);
+                if dist <= neighbor_radius {
+                    Some((j, dist))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Choose the parent which minimizes the cost to the new node.
+        let mut parent = nearest;
+        let mut cost = tree.costs[nearest] + distance(&tree.points[nearest], &new_point);
+        for &(j, dist) in &neighbors {
+            if j != nearest
+                && tree.costs[j] + dist < cost
+                && is_edge_free(&tree.points[j], &new_point, &mut is_free, extend_length)
+            {
+                parent = j;
+                cost = tree.costs[j] + dist;
+            }
+        }
+        let new_index = tree.add(new_point, parent, cost);
+
+        // Rewire the neighbors through the new node if it is shorter.
+        for &(j, dist) in &neighbors {
+            if j != parent
+                && cost + dist < tree.costs[j]
+                && is_edge_free(
+                    &tree.points[new_index],
+                    &tree.points[j],
+                    &mut is_free,
+                    extend_length,
+                )
+            {
+                tree.change_parent(j, new_index, cost + dist);
+            }
+        }
+
+        if distance(&tree.points[new_index], goal) <= extend_length
+            && is_edge_free(&tree.points[new_index], goal, &mut is_free, extend_length)
+        {
+            goal_parents.push(new_index);
+        }
+    }
+
+    // The costs may have been improved by rewiring, so select the parent here.
+    let best = goal_parents
+        .into_iter()
+        .map(|j| (j, tree.costs[j] + distance(&tree.points[j], goal)))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .ok_or_else(|| format!("failed to reach the goal in {} tries", num_max_try))?;
+    let mut path = tree.path_to(best.0);
+    if distance(&path[path.len() - 1], goal) > N::zero() {
+        path.push(goal.to_vec());
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::{Distribution, Uniform};
+
+    use super::*;
+
+    fn is_free(p: &[f64]) -> bool {
+        // A wall with a gap at the top
+        !(p[0].abs() < 0.5 && p[1] < 1.0)
+    }
+
+    fn random_sample() -> Vec<f64> {
+        let between = Uniform::new(-2.0, 2.0);
+        let mut rng = rand::thread_rng();
+        vec![between.sample(&mut rng), between.sample(&mut rng)]
+    }
+
+    fn path_length(path: &[Vec<f64>]) -> f64 {
+        path.windows(2).map(|w| distance(&w[0], &w[1])).sum()
+    }
+
+    #[test]
+    fn test_rrt_star() {
+        let start = [-1.0, 0.0];
+        let goal = [1.0, 0.0];
+        let path = rrt_star(&start, &goal, is_free, random_sample, 0.1, 0.5, 3000).unwrap();
+        assert_eq!(path[0], start);
+        assert_eq!(path[path.len() - 1], goal);
+        let mut free = is_free;
+        for w in path.windows(2) {
+            assert!(is_edge_free(&w[0], &w[1], &mut free, 0.1));
+        }
+        // The shortest path through the gap is about 3.24.
+        let length = path_length(&path);
+        assert!(length < 4.0, "{}", length);
+    }
+
+    #[test]
+    fn test_rrt_star_fail() {
+        let start = [-1.0, 0.0];
+        let goal = [1.0, 0.0];
+        let is_free = |p: &[f64]| p[0].abs() > 0.5;
+        assert!(rrt_star(&start, &goal, is_free, random_sample, 0.1, 0.5, 100).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use num_traits::Float;
+
+/// Euclidean distance in the joint space
+pub(crate) fn distance<N>(a: &[N], b: &[N]) -> N
+where
+    N: Float,
+{
+    a.iter()
+        .zip(b)
+        .fold(N::zero(), |sum, (&a, &b)| sum + (a - b) * (a - b))
+        .sqrt()
+}
+
+/// Point moved from `from` toward `to` by `extend_length` at most
+pub(crate) fn steer<N>(from: &[N], to: &[N], extend_length: N) -> Vec<N>
+where
+    N: Float,
+{
+    let dist = distance(from, to);
+    if dist <= extend_length {
+        return to.to_vec();
+    }
+    let ratio = extend_length / dist;
+    from.iter()
+        .zip(to)
+        .map(|(&a, &b)| a + (b - a) * ratio)
+        .collect()
+}
+
+/// Check the points between `from` and `to` at intervals of `extend_length`
+///
+/// `from` itself is not checked.
+pub(crate) fn is_edge_free<FF, N>(from: &[N], to: &[N], is_free: &mut FF, extend_length: N) -> bool
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float,
+{
+    let num_steps = (distance(from, to) / extend_length)
+        .ceil()
+        .to_usize()
+        .unwrap_or(0)
+        .max(1);
+    (1..=num_steps).all(|i| {
+        let ratio = N::from(i).unwrap() / N::from(num_steps).unwrap();
+        let point = from
+            .iter()
+            .zip(to)
+            .map(|(&a, &b)| a + (b - a) * ratio)
+            .collect::<Vec<_>>();
+        is_free(&point)
+    })
+}
+
+/// Shorten `path` by connecting each point to the farthest later point which
+/// can be reached directly
+///
+/// Unlike the random shortcut of `rrt::smooth_path`, the result is always the
+/// same for the same path.
+pub(crate) fn shortcut_path<FF, N>(path: &mut Vec<Vec<N>>, mut is_free: FF, extend_length: N)
+where
+    FF: FnMut(&[N]) -> bool,
+    N: Float,
+{
+    let mut shortened = vec![];
+    let mut current = 0;
+    while current + 1 < path.len() {
+        shortened.push(path[current].clone());
+        current = (current + 2..path.len())
+            .rev()
+            .find(|&next| is_edge_free(&path[current], &path[next], &mut is_free, extend_length))
+            .unwrap_or(current + 1);
+    }
+    if let Some(last) = path.last() {
+        shortened.push(last.clone());
+    }
+    *path = shortened;
+}
+
+/// Indices of `points` sorted by the distance from `target`, nearest first
+pub(crate) fn sorted_by_distance<N>(points: &[Vec<N>], target: &[N]) -> Vec<(usize, N)>
+where
+    N: Float,
+{
+    let mut indices = points
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i, distance(p, target)))
+        .collect::<Vec<_>>();
+    indices.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shortcut_path() {
+        let is_free = |p: &[f64]| !(p[0] > 0.5 && p[0] < 1.5 && p[1] < 0.5);
+        let original = vec![
+            vec![0.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![2.0, 1.0],
+            vec![2.0, 0.0],
+        ];
+        let mut path = original.clone();
+        shortcut_path(&mut path, is_free, 0.05);
+        assert_eq!(path, vec![vec![0.0, 0.0], vec![1.0, 1.0], vec![2.0, 0.0]]);
+        let mut again = original;
+        shortcut_path(&mut again, is_free, 0.05);
+        assert_eq!(again, path);
+
+        let mut single = vec![vec![0.0, 0.0]];
+        shortcut_path(&mut single, is_free, 0.05);
+        assert_eq!(single, vec![vec![0.0, 0.0]]);
+    }
+}
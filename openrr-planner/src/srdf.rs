@@ -0,0 +1,392 @@
+//! Parser of the semantic robot description format (SRDF) of MoveIt
+//!
+//! Only the planning groups, the group states and the disabled collisions are supported.
+
+use std::{collections::HashMap, path::Path};
+
+use xml::{attribute::OwnedAttribute, reader::XmlEvent, EventReader};
+
+use crate::errors::*;
+
+/// Serial chain from `base_link` to `tip_link`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupChain {
+    pub base_link: String,
+    pub tip_link: String,
+}
+
+/// Planning group
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Group {
+    pub name: String,
+    pub chains: Vec<GroupChain>,
+    pub joints: Vec<String>,
+    pub links: Vec<String>,
+    /// Names of the groups included in this group
+    pub subgroups: Vec<String>,
+}
+
+/// Position of a joint in the group state
+#[derive(Debug, Clone, PartialEq)]
+pub struct JointValue {
+    pub name: String,
+    /// Only the first value is used for the joints which have multiple DoF.
+    pub value: f64,
+}
+
+/// Named joint positions of a group
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GroupState {
+    pub name: String,
+    pub group: String,
+    pub joints: Vec<JointValue>,
+}
+
+/// Pair of the links whose collision checking is disabled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisableCollisions {
+    pub link1: String,
+    pub link2: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Srdf {
+    pub name: String,
+    pub groups: Vec<Group>,
+    pub group_states: Vec<GroupState>,
+    pub disable_collisions: Vec<DisableCollisions>,
+}
+
+fn attribute(attributes: &[OwnedAttribute], element: &str, name: &str) -> Result<String> {
+    attributes
+        .iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.clone())
+        .ok_or_else(|| Error::ParseError(format!("{} attribute of <{}> in SRDF", name, element)))
+}
+
+fn optional_attribute(attributes: &[OwnedAttribute], name: &str) -> Option<String> {
+    attributes
+        .iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.clone())
+}
+
+/// Read SRDF from the string
+pub fn read_from_string(s: &str) -> Result<Srdf> {
+    let mut srdf = Srdf::default();
+    // Depth of the <group> elements, which are nested if the group has subgroups.
+    let mut group_depth = 0;
+    let mut group = Group::default();
+    let mut group_state: Option<GroupState> = None;
+    for event in EventReader::from_str(s) {
+        match event.map_err(|e| Error::ParseError(format!("SRDF: {}", e)))? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let element = name.local_name.as_str();
+                let attr = |name: &str| attribute(&attributes, element, name);
+                match element {
+                    "robot" => srdf.name = attr("name")?,
+                    "group" if group_depth == 0 => {
+                        group = Group {
+                            name: attr("name")?,
+                            ..Default::default()
+                        };
+                        group_depth = 1;
+                    }
+                    "group" => {
+                        if group_depth == 1 {
+                            group.subgroups.push(attr("name")?);
+                        }
+                        group_depth += 1;
+                    }
+                    "chain" if group_depth == 1 => group.chains.push(GroupChain {
+                        base_link: attr("base_link")?,
+                        tip_link: attr("tip_link")?,
+                    }),
+                    "joint" if group_depth == 1 => group.joints.push(attr("name")?),
+                    "link" if group_depth == 1 => group.links.push(attr("name")?),
+                    "group_state" => {
+                        group_state = Some(GroupState {
+                            name: attr("name")?,
+                            group: attr("group")?,
+                            joints: vec![],
+                        })
+                    }
+                    "joint" => {
+                        if let Some(state) = &mut group_state {
+                            let value = attr("value")?;
+                            let value = value
+                                .split_whitespace()
+                                .next()
+                                .and_then(|v| v.parse().ok())
+                                .ok_or_else(|| {
+                                    Error::ParseError(format!("joint value {:?} in SRDF", value))
+                                })?;
+                            state.joints.push(JointValue {
+                                name: attr("name")?,
+                                value,
+                            });
+                        }
+                    }
+                    "disable_collisions" => srdf.disable_collisions.push(DisableCollisions {
+                        link1: attr("link1")?,
+                        link2: attr("link2")?,
+                        reason: optional_attribute(&attributes, "reason"),
+                    }),
+                    _ => {}
+                }
+            }
+            XmlEvent::EndElement { name } => match name.local_name.as_str() {
+                "group" => {
+                    group_depth -= 1;
+                    if group_depth == 0 {
+                        srdf.groups.push(std::mem::take(&mut group));
+                    }
+                }
+                "group_state" => srdf.group_states.extend(group_state.take()),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    Ok(srdf)
+}
+
+/// Read SRDF from the file
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Srdf> {
+    read_from_string(&std::fs::read_to_string(path)?)
+}
+
+impl Srdf {
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|g| g.name == name)
+    }
+
+    /// Names of the movable joints in the group, including its subgroups
+    ///
+    /// Returns an error if the group includes itself via its subgroups.
+    pub fn group_joint_names(
+        &self,
+        group: &Group,
+        urdf_robot: &urdf_rs::Robot,
+    ) -> Result<Vec<String>> {
+        self.group_joint_names_with_ancestors(group, urdf_robot, &mut vec![])
+    }
+
+    /// `ancestors` are the names of the groups which include `group`
+    fn group_joint_names_with_ancestors(
+        &self,
+        group: &Group,
+        urdf_robot: &urdf_rs::Robot,
+        ancestors: &mut Vec<String>,
+    ) -> Result<Vec<String>> {
+        if ancestors.contains(&group.name) {
+            return Err(Error::ParseError(format!(
+                "cyclic subgroups: {} -> {}",
+                ancestors.join(" -> "),
+                group.name
+            )));
+        }
+        ancestors.push(group.name.clone());
+        let mut names = vec![];
+        for chain in &group.chains {
+            names.extend(
+                chain
+                    .joints(urdf_robot)?
+                    .into_iter()
+                    .map(|j| j.name.clone()),
+            );
+        }
+        for name in &group.joints {
+            if !urdf_robot.joints.iter().any(|j| j.name == *name) {
+                return Err(Error::NotFound(name.to_owned()));
+            }
+            names.push(name.to_owned());
+        }
+        for link in &group.links {
+            // The root link has no joints.
+            if let Some(joint) = urdf_robot.joints.iter().find(|j| j.child.link == *link) {
+                names.push(joint.name.clone());
+            }
+        }
+        for subgroup in &group.subgroups {
+            let subgroup = self
+                .group(subgroup)
+                .ok_or_else(|| Error::NotFound(subgroup.to_owned()))?;
+            names.extend(self.group_joint_names_with_ancestors(subgroup, urdf_robot, ancestors)?);
+        }
+        ancestors.pop();
+
+        let mut joint_names: Vec<String> = vec![];
+        for name in names {
+            let is_movable = urdf_robot.joints.iter().any(|j| {
+                j.name == name
+                    && matches!(
+                        j.joint_type,
+                        urdf_rs::JointType::Revolute
+                            | urdf_rs::JointType::Continuous
+                            | urdf_rs::JointType::Prismatic
+                    )
+            });
+            if is_movable && !joint_names.contains(&name) {
+                joint_names.push(name);
+            }
+        }
+        Ok(joint_names)
+    }
+
+    /// Pairs of the joint names for the links whose collision checking is disabled
+    ///
+    /// The collision models are named after the joints in [`CollisionDetector`](crate::CollisionDetector).
+    pub fn disabled_collision_joint_pairs(
+        &self,
+        urdf_robot: &urdf_rs::Robot,
+    ) -> Result<Vec<(String, String)>> {
+        let link_joint_map: HashMap<String, String> = k::urdf::link_to_joint_map(urdf_robot);
+        let joint_name = |link: &String| {
+            link_joint_map
+                .get(link)
+                .cloned()
+                .ok_or_else(|| Error::NotFound(link.to_owned()))
+        };
+        self.disable_collisions
+            .iter()
+            .map(|d| Ok((joint_name(&d.link1)?, joint_name(&d.link2)?)))
+            .collect()
+    }
+}
+
+impl GroupChain {
+    /// Joints from `base_link` to `tip_link`, including fixed joints
+    pub fn joints<'a>(&self, urdf_robot: &'a urdf_rs::Robot) -> Result<Vec<&'a urdf_rs::Joint>> {
+        let mut joints = vec![];
+        let mut link = &self.tip_link;
+        while *link != self.base_link {
+            let joint = urdf_robot
+                .joints
+                .iter()
+                .find(|j| j.child.link == *link)
+                .ok_or_else(|| {
+                    Error::NotFound(format!(
+                        "chain from {} to {}",
+                        self.base_link, self.tip_link
+                    ))
+                })?;
+            joints.push(joint);
+            link = &joint.parent.link;
+        }
+        joints.reverse();
+        Ok(joints)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_SRDF: &str = r#"<?xml version="1.0" ?>
+<robot name="robo">
+  <group name="l_arm">
+    <chain base_link="root" tip_link="l_tool" />
+  </group>
+  <group name="l_gripper">
+    <joint name="l_gripper_linear1" />
+    <link name="l_gripper2" />
+  </group>
+  <group name="l_arm_with_gripper">
+    <group name="l_arm" />
+    <group name="l_gripper" />
+  </group>
+  <group_state name="home" group="l_gripper">
+    <joint name="l_gripper_linear1" value="0.01" />
+    <joint name="l_gripper_linear2" value="-0.01" />
+  </group_state>
+  <disable_collisions link1="l_shoulder1" link2="l_shoulder2" reason="Adjacent" />
+  <disable_collisions link1="l_gripper1" link2="l_gripper2" />
+</robot>
+"#;
+
+    #[test]
+    fn test_srdf() {
+        let srdf = read_from_string(SAMPLE_SRDF).unwrap();
+        assert_eq!(srdf.name, "robo");
+        assert_eq!(srdf.groups.len(), 3);
+        assert_eq!(
+            srdf.groups[0].chains,
+            vec![GroupChain {
+                base_link: "root".to_owned(),
+                tip_link: "l_tool".to_owned(),
+            }]
+        );
+        assert_eq!(
+            srdf.group("l_arm_with_gripper").unwrap().subgroups,
+            vec!["l_arm".to_owned(), "l_gripper".to_owned()]
+        );
+        assert_eq!(srdf.group_states.len(), 1);
+        assert_eq!(srdf.group_states[0].group, "l_gripper");
+        assert_eq!(srdf.group_states[0].joints[1].value, -0.01);
+        assert_eq!(srdf.disable_collisions.len(), 2);
+        assert_eq!(
+            srdf.disable_collisions[0].reason.as_deref(),
+            Some("Adjacent")
+        );
+        assert_eq!(srdf.disable_collisions[1].reason, None);
+
+        let urdf_robot = urdf_rs::read_file("sample.urdf").unwrap();
+        let arm = srdf.group("l_arm").unwrap();
+        assert_eq!(
+            srdf.group_joint_names(arm, &urdf_robot).unwrap(),
+            vec![
+                "l_shoulder_yaw",
+                "l_shoulder_pitch",
+                "l_shoulder_roll",
+                "l_elbow_pitch",
+                "l_wrist_yaw",
+                "l_wrist_pitch"
+            ]
+        );
+        let chain_joints = arm.chains[0].joints(&urdf_robot).unwrap();
+        assert_eq!(chain_joints.last().unwrap().name, "l_tool_fixed");
+        let all = srdf.group("l_arm_with_gripper").unwrap();
+        assert_eq!(srdf.group_joint_names(all, &urdf_robot).unwrap().len(), 8);
+        assert_eq!(
+            srdf.disabled_collision_joint_pairs(&urdf_robot).unwrap()[0],
+            ("l_shoulder_yaw".to_owned(), "l_shoulder_pitch".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_cyclic_subgroups() {
+        let srdf = read_from_string(
+            r#"<?xml version="1.0" ?>
+<robot name="robo">
+  <group name="a">
+    <joint name="l_shoulder_yaw" />
+    <group name="b" />
+  </group>
+  <group name="b">
+    <group name="c" />
+  </group>
+  <group name="c">
+    <group name="a" />
+  </group>
+  <group name="d">
+    <group name="a" />
+  </group>
+</robot>
+"#,
+        )
+        .unwrap();
+        let urdf_robot = urdf_rs::read_file("sample.urdf").unwrap();
+        for name in &["a", "b", "c", "d"] {
+            let group = srdf.group(name).unwrap();
+            assert!(matches!(
+                srdf.group_joint_names(group, &urdf_robot),
+                Err(Error::ParseError(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+use k::{nalgebra as na, InverseKinematicsSolver, SubsetOf};
+use na::RealField;
+
+use crate::errors::Error;
+
+/// Names of the virtual joints of the mobile base added by [`add_virtual_base_joints`]
+///
+/// The joints are x (prismatic), y (prismatic) and yaw (revolute) in this order.
+pub const VIRTUAL_BASE_JOINT_NAMES: [&str; 3] =
+    ["virtual_base_x", "virtual_base_y", "virtual_base_yaw"];
+
+/// Adds the virtual planar joints of the mobile base to the root of the robot
+///
+/// The IK of the chain from the virtual joints moves the base on the floor in
+/// addition to the joints of the robot. The positions of the virtual joints
+/// are the pose of the base relative to the current pose.
+pub fn add_virtual_base_joints<T>(robot: k::Chain<T>) -> k::Chain<T>
+where
+    T: RealField + SubsetOf<f64>,
+{
+    let x = k::NodeBuilder::new()
+        .name(VIRTUAL_BASE_JOINT_NAMES[0])
+        .joint_type(k::JointType::Linear {
+            axis: na::Vector3::x_axis(),
+        })
+        .into_node();
+    let y = k::NodeBuilder::new()
+        .name(VIRTUAL_BASE_JOINT_NAMES[1])
+        .joint_type(k::JointType::Linear {
+            axis: na::Vector3::y_axis(),
+        })
+        .into_node();
+    let yaw = k::NodeBuilder::new()
+        .name(VIRTUAL_BASE_JOINT_NAMES[2])
+        .joint_type(k::JointType::Rotational {
+            axis: na::Vector3::z_axis(),
+        })
+        .into_node();
+    y.set_parent(&x);
+    yaw.set_parent(&y);
+    if let Some(root) = robot.iter().next() {
+        root.set_parent(&yaw);
+    }
+    k::Chain::from_root(x)
+}
+
+/// Pose of the base from the positions of the virtual joints, which are the
+/// first three positions of the chain created by [`add_virtual_base_joints`]
+pub fn base_pose_from_virtual_joint_positions<T>(positions: &[T]) -> na::Isometry2<T>
+where
+    T: RealField,
+{
+    na::Isometry2::new(na::Vector2::new(positions[0], positions[1]), positions[2])
+}
+
+/// Jacobian IK solver which weights the motion of each joint
+///
+/// The weighted damped least squares method is used. The joints with larger
+/// weights move less, e.g. the virtual joints of the base added by
+/// [`add_virtual_base_joints`] can be heavier than the joints of the arm.
+#[derive(Debug, Clone)]
+pub struct WeightedJacobianIkSolver<T>
+where
+    T: RealField,
+{
+    /// If the distance is smaller than this value, it is reached.
+    pub allowable_target_distance: T,
+    /// If the angle distance is smaller than this value, it is reached.
+    pub allowable_target_angle: T,
+    /// Multiplier for the joint position update in each iteration
+    pub jacobian_multiplier: T,
+    /// The number of iterations
+    pub num_max_try: usize,
+    /// Damping factor, which avoids the large motions near the singularities
+    pub damping: T,
+    /// Weights of the joints, which must be positive and finite. The default weight is 1.0.
+    pub joint_weights: HashMap<String, T>,
+}
+
+impl<T> WeightedJacobianIkSolver<T>
+where
+    T: RealField + SubsetOf<f64>,
+{
+    pub fn new(
+        allowable_target_distance: T,
+        allowable_target_angle: T,
+        jacobian_multiplier: T,
+        num_max_try: usize,
+    ) -> Self {
+        Self {
+            allowable_target_distance,
+            allowable_target_angle,
+            jacobian_multiplier,
+            num_max_try,
+            damping: na::convert(0.01),
+            joint_weights: HashMap::new(),
+        }
+    }
+
+    /// Sets the weight of the joint
+    pub fn joint_weight(mut self, joint_name: &str, weight: T) -> Self {
+        self.joint_weights.insert(joint_name.to_owned(), weight);
+        self
+    }
+
+    /// Checks that all of the weights are positive and finite
+    pub fn validate(&self) -> crate::errors::Result<()> {
+        for (name, weight) in &self.joint_weights {
+            let weight: f64 = weight.to_superset();
+            if !weight.is_finite() || weight <= 0.0 {
+                return Err(Error::Other {
+                    error: format!(
+                        "the weight of the joint {} must be positive and finite, but {}",
+                        name, weight
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T> InverseKinematicsSolver<T> for WeightedJacobianIkSolver<T>
+where
+    T: RealField + SubsetOf<f64>,
+{
+    fn solve_with_constraints(
+        &self,
+        arm: &k::SerialChain<T>,
+        target_pose: &na::Isometry3<T>,
+        constraints: &k::Constraints,
+    ) -> Result<(), k::Error> {
+        let used = [
+            constraints.position_x,
+            constraints.position_y,
+            constraints.position_z,
+            constraints.rotation_x,
+            constraints.rotation_y,
+            constraints.rotation_z,
+        ];
+        let rows: Vec<usize> = (0..6).filter(|i| used[*i]).collect();
+        let dof = arm.dof();
+        let inverse_weights = na::DVector::from_iterator(
+            dof,
+            arm.iter_joints().map(|j| {
+                self.joint_weights
+                    .get(&j.name)
+                    .map_or_else(T::one, |w| T::one() / *w)
+            }),
+        );
+        let initial_positions = arm.joint_positions();
+
+        let mut diff = na::Vector6::zeros();
+        for _ in 0..self.num_max_try {
+            arm.update_transforms();
+            let current_pose = arm.end_transform();
+            let position_diff = target_pose.translation.vector - current_pose.translation.vector;
+            let rotation_diff =
+                (target_pose.rotation * current_pose.rotation.inverse()).scaled_axis();
+            diff = na::Vector6::new(
+                position_diff[0],
+                position_diff[1],
+                position_diff[2],
+                rotation_diff[0],
+                rotation_diff[1],
+                rotation_diff[2],
+            );
+            let (position_error2, angle_error2) =
+                rows.iter().fold((T::zero(), T::zero()), |(p, a), i| {
+                    let d2 = diff[*i] * diff[*i];
+                    if *i < 3 {
+                        (p + d2, a)
+                    } else {
+                        (p, a + d2)
+                    }
+                });
+            if position_error2.sqrt() < self.allowable_target_distance
+                && angle_error2.sqrt() < self.allowable_target_angle
+            {
+                return Ok(());
+            }
+
+            // dq = W^-1 J^T (J W^-1 J^T + damping^2 I)^-1 e
+            let jacobian = k::jacobian(arm);
+            let j = na::DMatrix::from_fn(rows.len(), dof, |r, c| jacobian[(rows[r], c)]);
+            let e = na::DVector::from_fn(rows.len(), |r, _| diff[rows[r]]);
+            let jw = &j * na::DMatrix::from_diagonal(&inverse_weights);
+            let a = &jw * j.transpose()
+                + na::DMatrix::identity(rows.len(), rows.len()) * (self.damping * self.damping);
+            let x = match a.lu().solve(&e) {
+                Some(x) => x,
+                None => break,
+            };
+            let dq = jw.transpose() * x;
+            let positions: Vec<T> = arm
+                .joint_positions()
+                .iter()
+                .zip(dq.iter())
+                .map(|(q, d)| *q + *d * self.jacobian_multiplier)
+                .collect();
+            arm.set_joint_positions_clamped(&positions);
+        }
+        arm.set_joint_positions_unchecked(&initial_positions);
+        let to_f64 = |v: T| -> f64 { v.to_superset() };
+        Err(k::Error::NotConvergedError {
+            num_tried: self.num_max_try,
+            position_diff: na::Vector3::new(to_f64(diff[0]), to_f64(diff[1]), to_f64(diff[2])),
+            rotation_diff: na::Vector3::new(to_f64(diff[3]), to_f64(diff[4]), to_f64(diff[5])),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whole_body_ik() {
+        let robot =
+            add_virtual_base_joints(k::Chain::<f64>::from_urdf_file("sample.urdf").unwrap());
+        let arm = k::SerialChain::from_end(robot.find("l_tool_fixed").unwrap());
+        assert_eq!(arm.dof(), 9);
+        assert_eq!(
+            arm.iter_joints()
+                .take(3)
+                .map(|j| j.name.clone())
+                .collect::<Vec<_>>(),
+            VIRTUAL_BASE_JOINT_NAMES
+        );
+        arm.update_transforms();
+        let initial_pose = arm.end_transform();
+
+        let mut solver = WeightedJacobianIkSolver::new(0.001, 0.005, 0.5, 1000);
+        for name in &VIRTUAL_BASE_JOINT_NAMES {
+            solver = solver.joint_weight(name, 10.0);
+        }
+        // Out of the range of the arm
+        let mut target_pose = initial_pose;
+        target_pose.translation.vector.x += 2.0;
+        solver.solve(&arm, &target_pose).unwrap();
+        arm.update_transforms();
+        assert!(
+            (arm.end_transform().translation.vector - target_pose.translation.vector).norm()
+                < 0.001
+        );
+        let base_pose = base_pose_from_virtual_joint_positions(&arm.joint_positions());
+        assert!(base_pose.translation.vector.x > 1.0, "{}", base_pose);
+
+        // The base is not moved if the weight is very large.
+        let robot =
+            add_virtual_base_joints(k::Chain::<f64>::from_urdf_file("sample.urdf").unwrap());
+        let arm = k::SerialChain::from_end(robot.find("l_tool_fixed").unwrap());
+        for name in &VIRTUAL_BASE_JOINT_NAMES {
+            solver = solver.joint_weight(name, 1e9);
+        }
+        assert!(solver.solve(&arm, &target_pose).is_err());
+        assert!(arm.joint_positions().iter().all(|p| *p == 0.0));
+    }
+
+    #[test]
+    fn test_validate_joint_weights() {
+        let solver = WeightedJacobianIkSolver::new(0.001, 0.005, 0.5, 1000);
+        assert!(solver.validate().is_ok());
+        assert!(solver.clone().joint_weight("a", 10.0).validate().is_ok());
+        for weight in &[0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(solver
+                .clone()
+                .joint_weight("a", *weight)
+                .validate()
+                .is_err());
+        }
+    }
+}
@@ -7,7 +7,7 @@
 use std::convert::TryInto;
 
 use abi_stable::StableAbi;
-use arci::{BaseVelocity, Error, Isometry2, Isometry3, WaitFuture};
+use arci::{BaseVelocity, Error, Isometry2, Isometry3, PoseWithCovariance, WaitFuture};
 
 use super::*;
 /// The plugin trait.
@@ -162,6 +162,22 @@ impl arci::Localization for LocalizationProxy {
     fn current_pose(&self, frame_id: &str) -> Result<Isometry2<f64>, Error> {
         Ok(self.0.current_pose(frame_id.into()).into_result()?.into())
     }
+
+    fn current_pose_with_covariance(&self, frame_id: &str) -> Result<PoseWithCovariance, Error> {
+        Ok(self
+            .0
+            .current_pose_with_covariance(frame_id.into())
+            .into_result()?
+            .into())
+    }
+
+    fn set_initial_pose(&self, pose: PoseWithCovariance, frame_id: &str) -> Result<(), Error> {
+        Ok(self
+            .0
+            .set_initial_pose(pose.into(), frame_id.into())
+            .into_result()?
+            .into())
+    }
 }
 impl std::fmt::Debug for LocalizationProxy {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
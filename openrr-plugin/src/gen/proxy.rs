@@ -99,6 +99,15 @@ pub(crate) type LocalizationTraitObject = RLocalizationTrait_TO<RBox<()>>;
 #[abi_stable::sabi_trait]
 pub(crate) trait RLocalizationTrait: Send + Sync + 'static {
     fn current_pose(&self, frame_id: RStr<'_>) -> RResult<RIsometry2F64, RError>;
+    fn current_pose_with_covariance(
+        &self,
+        frame_id: RStr<'_>,
+    ) -> RResult<RPoseWithCovariance, RError>;
+    fn set_initial_pose(
+        &self,
+        pose: RPoseWithCovariance,
+        frame_id: RStr<'_>,
+    ) -> RResult<(), RError>;
 }
 impl<T> RLocalizationTrait for T
 where
@@ -107,6 +116,30 @@ where
     fn current_pose(&self, frame_id: RStr<'_>) -> RResult<RIsometry2F64, RError> {
         ROk(rtry!(arci::Localization::current_pose(self, frame_id.into())).into())
     }
+
+    fn current_pose_with_covariance(
+        &self,
+        frame_id: RStr<'_>,
+    ) -> RResult<RPoseWithCovariance, RError> {
+        ROk(rtry!(arci::Localization::current_pose_with_covariance(
+            self,
+            frame_id.into()
+        ))
+        .into())
+    }
+
+    fn set_initial_pose(
+        &self,
+        pose: RPoseWithCovariance,
+        frame_id: RStr<'_>,
+    ) -> RResult<(), RError> {
+        ROk(rtry!(arci::Localization::set_initial_pose(
+            self,
+            pose.into(),
+            frame_id.into()
+        ))
+        .into())
+    }
 }
 pub(crate) type MoveBaseTraitObject = RMoveBaseTrait_TO<RBox<()>>;
 #[abi_stable::sabi_trait]
@@ -436,6 +436,36 @@ impl From<RBaseVelocity> for arci::BaseVelocity {
     }
 }
 
+// =============================================================================
+// arci::PoseWithCovariance
+
+/// FFI-safe equivalent of [`arci::PoseWithCovariance`].
+#[repr(C)]
+#[derive(StableAbi)]
+pub(crate) struct RPoseWithCovariance {
+    pose: RIsometry2F64,
+    /// Column-major 3x3 matrix.
+    covariance: RVec<RF64>,
+}
+
+impl From<arci::PoseWithCovariance> for RPoseWithCovariance {
+    fn from(val: arci::PoseWithCovariance) -> Self {
+        Self {
+            pose: val.pose.into(),
+            covariance: val.covariance.iter().copied().map(RF64::from).collect(),
+        }
+    }
+}
+
+impl From<RPoseWithCovariance> for arci::PoseWithCovariance {
+    fn from(val: RPoseWithCovariance) -> Self {
+        Self {
+            pose: val.pose.into(),
+            covariance: nalgebra::Matrix3::from_iterator(val.covariance.into_iter().map(f64::from)),
+        }
+    }
+}
+
 // =============================================================================
 // arci::gamepad::GamepadEvent
 
@@ -6,8 +6,8 @@ use std::{
 use arci::{
     gamepad::GamepadEvent, BaseVelocity, DummyGamepad, DummyJointTrajectoryClient,
     DummyLocalization, DummyMoveBase, DummyNavigation, DummySpeaker, DummyTransformResolver,
-    Gamepad, Isometry2, JointTrajectoryClient, Localization, MoveBase, Navigation, Speaker,
-    TrajectoryPoint, TransformResolver, Vector2,
+    Gamepad, Isometry2, JointTrajectoryClient, Localization, MoveBase, Navigation,
+    PoseWithCovariance, Speaker, TrajectoryPoint, TransformResolver, Vector2,
 };
 use assert_approx_eq::assert_approx_eq;
 use openrr_plugin::{
@@ -130,6 +130,16 @@ async fn localization() {
 
     let pose = proxy.current_pose("").unwrap();
     assert_eq!(pose, pose.inverse()); // only identity mapping satisfies this
+
+    let initial_pose =
+        PoseWithCovariance::from_std_dev(Isometry2::new(Vector2::new(1.0, 2.0), 3.0), 0.5, 0.1);
+    proxy.set_initial_pose(initial_pose, "map").unwrap();
+    let pose = proxy.current_pose_with_covariance("map").unwrap();
+    assert_approx_eq!(pose.pose.translation.x, 1.0);
+    assert_approx_eq!(pose.pose.translation.y, 2.0);
+    assert_approx_eq!(pose.pose.rotation.angle(), 3.0);
+    assert_approx_eq!(pose.covariance[(0, 0)], 0.25);
+    assert_approx_eq!(pose.covariance[(2, 2)], 0.01);
 }
 
 #[tokio::test]
@@ -56,6 +56,11 @@ async fn main() -> anyhow::Result<()> {
 
     println!("Localization");
     dbg!(loc.current_pose("")?);
+    dbg!(loc.current_pose_with_covariance("")?);
+    loc.set_initial_pose(
+        arci::Isometry2::new(arci::Vector2::new(1.0, 2.0), 3.0).into(),
+        "",
+    )?;
 
     println!("TransformResolver");
     resolver.resolve_transformation("", "", SystemTime::UNIX_EPOCH)?;
@@ -104,6 +104,29 @@ impl arci::Localization for DebugLocalization {
         );
         Ok(arci::Isometry2::new(arci::Vector2::new(0.0, 0.0), 0.0))
     }
+
+    fn current_pose_with_covariance(
+        &self,
+        frame_id: &str,
+    ) -> Result<arci::PoseWithCovariance, arci::Error> {
+        println!(
+            "Server received Localization::current_pose_with_covariance (frame_id: {:?})",
+            frame_id
+        );
+        Ok(arci::Isometry2::new(arci::Vector2::new(0.0, 0.0), 0.0).into())
+    }
+
+    fn set_initial_pose(
+        &self,
+        pose: arci::PoseWithCovariance,
+        frame_id: &str,
+    ) -> Result<(), arci::Error> {
+        println!(
+            "Server received Localization::set_initial_pose (pose: {:?}, frame_id: {:?})",
+            pose, frame_id
+        );
+        Ok(())
+    }
 }
 
 #[derive(Default)]
@@ -4,7 +4,7 @@
 #![allow(unused_variables)]
 #![allow(clippy::useless_conversion, clippy::unit_arg)]
 
-use arci::{BaseVelocity, Error, Isometry2, Isometry3, WaitFuture};
+use arci::{BaseVelocity, Error, Isometry2, Isometry3, PoseWithCovariance, WaitFuture};
 
 use super::*;
 #[derive(Debug, Clone)]
@@ -184,6 +184,25 @@ impl arci::Localization for RemoteLocalizationSender {
             .into_inner()
             .into())
     }
+
+    fn current_pose_with_covariance(&self, frame_id: &str) -> Result<PoseWithCovariance, Error> {
+        let mut client = self.client.clone();
+        let args = tonic::Request::new(frame_id.into());
+        Ok(block_in_place(client.current_pose_with_covariance(args))
+            .map_err(|e| arci::Error::Other(e.into()))?
+            .into_inner()
+            .try_into()
+            .map_err(|e: tonic::Status| arci::Error::Other(e.into()))?)
+    }
+
+    fn set_initial_pose(&self, pose: PoseWithCovariance, frame_id: &str) -> Result<(), Error> {
+        let mut client = self.client.clone();
+        let args = tonic::Request::new((pose, frame_id).into());
+        Ok(block_in_place(client.set_initial_pose(args))
+            .map_err(|e| arci::Error::Other(e.into()))?
+            .into_inner()
+            .into())
+    }
 }
 #[tonic::async_trait]
 impl<T> pb::localization_server::Localization for RemoteLocalizationReceiver<T>
@@ -200,6 +219,35 @@ where
             .into();
         Ok(tonic::Response::new(res))
     }
+
+    async fn current_pose_with_covariance(
+        &self,
+        request: tonic::Request<::prost::alloc::string::String>,
+    ) -> Result<tonic::Response<pb::PoseWithCovariance>, tonic::Status> {
+        let request = request.into_inner();
+        let res = arci::Localization::current_pose_with_covariance(&self.inner, &request)
+            .map_err(|e| tonic::Status::unknown(e.to_string()))?
+            .into();
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn set_initial_pose(
+        &self,
+        request: tonic::Request<pb::SetInitialPoseRequest>,
+    ) -> Result<tonic::Response<()>, tonic::Status> {
+        let request = request.into_inner();
+        let res = arci::Localization::set_initial_pose(
+            &self.inner,
+            request
+                .pose
+                .ok_or_else(|| tonic::Status::invalid_argument("pose is not set"))?
+                .try_into()?,
+            &request.frame_id,
+        )
+        .map_err(|e| tonic::Status::unknown(e.to_string()))?
+        .into();
+        Ok(tonic::Response::new(res))
+    }
 }
 #[derive(Debug, Clone)]
 pub struct RemoteMoveBaseSender {
@@ -12,7 +12,7 @@ mod pb {
 mod impls;
 
 use std::{
-    convert::TryInto,
+    convert::{TryFrom, TryInto},
     future::Future,
     net::SocketAddr,
     time::{Duration, SystemTime},
@@ -265,6 +265,35 @@ impl From<pb::Isometry2> for arci::Isometry2<f64> {
     }
 }
 
+impl From<arci::PoseWithCovariance> for pb::PoseWithCovariance {
+    fn from(val: arci::PoseWithCovariance) -> Self {
+        Self {
+            pose: Some(val.pose.into()),
+            covariance: val.covariance.transpose().iter().copied().collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::PoseWithCovariance> for arci::PoseWithCovariance {
+    type Error = tonic::Status;
+
+    fn try_from(val: pb::PoseWithCovariance) -> Result<Self, Self::Error> {
+        let pose = val
+            .pose
+            .ok_or_else(|| tonic::Status::invalid_argument("pose is not set"))?;
+        if val.covariance.len() != 9 {
+            return Err(tonic::Status::invalid_argument(format!(
+                "covariance must have 9 elements, but got {}",
+                val.covariance.len()
+            )));
+        }
+        Ok(Self {
+            pose: pose.into(),
+            covariance: nalgebra::Matrix3::from_row_slice(&val.covariance),
+        })
+    }
+}
+
 impl From<arci::Isometry3<f64>> for pb::Isometry3 {
     fn from(val: arci::Isometry3<f64>) -> Self {
         Self {
@@ -306,6 +335,15 @@ impl From<(arci::Isometry2<f64>, &str, Duration)> for pb::GoalPoseRequest {
     }
 }
 
+impl From<(arci::PoseWithCovariance, &str)> for pb::SetInitialPoseRequest {
+    fn from((pose, frame_id): (arci::PoseWithCovariance, &str)) -> Self {
+        Self {
+            pose: Some(pose.into()),
+            frame_id: frame_id.into(),
+        }
+    }
+}
+
 impl From<(&str, &str, SystemTime)> for pb::ResolveTransformationRequest {
     fn from((from, to, time): (&str, &str, SystemTime)) -> Self {
         Self {
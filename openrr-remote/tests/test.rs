@@ -11,8 +11,8 @@ use anyhow::Result;
 use arci::{
     gamepad::GamepadEvent, BaseVelocity, DummyGamepad, DummyJointTrajectoryClient,
     DummyLocalization, DummyMoveBase, DummyNavigation, DummySpeaker, DummyTransformResolver,
-    Gamepad, Isometry2, JointTrajectoryClient, Localization, MoveBase, Navigation, Speaker,
-    TrajectoryPoint, TransformResolver, Vector2,
+    Gamepad, Isometry2, JointTrajectoryClient, Localization, MoveBase, Navigation,
+    PoseWithCovariance, Speaker, TrajectoryPoint, TransformResolver, Vector2,
 };
 use assert_approx_eq::assert_approx_eq;
 use openrr_remote::{
@@ -173,6 +173,19 @@ async fn localization() -> Result<()> {
     let pose = loc.current_pose("")?;
     assert_eq!(pose, pose.inverse()); // only identity mapping satisfies this
 
+    let mut initial_pose =
+        PoseWithCovariance::from_std_dev(Isometry2::new(Vector2::new(1.0, 2.0), 3.0), 0.5, 0.1);
+    initial_pose.covariance[(0, 1)] = 0.2;
+    loc.set_initial_pose(initial_pose, "map")?;
+    let pose = loc.current_pose_with_covariance("map")?;
+    assert_approx_eq!(pose.pose.translation.x, 1.0);
+    assert_approx_eq!(pose.pose.translation.y, 2.0);
+    assert_approx_eq!(pose.pose.rotation.angle(), 3.0);
+    assert_approx_eq!(pose.covariance[(0, 0)], 0.25);
+    assert_approx_eq!(pose.covariance[(0, 1)], 0.2);
+    assert_approx_eq!(pose.covariance[(1, 0)], 0.0);
+    assert_approx_eq!(pose.covariance[(2, 2)], 0.01);
+
     Ok(())
 }
 
@@ -143,6 +143,7 @@ impl ControlNodesConfig {
                     joints_poses,
                     joint_trajectory_clients,
                     speaker.clone(),
+                    Some(robot_client.clone()),
                 )));
             }
         }
@@ -1,13 +1,18 @@
-use std::{collections::HashMap, sync::Mutex, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use arci::{
     gamepad::{Button, GamepadEvent},
     JointTrajectoryClient, Speaker,
 };
 use async_trait::async_trait;
-use openrr_client::JointsPose;
+use openrr_client::{ArcRobotClient, JointsPose};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use tracing::error;
 
 use crate::ControlNode;
 
@@ -63,6 +68,27 @@ impl JointsPoseSenderInner {
         None
     }
 
+    /// Name of the new pose of the client, which is not used yet
+    fn new_pose_name(&self, client_name: &str) -> String {
+        (1..)
+            .map(|i| format!("saved{}", i))
+            .find(|name| {
+                !self
+                    .joints_poses
+                    .iter()
+                    .any(|p| p.client_name == client_name && p.pose_name == *name)
+            })
+            .unwrap()
+    }
+
+    /// Adds the pose and selects it.
+    fn add_pose(&mut self, joints_pose: JointsPose) -> &str {
+        self.submode = format!(" {} {}", joints_pose.client_name, joints_pose.pose_name);
+        self.joints_poses.push(joints_pose);
+        self.pose_index = self.joints_poses.len() - 1;
+        &self.submode
+    }
+
     fn get_target_name_positions(&self) -> (String, Vec<f64>) {
         let joints_pose = &self.joints_poses[self.pose_index];
         (
@@ -80,6 +106,8 @@ where
     joint_trajectory_clients: HashMap<String, J>,
     speaker: S,
     duration: Duration,
+    /// Used to keep the saved poses. The poses are kept only in this node without it.
+    robot_client: Option<Arc<ArcRobotClient>>,
     inner: Mutex<JointsPoseSenderInner>,
 }
 
@@ -94,12 +122,14 @@ where
         joint_trajectory_clients: HashMap<String, J>,
         speaker: S,
         duration: Duration,
+        robot_client: Option<Arc<ArcRobotClient>>,
     ) -> Self {
         Self {
             mode,
             joint_trajectory_clients,
             speaker,
             duration,
+            robot_client,
             inner: Mutex::new(JointsPoseSenderInner::new(joints_poses)),
         }
     }
@@ -109,6 +139,7 @@ where
         joints_poses: Vec<JointsPose>,
         joint_trajectory_clients: HashMap<String, J>,
         speaker: S,
+        robot_client: Option<Arc<ArcRobotClient>>,
     ) -> Self {
         Self::new(
             config.mode,
@@ -116,8 +147,27 @@ where
             joint_trajectory_clients,
             speaker,
             Duration::from_secs_f64(config.duration_secs),
+            robot_client,
         )
     }
+
+    /// Saves the current joint positions of the client of the selected pose as a new pose.
+    fn save_current_pose(&self) -> Result<String, arci::Error> {
+        let mut inner = self.inner.lock().unwrap();
+        let client_name = inner.get_target_name_positions().0;
+        let pose_name = inner.new_pose_name(&client_name);
+        let joints_pose = match &self.robot_client {
+            Some(robot_client) => robot_client
+                .save_joints_pose(&client_name, &pose_name)
+                .map_err(|e| arci::Error::Other(e.into()))?,
+            None => JointsPose {
+                positions: self.joint_trajectory_clients[&client_name].current_joint_positions()?,
+                pose_name,
+                client_name,
+            },
+        };
+        Ok(inner.add_pose(joints_pose).to_owned())
+    }
 }
 
 #[async_trait]
@@ -127,6 +177,22 @@ where
     J: JointTrajectoryClient,
 {
     fn handle_event(&self, event: arci::gamepad::GamepadEvent) {
+        // Saving needs the trigger held as sending does, not to save by accident.
+        if matches!(event, GamepadEvent::ButtonPressed(Button::South))
+            && self.inner.lock().unwrap().is_trigger_holding
+        {
+            match self.save_current_pose() {
+                Ok(submode) => {
+                    // do not wait
+                    let _ = self
+                        .speaker
+                        .speak(&format!("{} saved{}", self.mode, submode))
+                        .unwrap();
+                }
+                Err(e) => error!("Failed to save the current pose: {}", e),
+            }
+            return;
+        }
         if let Some(submode) = self.inner.lock().unwrap().handle_event(event) {
             // do not wait
             let _ = self
@@ -177,3 +243,43 @@ fn default_mode() -> String {
 fn default_duration_secs() -> f64 {
     2.0
 }
+
+#[cfg(test)]
+mod tests {
+    use arci::{DummyJointTrajectoryClient, DummySpeaker};
+
+    use super::*;
+
+    #[test]
+    fn test_save_current_pose() {
+        let client = DummyJointTrajectoryClient::new(vec!["a".to_owned(), "b".to_owned()]);
+        *client.positions.lock().unwrap() = vec![0.5, -0.5];
+        let mut clients = HashMap::new();
+        clients.insert("arm".to_owned(), client);
+        let sender = JointsPoseSender::new(
+            "pose".to_owned(),
+            vec![JointsPose {
+                pose_name: "saved1".to_owned(),
+                client_name: "arm".to_owned(),
+                positions: vec![0.0, 0.0],
+            }],
+            clients,
+            DummySpeaker::new(),
+            Duration::from_secs(1),
+            None,
+        );
+
+        // South alone does not save the pose.
+        sender.handle_event(GamepadEvent::ButtonPressed(Button::South));
+        assert_eq!(sender.submode(), " arm saved1");
+        assert_eq!(sender.inner.lock().unwrap().joints_poses.len(), 1);
+
+        sender.handle_event(GamepadEvent::ButtonPressed(Button::RightTrigger2));
+        sender.handle_event(GamepadEvent::ButtonPressed(Button::South));
+        assert_eq!(sender.submode(), " arm saved2");
+        assert_eq!(sender.speaker.current_message(), "pose saved arm saved2");
+        let (name, positions) = sender.inner.lock().unwrap().get_target_name_positions();
+        assert_eq!(name, "arm");
+        assert_eq!(positions, vec![0.5, -0.5]);
+    }
+}
@@ -176,6 +176,7 @@ pub fn gen(workspace_root: &Path) -> Result<()> {
             Error,
             Isometry2,
             Isometry3,
+            PoseWithCovariance,
             WaitFuture,
         };
         use abi_stable::StableAbi;
@@ -14,6 +14,9 @@ use syn::{
 
 use super::*;
 
+/// Types converted from protobuf messages with `TryFrom<Error = tonic::Status>`.
+const USE_TRY_FROM: &[&str] = &["PoseWithCovariance"];
+
 pub fn gen(workspace_root: &Path) -> Result<()> {
     const FULLY_IGNORE: &[&str] = &["SetCompleteCondition"];
     const IGNORE: &[&str] = &["JointTrajectoryClient", "SetCompleteCondition", "Gamepad"];
@@ -51,6 +54,7 @@ pub fn gen(workspace_root: &Path) -> Result<()> {
             Error,
             Isometry2,
             Isometry3,
+            PoseWithCovariance,
             WaitFuture,
         };
         use super::*;
@@ -169,6 +173,16 @@ fn gen_client_impl(trait_name: &Ident, item: &ItemTrait) -> TokenStream {
                                 client.#name(args).await
                             })))
                         }
+                    } else if path.map_or(false, |p| {
+                        USE_TRY_FROM.contains(&&*p.segments.last().unwrap().ident.to_string())
+                    }) {
+                        quote! {
+                            Ok(block_in_place(client.#name(args))
+                                .map_err(|e| arci::Error::Other(e.into()))?
+                                .into_inner()
+                                .try_into()
+                                .map_err(|e: tonic::Status| arci::Error::Other(e.into()))?)
+                        }
                     } else {
                         quote! {
                             Ok(block_in_place(client.#name(args))
@@ -228,11 +242,14 @@ fn gen_server_impl(trait_name: &Ident, item: &ItemTrait, pb_traits: &[ItemTrait]
                     syn::FnArg::Typed(arg) => {
                         let pat = &arg.pat;
                         let mut into = quote! { .into() };
+                        let mut try_from = false;
                         if let Some(path) = get_ty_path(&arg.ty) {
-                            if USE_TRY_INTO
-                                .contains(&&*path.segments.last().unwrap().ident.to_string())
-                            {
+                            let ty_name = path.segments.last().unwrap().ident.to_string();
+                            if USE_TRY_INTO.contains(&&*ty_name) {
                                 into = quote! { .try_into().unwrap() }
+                            } else if USE_TRY_FROM.contains(&&*ty_name) {
+                                into = quote! { .try_into()? };
+                                try_from = true;
                             }
                         }
                         Some(match arg_len {
@@ -251,6 +268,14 @@ fn gen_server_impl(trait_name: &Ident, item: &ItemTrait, pb_traits: &[ItemTrait]
                                     quote! { &request.#pat }
                                 } else if matches!(&*arg.ty, syn::Type::Reference(..)) {
                                     quote! { &request.#pat.unwrap()#into }
+                                } else if try_from {
+                                    let msg = format!("{} is not set", quote! { #pat });
+                                    quote! {
+                                        request
+                                            .#pat
+                                            .ok_or_else(|| tonic::Status::invalid_argument(#msg))?
+                                            #into
+                                    }
                                 } else {
                                     quote! { request.#pat.unwrap()#into }
                                 }